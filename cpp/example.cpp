@@ -60,19 +60,30 @@ int main(int argc, char *argv[])
                                           SDL_WINDOW_HIDDEN);
     SDL_Renderer *renderer = SDL_CreateRenderer(window, -1, SDL_RENDERER_ACCELERATED);
 
-    auto decoder = FFI::tga_image_decoder_plugin_new(buffer, fd_stats.st_size);
-    if (!decoder) {
-        SDL_Log("Unable to create decoder");
+    // NOTE: The file extension lets the decoder recognize formats without a magic number, like TGA.
+    char const* extension = strrchr(argv[1], '.');
+    void* decoder = nullptr;
+    if (FFI::image_decoder_new(buffer, fd_stats.st_size, extension, &decoder) != FFI::ErrorCode::Success) {
+        SDL_Log("Unable to create decoder: %s", FFI::libgfx_last_error_message());
         return 1;
     }
 
     SDL_ShowWindow(window);
 
-    auto frame_count = FFI::image_decoder_plugin_frame_count(decoder);
+    size_t frame_count = 0;
+    if (FFI::image_decoder_plugin_frame_count(decoder, &frame_count) != FFI::ErrorCode::Success) {
+        SDL_Log("Unable to get frame count: %s", FFI::libgfx_last_error_message());
+        return 1;
+    }
 
     for (size_t i = 0; i < frame_count; i++) {
-        auto frame = FFI::image_decoder_plugin_frame(decoder, i);
-        auto depth = FFI::bitmap_format_bytes_per_pixel(frame.image.format);
+        FFI::FFIImageFrameDescriptor frame;
+        if (FFI::image_decoder_plugin_frame(decoder, i, &frame) != FFI::ErrorCode::Success) {
+            SDL_Log("Unable to decode frame %zu: %s", i, FFI::libgfx_last_error_message());
+            continue;
+        }
+        uint32_t depth = 0;
+        FFI::bitmap_format_bytes_per_pixel(frame.image.format, &depth);
 
         SDL_Surface* surface = SDL_CreateRGBSurfaceWithFormatFrom(frame.image.data.data,
                                                          frame.image.size.width,
@@ -95,7 +106,7 @@ int main(int argc, char *argv[])
         FFI::image_decoder_plugin_free_frame(frame);
     }
 
-    FFI::tga_image_decoder_plugin_free(decoder);
+    FFI::image_decoder_free(decoder);
     SDL_DestroyRenderer(renderer);
     SDL_DestroyWindow(window);
     munmap(buffer, fd_stats.st_size);
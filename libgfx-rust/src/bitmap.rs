@@ -1,4 +1,5 @@
 use crate::{ARGB, IntSize};
+use crate::error::{Error, ErrorCode, ffi_call, write_ffi_result};
 
 #[repr(u8)]
 #[derive(Clone, Copy)]
@@ -43,9 +44,17 @@ impl From<BitmapFormat> for StorageFormat {
     }
 }
 
+/// # Safety
+///
+/// `out_bytes_per_pixel` must be valid for writes.
 #[no_mangle]
-pub extern "C" fn bitmap_format_bytes_per_pixel(format: BitmapFormat) -> u32 {
-    StorageFormat::from(format).bytes_per_pixel()
+pub unsafe extern "C" fn bitmap_format_bytes_per_pixel(format: BitmapFormat, out_bytes_per_pixel: *mut u32) -> ErrorCode {
+    ffi_call(|| unsafe {
+        if matches!(format, BitmapFormat::Invalid) {
+            return Err(Error::InvalidArgument("Invalid bitmap format"));
+        }
+        write_ffi_result(out_bytes_per_pixel, StorageFormat::from(format).bytes_per_pixel())
+    })
 }
 
 #[repr(C)]
@@ -66,7 +75,7 @@ pub struct Bitmap {
 }
 
 impl Bitmap {
-    pub fn new(bitmap_format: BitmapFormat, size: IntSize, intrinsic_scale: i32) -> Result<Self, String> {
+    pub fn new(bitmap_format: BitmapFormat, size: IntSize, intrinsic_scale: i32) -> Result<Self, Error> {
         let (backing_store, pitch) = Self::allocate_backing_store(bitmap_format, size, intrinsic_scale)?;
         Ok(Self {
             format: bitmap_format,
@@ -82,18 +91,56 @@ impl Bitmap {
         physical_width * format.bytes_per_pixel() as usize
     }
 
+    pub fn physical_width(&self) -> i32 {
+        self.size.width * self.scale
+    }
+
+    pub fn physical_height(&self) -> i32 {
+        self.size.height * self.scale
+    }
+
     pub fn set_pixel(&mut self, x: i32, y: i32, color: ARGB) {
         let offset = (y as usize * self.pitch as usize) + x as usize * 4;
-        let color = color.to_be_bytes();
+        let [a, r, g, b] = color.to_be_bytes();
+        // NOTE: RGBA8888 stores the same bytes with red and blue swapped.
+        let color = match self.format {
+            BitmapFormat::RGBA8888 => [a, b, g, r],
+            _ => [a, r, g, b]
+        };
         self.data[offset..offset + 4].copy_from_slice(&color);
     }
 
+    pub fn get_pixel(&self, x: i32, y: i32) -> ARGB {
+        let offset = (y as usize * self.pitch as usize) + x as usize * 4;
+        let [a, c1, c2, c3]: [u8; 4] = self.data[offset..offset + 4].try_into().unwrap();
+        match self.format {
+            BitmapFormat::BGRx8888 => u32::from_be_bytes([0xFF, c1, c2, c3]),
+            BitmapFormat::RGBA8888 => u32::from_be_bytes([a, c3, c2, c1]),
+            _ => u32::from_be_bytes([a, c1, c2, c3])
+        }
+    }
+
+    /// All pixels in row order, as returned by `get_pixel()`.
+    #[cfg(test)]
+    pub(crate) fn pixels(&self) -> Vec<ARGB> {
+        (0..self.size.height).flat_map(|y| (0..self.size.width).map(move |x| self.get_pixel(x, y))).collect()
+    }
+
+    /// Fails with `Error::SizeOverflow` for dimensions larger than a bitmap can have. Decoders check their own buffers against
+    /// the same limits, which keeps the size computations for those from overflowing too.
+    pub(crate) fn check_dimensions(width: u32, height: u32) -> Result<(), Error> {
+        // This check is a bit arbitrary, but should protect us from most shenanigans:
+        if width > i16::MAX as u32 || height > i16::MAX as u32 {
+            return Err(Error::SizeOverflow);
+        }
+        Ok(())
+    }
+
     fn size_would_overflow(format: BitmapFormat, size: IntSize, scale_factor: i32) -> bool {
         if size.is_empty() {
             return true;
         }
-        // This check is a bit arbitrary, but should protect us from most shenanigans:
-        if size.width > i16::MAX as i32 || size.height > i16::MAX as i32 || !(1..=4).contains(&scale_factor) {
+        if Self::check_dimensions(size.width as u32, size.height as u32).is_err() || !(1..=4).contains(&scale_factor) {
             return true;
         }
         // In contrast, this check is absolutely necessary:
@@ -102,17 +149,60 @@ impl Bitmap {
         overflows
     }
 
-    fn allocate_backing_store(format: BitmapFormat, size: IntSize, scale: i32) -> Result<(Vec<u8>, usize), String> {
+    fn allocate_backing_store(format: BitmapFormat, size: IntSize, scale: i32) -> Result<(Vec<u8>, usize), Error> {
         if size.is_empty() {
-            return Err("Bitmap::allocate_backing_store: size is empty".to_string());
+            return Err(Error::InvalidData("Bitmap size is empty"));
         }
         if Self::size_would_overflow(format, size, scale) {
-            return Err("Bitmap::allocate_backing_store: size would overflow".to_string());
+            return Err(Error::SizeOverflow);
         }
 
         let pitch = Self::minimum_pitch(size.width as usize * scale as usize, format);
         let data_size_in_bytes = pitch * size.height as usize * scale as usize;
 
-        Ok((vec![0u8; data_size_in_bytes], pitch))
+        let mut backing_store = Vec::new();
+        if backing_store.try_reserve_exact(data_size_in_bytes).is_err() {
+            return Err(Error::OutOfMemory);
+        }
+        backing_store.resize(data_size_in_bytes, 0u8);
+        Ok((backing_store, pitch))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bitmap_with_pixel(format: BitmapFormat, color: ARGB) -> Bitmap {
+        let mut bitmap = Bitmap::new(format, IntSize { width: 2, height: 1 }, 1).unwrap();
+        bitmap.set_pixel(1, 0, color);
+        bitmap
+    }
+
+    #[test]
+    fn round_trips_pixels_in_every_format() {
+        for (format, expected) in [
+            (BitmapFormat::BGRA8888, 0x80123456),
+            (BitmapFormat::RGBA8888, 0x80123456),
+            (BitmapFormat::BGRx8888, 0xFF123456)
+        ] {
+            let bitmap = bitmap_with_pixel(format, 0x80123456);
+            assert_eq!(bitmap.get_pixel(1, 0), expected);
+            assert_eq!(bitmap.get_pixel(0, 0), if matches!(format, BitmapFormat::BGRx8888) { 0xFF000000 } else { 0 });
+        }
+    }
+
+    #[test]
+    fn stores_rgba_with_red_and_blue_swapped() {
+        assert_eq!(bitmap_with_pixel(BitmapFormat::BGRA8888, 0x80123456).data[4..], [0x80, 0x12, 0x34, 0x56]);
+        assert_eq!(bitmap_with_pixel(BitmapFormat::RGBA8888, 0x80123456).data[4..], [0x80, 0x56, 0x34, 0x12]);
+    }
+
+    #[test]
+    fn limits_dimensions() {
+        assert_eq!(Bitmap::check_dimensions(32767, 32767), Ok(()));
+        assert_eq!(Bitmap::check_dimensions(32768, 1), Err(Error::SizeOverflow));
+        assert_eq!(Bitmap::check_dimensions(1, u32::MAX), Err(Error::SizeOverflow));
+        assert_eq!(Bitmap::new(BitmapFormat::BGRA8888, IntSize { width: 32768, height: 1 }, 1).err(), Some(Error::SizeOverflow));
     }
 }
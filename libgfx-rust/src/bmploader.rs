@@ -0,0 +1,611 @@
+use std::ffi::c_void;
+use crate::imagedecoderplugin::{decoder_into_opaque, ImageDecoderPlugin, ImageFrameDescriptor};
+use crate::{ARGB, Color, IntSize};
+use crate::bitmap::{Bitmap, BitmapFormat};
+use crate::error::{Error, ErrorCode, ffi_call, write_ffi_result};
+
+const BMP_FILE_HEADER_SIZE: usize = 14;
+
+#[derive (Debug, PartialEq, Copy, Clone)]
+enum BMPCompression {
+    Uncompressed = 0,
+    RLE8 = 1,
+    RLE4 = 2,
+    Bitfields = 3,
+    AlphaBitfields = 6
+}
+
+#[derive (Debug, Copy, Clone)]
+struct BMPInfoHeader {
+    header_size: u32,
+    width: i32,
+    height: i32,
+    bits_per_pixel: u16,
+    compression: BMPCompression,
+    colors_used: u32,
+    red_mask: u32,
+    green_mask: u32,
+    blue_mask: u32,
+    alpha_mask: u32
+}
+
+impl BMPInfoHeader {
+    fn is_core_header(&self) -> bool {
+        self.header_size == 12
+    }
+
+    fn is_top_down(&self) -> bool {
+        self.height < 0
+    }
+}
+
+pub struct BMPImageDecoderPlugin<'a> {
+    context: BMPLoadingContext<'a>
+}
+
+struct BMPLoadingContext<'a> {
+    bytes: &'a [u8],
+    dib_header: BMPInfoHeader,
+    color_table: Vec<Color>,
+    pixel_data: &'a [u8],
+    is_included_in_ico: bool,
+    bitmap: Option<Bitmap>
+}
+
+fn read_u16_le(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+fn read_u32_le(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+}
+
+impl<'a> BMPImageDecoderPlugin<'a> {
+    pub fn sniff(bytes: &[u8]) -> bool {
+        bytes.len() >= BMP_FILE_HEADER_SIZE + 4 && bytes.starts_with(b"BM") && decode_dib_header(&bytes[BMP_FILE_HEADER_SIZE..]).is_ok()
+    }
+
+    pub fn create(bytes: &'a [u8]) -> Result<Self, Error> {
+        if bytes.len() < BMP_FILE_HEADER_SIZE {
+            return Err(Error::Truncated);
+        }
+        if !bytes.starts_with(b"BM") {
+            return Err(Error::InvalidHeader("Invalid BMP signature"));
+        }
+        let Some(pixel_data_offset) = (read_u32_le(bytes, 10) as usize).checked_sub(BMP_FILE_HEADER_SIZE) else {
+            return Err(Error::InvalidHeader("Invalid BMP pixel data offset"));
+        };
+        Ok(Self { context: BMPLoadingContext::create(bytes, &bytes[BMP_FILE_HEADER_SIZE..], Some(pixel_data_offset), false)? })
+    }
+
+    /// Creates a decoder for a DIB without the BMP file header, as found on clipboards and inside
+    /// other containers. The pixel data has to directly follow the color table.
+    pub fn create_from_dib(bytes: &'a [u8]) -> Result<Self, Error> {
+        Ok(Self { context: BMPLoadingContext::create(bytes, bytes, None, false)? })
+    }
+
+    /// Creates a decoder for a DIB stored in an ICO or CUR file, which has twice the height
+    /// in its header and a 1-bit transparency mask after the color data.
+    pub(crate) fn create_as_included_in_ico(bytes: &'a [u8]) -> Result<Self, Error> {
+        Ok(Self { context: BMPLoadingContext::create(bytes, bytes, None, true)? })
+    }
+}
+
+fn decode_dib_header(dib: &[u8]) -> Result<BMPInfoHeader, Error> {
+    if dib.len() < 4 {
+        return Err(Error::Truncated);
+    }
+    let header_size = read_u32_le(dib, 0);
+    // NOTE: 12 is BITMAPCOREHEADER, 16 and 64 are OS/2 2.x headers, the rest are BITMAPINFOHEADER through BITMAPV5HEADER.
+    if !matches!(header_size, 12 | 16 | 40 | 52 | 56 | 64 | 108 | 124) {
+        return Err(Error::Unsupported("BMP DIB header size"));
+    }
+    if dib.len() < header_size as usize {
+        return Err(Error::Truncated);
+    }
+
+    let mut header = BMPInfoHeader {
+        header_size,
+        width: 0,
+        height: 0,
+        bits_per_pixel: 0,
+        compression: BMPCompression::Uncompressed,
+        colors_used: 0,
+        red_mask: 0,
+        green_mask: 0,
+        blue_mask: 0,
+        alpha_mask: 0
+    };
+    if header.is_core_header() {
+        header.width = read_u16_le(dib, 4) as i32;
+        header.height = read_u16_le(dib, 6) as i32;
+        header.bits_per_pixel = read_u16_le(dib, 10);
+        return Ok(header);
+    }
+
+    header.width = read_u32_le(dib, 4) as i32;
+    header.height = read_u32_le(dib, 8) as i32;
+    header.bits_per_pixel = read_u16_le(dib, 14);
+    if header_size >= 20 {
+        let is_os2_header = matches!(header_size, 16 | 64);
+        header.compression = match read_u32_le(dib, 16) {
+            0 => BMPCompression::Uncompressed,
+            1 => BMPCompression::RLE8,
+            2 => BMPCompression::RLE4,
+            3 if !is_os2_header => BMPCompression::Bitfields,
+            6 if !is_os2_header => BMPCompression::AlphaBitfields,
+            _ => return Err(Error::Unsupported("BMP compression type"))
+        };
+    }
+    if header_size >= 36 {
+        header.colors_used = read_u32_le(dib, 32);
+    }
+    if matches!(header_size, 52 | 56 | 108 | 124) {
+        header.red_mask = read_u32_le(dib, 40);
+        header.green_mask = read_u32_le(dib, 44);
+        header.blue_mask = read_u32_le(dib, 48);
+    }
+    if matches!(header_size, 56 | 108 | 124) {
+        header.alpha_mask = read_u32_le(dib, 52);
+    }
+    Ok(header)
+}
+
+impl<'a> BMPLoadingContext<'a> {
+    fn create(bytes: &'a [u8], dib: &'a [u8], pixel_data_offset: Option<usize>, is_included_in_ico: bool) -> Result<Self, Error> {
+        let mut dib_header = decode_dib_header(dib)?;
+        if is_included_in_ico {
+            dib_header.height /= 2;
+        }
+        if dib_header.width <= 0 || dib_header.height == 0 {
+            return Err(Error::InvalidHeader("Invalid BMP dimensions"));
+        }
+        if !matches!(dib_header.bits_per_pixel, 1 | 2 | 4 | 8 | 16 | 24 | 32) {
+            return Err(Error::Unsupported("BMP bits per pixel"));
+        }
+        let is_valid_compression = match dib_header.compression {
+            BMPCompression::Uncompressed => true,
+            BMPCompression::RLE8 => dib_header.bits_per_pixel == 8 && !dib_header.is_top_down(),
+            BMPCompression::RLE4 => dib_header.bits_per_pixel == 4 && !dib_header.is_top_down(),
+            BMPCompression::Bitfields | BMPCompression::AlphaBitfields => matches!(dib_header.bits_per_pixel, 16 | 32)
+        };
+        if !is_valid_compression {
+            return Err(Error::InvalidHeader("Invalid BMP compression for bit depth"));
+        }
+
+        let mut offset = dib_header.header_size as usize;
+        match dib_header.compression {
+            // NOTE: BITMAPINFOHEADER has no room for the masks, so they follow it.
+            BMPCompression::Bitfields | BMPCompression::AlphaBitfields if dib_header.header_size == 40 => {
+                let mask_count = if dib_header.compression == BMPCompression::AlphaBitfields { 4 } else { 3 };
+                if dib.len() < offset + mask_count * 4 {
+                    return Err(Error::Truncated);
+                }
+                dib_header.red_mask = read_u32_le(dib, offset);
+                dib_header.green_mask = read_u32_le(dib, offset + 4);
+                dib_header.blue_mask = read_u32_le(dib, offset + 8);
+                if mask_count == 4 {
+                    dib_header.alpha_mask = read_u32_le(dib, offset + 12);
+                }
+                offset += mask_count * 4;
+            }
+            BMPCompression::Bitfields | BMPCompression::AlphaBitfields => {}
+            _ => {
+                (dib_header.red_mask, dib_header.green_mask, dib_header.blue_mask, dib_header.alpha_mask) = match dib_header.bits_per_pixel {
+                    16 => (0x7C00, 0x03E0, 0x001F, 0),
+                    _ => (0x00FF0000, 0x0000FF00, 0x000000FF, 0)
+                };
+                // NOTE: Unlike plain BMPs, 32-bit icons use the otherwise unused byte as alpha.
+                if is_included_in_ico && dib_header.bits_per_pixel == 32 {
+                    dib_header.alpha_mask = 0xFF000000;
+                }
+            }
+        }
+
+        let entry_size = if dib_header.is_core_header() { 3 } else { 4 };
+        let maximum_color_count = if dib_header.bits_per_pixel <= 8 { 1 << dib_header.bits_per_pixel } else { 0 };
+        let color_count = if dib_header.colors_used == 0 { maximum_color_count } else { dib_header.colors_used as usize };
+        let color_table_end = pixel_data_offset.unwrap_or(usize::MAX).min(dib.len());
+        let available_color_count = color_table_end.saturating_sub(offset) / entry_size;
+        // NOTE: Truncated color tables are common enough that we use whatever is there.
+        let color_table = dib[offset.min(color_table_end)..].chunks_exact(entry_size)
+            .take(color_count.min(available_color_count).min(maximum_color_count))
+            .map(|entry| Color::from_rgb(entry[2], entry[1], entry[0]))
+            .collect();
+
+        let pixel_data_offset = pixel_data_offset.unwrap_or(offset.saturating_add(color_count.saturating_mul(entry_size)));
+        let Some(pixel_data) = dib.get(pixel_data_offset..) else {
+            return Err(Error::Truncated);
+        };
+
+        Ok(Self {
+            bytes,
+            dib_header,
+            color_table,
+            pixel_data,
+            is_included_in_ico,
+            bitmap: None
+        })
+    }
+
+    fn width(&self) -> usize {
+        self.dib_header.width as usize
+    }
+
+    fn height(&self) -> usize {
+        self.dib_header.height.unsigned_abs() as usize
+    }
+
+    fn color_at_index(&self, index: usize) -> ARGB {
+        // NOTE: Out of range indices are drawn as black.
+        self.color_table.get(index).copied().unwrap_or(Color::from_rgb(0, 0, 0)).color
+    }
+
+    fn color_from_masks(&self, value: u32) -> ARGB {
+        let header = &self.dib_header;
+        let alpha = if header.alpha_mask == 0 { 0xFF } else { masked_channel(value, header.alpha_mask) };
+        Color::from_rgba(
+            masked_channel(value, header.red_mask),
+            masked_channel(value, header.green_mask),
+            masked_channel(value, header.blue_mask),
+            alpha
+        ).color
+    }
+
+    fn decode_uncompressed_pixels(&self, bitmap: &mut Bitmap) -> Result<(), Error> {
+        let bits_per_pixel = self.dib_header.bits_per_pixel as usize;
+        let pitch = (self.width() * bits_per_pixel).div_ceil(32) * 4;
+        let Some(size) = pitch.checked_mul(self.height()) else {
+            return Err(Error::SizeOverflow);
+        };
+        if self.pixel_data.len() < size {
+            return Err(Error::Truncated);
+        }
+
+        for (row, scanline) in self.pixel_data.chunks_exact(pitch).take(self.height()).enumerate() {
+            let y = if self.dib_header.is_top_down() { row } else { self.height() - 1 - row };
+            for x in 0..self.width() {
+                let color = match bits_per_pixel {
+                    1 | 2 | 4 | 8 => {
+                        let bit_offset = x * bits_per_pixel;
+                        let shift = 8 - bits_per_pixel - bit_offset % 8;
+                        self.color_at_index((scanline[bit_offset / 8] as usize >> shift) & ((1 << bits_per_pixel) - 1))
+                    }
+                    16 => self.color_from_masks(read_u16_le(scanline, x * 2) as u32),
+                    24 => Color::from_rgb(scanline[x * 3 + 2], scanline[x * 3 + 1], scanline[x * 3]).color,
+                    _ => self.color_from_masks(read_u32_le(scanline, x * 4))
+                };
+                bitmap.set_pixel(x as i32, y as i32, color);
+            }
+        }
+        Ok(())
+    }
+
+    fn apply_and_mask(&self, bitmap: &mut Bitmap) {
+        let (width, height) = (self.width(), self.height());
+        if self.dib_header.bits_per_pixel == 32 {
+            let has_alpha = (0..height).any(|y| (0..width).any(|x| bitmap.get_pixel(x as i32, y as i32) >> 24 != 0));
+            if has_alpha {
+                return;
+            }
+            // NOTE: Older 32-bit icons leave the alpha channel empty and rely on the mask instead.
+            for y in 0..height {
+                for x in 0..width {
+                    let color = bitmap.get_pixel(x as i32, y as i32);
+                    bitmap.set_pixel(x as i32, y as i32, color | 0xFF000000);
+                }
+            }
+        }
+
+        let color_pitch = (width * self.dib_header.bits_per_pixel as usize).div_ceil(32) * 4;
+        let mask_pitch = width.div_ceil(32) * 4;
+        let Some(mask) = self.pixel_data.get(color_pitch * height..).filter(|mask| mask.len() >= mask_pitch * height) else {
+            // NOTE: Some icons leave out the mask entirely, in which case everything is opaque.
+            return;
+        };
+        for (row, scanline) in mask.chunks_exact(mask_pitch).take(height).enumerate() {
+            let y = if self.dib_header.is_top_down() { row } else { height - 1 - row };
+            for x in 0..width {
+                if (scanline[x / 8] >> (7 - x % 8)) & 1 != 0 {
+                    bitmap.set_pixel(x as i32, y as i32, 0);
+                }
+            }
+        }
+    }
+
+    fn decode_run_length_encoded_pixels(&self, bitmap: &mut Bitmap) -> Result<(), Error> {
+        let is_rle4 = self.dib_header.compression == BMPCompression::RLE4;
+        let (width, height) = (self.width(), self.height());
+        let data = self.pixel_data;
+        // NOTE: Pixels skipped with deltas or early line ends stay transparent.
+        let mut set_pixel = |x: usize, row: usize, index: u8| {
+            if x < width && row < height {
+                bitmap.set_pixel(x as i32, (height - 1 - row) as i32, self.color_at_index(index as usize));
+            }
+        };
+        let nibble = |byte: u8, index: usize| if index.is_multiple_of(2) { byte >> 4 } else { byte & 0x0F };
+
+        let (mut x, mut row, mut position) = (0usize, 0usize, 0usize);
+        // NOTE: Plenty of encoders omit the end of bitmap marker, so running out of data just ends the image.
+        while row < height && position + 2 <= data.len() {
+            let (count, value) = (data[position] as usize, data[position + 1]);
+            position += 2;
+
+            if count > 0 {
+                for index in 0..count {
+                    set_pixel(x, row, if is_rle4 { nibble(value, index) } else { value });
+                    x += 1;
+                }
+                continue;
+            }
+
+            match value {
+                0 => {
+                    x = 0;
+                    row += 1;
+                }
+                1 => break,
+                2 => {
+                    let Some(delta) = data.get(position..position + 2) else {
+                        return Err(Error::Truncated);
+                    };
+                    x += delta[0] as usize;
+                    row += delta[1] as usize;
+                    position += 2;
+                }
+                pixel_count => {
+                    let pixel_count = pixel_count as usize;
+                    let byte_count = if is_rle4 { pixel_count.div_ceil(2) } else { pixel_count };
+                    let Some(pixels) = data.get(position..position + byte_count) else {
+                        return Err(Error::Truncated);
+                    };
+                    for index in 0..pixel_count {
+                        set_pixel(x, row, if is_rle4 { nibble(pixels[index / 2], index) } else { pixels[index] });
+                        x += 1;
+                    }
+                    // NOTE: Absolute runs are padded to a 16-bit boundary.
+                    position += byte_count + byte_count % 2;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn masked_channel(value: u32, mask: u32) -> u8 {
+    if mask == 0 {
+        return 0;
+    }
+    // NOTE: Scale in 64 bits, as wide or non-contiguous masks would overflow the multiplication otherwise.
+    let shift = mask.trailing_zeros();
+    let maximum = (mask >> shift) as u64;
+    ((((value & mask) >> shift) as u64 * 255 + maximum / 2) / maximum) as u8
+}
+
+impl<'a> ImageDecoderPlugin for BMPImageDecoderPlugin<'a> {
+    fn size(&self) -> IntSize {
+        IntSize {
+            width: self.context.width() as i32,
+            height: self.context.height() as i32
+        }
+    }
+
+    fn frame_with_ideal_size(&mut self, index: usize, _: Option<IntSize>) -> Result<ImageFrameDescriptor, Error> {
+        if index != 0 {
+            return Err(Error::FrameIndexOutOfRange);
+        }
+
+        if let Some(bitmap) = &self.context.bitmap {
+            return Ok(ImageFrameDescriptor {
+                image: bitmap.clone(),
+                duration: 0
+            });
+        }
+
+        let mut bitmap = Bitmap::new(BitmapFormat::BGRA8888, self.size(), 1)?;
+        match self.context.dib_header.compression {
+            BMPCompression::RLE8 | BMPCompression::RLE4 => self.context.decode_run_length_encoded_pixels(&mut bitmap)?,
+            _ => {
+                self.context.decode_uncompressed_pixels(&mut bitmap)?;
+                if self.context.is_included_in_ico {
+                    self.context.apply_and_mask(&mut bitmap);
+                }
+            }
+        }
+        self.context.bitmap = Some(bitmap.clone());
+
+        Ok(ImageFrameDescriptor {
+            image: bitmap,
+            duration: 0
+        })
+    }
+}
+
+/// # Safety
+///
+/// `bytes` must point to `size` readable bytes that outlive the returned decoder,
+/// and `out_decoder` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn bmp_image_decoder_plugin_new(bytes: *const u8, size: usize, out_decoder: *mut *mut c_void) -> ErrorCode {
+    ffi_call(|| unsafe {
+        if bytes.is_null() {
+            return Err(Error::InvalidArgument("Bytes are null"));
+        }
+        if out_decoder.is_null() {
+            return Err(Error::InvalidArgument("Output pointer is null"));
+        }
+        let bytes = std::slice::from_raw_parts(bytes, size);
+        let decoder = BMPImageDecoderPlugin::create(bytes)?;
+        write_ffi_result(out_decoder, decoder_into_opaque(Box::new(decoder)))
+    })
+}
+
+/// Like `bmp_image_decoder_plugin_new`, but for a DIB without the BMP file header.
+///
+/// # Safety
+///
+/// `bytes` must point to `size` readable bytes that outlive the returned decoder,
+/// and `out_decoder` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn bmp_image_decoder_plugin_new_from_dib(bytes: *const u8, size: usize, out_decoder: *mut *mut c_void) -> ErrorCode {
+    ffi_call(|| unsafe {
+        if bytes.is_null() {
+            return Err(Error::InvalidArgument("Bytes are null"));
+        }
+        if out_decoder.is_null() {
+            return Err(Error::InvalidArgument("Output pointer is null"));
+        }
+        let bytes = std::slice::from_raw_parts(bytes, size);
+        let decoder = BMPImageDecoderPlugin::create_from_dib(bytes)?;
+        write_ffi_result(out_decoder, decoder_into_opaque(Box::new(decoder)))
+    })
+}
+
+/// # Safety
+///
+/// `opaque_decoder` must either be null or come from one of the `bmp_image_decoder_plugin_new` functions and not be freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn bmp_image_decoder_plugin_free(opaque_decoder: *mut c_void) -> ErrorCode {
+    ffi_call(|| {
+        if !opaque_decoder.is_null() {
+            let decoder: Box<Box<dyn ImageDecoderPlugin>> = unsafe { Box::from_raw(opaque_decoder as *mut _) };
+            drop(decoder);
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // NOTE: 3x2 bottom-up 4-bit image with a palette of red, green, blue and white, padded to 4 bytes per row.
+    const PALETTED_4_IMAGE: [u8; 78] = [
+        0x42, 0x4D, 0x4E, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x46, 0x00, 0x00, 0x00, 0x28, 0x00, 0x00, 0x00, 0x03, 0x00,
+        0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x01, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0x00, 0x00, 0xFF,
+        0x00, 0x00, 0xFF, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0x00, 0x33, 0x00, 0x00, 0x00, 0x01, 0x20, 0x00, 0x00
+    ];
+
+    // NOTE: 2x2 top-down 24-bit image.
+    const TOP_DOWN_24_IMAGE: [u8; 70] = [
+        0x42, 0x4D, 0x46, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x36, 0x00, 0x00, 0x00, 0x28, 0x00, 0x00, 0x00, 0x02, 0x00,
+        0x00, 0x00, 0xFE, 0xFF, 0xFF, 0xFF, 0x01, 0x00, 0x18, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x30, 0x20, 0x10, 0x60, 0x50, 0x40,
+        0x00, 0x00, 0x90, 0x80, 0x70, 0xC0, 0xB0, 0xA0, 0x00, 0x00
+    ];
+
+    // NOTE: 2x1 16-bit image with the default 5-5-5 masks, holding 0x7C00 and 0x0210.
+    const RGB_555_IMAGE: [u8; 58] = [
+        0x42, 0x4D, 0x3A, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x36, 0x00, 0x00, 0x00, 0x28, 0x00, 0x00, 0x00, 0x02, 0x00,
+        0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x7C, 0x10, 0x02
+    ];
+
+    // NOTE: 2x1 16-bit image with 5-6-5 masks following the BITMAPINFOHEADER, holding 0x07E0 and 0x0010.
+    const RGB_565_IMAGE: [u8; 70] = [
+        0x42, 0x4D, 0x46, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x42, 0x00, 0x00, 0x00, 0x28, 0x00, 0x00, 0x00, 0x02, 0x00,
+        0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x10, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xF8, 0x00, 0x00, 0xE0, 0x07,
+        0x00, 0x00, 0x1F, 0x00, 0x00, 0x00, 0xE0, 0x07, 0x10, 0x00
+    ];
+
+    // NOTE: 2x1 32-bit image with a BITMAPV4HEADER whose masks put blue, green, red and alpha from the top byte down.
+    const ALPHA_BITFIELDS_IMAGE: [u8; 130] = [
+        0x42, 0x4D, 0x82, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x7A, 0x00, 0x00, 0x00, 0x6C, 0x00, 0x00, 0x00, 0x02, 0x00,
+        0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x20, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0x00, 0x00, 0x00, 0x00,
+        0xFF, 0x00, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x80, 0x33, 0x22, 0x11, 0xFF, 0x66, 0x55, 0x44
+    ];
+
+    // NOTE: 2x1 32-bit image whose red mask is the non-contiguous 0x80000001.
+    const NON_CONTIGUOUS_MASK_IMAGE: [u8; 74] = [
+        0x42, 0x4D, 0x4A, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x42, 0x00, 0x00, 0x00, 0x28, 0x00, 0x00, 0x00, 0x02, 0x00,
+        0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x20, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x80, 0x00, 0xFF,
+        0x00, 0x00, 0x00, 0x00, 0xFF, 0x00, 0x01, 0xFF, 0x00, 0x80, 0x01, 0x00, 0xFF, 0x00
+    ];
+
+    // NOTE: 4x3 RLE8 image using an encoded run, an end of line, a padded absolute run and a delta, without an end of bitmap marker.
+    const RLE8_IMAGE: [u8; 88] = [
+        0x42, 0x4D, 0x58, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x46, 0x00, 0x00, 0x00, 0x28, 0x00, 0x00, 0x00, 0x04, 0x00,
+        0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x01, 0x00, 0x08, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0xFF, 0x00, 0x00, 0xFF, 0x00, 0x00, 0xFF, 0x00, 0x00, 0x00, 0x04, 0x01, 0x00, 0x00, 0x00, 0x03, 0x02, 0x03, 0x01, 0x00,
+        0x00, 0x00, 0x00, 0x02, 0x01, 0x00, 0x02, 0x03
+    ];
+
+    // NOTE: 8x1 RLE4 image with a run alternating between two nibbles, followed by a padded absolute run of 5 pixels.
+    const RLE4_IMAGE: [u8; 80] = [
+        0x42, 0x4D, 0x50, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x46, 0x00, 0x00, 0x00, 0x28, 0x00, 0x00, 0x00, 0x08, 0x00,
+        0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x04, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0xFF, 0x00, 0x00, 0xFF, 0x00, 0x00, 0xFF, 0x00, 0x00, 0x00, 0x03, 0x12, 0x00, 0x05, 0x30, 0x12, 0x30, 0x00, 0x00, 0x01
+    ];
+
+    // NOTE: 9x1 1-bit image with a BITMAPCOREHEADER and 3 byte color table entries.
+    const CORE_1_IMAGE: [u8; 36] = [
+        0x42, 0x4D, 0x24, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x20, 0x00, 0x00, 0x00, 0x0C, 0x00, 0x00, 0x00, 0x09, 0x00,
+        0x01, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xB1, 0x80, 0x00, 0x00
+    ];
+
+    fn decode(bytes: &[u8]) -> Vec<ARGB> {
+        let mut decoder = BMPImageDecoderPlugin::create(bytes).unwrap();
+        decoder.frame(0).unwrap().image.pixels()
+    }
+
+    #[test]
+    fn decodes_bottom_up_paletted_image() {
+        assert_eq!(decode(&PALETTED_4_IMAGE), [0xFFFF0000, 0xFF00FF00, 0xFF0000FF, 0xFFFFFFFF, 0xFFFFFFFF, 0xFFFF0000]);
+    }
+
+    #[test]
+    fn decodes_top_down_image() {
+        assert_eq!(decode(&TOP_DOWN_24_IMAGE), [0xFF102030, 0xFF405060, 0xFF708090, 0xFFA0B0C0]);
+    }
+
+    #[test]
+    fn scales_masked_channels() {
+        assert_eq!(decode(&RGB_555_IMAGE), [0xFFFF0000, 0xFF008484]);
+        assert_eq!(decode(&RGB_565_IMAGE), [0xFF00FF00, 0xFF000084]);
+        assert_eq!(decode(&ALPHA_BITFIELDS_IMAGE), [0x80332211, 0xFF665544]);
+    }
+
+    #[test]
+    fn handles_non_contiguous_masks() {
+        assert_eq!(decode(&NON_CONTIGUOUS_MASK_IMAGE), [0xFFFFFF00, 0xFF0000FF]);
+    }
+
+    #[test]
+    fn decodes_run_length_encoded_images() {
+        assert_eq!(decode(&RLE8_IMAGE), [
+            0x00000000, 0xFF0000FF, 0xFF0000FF, 0x00000000,
+            0xFF00FF00, 0xFF0000FF, 0xFFFF0000, 0x00000000,
+            0xFFFF0000, 0xFFFF0000, 0xFFFF0000, 0xFFFF0000
+        ]);
+        assert_eq!(decode(&RLE4_IMAGE), [0xFFFF0000, 0xFF00FF00, 0xFFFF0000, 0xFF0000FF, 0xFF000000, 0xFFFF0000, 0xFF00FF00, 0xFF0000FF]);
+    }
+
+    #[test]
+    fn decodes_core_header() {
+        let (white, black) = (0xFFFFFFFF, 0xFF000000);
+        assert_eq!(decode(&CORE_1_IMAGE), [white, black, white, white, black, black, black, white, white]);
+    }
+
+    #[test]
+    fn decodes_dib_without_file_header() {
+        let mut decoder = BMPImageDecoderPlugin::create_from_dib(&PALETTED_4_IMAGE[BMP_FILE_HEADER_SIZE..]).unwrap();
+        assert_eq!(decoder.frame(0).unwrap().image.pixels(), decode(&PALETTED_4_IMAGE));
+    }
+
+    #[test]
+    fn rejects_invalid_headers() {
+        let mut data = RLE4_IMAGE;
+        data[28] = 8;
+        assert_eq!(BMPImageDecoderPlugin::create(&data).err(), Some(Error::InvalidHeader("Invalid BMP compression for bit depth")));
+        assert_eq!(BMPImageDecoderPlugin::create(&TOP_DOWN_24_IMAGE[..20]).err(), Some(Error::Truncated));
+    }
+}
@@ -0,0 +1,124 @@
+use crate::{Color, IntSize};
+use crate::bitmap::{Bitmap, BitmapFormat};
+use crate::error::{Error, ErrorCode, ffi_call, write_ffi_result};
+use crate::imagedecoderplugin::{FFIBitmap, FFICMYKBitmap};
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CMYK {
+    pub c: u8,
+    pub m: u8,
+    pub y: u8,
+    pub k: u8
+}
+
+impl CMYK {
+    /// Converts without any color management, which is only good enough for previews.
+    pub fn to_low_quality_rgb(&self) -> (u8, u8, u8) {
+        let ink = |value: u8| (((255 - value as u32) * (255 - self.k as u32) + 127) / 255) as u8;
+        (ink(self.c), ink(self.m), ink(self.y))
+    }
+}
+
+/// Pixel data in CMYK, with 0 meaning no ink. Pixels are stored as C, M, Y, K bytes without any padding.
+#[derive(Clone)]
+pub struct CMYKBitmap {
+    pub size: IntSize,
+    pub(crate) data: Vec<u8>
+}
+
+impl CMYKBitmap {
+    pub fn new(size: IntSize) -> Result<Self, Error> {
+        if size.is_empty() {
+            return Err(Error::InvalidData("Bitmap size is empty"));
+        }
+        Bitmap::check_dimensions(size.width as u32, size.height as u32)?;
+
+        let data_size_in_bytes = size.width as usize * size.height as usize * 4;
+        let mut data = Vec::new();
+        if data.try_reserve_exact(data_size_in_bytes).is_err() {
+            return Err(Error::OutOfMemory);
+        }
+        data.resize(data_size_in_bytes, 0u8);
+        Ok(Self { size, data })
+    }
+
+    pub fn pitch(&self) -> usize {
+        self.size.width as usize * 4
+    }
+
+    pub fn set_pixel(&mut self, x: i32, y: i32, color: CMYK) {
+        let offset = y as usize * self.pitch() + x as usize * 4;
+        self.data[offset..offset + 4].copy_from_slice(&[color.c, color.m, color.y, color.k]);
+    }
+
+    pub fn get_pixel(&self, x: i32, y: i32) -> CMYK {
+        let offset = y as usize * self.pitch() + x as usize * 4;
+        let [c, m, y, k]: [u8; 4] = self.data[offset..offset + 4].try_into().unwrap();
+        CMYK { c, m, y, k }
+    }
+
+    /// Fallback for callers that just want RGB, see `CMYK::to_low_quality_rgb`.
+    pub fn to_low_quality_rgb(&self) -> Result<Bitmap, Error> {
+        let mut bitmap = Bitmap::new(BitmapFormat::BGRx8888, self.size, 1)?;
+        for y in 0..self.size.height {
+            for x in 0..self.size.width {
+                let (red, green, blue) = self.get_pixel(x, y).to_low_quality_rgb();
+                bitmap.set_pixel(x, y, Color::from_rgb(red, green, blue).color);
+            }
+        }
+        Ok(bitmap)
+    }
+}
+
+/// The resulting bitmap data has to be released with `ffi_buffer_free`.
+///
+/// # Safety
+///
+/// `cmyk_bitmap` must point to a valid `FFICMYKBitmap` with readable pixel data, and `out_bitmap` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn cmyk_bitmap_to_low_quality_rgb(cmyk_bitmap: *const FFICMYKBitmap, out_bitmap: *mut FFIBitmap) -> ErrorCode {
+    ffi_call(|| unsafe {
+        let Some(cmyk_bitmap) = cmyk_bitmap.as_ref() else {
+            return Err(Error::InvalidArgument("Bitmap is null"));
+        };
+        if out_bitmap.is_null() {
+            return Err(Error::InvalidArgument("Output pointer is null"));
+        }
+        let bitmap = cmyk_bitmap.to_cmyk_bitmap()?.to_low_quality_rgb()?;
+        write_ffi_result(out_bitmap, FFIBitmap::from(bitmap))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::imagedecoderplugin::ffi_buffer_free;
+
+    #[test]
+    fn converts_inks_to_low_quality_rgb() {
+        assert_eq!(CMYK { c: 0, m: 0, y: 0, k: 0 }.to_low_quality_rgb(), (255, 255, 255));
+        assert_eq!(CMYK { c: 255, m: 0, y: 0, k: 0 }.to_low_quality_rgb(), (0, 255, 255));
+        assert_eq!(CMYK { c: 0, m: 0, y: 0, k: 255 }.to_low_quality_rgb(), (0, 0, 0));
+        assert_eq!(CMYK { c: 55, m: 155, y: 255, k: 128 }.to_low_quality_rgb(), (100, 50, 0));
+    }
+
+    #[test]
+    fn converts_bitmap_across_the_ffi() {
+        let mut bitmap = CMYKBitmap::new(IntSize { width: 2, height: 1 }).unwrap();
+        bitmap.set_pixel(1, 0, CMYK { c: 0, m: 255, y: 0, k: 0 });
+        let ffi_bitmap = FFICMYKBitmap::from(bitmap);
+        unsafe {
+            assert_eq!(cmyk_bitmap_to_low_quality_rgb(&ffi_bitmap, std::ptr::null_mut()), ErrorCode::InvalidArgument);
+            let mut rgb_bitmap = std::mem::MaybeUninit::uninit();
+            assert_eq!(cmyk_bitmap_to_low_quality_rgb(std::ptr::null(), rgb_bitmap.as_mut_ptr()), ErrorCode::InvalidArgument);
+            assert_eq!(cmyk_bitmap_to_low_quality_rgb(&ffi_bitmap, rgb_bitmap.as_mut_ptr()), ErrorCode::Success);
+            let rgb_bitmap = rgb_bitmap.assume_init();
+            assert!(matches!(rgb_bitmap.format, BitmapFormat::BGRx8888));
+            let converted = rgb_bitmap.to_bitmap().unwrap();
+            assert_eq!([converted.get_pixel(0, 0), converted.get_pixel(1, 0)], [0xFFFFFFFF, 0xFFFF00FF]);
+            assert_eq!(ffi_buffer_free(rgb_bitmap.data), ErrorCode::Success);
+            assert_eq!(ffi_buffer_free(ffi_bitmap.data), ErrorCode::Success);
+        }
+    }
+}
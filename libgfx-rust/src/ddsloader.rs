@@ -0,0 +1,788 @@
+use std::ffi::c_void;
+use crate::imagedecoderplugin::{decoder_into_opaque, ImageDecoderPlugin, ImageFrameDescriptor, NaturalFrameFormat};
+use crate::{ARGB, Color, IntSize};
+use crate::bitmap::{Bitmap, BitmapFormat};
+use crate::error::{Error, ErrorCode, ffi_call, write_ffi_result};
+
+// NOTE: See https://learn.microsoft.com/en-us/windows/win32/direct3ddds/dx-graphics-dds-pguide for the format and
+//       https://learn.microsoft.com/en-us/windows/win32/direct3d10/d3d10-graphics-programming-guide-resources-block-compression
+//       for the block compressed formats.
+const DDS_MAGIC: &[u8; 4] = b"DDS ";
+const DDS_HEADER_SIZE: u32 = 124;
+const DDS_PIXEL_FORMAT_SIZE: u32 = 32;
+const DDS_DX10_HEADER_SIZE: usize = 20;
+
+const DDS_PIXEL_FORMAT_ALPHA_PIXELS: u32 = 0x1;
+const DDS_PIXEL_FORMAT_ALPHA: u32 = 0x2;
+const DDS_PIXEL_FORMAT_FOURCC: u32 = 0x4;
+const DDS_PIXEL_FORMAT_RGB: u32 = 0x40;
+const DDS_PIXEL_FORMAT_LUMINANCE: u32 = 0x20000;
+
+const DDS_CAPS2_CUBEMAP: u32 = 0x200;
+const DDS_CAPS2_CUBEMAP_ALL_FACES: u32 = 0xFC00;
+const DDS_CAPS2_VOLUME: u32 = 0x200000;
+
+const DDS_DX10_DIMENSION_TEXTURE3D: u32 = 4;
+const DDS_DX10_MISC_TEXTURE_CUBE: u32 = 0x4;
+const DDS_DX10_ALPHA_MODE_MASK: u32 = 0x7;
+const DDS_DX10_ALPHA_MODE_PREMULTIPLIED: u32 = 2;
+
+const DDS_BLOCK_SIZE: usize = 4;
+
+#[derive (Debug, Copy, Clone, PartialEq)]
+enum DDSBlockFormat {
+    Bc1,
+    Bc2,
+    Bc3,
+    Bc4,
+    Bc5
+}
+
+/// Where each channel lives in the little-endian pixel value of an uncompressed format.
+#[derive (Debug, Copy, Clone, PartialEq)]
+struct DDSPixelLayout {
+    bits_per_pixel: u32,
+    red_mask: u32,
+    green_mask: u32,
+    blue_mask: u32,
+    alpha_mask: u32,
+    // NOTE: Luminance is stored in the red mask.
+    is_luminance: bool
+}
+
+#[derive (Debug, Copy, Clone, PartialEq)]
+enum DDSFormat {
+    Compressed(DDSBlockFormat),
+    Uncompressed(DDSPixelLayout)
+}
+
+#[derive (Debug, Copy, Clone)]
+struct DDSHeader {
+    width: u32,
+    height: u32,
+    mipmap_count: usize,
+    layer_count: usize,
+    format: DDSFormat,
+    is_alpha_premultiplied: bool,
+    data_offset: usize
+}
+
+pub struct DDSImageDecoderPlugin<'a> {
+    context: DDSLoadingContext<'a>
+}
+
+struct DDSLoadingContext<'a> {
+    header: DDSHeader,
+    bytes: &'a [u8],
+    // NOTE: Keyed by layer and mipmap level, only the surface decoded last is kept around.
+    bitmap: Option<(usize, usize, Bitmap)>
+}
+
+impl DDSBlockFormat {
+    fn block_size_in_bytes(self) -> usize {
+        match self {
+            DDSBlockFormat::Bc1 | DDSBlockFormat::Bc4 => 8,
+            _ => 16
+        }
+    }
+
+    fn decode_block(self, block: &[u8]) -> [ARGB; 16] {
+        let mut pixels = [0; 16];
+        match self {
+            DDSBlockFormat::Bc1 => decode_color_block(block, true, &mut pixels),
+            DDSBlockFormat::Bc2 => {
+                decode_color_block(&block[8..], false, &mut pixels);
+                for (index, pixel) in pixels.iter_mut().enumerate() {
+                    let alpha = (block[index / 2] >> (4 * (index % 2))) & 0xF;
+                    *pixel = Color::from(*pixel).with_alpha(alpha * 17).color;
+                }
+            }
+            DDSBlockFormat::Bc3 => {
+                decode_color_block(&block[8..], false, &mut pixels);
+                let alphas = decode_interpolated_block(&block[..8]);
+                for (pixel, alpha) in pixels.iter_mut().zip(alphas) {
+                    *pixel = Color::from(*pixel).with_alpha(alpha).color;
+                }
+            }
+            // NOTE: BC4 has a single channel, which we show as grayscale.
+            DDSBlockFormat::Bc4 => {
+                let values = decode_interpolated_block(block);
+                for (pixel, value) in pixels.iter_mut().zip(values) {
+                    *pixel = Color::from_rgb(value, value, value).color;
+                }
+            }
+            // NOTE: BC5 usually holds the X and Y of normal maps, we don't try to reconstruct Z into blue.
+            DDSBlockFormat::Bc5 => {
+                let reds = decode_interpolated_block(&block[..8]);
+                let greens = decode_interpolated_block(&block[8..]);
+                for (index, pixel) in pixels.iter_mut().enumerate() {
+                    *pixel = Color::from_rgb(reds[index], greens[index], 0).color;
+                }
+            }
+        }
+        pixels
+    }
+}
+
+impl DDSPixelLayout {
+    fn from_masks(bits_per_pixel: u32, masks: [u32; 4]) -> Self {
+        let [red_mask, green_mask, blue_mask, alpha_mask] = masks;
+        Self { bits_per_pixel, red_mask, green_mask, blue_mask, alpha_mask, is_luminance: false }
+    }
+
+    fn color(&self, value: u32) -> Color {
+        let channel = |mask: u32| {
+            if mask == 0 {
+                return None;
+            }
+            let shift = mask.trailing_zeros();
+            let maximum = (mask >> shift) as u64;
+            Some(((((value & mask) >> shift) as u64 * 255 + maximum / 2) / maximum) as u8)
+        };
+
+        let red = channel(self.red_mask).unwrap_or(0);
+        let (green, blue) = if self.is_luminance {
+            (red, red)
+        } else {
+            (channel(self.green_mask).unwrap_or(0), channel(self.blue_mask).unwrap_or(0))
+        };
+        Color::from_rgba(red, green, blue, channel(self.alpha_mask).unwrap_or(255))
+    }
+}
+
+impl DDSFormat {
+    fn from_dxgi_format(dxgi_format: u32) -> Result<Self, Error> {
+        Ok(match dxgi_format {
+            // DXGI_FORMAT_R10G10B10A2_TYPELESS and _UNORM
+            23 | 24 => DDSFormat::Uncompressed(DDSPixelLayout::from_masks(32, [0x3FF, 0xFFC00, 0x3FF00000, 0xC0000000])),
+            // DXGI_FORMAT_R8G8B8A8_TYPELESS, _UNORM and _UNORM_SRGB
+            27..=29 => DDSFormat::Uncompressed(DDSPixelLayout::from_masks(32, [0xFF, 0xFF00, 0xFF0000, 0xFF000000])),
+            // DXGI_FORMAT_R8_TYPELESS and _UNORM
+            60 | 61 => DDSFormat::Uncompressed(DDSPixelLayout { is_luminance: true, ..DDSPixelLayout::from_masks(8, [0xFF, 0, 0, 0]) }),
+            // DXGI_FORMAT_A8_UNORM
+            65 => DDSFormat::Uncompressed(DDSPixelLayout::from_masks(8, [0, 0, 0, 0xFF])),
+            // DXGI_FORMAT_BC1_TYPELESS through DXGI_FORMAT_BC5_UNORM, skipping the signed BC4 and BC5 variants
+            70..=72 => DDSFormat::Compressed(DDSBlockFormat::Bc1),
+            73..=75 => DDSFormat::Compressed(DDSBlockFormat::Bc2),
+            76..=78 => DDSFormat::Compressed(DDSBlockFormat::Bc3),
+            79 | 80 => DDSFormat::Compressed(DDSBlockFormat::Bc4),
+            82 | 83 => DDSFormat::Compressed(DDSBlockFormat::Bc5),
+            // DXGI_FORMAT_B5G6R5_UNORM
+            85 => DDSFormat::Uncompressed(DDSPixelLayout::from_masks(16, [0xF800, 0x7E0, 0x1F, 0])),
+            // DXGI_FORMAT_B5G5R5A1_UNORM
+            86 => DDSFormat::Uncompressed(DDSPixelLayout::from_masks(16, [0x7C00, 0x3E0, 0x1F, 0x8000])),
+            // DXGI_FORMAT_B8G8R8A8_UNORM, _TYPELESS and _UNORM_SRGB
+            87 | 90 | 91 => DDSFormat::Uncompressed(DDSPixelLayout::from_masks(32, [0xFF0000, 0xFF00, 0xFF, 0xFF000000])),
+            // DXGI_FORMAT_B8G8R8X8_UNORM, _TYPELESS and _UNORM_SRGB
+            88 | 92 | 93 => DDSFormat::Uncompressed(DDSPixelLayout::from_masks(32, [0xFF0000, 0xFF00, 0xFF, 0])),
+            // DXGI_FORMAT_B4G4R4A4_UNORM
+            115 => DDSFormat::Uncompressed(DDSPixelLayout::from_masks(16, [0xF00, 0xF0, 0xF, 0xF000])),
+            _ => return Err(Error::Unsupported("DDS DXGI format"))
+        })
+    }
+
+    fn has_alpha(&self) -> bool {
+        match self {
+            DDSFormat::Compressed(block_format) => matches!(block_format, DDSBlockFormat::Bc1 | DDSBlockFormat::Bc2 | DDSBlockFormat::Bc3),
+            DDSFormat::Uncompressed(layout) => layout.alpha_mask != 0
+        }
+    }
+
+    fn surface_size_in_bytes(&self, size: IntSize) -> usize {
+        let width = size.width as usize;
+        let height = size.height as usize;
+        match self {
+            DDSFormat::Compressed(block_format) => {
+                width.div_ceil(DDS_BLOCK_SIZE) * height.div_ceil(DDS_BLOCK_SIZE) * block_format.block_size_in_bytes()
+            }
+            DDSFormat::Uncompressed(layout) => width * height * (layout.bits_per_pixel as usize / 8)
+        }
+    }
+}
+
+fn rgb565_to_color(value: u16) -> Color {
+    let red = (value >> 11) as u8 & 0x1F;
+    let green = (value >> 5) as u8 & 0x3F;
+    let blue = value as u8 & 0x1F;
+    Color::from_rgb((red << 3) | (red >> 2), (green << 2) | (green >> 4), (blue << 3) | (blue >> 2))
+}
+
+/// Decodes the BC1 style color part of a block. Only BC1 itself has a mode with transparency.
+fn decode_color_block(block: &[u8], has_transparent_mode: bool, pixels: &mut [ARGB; 16]) {
+    let value0 = u16::from_le_bytes([block[0], block[1]]);
+    let value1 = u16::from_le_bytes([block[2], block[3]]);
+    let color0 = rgb565_to_color(value0);
+    let color1 = rgb565_to_color(value1);
+    let interpolate = |weight0: u32, weight1: u32| {
+        let mix = |channel0: u8, channel1: u8| {
+            ((channel0 as u32 * weight0 + channel1 as u32 * weight1 + (weight0 + weight1) / 2) / (weight0 + weight1)) as u8
+        };
+        Color::from_rgb(mix(color0.red(), color1.red()), mix(color0.green(), color1.green()), mix(color0.blue(), color1.blue()))
+    };
+
+    let palette = if !has_transparent_mode || value0 > value1 {
+        [color0, color1, interpolate(2, 1), interpolate(1, 2)]
+    } else {
+        [color0, color1, interpolate(1, 1), Color::new()]
+    };
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+    for (index, pixel) in pixels.iter_mut().enumerate() {
+        *pixel = palette[(indices >> (2 * index)) as usize & 3].color;
+    }
+}
+
+/// Decodes a BC3 alpha block, which is also how BC4 and BC5 store their channels.
+fn decode_interpolated_block(block: &[u8]) -> [u8; 16] {
+    let value0 = block[0] as u32;
+    let value1 = block[1] as u32;
+    let mut palette = [block[0], block[1], 0, 0, 0, 0, 0, 255];
+    if value0 > value1 {
+        for step in 1..7 {
+            palette[step as usize + 1] = (((7 - step) * value0 + step * value1 + 3) / 7) as u8;
+        }
+    } else {
+        for step in 1..5 {
+            palette[step as usize + 1] = (((5 - step) * value0 + step * value1 + 2) / 5) as u8;
+        }
+    }
+
+    let mut index_bytes = [0u8; 8];
+    index_bytes[..6].copy_from_slice(&block[2..8]);
+    let indices = u64::from_le_bytes(index_bytes);
+    std::array::from_fn(|index| palette[(indices >> (3 * index)) as usize & 7])
+}
+
+impl<'a> DDSImageDecoderPlugin<'a> {
+    pub fn sniff(bytes: &[u8]) -> bool {
+        bytes.starts_with(DDS_MAGIC)
+    }
+
+    pub fn create(bytes: &'a [u8]) -> Result<Self, Error> {
+        if bytes.len() < DDS_MAGIC.len() + DDS_HEADER_SIZE as usize {
+            return Err(Error::Truncated);
+        }
+        if !Self::sniff(bytes) {
+            return Err(Error::InvalidHeader("Invalid DDS magic"));
+        }
+
+        let read_u32 = |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+        if read_u32(4) != DDS_HEADER_SIZE || read_u32(76) != DDS_PIXEL_FORMAT_SIZE {
+            return Err(Error::InvalidHeader("Invalid DDS header size"));
+        }
+
+        let height = read_u32(12);
+        let width = read_u32(16);
+        let depth = read_u32(24);
+        let mipmap_count = read_u32(28).max(1) as usize;
+        let pixel_format_flags = read_u32(80);
+        let caps2 = read_u32(112);
+        if width == 0 || height == 0 {
+            return Err(Error::InvalidHeader("Invalid DDS dimensions"));
+        }
+        Bitmap::check_dimensions(width, height)?;
+        if mipmap_count > (32 - width.max(height).leading_zeros()) as usize {
+            return Err(Error::InvalidHeader("Invalid DDS mipmap count"));
+        }
+
+        let mut data_offset = DDS_MAGIC.len() + DDS_HEADER_SIZE as usize;
+        let mut is_alpha_premultiplied = false;
+        let mut layer_count = 1;
+        let format = if pixel_format_flags & DDS_PIXEL_FORMAT_FOURCC != 0 {
+            match &bytes[84..88] {
+                b"DXT1" => DDSFormat::Compressed(DDSBlockFormat::Bc1),
+                b"DXT2" => {
+                    is_alpha_premultiplied = true;
+                    DDSFormat::Compressed(DDSBlockFormat::Bc2)
+                }
+                b"DXT3" => DDSFormat::Compressed(DDSBlockFormat::Bc2),
+                b"DXT4" => {
+                    is_alpha_premultiplied = true;
+                    DDSFormat::Compressed(DDSBlockFormat::Bc3)
+                }
+                b"DXT5" => DDSFormat::Compressed(DDSBlockFormat::Bc3),
+                b"ATI1" | b"BC4U" => DDSFormat::Compressed(DDSBlockFormat::Bc4),
+                b"ATI2" | b"BC5U" => DDSFormat::Compressed(DDSBlockFormat::Bc5),
+                b"DX10" => {
+                    if bytes.len() < data_offset + DDS_DX10_HEADER_SIZE {
+                        return Err(Error::Truncated);
+                    }
+                    let format = DDSFormat::from_dxgi_format(read_u32(data_offset))?;
+                    if read_u32(data_offset + 4) == DDS_DX10_DIMENSION_TEXTURE3D {
+                        return Err(Error::Unsupported("DDS volume textures"));
+                    }
+                    let faces = if read_u32(data_offset + 8) & DDS_DX10_MISC_TEXTURE_CUBE != 0 { 6 } else { 1 };
+                    let array_size = read_u32(data_offset + 12) as usize;
+                    if array_size == 0 {
+                        return Err(Error::InvalidHeader("Invalid DDS array size"));
+                    }
+                    layer_count = array_size * faces;
+                    is_alpha_premultiplied = read_u32(data_offset + 16) & DDS_DX10_ALPHA_MODE_MASK == DDS_DX10_ALPHA_MODE_PREMULTIPLIED;
+                    data_offset += DDS_DX10_HEADER_SIZE;
+                    format
+                }
+                _ => return Err(Error::Unsupported("DDS FourCC"))
+            }
+        } else if pixel_format_flags & (DDS_PIXEL_FORMAT_RGB | DDS_PIXEL_FORMAT_LUMINANCE | DDS_PIXEL_FORMAT_ALPHA) != 0 {
+            let bits_per_pixel = read_u32(88);
+            if !matches!(bits_per_pixel, 8 | 16 | 24 | 32) {
+                return Err(Error::Unsupported("DDS bit depth"));
+            }
+            let has_alpha = pixel_format_flags & (DDS_PIXEL_FORMAT_ALPHA_PIXELS | DDS_PIXEL_FORMAT_ALPHA) != 0;
+            // NOTE: Alpha-only formats have no color, whatever their other masks say.
+            let has_color = pixel_format_flags & DDS_PIXEL_FORMAT_ALPHA == 0;
+            let mask = |offset: usize, is_present: bool| if is_present { read_u32(offset) } else { 0 };
+            DDSFormat::Uncompressed(DDSPixelLayout {
+                is_luminance: pixel_format_flags & DDS_PIXEL_FORMAT_LUMINANCE != 0,
+                ..DDSPixelLayout::from_masks(bits_per_pixel, [mask(92, has_color), mask(96, has_color), mask(100, has_color), mask(104, has_alpha)])
+            })
+        } else {
+            return Err(Error::Unsupported("DDS pixel format"));
+        };
+
+        if caps2 & DDS_CAPS2_VOLUME != 0 && depth > 1 {
+            return Err(Error::Unsupported("DDS volume textures"));
+        }
+        // NOTE: Cube map faces are stored as the layers of DX10 files are, each with their own mipmaps.
+        if caps2 & DDS_CAPS2_CUBEMAP != 0 && layer_count == 1 {
+            if caps2 & DDS_CAPS2_CUBEMAP_ALL_FACES != DDS_CAPS2_CUBEMAP_ALL_FACES {
+                return Err(Error::Unsupported("DDS cube maps without all faces"));
+            }
+            layer_count = 6;
+        }
+
+        let context = DDSLoadingContext {
+            header: DDSHeader {
+                width,
+                height,
+                mipmap_count,
+                layer_count,
+                format,
+                is_alpha_premultiplied,
+                data_offset
+            },
+            bytes,
+            bitmap: None
+        };
+        let data_size = context.layer_size_in_bytes().checked_mul(layer_count);
+        if data_size.is_none_or(|data_size| data_size > bytes.len() - data_offset) {
+            return Err(Error::Truncated);
+        }
+
+        Ok(Self { context })
+    }
+
+    /// The number of mipmap levels in the file, `frame_with_ideal_size` picks between them.
+    pub fn mipmap_count(&self) -> usize {
+        self.context.header.mipmap_count
+    }
+}
+
+impl<'a> DDSLoadingContext<'a> {
+    fn level_size(&self, level: usize) -> IntSize {
+        IntSize {
+            width: (self.header.width >> level).max(1) as i32,
+            height: (self.header.height >> level).max(1) as i32
+        }
+    }
+
+    fn layer_size_in_bytes(&self) -> usize {
+        (0..self.header.mipmap_count).map(|level| self.header.format.surface_size_in_bytes(self.level_size(level))).sum()
+    }
+
+    fn level_for_ideal_size(&self, ideal_size: Option<IntSize>) -> usize {
+        let Some(ideal_size) = ideal_size.filter(|size| !size.is_empty()) else {
+            return 0;
+        };
+        // NOTE: Pick the smallest mipmap level that still covers the requested size.
+        (1..self.header.mipmap_count).rev()
+            .find(|&level| {
+                let size = self.level_size(level);
+                size.width >= ideal_size.width && size.height >= ideal_size.height
+            })
+            .unwrap_or(0)
+    }
+
+    fn decode_surface(&self, layer: usize, level: usize) -> Result<Bitmap, Error> {
+        let format = self.header.format;
+        let size = self.level_size(level);
+        let level_offset: usize = (0..level).map(|level| format.surface_size_in_bytes(self.level_size(level))).sum();
+        let offset = self.header.data_offset + layer * self.layer_size_in_bytes() + level_offset;
+        let data = &self.bytes[offset..offset + format.surface_size_in_bytes(size)];
+
+        let bitmap_format = if format.has_alpha() { BitmapFormat::BGRA8888 } else { BitmapFormat::BGRx8888 };
+        let mut bitmap = Bitmap::new(bitmap_format, size, 1)?;
+        let mut set_pixel = |x: usize, y: usize, color: Color| {
+            let color = if self.header.is_alpha_premultiplied { color.unpremultiplied() } else { color };
+            bitmap.set_pixel(x as i32, y as i32, color.color);
+        };
+
+        let width = size.width as usize;
+        let height = size.height as usize;
+        match format {
+            DDSFormat::Compressed(block_format) => {
+                let blocks_across = width.div_ceil(DDS_BLOCK_SIZE);
+                for (block_index, block) in data.chunks_exact(block_format.block_size_in_bytes()).enumerate() {
+                    let block_x = (block_index % blocks_across) * DDS_BLOCK_SIZE;
+                    let block_y = (block_index / blocks_across) * DDS_BLOCK_SIZE;
+                    for (pixel_index, pixel) in block_format.decode_block(block).into_iter().enumerate() {
+                        let x = block_x + pixel_index % DDS_BLOCK_SIZE;
+                        let y = block_y + pixel_index / DDS_BLOCK_SIZE;
+                        if x < width && y < height {
+                            set_pixel(x, y, Color::from(pixel));
+                        }
+                    }
+                }
+            }
+            DDSFormat::Uncompressed(layout) => {
+                let bytes_per_pixel = layout.bits_per_pixel as usize / 8;
+                for (index, pixel) in data.chunks_exact(bytes_per_pixel).enumerate() {
+                    let value = pixel.iter().rev().fold(0u32, |value, byte| (value << 8) | *byte as u32);
+                    set_pixel(index % width, index / width, layout.color(value));
+                }
+            }
+        }
+
+        Ok(bitmap)
+    }
+}
+
+impl<'a> ImageDecoderPlugin for DDSImageDecoderPlugin<'a> {
+    fn size(&self) -> IntSize {
+        self.context.level_size(0)
+    }
+
+    // NOTE: Cube map faces and texture array layers are exposed as frames, mipmap levels are picked by ideal size.
+    fn frame_count(&self) -> usize {
+        self.context.header.layer_count
+    }
+
+    fn frame_with_ideal_size(&mut self, index: usize, ideal_size: Option<IntSize>) -> Result<ImageFrameDescriptor, Error> {
+        if index >= self.context.header.layer_count {
+            return Err(Error::FrameIndexOutOfRange);
+        }
+
+        let level = self.context.level_for_ideal_size(ideal_size);
+        if let Some((bitmap_index, bitmap_level, bitmap)) = &self.context.bitmap {
+            if *bitmap_index == index && *bitmap_level == level {
+                return Ok(ImageFrameDescriptor {
+                    image: bitmap.clone(),
+                    duration: 0
+                });
+            }
+        }
+
+        let bitmap = self.context.decode_surface(index, level)?;
+        self.context.bitmap = Some((index, level, bitmap.clone()));
+
+        Ok(ImageFrameDescriptor {
+            image: bitmap,
+            duration: 0
+        })
+    }
+
+    fn natural_frame_format(&self) -> NaturalFrameFormat {
+        match self.context.header.format {
+            DDSFormat::Compressed(DDSBlockFormat::Bc4) => NaturalFrameFormat::Grayscale,
+            DDSFormat::Uncompressed(layout) if layout.is_luminance => NaturalFrameFormat::Grayscale,
+            _ => NaturalFrameFormat::RGB
+        }
+    }
+}
+
+/// # Safety
+///
+/// `bytes` must point to `size` readable bytes that outlive the returned decoder,
+/// and `out_decoder` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn dds_image_decoder_plugin_new(bytes: *const u8, size: usize, out_decoder: *mut *mut c_void) -> ErrorCode {
+    ffi_call(|| unsafe {
+        if bytes.is_null() {
+            return Err(Error::InvalidArgument("Bytes are null"));
+        }
+        if out_decoder.is_null() {
+            return Err(Error::InvalidArgument("Output pointer is null"));
+        }
+        let bytes = std::slice::from_raw_parts(bytes, size);
+        let decoder = DDSImageDecoderPlugin::create(bytes)?;
+        write_ffi_result(out_decoder, decoder_into_opaque(Box::new(decoder)))
+    })
+}
+
+/// # Safety
+///
+/// `opaque_decoder` must either be null or come from `dds_image_decoder_plugin_new` and not be freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn dds_image_decoder_plugin_free(opaque_decoder: *mut c_void) -> ErrorCode {
+    ffi_call(|| {
+        if !opaque_decoder.is_null() {
+            let decoder: Box<Box<dyn ImageDecoderPlugin>> = unsafe { Box::from_raw(opaque_decoder as *mut _) };
+            drop(decoder);
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testsupport::assert_pixels;
+
+    const RED: [u8; 4] = [255, 0, 0, 255];
+    const BLUE: [u8; 4] = [0, 0, 255, 255];
+
+    // NOTE: The interpolated palettes for endpoints 255 and 0 in the eight value mode and 0 and 255 in the six value mode.
+    const EIGHT_VALUE_PALETTE: [u8; 8] = [255, 0, 219, 182, 146, 109, 73, 36];
+    const SIX_VALUE_PALETTE: [u8; 8] = [0, 255, 51, 102, 153, 204, 0, 255];
+
+    /// Builds a DDS file, `pixel_format` holds the flags, FourCC, bit count and red, green, blue and alpha masks.
+    fn dds_file(width: u32, height: u32, mipmap_count: u32, caps2: u32, pixel_format: [u32; 7], data: &[u8]) -> Vec<u8> {
+        let mut header = [0u32; 31];
+        header[0] = DDS_HEADER_SIZE;
+        header[1] = 0x1007;
+        header[2] = height;
+        header[3] = width;
+        header[6] = mipmap_count;
+        header[18] = DDS_PIXEL_FORMAT_SIZE;
+        header[19..26].copy_from_slice(&pixel_format);
+        header[26] = 0x1000;
+        header[27] = caps2;
+
+        let mut bytes = DDS_MAGIC.to_vec();
+        bytes.extend(header.iter().flat_map(|value| value.to_le_bytes()));
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    fn fourcc(fourcc: &[u8; 4]) -> [u32; 7] {
+        [DDS_PIXEL_FORMAT_FOURCC, u32::from_le_bytes(*fourcc), 0, 0, 0, 0, 0]
+    }
+
+    fn masks(flags: u32, bits_per_pixel: u32, [red, green, blue, alpha]: [u32; 4]) -> [u32; 7] {
+        [flags, 0, bits_per_pixel, red, green, blue, alpha]
+    }
+
+    fn dx10_file(width: u32, height: u32, [dxgi_format, dimension, misc_flag, array_size, alpha_mode]: [u32; 5], data: &[u8]) -> Vec<u8> {
+        let mut dx10_data: Vec<u8> = [dxgi_format, dimension, misc_flag, array_size, alpha_mode].iter().flat_map(|value| value.to_le_bytes()).collect();
+        dx10_data.extend_from_slice(data);
+        dds_file(width, height, 1, 0, fourcc(b"DX10"), &dx10_data)
+    }
+
+    /// A BC1 style color block, with `row_indices` used for every row.
+    fn color_block(color0: u16, color1: u16, row_indices: u8) -> [u8; 8] {
+        let [color0_low, color0_high] = color0.to_le_bytes();
+        let [color1_low, color1_high] = color1.to_le_bytes();
+        [color0_low, color0_high, color1_low, color1_high, row_indices, row_indices, row_indices, row_indices]
+    }
+
+    /// A BC3 style alpha block, where pixel `index` uses entry `index % 8` of the palette.
+    fn interpolated_block(value0: u8, value1: u8) -> [u8; 8] {
+        let indices = (0..16u64).fold(0u64, |indices, index| indices | ((index % 8) << (3 * index)));
+        let mut block = [value0, value1, 0, 0, 0, 0, 0, 0];
+        block[2..].copy_from_slice(&indices.to_le_bytes()[..6]);
+        block
+    }
+
+    fn decode(bytes: &[u8]) -> Bitmap {
+        DDSImageDecoderPlugin::create(bytes).unwrap().frame(0).unwrap().image
+    }
+
+    #[test]
+    fn decodes_bc1_with_and_without_transparency() {
+        // NOTE: 6x4, so the second block is cut off. It swaps the endpoints to select the three color mode, and
+        //       walks the palette backwards, leaving only the transparent and the halfway entries visible.
+        let mut data = color_block(0xF800, 0x001F, 0xE4).to_vec();
+        data.extend_from_slice(&color_block(0x001F, 0xF800, 0x1B));
+        let image = decode(&dds_file(6, 4, 1, 0, fourcc(b"DXT1"), &data));
+        assert!(matches!(image.format, BitmapFormat::BGRA8888));
+        assert_pixels(&image, 6, 4, |x, _| [RED, BLUE, [170, 0, 85, 255], [85, 0, 170, 255], [0, 0, 0, 0], [128, 0, 128, 255]][x as usize]);
+    }
+
+    #[test]
+    fn decodes_bc2_explicit_alpha() {
+        // NOTE: BC2 always uses four colors, even when the endpoints would select the three color mode in BC1.
+        let mut data = vec![0x10, 0x32, 0x54, 0x76, 0x98, 0xBA, 0xDC, 0xFE];
+        data.extend_from_slice(&color_block(0x001F, 0xF800, 0xE4));
+        assert_pixels(&decode(&dds_file(4, 4, 1, 0, fourcc(b"DXT3"), &data)), 4, 4, |x, y| {
+            let [red, green, blue, _] = [BLUE, RED, [85, 0, 170, 255], [170, 0, 85, 255]][x as usize];
+            [red, green, blue, ((y * 4 + x) * 17) as u8]
+        });
+    }
+
+    #[test]
+    fn decodes_bc3_interpolated_alpha() {
+        let mut data = interpolated_block(255, 0).to_vec();
+        data.extend_from_slice(&color_block(0xF800, 0, 0));
+        data.extend_from_slice(&interpolated_block(0, 255));
+        data.extend_from_slice(&color_block(0xF800, 0, 0));
+        assert_pixels(&decode(&dds_file(8, 4, 1, 0, fourcc(b"DXT5"), &data)), 8, 4, |x, y| {
+            let palette = if x < 4 { EIGHT_VALUE_PALETTE } else { SIX_VALUE_PALETTE };
+            [255, 0, 0, palette[((y * 4 + x % 4) % 8) as usize]]
+        });
+    }
+
+    #[test]
+    fn decodes_bc4_as_grayscale() {
+        let file = dds_file(4, 4, 1, 0, fourcc(b"ATI1"), &interpolated_block(255, 0));
+        let mut decoder = DDSImageDecoderPlugin::create(&file).unwrap();
+        assert_eq!(decoder.natural_frame_format(), NaturalFrameFormat::Grayscale);
+        let image = decoder.frame(0).unwrap().image;
+        assert!(matches!(image.format, BitmapFormat::BGRx8888));
+        assert_pixels(&image, 4, 4, |x, y| {
+            let value = EIGHT_VALUE_PALETTE[((y * 4 + x) % 8) as usize];
+            [value, value, value, 255]
+        });
+    }
+
+    #[test]
+    fn decodes_bc5_into_red_and_green() {
+        let mut data = interpolated_block(255, 0).to_vec();
+        data.extend_from_slice(&interpolated_block(0, 255));
+        assert_pixels(&decode(&dds_file(4, 4, 1, 0, fourcc(b"ATI2"), &data)), 4, 4, |x, y| {
+            let index = ((y * 4 + x) % 8) as usize;
+            [EIGHT_VALUE_PALETTE[index], SIX_VALUE_PALETTE[index], 0, 255]
+        });
+    }
+
+    #[test]
+    fn unpremultiplies_dxt2_and_dxt4() {
+        // NOTE: The first pixel is fully transparent, the others have a 136 alpha over the (132, 130, 132) endpoint.
+        let mut dxt2_data = vec![0x80, 0x88, 0x88, 0x88, 0x88, 0x88, 0x88, 0x88];
+        dxt2_data.extend_from_slice(&color_block(0x8410, 0, 0));
+        let mut dxt4_data = vec![136, 0, 0x01, 0, 0, 0, 0, 0];
+        dxt4_data.extend_from_slice(&color_block(0x8410, 0, 0));
+
+        for (fourcc_code, data) in [(b"DXT2", dxt2_data), (b"DXT4", dxt4_data)] {
+            assert_pixels(&decode(&dds_file(4, 4, 1, 0, fourcc(fourcc_code), &data)), 4, 4, |x, y| {
+                if x == 0 && y == 0 { [0, 0, 0, 0] } else { [248, 244, 248, 136] }
+            });
+        }
+    }
+
+    #[test]
+    fn decodes_uncompressed_mask_layouts() {
+        let image = decode(&dds_file(2, 1, 1, 0, masks(0x41, 16, [0xF00, 0xF0, 0xF, 0xF000]), &[0x0A, 0x8F, 0x23, 0xF1]));
+        assert!(matches!(image.format, BitmapFormat::BGRA8888));
+        assert_pixels(&image, 2, 1, |x, _| [[255, 0, 170, 136], [17, 34, 51, 255]][x as usize]);
+
+        let image = decode(&dds_file(2, 1, 1, 0, masks(0x40, 16, [0xF800, 0x7E0, 0x1F, 0]), &[0xE0, 0xFF, 0x1F, 0x00]));
+        assert!(matches!(image.format, BitmapFormat::BGRx8888));
+        assert_pixels(&image, 2, 1, |x, _| [[255, 255, 0, 255], BLUE][x as usize]);
+
+        let image = decode(&dds_file(1, 1, 1, 0, masks(0x40, 24, [0xFF0000, 0xFF00, 0xFF, 0]), &[0x30, 0x20, 0x10]));
+        assert_pixels(&image, 1, 1, |_, _| [0x10, 0x20, 0x30, 255]);
+
+        let file = dds_file(1, 1, 1, 0, masks(0x20000, 8, [0xFF, 0, 0, 0]), &[0x7F]);
+        let mut decoder = DDSImageDecoderPlugin::create(&file).unwrap();
+        assert_eq!(decoder.natural_frame_format(), NaturalFrameFormat::Grayscale);
+        assert_pixels(&decoder.frame(0).unwrap().image, 1, 1, |_, _| [0x7F, 0x7F, 0x7F, 255]);
+
+        // NOTE: Alpha-only, with color masks that should be ignored.
+        let image = decode(&dds_file(1, 1, 1, 0, masks(0x2, 8, [0xFF, 0xFF, 0xFF, 0xFF]), &[0x40]));
+        assert_pixels(&image, 1, 1, |_, _| [0, 0, 0, 0x40]);
+    }
+
+    #[test]
+    fn decodes_dx10_header() {
+        let layers = [0x10, 0x20, 0x30, 0x80, 0xFF, 0x00, 0x00, 0xFF];
+        let file = dx10_file(1, 1, [28, 3, 0, 2, 1], &layers);
+        let mut decoder = DDSImageDecoderPlugin::create(&file).unwrap();
+        assert_eq!(decoder.frame_count(), 2);
+        assert_pixels(&decoder.frame(0).unwrap().image, 1, 1, |_, _| [0x10, 0x20, 0x30, 0x80]);
+        assert_pixels(&decoder.frame(1).unwrap().image, 1, 1, |_, _| RED);
+        assert_eq!(decoder.frame(2).err(), Some(Error::FrameIndexOutOfRange));
+
+        let image = decode(&dx10_file(1, 1, [28, 3, 0, 1, DDS_DX10_ALPHA_MODE_PREMULTIPLIED], &layers[..4]));
+        assert_pixels(&image, 1, 1, |_, _| [32, 64, 96, 128]);
+
+        let image = decode(&dx10_file(4, 4, [71, 3, 0, 1, 0], &color_block(0xF800, 0x001F, 0xE4)));
+        assert_pixels(&image, 4, 4, |x, _| [RED, BLUE, [170, 0, 85, 255], [85, 0, 170, 255]][x as usize]);
+
+        assert_eq!(DDSImageDecoderPlugin::create(&dx10_file(1, 1, [2, 3, 0, 1, 0], &[0; 16])).err(), Some(Error::Unsupported("DDS DXGI format")));
+        assert_eq!(DDSImageDecoderPlugin::create(&dx10_file(1, 1, [28, 4, 0, 1, 0], &layers)).err(), Some(Error::Unsupported("DDS volume textures")));
+        assert_eq!(DDSImageDecoderPlugin::create(&dx10_file(1, 1, [28, 3, 0, 0, 0], &layers)).err(), Some(Error::InvalidHeader("Invalid DDS array size")));
+    }
+
+    #[test]
+    fn picks_mipmap_level_by_ideal_size() {
+        let mut data = [10; 16].to_vec();
+        data.extend_from_slice(&[20; 4]);
+        data.extend_from_slice(&[30; 2]);
+        data.push(40);
+        let file = dds_file(8, 2, 4, 0, masks(0x20000, 8, [0xFF, 0, 0, 0]), &data);
+        let mut decoder = DDSImageDecoderPlugin::create(&file).unwrap();
+        assert_eq!(decoder.mipmap_count(), 4);
+        assert_eq!(decoder.frame_count(), 1);
+
+        for (ideal_size, (width, height, value)) in [
+            (None, (8, 2, 10)),
+            (Some((0, 0)), (8, 2, 10)),
+            (Some((8, 2)), (8, 2, 10)),
+            (Some((5, 1)), (8, 2, 10)),
+            (Some((3, 3)), (8, 2, 10)),
+            (Some((4, 1)), (4, 1, 20)),
+            (Some((3, 1)), (4, 1, 20)),
+            (Some((2, 1)), (2, 1, 30)),
+            (Some((1, 1)), (1, 1, 40))
+        ] {
+            let ideal_size = ideal_size.map(|(width, height)| IntSize { width, height });
+            let image = decoder.frame_with_ideal_size(0, ideal_size).unwrap().image;
+            assert_pixels(&image, width, height, |_, _| [value, value, value, 255]);
+        }
+
+        assert_eq!(
+            DDSImageDecoderPlugin::create(&dds_file(8, 2, 5, 0, masks(0x20000, 8, [0xFF, 0, 0, 0]), &data)).err(),
+            Some(Error::InvalidHeader("Invalid DDS mipmap count"))
+        );
+    }
+
+    #[test]
+    fn exposes_cube_map_faces_and_array_layers_as_frames() {
+        // NOTE: Each 2x2 face is followed by its own 1x1 mipmap.
+        let data: Vec<u8> = (0..6u8).flat_map(|face| [face * 40, face * 40, face * 40, face * 40, face * 40 + 1]).collect();
+        let luminance = masks(0x20000, 8, [0xFF, 0, 0, 0]);
+        let file = dds_file(2, 2, 2, DDS_CAPS2_CUBEMAP | DDS_CAPS2_CUBEMAP_ALL_FACES, luminance, &data);
+        let mut decoder = DDSImageDecoderPlugin::create(&file).unwrap();
+        assert_eq!(decoder.frame_count(), 6);
+        for face in 0..6u8 {
+            let value = face * 40;
+            assert_pixels(&decoder.frame(face as usize).unwrap().image, 2, 2, |_, _| [value, value, value, 255]);
+            let image = decoder.frame_with_ideal_size(face as usize, Some(IntSize { width: 1, height: 1 })).unwrap().image;
+            assert_pixels(&image, 1, 1, |_, _| [value + 1, value + 1, value + 1, 255]);
+        }
+        assert_eq!(decoder.frame(6).err(), Some(Error::FrameIndexOutOfRange));
+
+        assert_eq!(
+            DDSImageDecoderPlugin::create(&dds_file(2, 2, 2, DDS_CAPS2_CUBEMAP | 0x400, luminance, &data)).err(),
+            Some(Error::Unsupported("DDS cube maps without all faces"))
+        );
+
+        // NOTE: A DX10 cube map array, with two cubes of BC4 faces that are each filled with a single value.
+        let data: Vec<u8> = (0..12u8).flat_map(|layer| [layer * 20, layer * 20, 0, 0, 0, 0, 0, 0]).collect();
+        let file = dx10_file(4, 4, [80, 3, DDS_DX10_MISC_TEXTURE_CUBE, 2, 0], &data);
+        let mut decoder = DDSImageDecoderPlugin::create(&file).unwrap();
+        assert_eq!(decoder.frame_count(), 12);
+        for layer in 0..12u8 {
+            let value = layer * 20;
+            assert_pixels(&decoder.frame(layer as usize).unwrap().image, 4, 4, |_, _| [value, value, value, 255]);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_files() {
+        let data = dds_file(4, 4, 1, 0, fourcc(b"DXT1"), &color_block(0xF800, 0x001F, 0xE4));
+        assert_eq!(DDSImageDecoderPlugin::create(&data[..100]).err(), Some(Error::Truncated));
+        assert_eq!(DDSImageDecoderPlugin::create(&data[..data.len() - 1]).err(), Some(Error::Truncated));
+
+        let mut invalid_data = data.clone();
+        invalid_data[4] = 0;
+        assert_eq!(DDSImageDecoderPlugin::create(&invalid_data).err(), Some(Error::InvalidHeader("Invalid DDS header size")));
+
+        let mut invalid_data = data.clone();
+        invalid_data[84..88].copy_from_slice(b"DXT9");
+        assert_eq!(DDSImageDecoderPlugin::create(&invalid_data).err(), Some(Error::Unsupported("DDS FourCC")));
+
+        unsafe {
+            assert_eq!(dds_image_decoder_plugin_new(data.as_ptr(), data.len(), std::ptr::null_mut()), ErrorCode::InvalidArgument);
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use std::any::Any;
+use std::cell::RefCell;
+use std::ffi::{c_char, CString};
+use std::fmt;
+use std::panic::AssertUnwindSafe;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Error {
+    InvalidArgument(&'static str),
+    InvalidHeader(&'static str),
+    InvalidData(&'static str),
+    Truncated,
+    Unsupported(&'static str),
+    SizeOverflow,
+    OutOfMemory,
+    FrameIndexOutOfRange,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidArgument(reason) => write!(f, "Invalid argument: {}", reason),
+            Error::InvalidHeader(reason) => write!(f, "Invalid header: {}", reason),
+            Error::InvalidData(reason) => write!(f, "Invalid data: {}", reason),
+            Error::Truncated => write!(f, "Unexpected end of data"),
+            Error::Unsupported(feature) => write!(f, "Unsupported: {}", feature),
+            Error::SizeOverflow => write!(f, "Size would overflow"),
+            Error::OutOfMemory => write!(f, "Out of memory"),
+            Error::FrameIndexOutOfRange => write!(f, "Frame index out of range"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<std::io::Error> for Error {
+    fn from(_: std::io::Error) -> Self {
+        // NOTE: We only ever read from in-memory buffers, so the only failure is running out of data.
+        Error::Truncated
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorCode {
+    Success = 0,
+    InvalidArgument,
+    InvalidHeader,
+    InvalidData,
+    Truncated,
+    Unsupported,
+    SizeOverflow,
+    OutOfMemory,
+    FrameIndexOutOfRange,
+    Panic,
+}
+
+impl From<Error> for ErrorCode {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::InvalidArgument(_) => ErrorCode::InvalidArgument,
+            Error::InvalidHeader(_) => ErrorCode::InvalidHeader,
+            Error::InvalidData(_) => ErrorCode::InvalidData,
+            Error::Truncated => ErrorCode::Truncated,
+            Error::Unsupported(_) => ErrorCode::Unsupported,
+            Error::SizeOverflow => ErrorCode::SizeOverflow,
+            Error::OutOfMemory => ErrorCode::OutOfMemory,
+            Error::FrameIndexOutOfRange => ErrorCode::FrameIndexOutOfRange,
+        }
+    }
+}
+
+thread_local! {
+    static LAST_ERROR_MESSAGE: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error_message(message: String) {
+    let message = CString::new(message.replace('\0', "")).unwrap_or_default();
+    LAST_ERROR_MESSAGE.with(|last_error_message| *last_error_message.borrow_mut() = Some(message));
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "Unknown panic".to_string()
+    }
+}
+
+/// Runs the body of an `extern "C"` entry point, turning both errors and panics into an `ErrorCode`
+/// so that they never unwind into the host. The message is kept for `libgfx_last_error_message`.
+pub(crate) fn ffi_call<F: FnOnce() -> Result<(), Error>>(body: F) -> ErrorCode {
+    match std::panic::catch_unwind(AssertUnwindSafe(body)) {
+        Ok(Ok(())) => ErrorCode::Success,
+        Ok(Err(error)) => {
+            set_last_error_message(error.to_string());
+            error.into()
+        }
+        Err(payload) => {
+            set_last_error_message(format!("Panic: {}", panic_message(payload.as_ref())));
+            ErrorCode::Panic
+        }
+    }
+}
+
+/// # Safety
+///
+/// `out` must either be null or be valid for writes.
+pub(crate) unsafe fn write_ffi_result<T>(out: *mut T, value: T) -> Result<(), Error> {
+    if out.is_null() {
+        return Err(Error::InvalidArgument("Output pointer is null"));
+    }
+    unsafe { out.write(value) };
+    Ok(())
+}
+
+/// Returns the message of the last error reported by an entry point on the calling thread,
+/// or null if there was none. The string stays valid until the next failing call on this thread.
+#[no_mangle]
+pub extern "C" fn libgfx_last_error_message() -> *const c_char {
+    LAST_ERROR_MESSAGE.with(|last_error_message| match &*last_error_message.borrow() {
+        Some(message) => message.as_ptr(),
+        None => std::ptr::null()
+    })
+}
@@ -0,0 +1,537 @@
+use std::ffi::c_void;
+use crate::imagedecoderplugin::{decoder_into_opaque, ImageDecoderPlugin, ImageFrameDescriptor};
+use crate::{Color, IntSize};
+use crate::bitmap::{Bitmap, BitmapFormat};
+use crate::error::{Error, ErrorCode, ffi_call, write_ffi_result};
+
+const GIF_MAX_LZW_CODE_SIZE: u32 = 12;
+
+#[derive (Debug, PartialEq, Copy, Clone)]
+enum GIFDisposalMethod {
+    None,
+    InPlace,
+    RestoreBackground,
+    RestorePrevious
+}
+
+#[derive (Debug, Clone)]
+struct GIFImageDescriptor<'a> {
+    x: u16,
+    y: u16,
+    width: u16,
+    height: u16,
+    interlaced: bool,
+    local_color_map: Option<Vec<Color>>,
+    lzw_minimum_code_size: u8,
+    lzw_data: Vec<&'a [u8]>,
+    transparency_index: Option<u8>,
+    delay_in_centiseconds: u16,
+    disposal_method: GIFDisposalMethod
+}
+
+// NOTE: Frames have to be composited in order, so we keep the canvas around to make sequential access cheap.
+struct GIFAnimationState {
+    canvas: Bitmap,
+    next_frame_index: usize,
+    previous_canvas: Option<Bitmap>
+}
+
+pub struct GIFImageDecoderPlugin<'a> {
+    context: GIFLoadingContext<'a>
+}
+
+struct GIFLoadingContext<'a> {
+    width: u16,
+    height: u16,
+    bytes: &'a [u8],
+    global_color_map: Option<Vec<Color>>,
+    images: Vec<GIFImageDescriptor<'a>>,
+    loop_count: usize,
+    animation_state: Option<GIFAnimationState>
+}
+
+/// A tiny cursor over the GIF bytes, GIF integers are little endian.
+struct GIFReader<'a> {
+    data: &'a [u8],
+    position: usize
+}
+
+impl<'a> GIFReader<'a> {
+    fn read_u8(&mut self) -> Result<u8, Error> {
+        let Some(byte) = self.data.get(self.position) else {
+            return Err(Error::Truncated);
+        };
+        self.position += 1;
+        Ok(*byte)
+    }
+
+    fn read_u16(&mut self) -> Result<u16, Error> {
+        Ok(u16::from_le_bytes([self.read_u8()?, self.read_u8()?]))
+    }
+
+    fn read_bytes(&mut self, count: usize) -> Result<&'a [u8], Error> {
+        let Some(bytes) = self.data.get(self.position..self.position + count) else {
+            return Err(Error::Truncated);
+        };
+        self.position += count;
+        Ok(bytes)
+    }
+
+    fn read_color_map(&mut self, flags: u8) -> Result<Vec<Color>, Error> {
+        let entry_count = 1 << ((flags & 0x07) + 1);
+        let entries = self.read_bytes(entry_count * 3)?;
+        Ok(entries.chunks_exact(3).map(|entry| Color::from_rgb(entry[0], entry[1], entry[2])).collect())
+    }
+
+    fn read_sub_blocks(&mut self) -> Result<Vec<&'a [u8]>, Error> {
+        let mut sub_blocks = Vec::new();
+        loop {
+            let size = self.read_u8()? as usize;
+            if size == 0 {
+                return Ok(sub_blocks);
+            }
+            sub_blocks.push(self.read_bytes(size)?);
+        }
+    }
+}
+
+impl<'a> GIFImageDecoderPlugin<'a> {
+    pub fn sniff(bytes: &[u8]) -> bool {
+        bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a")
+    }
+
+    pub fn create(bytes: &'a [u8]) -> Result<Self, Error> {
+        if !Self::sniff(bytes) {
+            return Err(Error::InvalidHeader("Invalid GIF signature"));
+        }
+
+        let mut reader = GIFReader { data: bytes, position: 6 };
+        let width = reader.read_u16()?;
+        let height = reader.read_u16()?;
+        let flags = reader.read_u8()?;
+        let _background_color_index = reader.read_u8()?;
+        let _pixel_aspect_ratio = reader.read_u8()?;
+        let global_color_map = if flags & 0x80 != 0 { Some(reader.read_color_map(flags)?) } else { None };
+
+        let mut context = GIFLoadingContext {
+            width,
+            height,
+            bytes,
+            global_color_map,
+            images: Vec::new(),
+            loop_count: 1,
+            animation_state: None
+        };
+        context.decode_blocks(&mut reader)?;
+        if context.images.is_empty() {
+            return Err(Error::InvalidData("GIF without any images"));
+        }
+        Ok(Self { context })
+    }
+}
+
+impl<'a> GIFLoadingContext<'a> {
+    fn decode_blocks(&mut self, reader: &mut GIFReader<'a>) -> Result<(), Error> {
+        let mut transparency_index = None;
+        let mut delay_in_centiseconds = 0;
+        let mut disposal_method = GIFDisposalMethod::None;
+
+        loop {
+            // NOTE: Plenty of GIFs in the wild are cut short or lack a trailer, keep what we have in that case.
+            let Ok(sentinel) = reader.read_u8() else {
+                return Ok(());
+            };
+            match sentinel {
+                0x21 => {
+                    let label = reader.read_u8()?;
+                    let sub_blocks = reader.read_sub_blocks()?;
+                    match label {
+                        0xF9 => {
+                            let Some(data) = sub_blocks.first().filter(|data| data.len() >= 4) else {
+                                return Err(Error::InvalidData("Invalid GIF graphic control extension"));
+                            };
+                            disposal_method = match (data[0] >> 2) & 0x07 {
+                                1 => GIFDisposalMethod::InPlace,
+                                2 => GIFDisposalMethod::RestoreBackground,
+                                3 => GIFDisposalMethod::RestorePrevious,
+                                _ => GIFDisposalMethod::None
+                            };
+                            delay_in_centiseconds = u16::from_le_bytes([data[1], data[2]]);
+                            transparency_index = if data[0] & 0x01 != 0 { Some(data[3]) } else { None };
+                        }
+                        0xFF => {
+                            let is_looping_extension = sub_blocks.first().is_some_and(|identifier| *identifier == b"NETSCAPE2.0" || *identifier == b"ANIMEXTS1.0");
+                            if let Some(data) = sub_blocks.get(1).filter(|data| is_looping_extension && data.len() >= 3 && data[0] == 1) {
+                                self.loop_count = u16::from_le_bytes([data[1], data[2]]) as usize;
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                0x2C => {
+                    let x = reader.read_u16()?;
+                    let y = reader.read_u16()?;
+                    let width = reader.read_u16()?;
+                    let height = reader.read_u16()?;
+                    let flags = reader.read_u8()?;
+                    let local_color_map = if flags & 0x80 != 0 { Some(reader.read_color_map(flags)?) } else { None };
+                    let lzw_minimum_code_size = reader.read_u8()?;
+                    let lzw_data = reader.read_sub_blocks()?;
+
+                    if local_color_map.is_none() && self.global_color_map.is_none() {
+                        return Err(Error::InvalidData("GIF image without a color map"));
+                    }
+                    if !(2..=8).contains(&lzw_minimum_code_size) {
+                        return Err(Error::InvalidData("Invalid GIF LZW minimum code size"));
+                    }
+
+                    self.images.push(GIFImageDescriptor {
+                        x,
+                        y,
+                        width,
+                        height,
+                        interlaced: flags & 0x40 != 0,
+                        local_color_map,
+                        lzw_minimum_code_size,
+                        lzw_data,
+                        transparency_index,
+                        delay_in_centiseconds,
+                        disposal_method
+                    });
+
+                    // NOTE: A graphic control extension only applies to the image right after it.
+                    transparency_index = None;
+                    delay_in_centiseconds = 0;
+                    disposal_method = GIFDisposalMethod::None;
+                }
+                0x3B => return Ok(()),
+                _ => return Err(Error::InvalidData("Unexpected GIF block"))
+            }
+        }
+    }
+
+    fn render_frame(&mut self, index: usize) -> Result<ImageFrameDescriptor, Error> {
+        let mut state = match self.animation_state.take() {
+            Some(state) if state.next_frame_index <= index => state,
+            _ => GIFAnimationState {
+                canvas: Bitmap::new(BitmapFormat::BGRA8888, IntSize { width: self.width as i32, height: self.height as i32 }, 1)?,
+                next_frame_index: 0,
+                previous_canvas: None
+            }
+        };
+
+        while state.next_frame_index <= index {
+            if state.next_frame_index > 0 {
+                let previous_image = &self.images[state.next_frame_index - 1];
+                match previous_image.disposal_method {
+                    GIFDisposalMethod::RestoreBackground => {
+                        // NOTE: Like browsers, we restore to transparent instead of the background color.
+                        for y in previous_image.y..previous_image.y.saturating_add(previous_image.height).min(self.height) {
+                            for x in previous_image.x..previous_image.x.saturating_add(previous_image.width).min(self.width) {
+                                state.canvas.set_pixel(x as i32, y as i32, 0);
+                            }
+                        }
+                    }
+                    GIFDisposalMethod::RestorePrevious => {
+                        if let Some(previous_canvas) = state.previous_canvas.take() {
+                            state.canvas = previous_canvas;
+                        }
+                    }
+                    GIFDisposalMethod::None | GIFDisposalMethod::InPlace => {}
+                }
+            }
+
+            let image = &self.images[state.next_frame_index];
+            if image.disposal_method == GIFDisposalMethod::RestorePrevious {
+                state.previous_canvas = Some(state.canvas.clone());
+            }
+
+            let color_map = image.local_color_map.as_ref().or(self.global_color_map.as_ref()).unwrap();
+            let indices = decode_lzw(&image.lzw_data.concat(), image.lzw_minimum_code_size, image.width as usize * image.height as usize)?;
+            let rows = interlaced_row_order(image.height, image.interlaced);
+            for (row, y) in rows.enumerate() {
+                for x in 0..image.width {
+                    // NOTE: Images may be cut short, the remaining pixels are left untouched.
+                    let Some(color_index) = indices.get(row * image.width as usize + x as usize) else {
+                        break;
+                    };
+                    let (canvas_x, canvas_y) = (image.x as u32 + x as u32, image.y as u32 + y as u32);
+                    if Some(*color_index) == image.transparency_index || canvas_x >= self.width as u32 || canvas_y >= self.height as u32 {
+                        continue;
+                    }
+                    // NOTE: Out of range indices are drawn as black, which is what browsers do.
+                    let color = color_map.get(*color_index as usize).copied().unwrap_or(Color::from_rgb(0, 0, 0));
+                    state.canvas.set_pixel(canvas_x as i32, canvas_y as i32, color.color);
+                }
+            }
+            state.next_frame_index += 1;
+        }
+
+        let image = state.canvas.clone();
+        self.animation_state = Some(state);
+        Ok(ImageFrameDescriptor {
+            image,
+            duration: frame_duration(&self.images[index])
+        })
+    }
+}
+
+fn frame_duration(image: &GIFImageDescriptor) -> i32 {
+    // NOTE: Browsers play very short delays at 100ms, and so many GIFs depend on that.
+    if image.delay_in_centiseconds < 2 {
+        return 100;
+    }
+    image.delay_in_centiseconds as i32 * 10
+}
+
+/// Maps each decoded row to its y position, interlaced images store rows in four passes.
+fn interlaced_row_order(height: u16, interlaced: bool) -> Box<dyn Iterator<Item = u16>> {
+    if !interlaced {
+        return Box::new(0..height);
+    }
+    Box::new((0..height).step_by(8)
+        .chain((4..height).step_by(8))
+        .chain((2..height).step_by(4))
+        .chain((1..height).step_by(2)))
+}
+
+/// Decompresses GIF LZW data into color indices, stopping after `pixel_count` of them.
+fn decode_lzw(data: &[u8], minimum_code_size: u8, pixel_count: usize) -> Result<Vec<u8>, Error> {
+    let clear_code = 1u16 << minimum_code_size;
+    let end_of_information_code = clear_code + 1;
+
+    let mut output = Vec::new();
+    if output.try_reserve(pixel_count).is_err() {
+        return Err(Error::OutOfMemory);
+    }
+
+    // NOTE: Each code is stored as the code it extends plus its last byte, and expanded backwards.
+    let mut prefixes = [0u16; 1 << GIF_MAX_LZW_CODE_SIZE];
+    let mut suffixes = [0u8; 1 << GIF_MAX_LZW_CODE_SIZE];
+    let mut first_bytes = [0u8; 1 << GIF_MAX_LZW_CODE_SIZE];
+    let mut lengths = [0u16; 1 << GIF_MAX_LZW_CODE_SIZE];
+    for code in 0..clear_code {
+        suffixes[code as usize] = code as u8;
+        first_bytes[code as usize] = code as u8;
+        lengths[code as usize] = 1;
+    }
+
+    let mut code_size = minimum_code_size as u32 + 1;
+    let mut next_code = end_of_information_code + 1;
+    let mut previous_code: Option<u16> = None;
+    let mut bit_buffer = 0u32;
+    let mut bit_count = 0u32;
+    let mut position = 0;
+
+    while output.len() < pixel_count {
+        while bit_count < code_size {
+            let Some(byte) = data.get(position) else {
+                return Ok(output);
+            };
+            bit_buffer |= (*byte as u32) << bit_count;
+            bit_count += 8;
+            position += 1;
+        }
+        let code = (bit_buffer & ((1 << code_size) - 1)) as u16;
+        bit_buffer >>= code_size;
+        bit_count -= code_size;
+
+        if code == clear_code {
+            code_size = minimum_code_size as u32 + 1;
+            next_code = end_of_information_code + 1;
+            previous_code = None;
+            continue;
+        }
+        if code == end_of_information_code {
+            break;
+        }
+
+        let Some(previous) = previous_code else {
+            if code >= clear_code {
+                return Err(Error::InvalidData("Invalid first GIF LZW code"));
+            }
+            output.push(code as u8);
+            previous_code = Some(code);
+            continue;
+        };
+
+        // NOTE: A code that is about to be defined is the previous string followed by its own first byte.
+        let first_byte = if code < next_code {
+            first_bytes[code as usize]
+        } else if code == next_code {
+            first_bytes[previous as usize]
+        } else {
+            return Err(Error::InvalidData("Invalid GIF LZW code"));
+        };
+
+        if (next_code as usize) < prefixes.len() {
+            prefixes[next_code as usize] = previous;
+            suffixes[next_code as usize] = first_byte;
+            first_bytes[next_code as usize] = first_bytes[previous as usize];
+            lengths[next_code as usize] = lengths[previous as usize] + 1;
+            next_code += 1;
+            if next_code as u32 == 1 << code_size && code_size < GIF_MAX_LZW_CODE_SIZE {
+                code_size += 1;
+            }
+        }
+
+        let length = lengths[code as usize] as usize;
+        let start = output.len();
+        output.resize(start + length, 0);
+        let mut current = code;
+        for index in (start..start + length).rev() {
+            output[index] = suffixes[current as usize];
+            current = prefixes[current as usize];
+        }
+        previous_code = Some(code);
+    }
+
+    output.truncate(pixel_count);
+    Ok(output)
+}
+
+impl<'a> ImageDecoderPlugin for GIFImageDecoderPlugin<'a> {
+    fn size(&self) -> IntSize {
+        IntSize {
+            width: self.context.width as i32,
+            height: self.context.height as i32
+        }
+    }
+
+    fn is_animated(&self) -> bool {
+        self.context.images.len() > 1
+    }
+
+    fn loop_count(&self) -> usize {
+        self.context.loop_count
+    }
+
+    fn frame_count(&self) -> usize {
+        self.context.images.len()
+    }
+
+    fn frame_with_ideal_size(&mut self, index: usize, _: Option<IntSize>) -> Result<ImageFrameDescriptor, Error> {
+        if index >= self.context.images.len() {
+            return Err(Error::FrameIndexOutOfRange);
+        }
+        self.context.render_frame(index)
+    }
+}
+
+/// # Safety
+///
+/// `bytes` must point to `size` readable bytes that outlive the returned decoder,
+/// and `out_decoder` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn gif_image_decoder_plugin_new(bytes: *const u8, size: usize, out_decoder: *mut *mut c_void) -> ErrorCode {
+    ffi_call(|| unsafe {
+        if bytes.is_null() {
+            return Err(Error::InvalidArgument("Bytes are null"));
+        }
+        if out_decoder.is_null() {
+            return Err(Error::InvalidArgument("Output pointer is null"));
+        }
+        let bytes = std::slice::from_raw_parts(bytes, size);
+        let decoder = GIFImageDecoderPlugin::create(bytes)?;
+        write_ffi_result(out_decoder, decoder_into_opaque(Box::new(decoder)))
+    })
+}
+
+/// # Safety
+///
+/// `opaque_decoder` must either be null or come from `gif_image_decoder_plugin_new` and not be freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn gif_image_decoder_plugin_free(opaque_decoder: *mut c_void) -> ErrorCode {
+    ffi_call(|| {
+        if !opaque_decoder.is_null() {
+            let decoder: Box<Box<dyn ImageDecoderPlugin>> = unsafe { Box::from_raw(opaque_decoder as *mut _) };
+            drop(decoder);
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ARGB;
+
+    // NOTE: 3x1 GIF looping 5 times, with a global color map of black, red, green, blue and white:
+    //       0: red, green, blue, 10/100 s, dispose in place
+    //       1: white at x = 1, 1/100 s, restore to previous
+    //       2: transparent and yellow at x = 1 through a local color map, no delay, restore to background
+    //       3: white at x = 0, 25/100 s, no disposal
+    const ANIMATED_IMAGE: [u8; 155] = [
+        0x47, 0x49, 0x46, 0x38, 0x39, 0x61, 0x03, 0x00, 0x01, 0x00, 0xA2, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0x00, 0x00, 0x00,
+        0xFF, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x21, 0xFF, 0x0B,
+        0x4E, 0x45, 0x54, 0x53, 0x43, 0x41, 0x50, 0x45, 0x32, 0x2E, 0x30, 0x03, 0x01, 0x05, 0x00, 0x00, 0x21, 0xF9, 0x04, 0x04,
+        0x0A, 0x00, 0x00, 0x00, 0x2C, 0x00, 0x00, 0x00, 0x00, 0x03, 0x00, 0x01, 0x00, 0x00, 0x02, 0x02, 0x8C, 0x56, 0x00, 0x21,
+        0xF9, 0x04, 0x0C, 0x01, 0x00, 0x00, 0x00, 0x2C, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x03, 0x02, 0x48,
+        0x09, 0x00, 0x21, 0xF9, 0x04, 0x09, 0x00, 0x00, 0x00, 0x00, 0x2C, 0x01, 0x00, 0x00, 0x00, 0x02, 0x00, 0x01, 0x00, 0x80,
+        0x00, 0x00, 0x00, 0xFF, 0xFF, 0x00, 0x02, 0x02, 0x44, 0x0A, 0x00, 0x21, 0xF9, 0x04, 0x00, 0x19, 0x00, 0x00, 0x00, 0x2C,
+        0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x03, 0x02, 0x48, 0x09, 0x00, 0x3B
+    ];
+
+    // NOTE: 1x8 interlaced GIF where row y uses color index y, with the gray level 30 * y.
+    const INTERLACED_IMAGE: [u8; 65] = [
+        0x47, 0x49, 0x46, 0x38, 0x39, 0x61, 0x01, 0x00, 0x08, 0x00, 0xA2, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1E, 0x1E, 0x1E, 0x3C,
+        0x3C, 0x3C, 0x5A, 0x5A, 0x5A, 0x78, 0x78, 0x78, 0x96, 0x96, 0x96, 0xB4, 0xB4, 0xB4, 0xD2, 0xD2, 0xD2, 0x21, 0xF9, 0x04,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x2C, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x08, 0x00, 0x40, 0x03, 0x06, 0x08, 0x24, 0x16,
+        0x53, 0x27, 0x01, 0x00, 0x3B
+    ];
+
+    #[test]
+    fn decodes_netscape_loop_count() {
+        let decoder = GIFImageDecoderPlugin::create(&ANIMATED_IMAGE).unwrap();
+        assert!(decoder.is_animated());
+        assert_eq!(decoder.frame_count(), 4);
+        assert_eq!(decoder.loop_count(), 5);
+
+        let decoder = GIFImageDecoderPlugin::create(&INTERLACED_IMAGE).unwrap();
+        assert!(!decoder.is_animated());
+        assert_eq!(decoder.loop_count(), 1);
+    }
+
+    #[test]
+    fn composites_frames_with_each_disposal_method() {
+        let mut decoder = GIFImageDecoderPlugin::create(&ANIMATED_IMAGE).unwrap();
+        let expected: [(i32, [ARGB; 3]); 4] = [
+            (100, [0xFFFF0000, 0xFF00FF00, 0xFF0000FF]),
+            // NOTE: Delays under 2/100 s are played at 100ms.
+            (100, [0xFFFF0000, 0xFFFFFFFF, 0xFF0000FF]),
+            // NOTE: The white pixel was restored to green, which shows through the transparent index.
+            (100, [0xFFFF0000, 0xFF00FF00, 0xFFFFFF00]),
+            // NOTE: The area of the previous frame was restored to transparent.
+            (250, [0xFFFFFFFF, 0x00000000, 0x00000000])
+        ];
+        for (index, (duration, frame_pixels)) in expected.iter().enumerate() {
+            let frame = decoder.frame(index).unwrap();
+            assert_eq!(frame.duration, *duration, "frame {index}");
+            assert_eq!(frame.image.pixels(), frame_pixels, "frame {index}");
+        }
+    }
+
+    #[test]
+    fn renders_frames_out_of_order() {
+        let mut decoder = GIFImageDecoderPlugin::create(&ANIMATED_IMAGE).unwrap();
+        assert_eq!(decoder.frame(2).unwrap().image.pixels(), [0xFFFF0000, 0xFF00FF00, 0xFFFFFF00]);
+        assert_eq!(decoder.frame(1).unwrap().image.pixels(), [0xFFFF0000, 0xFFFFFFFF, 0xFF0000FF]);
+        assert_eq!(decoder.frame(4).err(), Some(Error::FrameIndexOutOfRange));
+    }
+
+    #[test]
+    fn decodes_interlaced_rows() {
+        let mut decoder = GIFImageDecoderPlugin::create(&INTERLACED_IMAGE).unwrap();
+        let expected: Vec<ARGB> = (0..8).map(|y| {
+            let gray = y * 30;
+            Color::from_rgb(gray, gray, gray).color
+        }).collect();
+        assert_eq!(decoder.frame(0).unwrap().image.pixels(), expected);
+    }
+
+    #[test]
+    fn keeps_frames_of_gif_without_trailer() {
+        let decoder = GIFImageDecoderPlugin::create(&ANIMATED_IMAGE[..ANIMATED_IMAGE.len() - 1]).unwrap();
+        assert_eq!(decoder.frame_count(), 4);
+    }
+}
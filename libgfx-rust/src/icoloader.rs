@@ -0,0 +1,313 @@
+use std::ffi::c_void;
+use crate::imagedecoderplugin::{decoder_into_opaque, ImageDecoderPlugin, ImageFrameDescriptor};
+use crate::{IntPoint, IntSize};
+use crate::bitmap::Bitmap;
+use crate::bmploader::BMPImageDecoderPlugin;
+use crate::error::{Error, ErrorCode, ffi_call, write_ffi_result};
+use crate::pngloader::PNGImageDecoderPlugin;
+
+const ICO_HEADER_SIZE: usize = 6;
+const ICO_DIRECTORY_ENTRY_SIZE: usize = 16;
+
+#[derive (Debug, PartialEq, Copy, Clone)]
+enum ICOType {
+    Icon = 1,
+    Cursor = 2
+}
+
+#[derive (Debug, Copy, Clone)]
+struct ICOImageDescriptor {
+    width: u16,
+    height: u16,
+    bits_per_pixel: u16,
+    hotspot: Option<IntPoint>,
+    offset: usize,
+    size: usize
+}
+
+pub struct ICOImageDecoderPlugin<'a> {
+    context: ICOLoadingContext<'a>
+}
+
+struct ICOLoadingContext<'a> {
+    bytes: &'a [u8],
+    images: Vec<ICOImageDescriptor>,
+    bitmaps: Vec<Option<Bitmap>>
+}
+
+fn read_u16_le(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+fn read_u32_le(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+}
+
+fn decode_ico_directory(bytes: &[u8]) -> Result<Vec<ICOImageDescriptor>, Error> {
+    if bytes.len() < ICO_HEADER_SIZE {
+        return Err(Error::Truncated);
+    }
+    let ico_type = match read_u16_le(bytes, 2) {
+        1 => ICOType::Icon,
+        2 => ICOType::Cursor,
+        _ => return Err(Error::InvalidHeader("Invalid ICO type"))
+    };
+    let image_count = read_u16_le(bytes, 4) as usize;
+    if read_u16_le(bytes, 0) != 0 || image_count == 0 {
+        return Err(Error::InvalidHeader("Invalid ICO header"));
+    }
+    let Some(directory) = bytes.get(ICO_HEADER_SIZE..ICO_HEADER_SIZE + image_count * ICO_DIRECTORY_ENTRY_SIZE) else {
+        return Err(Error::Truncated);
+    };
+
+    let mut images = Vec::with_capacity(image_count);
+    for entry in directory.chunks_exact(ICO_DIRECTORY_ENTRY_SIZE) {
+        // NOTE: In cursors, the planes and bit count fields hold the hotspot instead.
+        let (hotspot, bits_per_pixel) = match ico_type {
+            ICOType::Icon => (None, read_u16_le(entry, 6)),
+            ICOType::Cursor => (Some(IntPoint { x: read_u16_le(entry, 4) as i32, y: read_u16_le(entry, 6) as i32 }), 0)
+        };
+        let image = ICOImageDescriptor {
+            // NOTE: A size of 0 means 256 pixels.
+            width: if entry[0] == 0 { 256 } else { entry[0] as u16 },
+            height: if entry[1] == 0 { 256 } else { entry[1] as u16 },
+            bits_per_pixel,
+            hotspot,
+            size: read_u32_le(entry, 8) as usize,
+            offset: read_u32_le(entry, 12) as usize
+        };
+        if image.offset.checked_add(image.size).is_none_or(|end| end > bytes.len()) {
+            return Err(Error::InvalidData("ICO image is outside of the file"));
+        }
+        images.push(image);
+    }
+    Ok(images)
+}
+
+impl<'a> ICOImageDecoderPlugin<'a> {
+    pub fn sniff(bytes: &[u8]) -> bool {
+        decode_ico_directory(bytes).is_ok()
+    }
+
+    pub fn create(bytes: &'a [u8]) -> Result<Self, Error> {
+        let images = decode_ico_directory(bytes)?;
+        let bitmaps = vec![None; images.len()];
+        Ok(Self {
+            context: ICOLoadingContext { bytes, images, bitmaps }
+        })
+    }
+}
+
+impl<'a> ICOLoadingContext<'a> {
+    fn largest_image_index(&self) -> usize {
+        let mut best_index = 0;
+        for (index, image) in self.images.iter().enumerate() {
+            let best_image = &self.images[best_index];
+            let area = image.width as u32 * image.height as u32;
+            let best_area = best_image.width as u32 * best_image.height as u32;
+            if area > best_area || (area == best_area && image.bits_per_pixel > best_image.bits_per_pixel) {
+                best_index = index;
+            }
+        }
+        best_index
+    }
+
+    fn best_image_index(&self, ideal_size: Option<IntSize>) -> usize {
+        let Some(ideal_size) = ideal_size.filter(|size| !size.is_empty()) else {
+            return self.largest_image_index();
+        };
+
+        // NOTE: On equal distance, prefer scaling a bigger image down and then the deeper color.
+        let distance = |image: &ICOImageDescriptor| {
+            let distance = (image.width as i32 - ideal_size.width).unsigned_abs() + (image.height as i32 - ideal_size.height).unsigned_abs();
+            let is_smaller = (image.width as i32) < ideal_size.width || (image.height as i32) < ideal_size.height;
+            (distance, is_smaller, std::cmp::Reverse(image.bits_per_pixel))
+        };
+        let mut best_index = 0;
+        for (index, image) in self.images.iter().enumerate() {
+            if distance(image) < distance(&self.images[best_index]) {
+                best_index = index;
+            }
+        }
+        best_index
+    }
+
+    fn decode_image(&mut self, index: usize) -> Result<Bitmap, Error> {
+        if let Some(bitmap) = &self.bitmaps[index] {
+            return Ok(bitmap.clone());
+        }
+
+        let image = &self.images[index];
+        let data = &self.bytes[image.offset..image.offset + image.size];
+        let bitmap = if PNGImageDecoderPlugin::sniff(data) {
+            PNGImageDecoderPlugin::create(data)?.frame(0)?.image
+        } else {
+            BMPImageDecoderPlugin::create_as_included_in_ico(data)?.frame(0)?.image
+        };
+        self.bitmaps[index] = Some(bitmap.clone());
+        Ok(bitmap)
+    }
+}
+
+impl<'a> ImageDecoderPlugin for ICOImageDecoderPlugin<'a> {
+    fn size(&self) -> IntSize {
+        let image = &self.context.images[self.context.largest_image_index()];
+        IntSize {
+            width: image.width as i32,
+            height: image.height as i32
+        }
+    }
+
+    fn frame_with_ideal_size(&mut self, index: usize, ideal_size: Option<IntSize>) -> Result<ImageFrameDescriptor, Error> {
+        if index != 0 {
+            return Err(Error::FrameIndexOutOfRange);
+        }
+
+        let image_index = self.context.best_image_index(ideal_size);
+        Ok(ImageFrameDescriptor {
+            image: self.context.decode_image(image_index)?,
+            duration: 0
+        })
+    }
+
+    fn hotspot(&self, ideal_size: Option<IntSize>) -> Option<IntPoint> {
+        self.context.images[self.context.best_image_index(ideal_size)].hotspot
+    }
+}
+
+/// # Safety
+///
+/// `bytes` must point to `size` readable bytes that outlive the returned decoder,
+/// and `out_decoder` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn ico_image_decoder_plugin_new(bytes: *const u8, size: usize, out_decoder: *mut *mut c_void) -> ErrorCode {
+    ffi_call(|| unsafe {
+        if bytes.is_null() {
+            return Err(Error::InvalidArgument("Bytes are null"));
+        }
+        if out_decoder.is_null() {
+            return Err(Error::InvalidArgument("Output pointer is null"));
+        }
+        let bytes = std::slice::from_raw_parts(bytes, size);
+        let decoder = ICOImageDecoderPlugin::create(bytes)?;
+        write_ffi_result(out_decoder, decoder_into_opaque(Box::new(decoder)))
+    })
+}
+
+/// # Safety
+///
+/// `opaque_decoder` must either be null or come from `ico_image_decoder_plugin_new` and not be freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn ico_image_decoder_plugin_free(opaque_decoder: *mut c_void) -> ErrorCode {
+    ffi_call(|| {
+        if !opaque_decoder.is_null() {
+            let decoder: Box<Box<dyn ImageDecoderPlugin>> = unsafe { Box::from_raw(opaque_decoder as *mut _) };
+            drop(decoder);
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::imagedecoderplugin::image_decoder_plugin_hotspot;
+
+    // NOTE: Icon with three images:
+    //       0: 1x1 32-bit BMP of red with an empty alpha channel and mask
+    //       1: 2x2 24-bit BMP of green, white, blue and black, where the AND mask hides the white pixel
+    //       2: 4x4 PNG of blue
+    const ICON_IMAGE: [u8; 240] = [
+        0x00, 0x00, 0x01, 0x00, 0x03, 0x00, 0x01, 0x01, 0x00, 0x00, 0x01, 0x00, 0x20, 0x00, 0x30, 0x00, 0x00, 0x00, 0x36, 0x00,
+        0x00, 0x00, 0x02, 0x02, 0x00, 0x00, 0x01, 0x00, 0x18, 0x00, 0x40, 0x00, 0x00, 0x00, 0x66, 0x00, 0x00, 0x00, 0x04, 0x04,
+        0x00, 0x00, 0x01, 0x00, 0x20, 0x00, 0x4A, 0x00, 0x00, 0x00, 0xA6, 0x00, 0x00, 0x00, 0x28, 0x00, 0x00, 0x00, 0x01, 0x00,
+        0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x01, 0x00, 0x20, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x28, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x01, 0x00, 0x18, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0xFF, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0x00, 0xFF, 0xFF, 0xFF, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x40, 0x00, 0x00, 0x00, 0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A, 0x00, 0x00, 0x00, 0x0D, 0x49, 0x48,
+        0x44, 0x52, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x04, 0x08, 0x06, 0x00, 0x00, 0x00, 0xA9, 0xF1, 0x9E, 0x7E, 0x00,
+        0x00, 0x00, 0x11, 0x49, 0x44, 0x41, 0x54, 0x78, 0xDA, 0x63, 0x60, 0x60, 0xF8, 0xFF, 0x1F, 0x15, 0x93, 0x2C, 0x00, 0x00,
+        0x1C, 0x60, 0x1F, 0xE1, 0xCB, 0x7F, 0x73, 0xF9, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4E, 0x44, 0xAE, 0x42, 0x60, 0x82
+    ];
+
+    // NOTE: Cursor with the first two images of the icon above, with the hotspots (0, 0) and (1, 1).
+    const CURSOR_IMAGE: [u8; 150] = [
+        0x00, 0x00, 0x02, 0x00, 0x02, 0x00, 0x01, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x30, 0x00, 0x00, 0x00, 0x26, 0x00,
+        0x00, 0x00, 0x02, 0x02, 0x00, 0x00, 0x01, 0x00, 0x01, 0x00, 0x40, 0x00, 0x00, 0x00, 0x56, 0x00, 0x00, 0x00, 0x28, 0x00,
+        0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x01, 0x00, 0x20, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0xFF, 0x00, 0x00, 0x00, 0x00, 0x00, 0x28, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x01, 0x00,
+        0x18, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0x00, 0xFF, 0xFF, 0xFF,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x40, 0x00, 0x00, 0x00
+    ];
+
+    #[test]
+    fn picks_best_image_for_ideal_size() {
+        let mut decoder = ICOImageDecoderPlugin::create(&ICON_IMAGE).unwrap();
+        assert_eq!((decoder.size().width, decoder.size().height), (4, 4));
+
+        let frame_size = |decoder: &mut ICOImageDecoderPlugin, ideal_size: Option<IntSize>| {
+            let size = decoder.frame_with_ideal_size(0, ideal_size).unwrap().image.size;
+            (size.width, size.height)
+        };
+        assert_eq!(frame_size(&mut decoder, None), (4, 4));
+        assert_eq!(frame_size(&mut decoder, Some(IntSize { width: 0, height: 0 })), (4, 4));
+        assert_eq!(frame_size(&mut decoder, Some(IntSize { width: 1, height: 1 })), (1, 1));
+        assert_eq!(frame_size(&mut decoder, Some(IntSize { width: 2, height: 2 })), (2, 2));
+        // NOTE: 3x3 is as far from 2x2 as from 4x4, so the bigger image wins.
+        assert_eq!(frame_size(&mut decoder, Some(IntSize { width: 3, height: 3 })), (4, 4));
+        assert_eq!(frame_size(&mut decoder, Some(IntSize { width: 64, height: 64 })), (4, 4));
+    }
+
+    #[test]
+    fn decodes_png_and_bmp_images() {
+        let mut decoder = ICOImageDecoderPlugin::create(&ICON_IMAGE).unwrap();
+        let image = decoder.frame(0).unwrap().image;
+        assert_eq!(image.pixels(), [0xFF0000FF; 16]);
+
+        let image = decoder.frame_with_ideal_size(0, Some(IntSize { width: 1, height: 1 })).unwrap().image;
+        assert_eq!(image.pixels(), [0xFFFF0000]);
+    }
+
+    #[test]
+    fn applies_and_mask() {
+        let mut decoder = ICOImageDecoderPlugin::create(&ICON_IMAGE).unwrap();
+        let image = decoder.frame_with_ideal_size(0, Some(IntSize { width: 2, height: 2 })).unwrap().image;
+        assert_eq!(image.pixels(), [0xFF00FF00, 0x00000000, 0xFF0000FF, 0xFF000000]);
+    }
+
+    #[test]
+    fn exposes_cursor_hotspot() {
+        let decoder = ICOImageDecoderPlugin::create(&CURSOR_IMAGE).unwrap();
+        let hotspot = |ideal_size| decoder.hotspot(ideal_size).map(|hotspot| (hotspot.x, hotspot.y));
+        assert_eq!(hotspot(None), Some((1, 1)));
+        assert_eq!(hotspot(Some(IntSize { width: 1, height: 1 })), Some((0, 0)));
+
+        let decoder = ICOImageDecoderPlugin::create(&ICON_IMAGE).unwrap();
+        assert!(decoder.hotspot(None).is_none());
+    }
+
+    #[test]
+    fn exposes_cursor_hotspot_across_the_ffi() {
+        unsafe {
+            let mut decoder = std::ptr::null_mut();
+            assert_eq!(ico_image_decoder_plugin_new(CURSOR_IMAGE.as_ptr(), CURSOR_IMAGE.len(), &mut decoder), ErrorCode::Success);
+            let (mut has_hotspot, mut hotspot) = (false, IntPoint { x: -1, y: -1 });
+            let ideal_size = IntSize { width: 1, height: 1 };
+            assert_eq!(image_decoder_plugin_hotspot(decoder, &ideal_size, &mut has_hotspot, &mut hotspot), ErrorCode::Success);
+            assert_eq!((has_hotspot, hotspot.x, hotspot.y), (true, 0, 0));
+            assert_eq!(image_decoder_plugin_hotspot(decoder, std::ptr::null(), &mut has_hotspot, &mut hotspot), ErrorCode::Success);
+            assert_eq!((has_hotspot, hotspot.x, hotspot.y), (true, 1, 1));
+            assert_eq!(image_decoder_plugin_hotspot(decoder, std::ptr::null(), &mut has_hotspot, std::ptr::null_mut()), ErrorCode::InvalidArgument);
+            assert_eq!(ico_image_decoder_plugin_free(decoder), ErrorCode::Success);
+
+            assert_eq!(ico_image_decoder_plugin_new(ICON_IMAGE.as_ptr(), ICON_IMAGE.len(), &mut decoder), ErrorCode::Success);
+            assert_eq!(image_decoder_plugin_hotspot(decoder, std::ptr::null(), &mut has_hotspot, &mut hotspot), ErrorCode::Success);
+            assert!(!has_hotspot);
+            assert_eq!(ico_image_decoder_plugin_free(decoder), ErrorCode::Success);
+        }
+    }
+}
@@ -0,0 +1,279 @@
+use std::ffi::{c_char, c_void, CStr};
+use crate::{IntPoint, IntSize};
+use crate::cmykbitmap::CMYKBitmap;
+use crate::error::{Error, ErrorCode, ffi_call, write_ffi_result};
+use crate::imagedecoderplugin::{decoder_into_opaque, ImageDecoderPlugin, ImageFrameDescriptor, Metadata, NaturalFrameFormat, VectorImageFrameDescriptor};
+use crate::bmploader::BMPImageDecoderPlugin;
+use crate::ddsloader::DDSImageDecoderPlugin;
+use crate::gifloader::GIFImageDecoderPlugin;
+use crate::icoloader::ICOImageDecoderPlugin;
+use crate::jpegloader::JPEGImageDecoderPlugin;
+use crate::pngloader::PNGImageDecoderPlugin;
+use crate::portableimageloader::PortableImageDecoderPlugin;
+use crate::qoiloader::QOIImageDecoderPlugin;
+use crate::tgaloader::TGAImageDecoderPlugin;
+use crate::tiffloader::TIFFImageDecoderPlugin;
+use crate::tinyvgloader::TinyVGImageDecoderPlugin;
+use crate::webploader::WebPImageDecoderPlugin;
+
+type PluginCreator = for<'a> fn(&'a [u8]) -> Result<Box<dyn ImageDecoderPlugin + 'a>, Error>;
+
+struct ImagePluginInitializer {
+    sniff: fn(&[u8]) -> bool,
+    create: PluginCreator
+}
+
+// NOTE: Formats without a reliable magic number are only tried when the caller gives us a hint.
+struct ImagePluginWithHintInitializer {
+    validate_before_create: fn(&[u8]) -> Result<(), Error>,
+    create: PluginCreator,
+    mime_types: &'static [&'static str],
+    extensions: &'static [&'static str]
+}
+
+const INITIALIZERS: &[ImagePluginInitializer] = &[
+    ImagePluginInitializer { sniff: BMPImageDecoderPlugin::sniff, create: create_bmp_plugin },
+    ImagePluginInitializer { sniff: DDSImageDecoderPlugin::sniff, create: create_dds_plugin },
+    ImagePluginInitializer { sniff: GIFImageDecoderPlugin::sniff, create: create_gif_plugin },
+    ImagePluginInitializer { sniff: JPEGImageDecoderPlugin::sniff, create: create_jpeg_plugin },
+    ImagePluginInitializer { sniff: PNGImageDecoderPlugin::sniff, create: create_png_plugin },
+    ImagePluginInitializer { sniff: PortableImageDecoderPlugin::sniff, create: create_portable_image_plugin },
+    ImagePluginInitializer { sniff: QOIImageDecoderPlugin::sniff, create: create_qoi_plugin },
+    ImagePluginInitializer { sniff: TGAImageDecoderPlugin::sniff, create: create_tga_plugin },
+    ImagePluginInitializer { sniff: TIFFImageDecoderPlugin::sniff, create: create_tiff_plugin },
+    ImagePluginInitializer { sniff: TinyVGImageDecoderPlugin::sniff, create: create_tinyvg_plugin },
+    ImagePluginInitializer { sniff: WebPImageDecoderPlugin::sniff, create: create_webp_plugin },
+    // NOTE: ICO has the weakest signature of the bunch, so it goes last.
+    ImagePluginInitializer { sniff: ICOImageDecoderPlugin::sniff, create: create_ico_plugin },
+];
+
+const INITIALIZERS_WITH_HINT: &[ImagePluginWithHintInitializer] = &[
+    ImagePluginWithHintInitializer {
+        validate_before_create: TGAImageDecoderPlugin::validate_before_create,
+        create: create_tga_plugin,
+        mime_types: &["image/x-targa", "image/x-tga", "image/tga"],
+        extensions: &["tga", "icb", "tpic", "vda", "vst"]
+    },
+];
+
+fn create_bmp_plugin(bytes: &[u8]) -> Result<Box<dyn ImageDecoderPlugin + '_>, Error> {
+    Ok(Box::new(BMPImageDecoderPlugin::create(bytes)?))
+}
+
+fn create_dds_plugin(bytes: &[u8]) -> Result<Box<dyn ImageDecoderPlugin + '_>, Error> {
+    Ok(Box::new(DDSImageDecoderPlugin::create(bytes)?))
+}
+
+fn create_gif_plugin(bytes: &[u8]) -> Result<Box<dyn ImageDecoderPlugin + '_>, Error> {
+    Ok(Box::new(GIFImageDecoderPlugin::create(bytes)?))
+}
+
+fn create_ico_plugin(bytes: &[u8]) -> Result<Box<dyn ImageDecoderPlugin + '_>, Error> {
+    Ok(Box::new(ICOImageDecoderPlugin::create(bytes)?))
+}
+
+fn create_jpeg_plugin(bytes: &[u8]) -> Result<Box<dyn ImageDecoderPlugin + '_>, Error> {
+    Ok(Box::new(JPEGImageDecoderPlugin::create(bytes)?))
+}
+
+fn create_png_plugin(bytes: &[u8]) -> Result<Box<dyn ImageDecoderPlugin + '_>, Error> {
+    Ok(Box::new(PNGImageDecoderPlugin::create(bytes)?))
+}
+
+fn create_portable_image_plugin(bytes: &[u8]) -> Result<Box<dyn ImageDecoderPlugin + '_>, Error> {
+    Ok(Box::new(PortableImageDecoderPlugin::create(bytes)?))
+}
+
+fn create_qoi_plugin(bytes: &[u8]) -> Result<Box<dyn ImageDecoderPlugin + '_>, Error> {
+    Ok(Box::new(QOIImageDecoderPlugin::create(bytes)?))
+}
+
+fn create_tga_plugin(bytes: &[u8]) -> Result<Box<dyn ImageDecoderPlugin + '_>, Error> {
+    Ok(Box::new(TGAImageDecoderPlugin::create(bytes)?))
+}
+
+fn create_tiff_plugin(bytes: &[u8]) -> Result<Box<dyn ImageDecoderPlugin + '_>, Error> {
+    Ok(Box::new(TIFFImageDecoderPlugin::create(bytes)?))
+}
+
+fn create_tinyvg_plugin(bytes: &[u8]) -> Result<Box<dyn ImageDecoderPlugin + '_>, Error> {
+    Ok(Box::new(TinyVGImageDecoderPlugin::create(bytes)?))
+}
+
+fn create_webp_plugin(bytes: &[u8]) -> Result<Box<dyn ImageDecoderPlugin + '_>, Error> {
+    Ok(Box::new(WebPImageDecoderPlugin::create(bytes)?))
+}
+
+pub struct ImageDecoder<'a> {
+    plugin: Box<dyn ImageDecoderPlugin + 'a>
+}
+
+impl<'a> ImageDecoder<'a> {
+    /// Picks a decoder by sniffing `bytes`. The optional `hint` is either a MIME type
+    /// (e.g. "image/x-targa") or a file extension (e.g. "tga" or ".tga").
+    pub fn try_create(bytes: &'a [u8], hint: Option<&str>) -> Result<Self, Error> {
+        if let Some(initializer) = INITIALIZERS.iter().find(|initializer| (initializer.sniff)(bytes)) {
+            return Ok(Self { plugin: (initializer.create)(bytes)? });
+        }
+
+        if let Some(hint) = hint {
+            let hint = hint.trim().to_ascii_lowercase();
+            let matches_hint = |initializer: &&ImagePluginWithHintInitializer| {
+                if hint.contains('/') {
+                    initializer.mime_types.contains(&hint.as_str())
+                } else {
+                    initializer.extensions.contains(&hint.trim_start_matches('.'))
+                }
+            };
+            if let Some(initializer) = INITIALIZERS_WITH_HINT.iter().find(matches_hint) {
+                (initializer.validate_before_create)(bytes)?;
+                return Ok(Self { plugin: (initializer.create)(bytes)? });
+            }
+        }
+
+        Err(Error::Unsupported("Unknown image format"))
+    }
+
+    pub fn size(&self) -> IntSize {
+        self.plugin.size()
+    }
+
+    pub fn is_animated(&self) -> bool {
+        self.plugin.is_animated()
+    }
+
+    pub fn loop_count(&self) -> usize {
+        self.plugin.loop_count()
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.plugin.frame_count()
+    }
+
+    pub fn first_animated_frame_index(&self) -> usize {
+        self.plugin.first_animated_frame_index()
+    }
+
+    pub fn frame(&mut self, frame_index: usize, ideal_size: Option<IntSize>) -> Result<ImageFrameDescriptor, Error> {
+        self.plugin.frame_with_ideal_size(frame_index, ideal_size)
+    }
+
+    pub fn metadata(&self) -> Option<&dyn Metadata> {
+        self.plugin.metadata()
+    }
+
+    pub fn hotspot(&self, ideal_size: Option<IntSize>) -> Option<IntPoint> {
+        self.plugin.hotspot(ideal_size)
+    }
+
+    pub fn natural_frame_format(&self) -> NaturalFrameFormat {
+        self.plugin.natural_frame_format()
+    }
+
+    pub fn cmyk_frame(&mut self, frame_index: usize) -> Result<CMYKBitmap, Error> {
+        self.plugin.cmyk_frame(frame_index)
+    }
+
+    pub fn vector_frame(&mut self, frame_index: usize) -> Result<VectorImageFrameDescriptor, Error> {
+        self.plugin.vector_frame(frame_index)
+    }
+
+    pub fn into_plugin(self) -> Box<dyn ImageDecoderPlugin + 'a> {
+        self.plugin
+    }
+}
+
+/// Creates a decoder for any supported format, usable with the `image_decoder_plugin_*` functions.
+///
+/// # Safety
+///
+/// `bytes` must point to `size` readable bytes that outlive the returned decoder, `hint` must either
+/// be null or a NUL-terminated MIME type or file extension, and `out_decoder` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn image_decoder_new(bytes: *const u8, size: usize, hint: *const c_char, out_decoder: *mut *mut c_void) -> ErrorCode {
+    ffi_call(|| unsafe {
+        if bytes.is_null() {
+            return Err(Error::InvalidArgument("Bytes are null"));
+        }
+        if out_decoder.is_null() {
+            return Err(Error::InvalidArgument("Output pointer is null"));
+        }
+        let bytes = std::slice::from_raw_parts(bytes, size);
+        let hint = if hint.is_null() {
+            None
+        } else {
+            match CStr::from_ptr(hint).to_str() {
+                Ok(hint) => Some(hint),
+                Err(_) => return Err(Error::InvalidArgument("Hint is not valid UTF-8"))
+            }
+        };
+        let decoder = ImageDecoder::try_create(bytes, hint)?;
+        write_ffi_result(out_decoder, decoder_into_opaque(decoder.into_plugin()))
+    })
+}
+
+/// # Safety
+///
+/// `opaque_decoder` must either be null or come from `image_decoder_new` and not be freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn image_decoder_free(opaque_decoder: *mut c_void) -> ErrorCode {
+    ffi_call(|| {
+        if !opaque_decoder.is_null() {
+            let decoder: Box<Box<dyn ImageDecoderPlugin>> = unsafe { Box::from_raw(opaque_decoder as *mut _) };
+            drop(decoder);
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // NOTE: 1x1 uncompressed 24-bit TGA without a footer, so it can only be found through a hint.
+    const TGA_IMAGE: [u8; 21] = [
+        0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x01, 0x00, 0x18, 0x00, 0x20, 0x40,
+        0x60
+    ];
+
+    // NOTE: The same image followed by an empty TGA 2.0 footer, which makes it sniffable.
+    const TGA_2_0_IMAGE: [u8; 47] = [
+        0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x01, 0x00, 0x18, 0x00, 0x20, 0x40,
+        0x60, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x54, 0x52, 0x55, 0x45, 0x56, 0x49, 0x53, 0x49, 0x4F, 0x4E, 0x2D,
+        0x58, 0x46, 0x49, 0x4C, 0x45, 0x2E, 0x00
+    ];
+
+    #[test]
+    fn sniffs_formats_with_a_signature() {
+        let decoder = ImageDecoder::try_create(&TGA_2_0_IMAGE, None).unwrap();
+        assert_eq!((decoder.size().width, decoder.size().height), (1, 1));
+    }
+
+    #[test]
+    fn uses_hint_for_formats_without_a_signature() {
+        assert_eq!(ImageDecoder::try_create(&TGA_IMAGE, None).err(), Some(Error::Unsupported("Unknown image format")));
+        for hint in ["tga", ".TGA", " vda ", "image/x-targa", "IMAGE/TGA"] {
+            assert!(ImageDecoder::try_create(&TGA_IMAGE, Some(hint)).is_ok(), "hint {hint:?}");
+        }
+        assert_eq!(ImageDecoder::try_create(&TGA_IMAGE, Some("image/tga.tga")).err(), Some(Error::Unsupported("Unknown image format")));
+    }
+
+    #[test]
+    fn validates_hinted_format_before_creating_the_decoder() {
+        assert_eq!(ImageDecoder::try_create(&TGA_IMAGE[..10], Some("tga")).err(), Some(Error::Truncated));
+    }
+
+    #[test]
+    fn rejects_null_pointers_across_the_ffi() {
+        unsafe {
+            let mut decoder = std::ptr::null_mut();
+            assert_eq!(image_decoder_new(std::ptr::null(), 0, std::ptr::null(), &mut decoder), ErrorCode::InvalidArgument);
+            assert_eq!(image_decoder_new(TGA_2_0_IMAGE.as_ptr(), TGA_2_0_IMAGE.len(), std::ptr::null(), std::ptr::null_mut()), ErrorCode::InvalidArgument);
+            assert!(decoder.is_null());
+            assert_eq!(image_decoder_new(TGA_IMAGE.as_ptr(), TGA_IMAGE.len(), c"tga".as_ptr(), &mut decoder), ErrorCode::Success);
+            assert!(!decoder.is_null());
+            assert_eq!(image_decoder_free(decoder), ErrorCode::Success);
+            assert_eq!(image_decoder_free(std::ptr::null_mut()), ErrorCode::Success);
+        }
+    }
+}
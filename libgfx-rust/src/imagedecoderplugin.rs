@@ -1,13 +1,22 @@
+use std::collections::BTreeMap;
 use std::ffi::c_void;
 use std::mem::ManuallyDrop;
 use crate::bitmap::{Bitmap, BitmapFormat};
-use crate::IntSize;
+use crate::cmykbitmap::CMYKBitmap;
+use crate::vectorgraphic::VectorGraphic;
+use crate::{IntPoint, IntSize};
+use crate::error::{Error, ErrorCode, ffi_call, write_ffi_result};
 
 pub struct ImageFrameDescriptor {
     pub image: Bitmap,
     pub duration: i32
 }
 
+pub struct VectorImageFrameDescriptor {
+    pub graphic: VectorGraphic,
+    pub duration: i32
+}
+
 #[repr(C)]
 #[derive (Debug, PartialEq)]
 pub enum NaturalFrameFormat {
@@ -44,6 +53,35 @@ pub struct FFIBitmap {
     pub data: FFIBuffer
 }
 
+impl FFIBitmap {
+    /// Copies a bitmap owned by the caller into a `Bitmap`.
+    ///
+    /// # Safety
+    ///
+    /// `data.data` must point to `data.size` readable bytes.
+    pub(crate) unsafe fn to_bitmap(&self) -> Result<Bitmap, Error> {
+        if matches!(self.format, BitmapFormat::Invalid) {
+            return Err(Error::InvalidArgument("Invalid bitmap format"));
+        }
+        if self.data.data.is_null() {
+            return Err(Error::InvalidArgument("Bitmap data is null"));
+        }
+        let mut bitmap = Bitmap::new(self.format, self.size, self.scale)?;
+        let row_size = bitmap.pitch as usize;
+        let row_count = bitmap.physical_height() as usize;
+        if (self.pitch as usize) < row_size || self.data.size < self.pitch as usize * (row_count - 1) + row_size {
+            return Err(Error::InvalidArgument("Bitmap data is too small"));
+        }
+
+        let data = unsafe { std::slice::from_raw_parts(self.data.data, self.data.size) };
+        for (row, scanline) in bitmap.data.chunks_exact_mut(row_size).enumerate() {
+            let offset = row * self.pitch as usize;
+            scanline.copy_from_slice(&data[offset..offset + row_size]);
+        }
+        Ok(bitmap)
+    }
+}
+
 impl From<Bitmap> for FFIBitmap {
     fn from(bitmap: Bitmap) -> Self {
         FFIBitmap {
@@ -56,6 +94,49 @@ impl From<Bitmap> for FFIBitmap {
     }
 }
 
+#[repr(C)]
+pub struct FFICMYKBitmap {
+    pub size: IntSize,
+    pub pitch: u32,
+    pub data: FFIBuffer
+}
+
+impl FFICMYKBitmap {
+    /// Copies a CMYK bitmap owned by the caller into a `CMYKBitmap`.
+    ///
+    /// # Safety
+    ///
+    /// `data.data` must point to `data.size` readable bytes.
+    pub(crate) unsafe fn to_cmyk_bitmap(&self) -> Result<CMYKBitmap, Error> {
+        if self.data.data.is_null() {
+            return Err(Error::InvalidArgument("Bitmap data is null"));
+        }
+        let mut bitmap = CMYKBitmap::new(self.size)?;
+        let row_size = bitmap.pitch();
+        let row_count = bitmap.size.height as usize;
+        if (self.pitch as usize) < row_size || self.data.size < self.pitch as usize * (row_count - 1) + row_size {
+            return Err(Error::InvalidArgument("Bitmap data is too small"));
+        }
+
+        let data = unsafe { std::slice::from_raw_parts(self.data.data, self.data.size) };
+        for (row, scanline) in bitmap.data.chunks_exact_mut(row_size).enumerate() {
+            let offset = row * self.pitch as usize;
+            scanline.copy_from_slice(&data[offset..offset + row_size]);
+        }
+        Ok(bitmap)
+    }
+}
+
+impl From<CMYKBitmap> for FFICMYKBitmap {
+    fn from(bitmap: CMYKBitmap) -> Self {
+        FFICMYKBitmap {
+            size: bitmap.size,
+            pitch: bitmap.pitch() as u32,
+            data: bitmap.data.into()
+        }
+    }
+}
+
 #[repr(C)]
 pub struct FFIImageFrameDescriptor {
     pub image: FFIBitmap,
@@ -71,78 +152,226 @@ impl From<ImageFrameDescriptor> for FFIImageFrameDescriptor {
     }
 }
 
+pub trait Metadata {
+    fn main_tags(&self) -> BTreeMap<&'static str, String>;
+}
+
 pub trait ImageDecoderPlugin {
     fn size(&self) -> IntSize;
     fn is_animated(&self) -> bool { false }
     fn loop_count(&self) -> usize { 0 }
     fn frame_count(&self) -> usize { 1 }
     fn first_animated_frame_index(&self) -> usize { 0 }
-    fn frame_with_ideal_size(&mut self, frame_index: usize, ideal_size: Option<IntSize>) -> Result<ImageFrameDescriptor, String>;
-    fn frame(&mut self, frame_index: usize) -> Result<ImageFrameDescriptor, String> {
+    fn frame_with_ideal_size(&mut self, frame_index: usize, ideal_size: Option<IntSize>) -> Result<ImageFrameDescriptor, Error>;
+    fn frame(&mut self, frame_index: usize) -> Result<ImageFrameDescriptor, Error> {
         self.frame_with_ideal_size(frame_index, None)
     }
-    // FIXME: Metadata
+    fn metadata(&self) -> Option<&dyn Metadata> { None }
+    // NOTE: Only cursors have a hotspot, which can differ between the images picked for each ideal size.
+    fn hotspot(&self, _ideal_size: Option<IntSize>) -> Option<IntPoint> { None }
     // FIXME: ICC data
     fn natural_frame_format(&self) -> NaturalFrameFormat { NaturalFrameFormat::RGB }
-    // FIXME: CMYK Frame
-    // FIXME: Vector Frame
+    fn cmyk_frame(&mut self, _frame_index: usize) -> Result<CMYKBitmap, Error> {
+        Err(Error::Unsupported("This image decoder does not support CMYK frames"))
+    }
+    fn vector_frame(&mut self, _frame_index: usize) -> Result<VectorImageFrameDescriptor, Error> {
+        Err(Error::Unsupported("This image decoder does not support vector frames"))
+    }
+}
+
+pub(crate) fn decoder_into_opaque(decoder: Box<dyn ImageDecoderPlugin + '_>) -> *mut c_void {
+    let boxed_interface = Box::new(decoder);
+    Box::into_raw(boxed_interface) as *mut c_void
+}
+
+/// # Safety
+///
+/// `opaque_decoder` must either be null or come from one of the `*_image_decoder_plugin_new` functions.
+pub(crate) unsafe fn decoder_from_opaque<'a>(opaque_decoder: *mut c_void) -> Result<&'a mut Box<dyn ImageDecoderPlugin>, Error> {
+    if opaque_decoder.is_null() {
+        return Err(Error::InvalidArgument("Decoder is null"));
+    }
+    Ok(unsafe { &mut *(opaque_decoder as *mut Box<dyn ImageDecoderPlugin>) })
+}
+
+/// # Safety
+///
+/// `opaque_decoder` must come from a decoder constructor and `out_size` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn image_decoder_plugin_size(opaque_decoder: *mut c_void, out_size: *mut IntSize) -> ErrorCode {
+    ffi_call(|| unsafe {
+        let decoder = decoder_from_opaque(opaque_decoder)?;
+        write_ffi_result(out_size, decoder.size())
+    })
+}
+
+/// # Safety
+///
+/// `opaque_decoder` must come from a decoder constructor and `out_is_animated` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn image_decoder_plugin_is_animated(opaque_decoder: *mut c_void, out_is_animated: *mut bool) -> ErrorCode {
+    ffi_call(|| unsafe {
+        let decoder = decoder_from_opaque(opaque_decoder)?;
+        write_ffi_result(out_is_animated, decoder.is_animated())
+    })
+}
+
+/// # Safety
+///
+/// `opaque_decoder` must come from a decoder constructor and `out_loop_count` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn image_decoder_plugin_loop_count(opaque_decoder: *mut c_void, out_loop_count: *mut usize) -> ErrorCode {
+    ffi_call(|| unsafe {
+        let decoder = decoder_from_opaque(opaque_decoder)?;
+        write_ffi_result(out_loop_count, decoder.loop_count())
+    })
+}
+
+/// # Safety
+///
+/// `opaque_decoder` must come from a decoder constructor and `out_frame_count` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn image_decoder_plugin_frame_count(opaque_decoder: *mut c_void, out_frame_count: *mut usize) -> ErrorCode {
+    ffi_call(|| unsafe {
+        let decoder = decoder_from_opaque(opaque_decoder)?;
+        write_ffi_result(out_frame_count, decoder.frame_count())
+    })
 }
 
+/// # Safety
+///
+/// `opaque_decoder` must come from a decoder constructor and `out_index` must be valid for writes.
 #[no_mangle]
-pub extern "C" fn image_decoder_plugin_size(opaque_decoder: *mut c_void) -> IntSize {
-    let decoder: ManuallyDrop<Box<Box<dyn ImageDecoderPlugin>>> = unsafe { ManuallyDrop::new(Box::from_raw(opaque_decoder as *mut _)) };
-    decoder.size()
+pub unsafe extern "C" fn image_decoder_plugin_first_animated_frame_index(opaque_decoder: *mut c_void, out_index: *mut usize) -> ErrorCode {
+    ffi_call(|| unsafe {
+        let decoder = decoder_from_opaque(opaque_decoder)?;
+        write_ffi_result(out_index, decoder.first_animated_frame_index())
+    })
 }
 
+/// # Safety
+///
+/// `opaque_decoder` must come from a decoder constructor, `ideal_size` must either be null or point
+/// to a valid `IntSize`, and `out_frame` must be valid for writes.
 #[no_mangle]
-pub extern "C" fn image_decoder_plugin_is_animated(opaque_decoder: *mut c_void) -> bool {
-    let decoder: ManuallyDrop<Box<Box<dyn ImageDecoderPlugin>>> = unsafe { ManuallyDrop::new(Box::from_raw(opaque_decoder as *mut _)) };
-    decoder.is_animated()
+pub unsafe extern "C" fn image_decoder_plugin_frame_with_ideal_size(opaque_decoder: *mut c_void, frame_index: usize, ideal_size: *const IntSize, out_frame: *mut FFIImageFrameDescriptor) -> ErrorCode {
+    ffi_call(|| unsafe {
+        let decoder = decoder_from_opaque(opaque_decoder)?;
+        let ideal_size = if ideal_size.is_null() {
+            None
+        } else {
+            Some(*ideal_size)
+        };
+        if out_frame.is_null() {
+            return Err(Error::InvalidArgument("Output pointer is null"));
+        }
+        let frame = decoder.frame_with_ideal_size(frame_index, ideal_size)?;
+        write_ffi_result(out_frame, frame.into())
+    })
 }
 
+/// # Safety
+///
+/// `ffiimage_frame_descriptor` must have been returned by one of the frame functions and not freed yet.
 #[no_mangle]
-pub extern "C" fn image_decoder_plugin_loop_count(opaque_decoder: *mut c_void) -> usize {
-    let decoder: ManuallyDrop<Box<Box<dyn ImageDecoderPlugin>>> = unsafe { ManuallyDrop::new(Box::from_raw(opaque_decoder as *mut _)) };
-    decoder.loop_count()
+pub unsafe extern "C" fn image_decoder_plugin_free_frame(ffiimage_frame_descriptor: FFIImageFrameDescriptor) -> ErrorCode {
+    ffi_call(|| {
+        let ffi_buffer = ffiimage_frame_descriptor.image.data;
+        if !ffi_buffer.data.is_null() {
+            let _ = unsafe { Vec::from_raw_parts(ffi_buffer.data, ffi_buffer.size, ffi_buffer.capacity) };
+        }
+        Ok(())
+    })
 }
 
+/// # Safety
+///
+/// `ffi_buffer` must have been returned by one of the encoder functions and not freed yet.
 #[no_mangle]
-pub extern "C" fn image_decoder_plugin_frame_count(opaque_decoder: *mut c_void) -> usize {
-    let decoder: ManuallyDrop<Box<Box<dyn ImageDecoderPlugin>>> = unsafe { ManuallyDrop::new(Box::from_raw(opaque_decoder as *mut _)) };
-    decoder.frame_count()
+pub unsafe extern "C" fn ffi_buffer_free(ffi_buffer: FFIBuffer) -> ErrorCode {
+    ffi_call(|| {
+        if !ffi_buffer.data.is_null() {
+            let _ = unsafe { Vec::from_raw_parts(ffi_buffer.data, ffi_buffer.size, ffi_buffer.capacity) };
+        }
+        Ok(())
+    })
 }
 
+/// # Safety
+///
+/// `opaque_decoder` must come from a decoder constructor and `out_frame` must be valid for writes.
 #[no_mangle]
-pub extern "C" fn image_decoder_plugin_first_animated_frame_index(opaque_decoder: *mut c_void) -> usize {
-    let decoder: ManuallyDrop<Box<Box<dyn ImageDecoderPlugin>>> = unsafe { ManuallyDrop::new(Box::from_raw(opaque_decoder as *mut _)) };
-    decoder.first_animated_frame_index()
+pub unsafe extern "C" fn image_decoder_plugin_frame(opaque_decoder: *mut c_void, frame_index: usize, out_frame: *mut FFIImageFrameDescriptor) -> ErrorCode {
+    ffi_call(|| unsafe {
+        let decoder = decoder_from_opaque(opaque_decoder)?;
+        if out_frame.is_null() {
+            return Err(Error::InvalidArgument("Output pointer is null"));
+        }
+        let frame = decoder.frame(frame_index)?;
+        write_ffi_result(out_frame, frame.into())
+    })
 }
 
+/// # Safety
+///
+/// `opaque_decoder` must come from a decoder constructor and `out_format` must be valid for writes.
 #[no_mangle]
-pub unsafe extern "C" fn image_decoder_plugin_frame_with_ideal_size(opaque_decoder: *mut c_void, frame_index: usize, ideal_size: *const IntSize) -> FFIImageFrameDescriptor {
-    let mut decoder: ManuallyDrop<Box<Box<dyn ImageDecoderPlugin>>> = unsafe { ManuallyDrop::new(Box::from_raw(opaque_decoder as *mut _)) };
-    let ideal_size = if ideal_size.is_null() {
-        None
-    } else {
-        Some(unsafe { *ideal_size })
-    };
-    decoder.frame_with_ideal_size(frame_index, ideal_size).unwrap().into()
+pub unsafe extern "C" fn image_decoder_plugin_natural_frame_format(opaque_decoder: *mut c_void, out_format: *mut NaturalFrameFormat) -> ErrorCode {
+    ffi_call(|| unsafe {
+        let decoder = decoder_from_opaque(opaque_decoder)?;
+        write_ffi_result(out_format, decoder.natural_frame_format())
+    })
 }
 
+/// Writes the hotspot of the image picked for `ideal_size`, and whether there is one at all.
+///
+/// # Safety
+///
+/// `opaque_decoder` must come from a decoder constructor, `ideal_size` must either be null or point
+/// to a valid `IntSize`, and `out_has_hotspot` and `out_hotspot` must be valid for writes.
 #[no_mangle]
-pub extern "C" fn image_decoder_plugin_free_frame(ffiimage_frame_descriptor: FFIImageFrameDescriptor) {
-    let ffi_buffer = ffiimage_frame_descriptor.image.data;
-    let _ = unsafe { Vec::from_raw_parts(ffi_buffer.data, ffi_buffer.size, ffi_buffer.capacity) };
+pub unsafe extern "C" fn image_decoder_plugin_hotspot(opaque_decoder: *mut c_void, ideal_size: *const IntSize, out_has_hotspot: *mut bool, out_hotspot: *mut IntPoint) -> ErrorCode {
+    ffi_call(|| unsafe {
+        let decoder = decoder_from_opaque(opaque_decoder)?;
+        let ideal_size = if ideal_size.is_null() {
+            None
+        } else {
+            Some(*ideal_size)
+        };
+        if out_has_hotspot.is_null() || out_hotspot.is_null() {
+            return Err(Error::InvalidArgument("Output pointer is null"));
+        }
+        let hotspot = decoder.hotspot(ideal_size);
+        write_ffi_result(out_has_hotspot, hotspot.is_some())?;
+        write_ffi_result(out_hotspot, hotspot.unwrap_or(IntPoint { x: 0, y: 0 }))
+    })
 }
 
+/// # Safety
+///
+/// `opaque_decoder` must come from a decoder constructor and `out_frame` must be valid for writes.
 #[no_mangle]
-pub extern "C" fn image_decoder_plugin_frame(opaque_decoder: *mut c_void, frame_index: usize) -> FFIImageFrameDescriptor {
-    let mut decoder: ManuallyDrop<Box<Box<dyn ImageDecoderPlugin>>> = unsafe { ManuallyDrop::new(Box::from_raw(opaque_decoder as *mut _)) };
-    decoder.frame(frame_index).unwrap().into()
+pub unsafe extern "C" fn image_decoder_plugin_cmyk_frame(opaque_decoder: *mut c_void, frame_index: usize, out_frame: *mut FFICMYKBitmap) -> ErrorCode {
+    ffi_call(|| unsafe {
+        let decoder = decoder_from_opaque(opaque_decoder)?;
+        if out_frame.is_null() {
+            return Err(Error::InvalidArgument("Output pointer is null"));
+        }
+        let frame = decoder.cmyk_frame(frame_index)?;
+        write_ffi_result(out_frame, frame.into())
+    })
 }
 
+/// # Safety
+///
+/// `ffi_cmyk_bitmap` must have been returned by `image_decoder_plugin_cmyk_frame` and not freed yet.
 #[no_mangle]
-pub extern "C" fn image_decoder_plugin_natural_frame_format(opaque_decoder: *mut c_void) -> NaturalFrameFormat {
-    let decoder: ManuallyDrop<Box<Box<dyn ImageDecoderPlugin>>> = unsafe { ManuallyDrop::new(Box::from_raw(opaque_decoder as *mut _)) };
-    decoder.natural_frame_format()
+pub unsafe extern "C" fn image_decoder_plugin_free_cmyk_frame(ffi_cmyk_bitmap: FFICMYKBitmap) -> ErrorCode {
+    ffi_call(|| {
+        let ffi_buffer = ffi_cmyk_bitmap.data;
+        if !ffi_buffer.data.is_null() {
+            let _ = unsafe { Vec::from_raw_parts(ffi_buffer.data, ffi_buffer.size, ffi_buffer.capacity) };
+        }
+        Ok(())
+    })
 }
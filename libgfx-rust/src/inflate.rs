@@ -0,0 +1,355 @@
+use crate::error::Error;
+
+const LENGTH_BASE: [u16; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+const LENGTH_EXTRA_BITS: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DISTANCE_BASE: [u16; 30] = [1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577];
+const DISTANCE_EXTRA_BITS: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+const CODE_LENGTHS_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+const MAX_CODE_LENGTH: usize = 15;
+
+/// Reads bits least significant bit first, as DEFLATE streams are packed.
+pub(crate) struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+    bit_buffer: u64,
+    bit_count: u32
+}
+
+impl<'a> BitReader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Self { data, position: 0, bit_buffer: 0, bit_count: 0 }
+    }
+
+    fn refill(&mut self) {
+        while self.bit_count <= 56 && self.position < self.data.len() {
+            self.bit_buffer |= (self.data[self.position] as u64) << self.bit_count;
+            self.position += 1;
+            self.bit_count += 8;
+        }
+    }
+
+    pub(crate) fn peek_bits(&mut self, count: u32) -> u32 {
+        if self.bit_count < count {
+            self.refill();
+        }
+        // NOTE: Missing bits past the end of the data read as zeroes, consume_bits() catches them.
+        (self.bit_buffer & ((1u64 << count) - 1)) as u32
+    }
+
+    pub(crate) fn consume_bits(&mut self, count: u32) -> Result<(), Error> {
+        if self.bit_count < count {
+            return Err(Error::Truncated);
+        }
+        self.bit_buffer >>= count;
+        self.bit_count -= count;
+        Ok(())
+    }
+
+    pub(crate) fn read_bits(&mut self, count: u32) -> Result<u32, Error> {
+        let value = self.peek_bits(count);
+        self.consume_bits(count)?;
+        Ok(value)
+    }
+
+    fn align_to_byte(&mut self) {
+        let padding = self.bit_count % 8;
+        self.bit_buffer >>= padding;
+        self.bit_count -= padding;
+    }
+
+    fn bytes_consumed(&self) -> usize {
+        self.position - (self.bit_count / 8) as usize
+    }
+}
+
+struct HuffmanTable {
+    // NOTE: Indexed by the next max_length bits of the stream, each entry is (symbol << 4) | code length.
+    entries: Vec<u32>,
+    max_length: u32
+}
+
+impl HuffmanTable {
+    fn new(code_lengths: &[u8]) -> Result<Self, Error> {
+        let mut length_counts = [0u16; MAX_CODE_LENGTH + 1];
+        for length in code_lengths {
+            length_counts[*length as usize] += 1;
+        }
+        length_counts[0] = 0;
+
+        let mut codes_left = 1i32;
+        for count in &length_counts[1..] {
+            codes_left = (codes_left << 1) - *count as i32;
+            if codes_left < 0 {
+                return Err(Error::InvalidData("Over-subscribed Huffman code"));
+            }
+        }
+
+        let max_length = code_lengths.iter().copied().max().unwrap_or(0) as u32;
+        let mut next_code = [0u32; MAX_CODE_LENGTH + 2];
+        for length in 1..=MAX_CODE_LENGTH {
+            next_code[length + 1] = (next_code[length] + length_counts[length] as u32) << 1;
+        }
+
+        let mut entries = vec![0u32; 1 << max_length];
+        for (symbol, length) in code_lengths.iter().enumerate() {
+            let length = *length as u32;
+            if length == 0 {
+                continue;
+            }
+            let code = next_code[length as usize];
+            next_code[length as usize] += 1;
+            let reversed_code = code.reverse_bits() >> (32 - length);
+            let mut index = reversed_code as usize;
+            while index < entries.len() {
+                entries[index] = ((symbol as u32) << 4) | length;
+                index += 1 << length;
+            }
+        }
+
+        Ok(Self { entries, max_length })
+    }
+
+    fn decode_symbol(&self, reader: &mut BitReader) -> Result<u16, Error> {
+        if self.max_length == 0 {
+            return Err(Error::InvalidData("Empty Huffman code"));
+        }
+        let entry = self.entries[reader.peek_bits(self.max_length) as usize];
+        let length = entry & 0xF;
+        if length == 0 {
+            return Err(Error::InvalidData("Invalid Huffman code"));
+        }
+        reader.consume_bits(length)?;
+        Ok((entry >> 4) as u16)
+    }
+}
+
+fn fixed_huffman_tables() -> Result<(HuffmanTable, HuffmanTable), Error> {
+    let mut literal_lengths = [0u8; 288];
+    literal_lengths[..144].fill(8);
+    literal_lengths[144..256].fill(9);
+    literal_lengths[256..280].fill(7);
+    literal_lengths[280..].fill(8);
+    Ok((HuffmanTable::new(&literal_lengths)?, HuffmanTable::new(&[5u8; 30])?))
+}
+
+fn dynamic_huffman_tables(reader: &mut BitReader) -> Result<(HuffmanTable, HuffmanTable), Error> {
+    let literal_count = reader.read_bits(5)? as usize + 257;
+    let distance_count = reader.read_bits(5)? as usize + 1;
+    let code_length_count = reader.read_bits(4)? as usize + 4;
+    if literal_count > 286 || distance_count > 30 {
+        return Err(Error::InvalidData("Too many Huffman codes"));
+    }
+
+    let mut code_length_lengths = [0u8; 19];
+    for index in CODE_LENGTHS_ORDER.iter().take(code_length_count) {
+        code_length_lengths[*index] = reader.read_bits(3)? as u8;
+    }
+    let code_length_table = HuffmanTable::new(&code_length_lengths)?;
+
+    let mut lengths = Vec::with_capacity(literal_count + distance_count);
+    while lengths.len() < literal_count + distance_count {
+        let symbol = code_length_table.decode_symbol(reader)?;
+        let (length, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => {
+                let Some(previous) = lengths.last() else {
+                    return Err(Error::InvalidData("Repeated code length without a previous one"));
+                };
+                (*previous, 3 + reader.read_bits(2)? as usize)
+            }
+            17 => (0, 3 + reader.read_bits(3)? as usize),
+            _ => (0, 11 + reader.read_bits(7)? as usize)
+        };
+        if lengths.len() + repeat > literal_count + distance_count {
+            return Err(Error::InvalidData("Too many code lengths"));
+        }
+        lengths.resize(lengths.len() + repeat, length);
+    }
+
+    if lengths[256] == 0 {
+        return Err(Error::InvalidData("Missing end of block code"));
+    }
+    Ok((HuffmanTable::new(&lengths[..literal_count])?, HuffmanTable::new(&lengths[literal_count..])?))
+}
+
+fn inflate_block(reader: &mut BitReader, output: &mut Vec<u8>, output_limit: usize, literals: &HuffmanTable, distances: &HuffmanTable) -> Result<(), Error> {
+    loop {
+        let symbol = literals.decode_symbol(reader)? as usize;
+        if symbol < 256 {
+            if output.len() >= output_limit {
+                return Err(Error::InvalidData("Decompressed data is larger than expected"));
+            }
+            output.push(symbol as u8);
+            continue;
+        }
+        if symbol == 256 {
+            return Ok(());
+        }
+
+        let length_index = symbol - 257;
+        if length_index >= LENGTH_BASE.len() {
+            return Err(Error::InvalidData("Invalid length symbol"));
+        }
+        let length = LENGTH_BASE[length_index] as usize + reader.read_bits(LENGTH_EXTRA_BITS[length_index] as u32)? as usize;
+
+        let distance_index = distances.decode_symbol(reader)? as usize;
+        if distance_index >= DISTANCE_BASE.len() {
+            return Err(Error::InvalidData("Invalid distance symbol"));
+        }
+        let distance = DISTANCE_BASE[distance_index] as usize + reader.read_bits(DISTANCE_EXTRA_BITS[distance_index] as u32)? as usize;
+        if distance > output.len() {
+            return Err(Error::InvalidData("Distance goes past the start of the output"));
+        }
+        if length > output_limit - output.len() {
+            return Err(Error::InvalidData("Decompressed data is larger than expected"));
+        }
+
+        // NOTE: The copy may overlap the bytes it produces, so it has to go byte by byte.
+        let start = output.len() - distance;
+        for index in 0..length {
+            output.push(output[start + index]);
+        }
+    }
+}
+
+/// Decompresses a raw DEFLATE stream, returning the output and the number of input bytes used.
+/// Streams that would produce more than `output_limit` bytes are rejected rather than grown without bound.
+pub(crate) fn inflate(data: &[u8], output_limit: usize) -> Result<(Vec<u8>, usize), Error> {
+    let mut reader = BitReader::new(data);
+    let mut output = Vec::new();
+    if output.try_reserve(output_limit).is_err() {
+        return Err(Error::OutOfMemory);
+    }
+
+    loop {
+        let is_final_block = reader.read_bits(1)? == 1;
+        match reader.read_bits(2)? {
+            0 => {
+                reader.align_to_byte();
+                let length = reader.read_bits(16)? as u16;
+                let complement = reader.read_bits(16)? as u16;
+                if length != !complement {
+                    return Err(Error::InvalidData("Stored block length mismatch"));
+                }
+                if length as usize > output_limit - output.len() {
+                    return Err(Error::InvalidData("Decompressed data is larger than expected"));
+                }
+                for _ in 0..length {
+                    output.push(reader.read_bits(8)? as u8);
+                }
+            }
+            1 => {
+                let (literals, distances) = fixed_huffman_tables()?;
+                inflate_block(&mut reader, &mut output, output_limit, &literals, &distances)?;
+            }
+            2 => {
+                let (literals, distances) = dynamic_huffman_tables(&mut reader)?;
+                inflate_block(&mut reader, &mut output, output_limit, &literals, &distances)?;
+            }
+            _ => return Err(Error::InvalidData("Invalid DEFLATE block type"))
+        }
+        if is_final_block {
+            break;
+        }
+    }
+
+    reader.align_to_byte();
+    Ok((output, reader.bytes_consumed()))
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let mut a = 1u32;
+    let mut b = 0u32;
+    // NOTE: 5552 is the largest chunk size for which the sums cannot overflow before the modulo.
+    for chunk in data.chunks(5552) {
+        for byte in chunk {
+            a += *byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
+
+/// Decompresses a zlib stream (RFC 1950), verifying its Adler-32 checksum.
+pub(crate) fn zlib_decompress(data: &[u8], output_limit: usize) -> Result<Vec<u8>, Error> {
+    if data.len() < 2 {
+        return Err(Error::Truncated);
+    }
+    let compression_method_and_flags = data[0];
+    let flags = data[1];
+    if compression_method_and_flags & 0x0F != 8 || !u16::from_be_bytes([compression_method_and_flags, flags]).is_multiple_of(31) {
+        return Err(Error::InvalidHeader("Invalid zlib header"));
+    }
+    if flags & 0x20 != 0 {
+        return Err(Error::Unsupported("zlib preset dictionary"));
+    }
+
+    let (output, consumed) = inflate(&data[2..], output_limit)?;
+    let Some(checksum) = data.get(2 + consumed..2 + consumed + 4) else {
+        return Err(Error::Truncated);
+    };
+    if u32::from_be_bytes(checksum.try_into().unwrap()) != adler32(&output) {
+        return Err(Error::InvalidData("zlib checksum mismatch"));
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STORED_BLOCK: [u8; 25] = [
+        0x01, 0x14, 0x00, 0xEB, 0xFF, 0x48, 0x65, 0x6C, 0x6C, 0x6F, 0x2C, 0x20, 0x48, 0x65, 0x6C, 0x6C, 0x6F, 0x2C, 0x20, 0x48,
+        0x65, 0x6C, 0x6C, 0x6F, 0x21
+    ];
+    const FIXED_BLOCK: [u8; 12] = [0xF3, 0x48, 0xCD, 0xC9, 0xC9, 0xD7, 0x51, 0xF0, 0x40, 0xA2, 0x14, 0x01];
+    const DYNAMIC_BLOCK: [u8; 36] = [
+        0x25, 0x8A, 0x81, 0x09, 0x00, 0x30, 0x0C, 0xC2, 0x6E, 0x4D, 0xF4, 0xFF, 0x1B, 0xD6, 0x76, 0x20, 0x28, 0x31, 0x4A, 0x91,
+        0x89, 0x64, 0x8B, 0x3F, 0x0A, 0xA9, 0xDD, 0xC7, 0xE3, 0x55, 0xC7, 0x4C, 0x4F, 0x29, 0x91, 0x07
+    ];
+    const DYNAMIC_BLOCK_OUTPUT: &[u8] = b"bbadabaababacaabaaabacaadaacdbdbaabbcaabadbbbdabcdbaaabdacba";
+    // NOTE: 1000 zero bytes, compressed mostly into back references.
+    const ZLIB_ZEROES: [u8; 17] = [0x78, 0xDA, 0x63, 0x60, 0x18, 0x05, 0xA3, 0x60, 0x14, 0x0C, 0x77, 0x00, 0x00, 0x03, 0xE8, 0x00, 0x01];
+
+    #[test]
+    fn inflates_stored_block() {
+        assert_eq!(inflate(&STORED_BLOCK, 64), Ok((b"Hello, Hello, Hello!".to_vec(), STORED_BLOCK.len())));
+    }
+
+    #[test]
+    fn inflates_fixed_huffman_block() {
+        assert_eq!(inflate(&FIXED_BLOCK, 64), Ok((b"Hello, Hello, Hello!".to_vec(), FIXED_BLOCK.len())));
+    }
+
+    #[test]
+    fn inflates_dynamic_huffman_block() {
+        assert_eq!(inflate(&DYNAMIC_BLOCK, 64), Ok((DYNAMIC_BLOCK_OUTPUT.to_vec(), DYNAMIC_BLOCK.len())));
+    }
+
+    #[test]
+    fn rejects_output_over_the_limit() {
+        assert!(matches!(inflate(&STORED_BLOCK, 19), Err(Error::InvalidData(_))));
+        assert!(matches!(inflate(&FIXED_BLOCK, 19), Err(Error::InvalidData(_))));
+        assert!(matches!(inflate(&DYNAMIC_BLOCK, DYNAMIC_BLOCK_OUTPUT.len() - 1), Err(Error::InvalidData(_))));
+        assert!(matches!(zlib_decompress(&ZLIB_ZEROES, 999), Err(Error::InvalidData(_))));
+    }
+
+    #[test]
+    fn decompresses_zlib_stream() {
+        assert_eq!(zlib_decompress(&ZLIB_ZEROES, 1000), Ok(vec![0; 1000]));
+    }
+
+    #[test]
+    fn rejects_zlib_checksum_mismatch() {
+        let mut data = ZLIB_ZEROES;
+        data[16] ^= 1;
+        assert!(matches!(zlib_decompress(&data, 1000), Err(Error::InvalidData(_))));
+    }
+
+    #[test]
+    fn rejects_truncated_stream() {
+        assert_eq!(inflate(&DYNAMIC_BLOCK[..20], 64), Err(Error::Truncated));
+    }
+}
@@ -0,0 +1,1304 @@
+use std::ffi::c_void;
+use crate::imagedecoderplugin::{decoder_into_opaque, ImageDecoderPlugin, ImageFrameDescriptor, NaturalFrameFormat};
+use crate::{Color, IntSize};
+use crate::bitmap::{Bitmap, BitmapFormat};
+use crate::cmykbitmap::{CMYK, CMYKBitmap};
+use crate::error::{Error, ErrorCode, ffi_call, write_ffi_result};
+
+const JPEG_SOF0: u8 = 0xC0;
+const JPEG_SOF1: u8 = 0xC1;
+const JPEG_SOF2: u8 = 0xC2;
+const JPEG_DHT: u8 = 0xC4;
+const JPEG_RST0: u8 = 0xD0;
+const JPEG_RST7: u8 = 0xD7;
+const JPEG_SOI: u8 = 0xD8;
+const JPEG_EOI: u8 = 0xD9;
+const JPEG_SOS: u8 = 0xDA;
+const JPEG_DQT: u8 = 0xDB;
+const JPEG_DRI: u8 = 0xDD;
+const JPEG_APP0: u8 = 0xE0;
+const JPEG_APP14: u8 = 0xEE;
+
+/// Maps the position of a coefficient in the bitstream to its position in the 8x8 block.
+const ZIGZAG_TO_NATURAL: [usize; 64] = [
+    0, 1, 8, 16, 9, 2, 3, 10,
+    17, 24, 32, 25, 18, 11, 4, 5,
+    12, 19, 26, 33, 40, 48, 41, 34,
+    27, 20, 13, 6, 7, 14, 21, 28,
+    35, 42, 49, 56, 57, 50, 43, 36,
+    29, 22, 15, 23, 30, 37, 44, 51,
+    58, 59, 52, 45, 38, 31, 39, 46,
+    53, 60, 61, 54, 47, 55, 62, 63
+];
+
+#[derive (Debug, Clone)]
+struct JPEGHuffmanTable {
+    // NOTE: Indexed by code length, see F.2.2.3 of the specification.
+    max_code: [i32; 17],
+    value_offset: [i32; 17],
+    values: Vec<u8>
+}
+
+impl JPEGHuffmanTable {
+    fn new(counts: &[u8], values: &[u8]) -> Result<Self, Error> {
+        let mut max_code = [-1; 17];
+        let mut value_offset = [0; 17];
+        let mut code = 0i32;
+        let mut value_index = 0i32;
+        for length in 1..=16 {
+            let count = counts[length - 1] as i32;
+            value_offset[length] = value_index - code;
+            if count > 0 {
+                code += count;
+                value_index += count;
+                max_code[length] = code - 1;
+            }
+            if code > 1 << length {
+                return Err(Error::InvalidData("Invalid JPEG Huffman table"));
+            }
+            code <<= 1;
+        }
+        Ok(Self { max_code, value_offset, values: values.to_vec() })
+    }
+}
+
+#[derive (Debug, Clone)]
+struct JPEGComponent {
+    id: u8,
+    horizontal_sampling_factor: usize,
+    vertical_sampling_factor: usize,
+    quantization_table_index: usize,
+    dc_table_index: usize,
+    ac_table_index: usize,
+    // NOTE: The block grid is padded to whole MCUs, which non-interleaved scans don't cover entirely.
+    blocks_per_line: usize,
+    blocks_per_column: usize,
+    coefficients: Vec<i16>,
+    dc_predictor: i32
+}
+
+#[derive (Debug, Clone)]
+struct JPEGFrame {
+    is_progressive: bool,
+    width: u16,
+    height: u16,
+    max_horizontal_sampling_factor: usize,
+    max_vertical_sampling_factor: usize,
+    mcus_per_line: usize,
+    mcus_per_column: usize,
+    components: Vec<JPEGComponent>
+}
+
+#[derive (Debug, Clone)]
+struct JPEGScan {
+    component_indices: Vec<usize>,
+    spectral_selection_start: usize,
+    spectral_selection_end: usize,
+    successive_approximation_high: u8,
+    successive_approximation_low: u8
+}
+
+#[derive (Debug, PartialEq, Copy, Clone)]
+enum JPEGColorTransform {
+    Grayscale,
+    Rgb,
+    YCbCr,
+    Cmyk,
+    Ycck
+}
+
+/// Everything we collect from the segments surrounding the frame header and the scans.
+#[derive (Debug, Clone)]
+struct JPEGHeader {
+    quantization_tables: [[u16; 64]; 4],
+    dc_tables: [Option<JPEGHuffmanTable>; 4],
+    ac_tables: [Option<JPEGHuffmanTable>; 4],
+    restart_interval: usize,
+    has_jfif: bool,
+    adobe_transform: Option<u8>
+}
+
+pub struct JPEGImageDecoderPlugin<'a> {
+    context: JPEGLoadingContext<'a>
+}
+
+struct JPEGLoadingContext<'a> {
+    bytes: &'a [u8],
+    data_offset: usize,
+    header: JPEGHeader,
+    frame: JPEGFrame,
+    has_decoded_scans: bool,
+    // NOTE: Indexed by the DCT scaling shift, from full size to 1/8.
+    bitmaps: [Option<Bitmap>; 4],
+    cmyk_bitmap: Option<CMYKBitmap>
+}
+
+struct JPEGReader<'a> {
+    data: &'a [u8],
+    position: usize
+}
+
+impl<'a> JPEGReader<'a> {
+    fn is_eof(&self) -> bool {
+        self.position >= self.data.len()
+    }
+
+    fn read_u8(&mut self) -> Result<u8, Error> {
+        let Some(byte) = self.data.get(self.position) else {
+            return Err(Error::Truncated);
+        };
+        self.position += 1;
+        Ok(*byte)
+    }
+
+    fn read_u16(&mut self) -> Result<u16, Error> {
+        Ok(u16::from_be_bytes([self.read_u8()?, self.read_u8()?]))
+    }
+
+    fn read_bytes(&mut self, count: usize) -> Result<&'a [u8], Error> {
+        let Some(bytes) = self.data.get(self.position..self.position + count) else {
+            return Err(Error::Truncated);
+        };
+        self.position += count;
+        Ok(bytes)
+    }
+
+    fn read_marker(&mut self) -> Result<u8, Error> {
+        // NOTE: Garbage between segments is skipped, as are fill bytes in front of a marker.
+        while self.read_u8()? != 0xFF {}
+        loop {
+            let marker = self.read_u8()?;
+            if marker != 0xFF {
+                return Ok(marker);
+            }
+        }
+    }
+
+    fn read_segment(&mut self) -> Result<JPEGReader<'a>, Error> {
+        let length = self.read_u16()? as usize;
+        if length < 2 {
+            return Err(Error::InvalidData("Invalid JPEG segment length"));
+        }
+        Ok(JPEGReader { data: self.read_bytes(length - 2)?, position: 0 })
+    }
+
+    fn read_entropy_coded_data(&mut self) -> &'a [u8] {
+        let start = self.position;
+        while self.position < self.data.len() {
+            if self.data[self.position] == 0xFF {
+                match self.data.get(self.position + 1) {
+                    Some(0x00) | Some(JPEG_RST0..=JPEG_RST7) => self.position += 1,
+                    _ => break
+                }
+            }
+            self.position += 1;
+        }
+        &self.data[start..self.position]
+    }
+}
+
+struct JPEGBitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+    bit_buffer: u32,
+    bit_count: u32
+}
+
+impl<'a> JPEGBitReader<'a> {
+    fn read_bit(&mut self) -> u32 {
+        if self.bit_count == 0 {
+            // NOTE: Once we run into a marker or the end of the data, we keep feeding zeroes like libjpeg does.
+            let mut byte = 0;
+            if let Some(&next) = self.data.get(self.position) {
+                if next != 0xFF {
+                    byte = next;
+                    self.position += 1;
+                } else if self.data.get(self.position + 1) == Some(&0x00) {
+                    byte = 0xFF;
+                    self.position += 2;
+                }
+            }
+            self.bit_buffer = byte as u32;
+            self.bit_count = 8;
+        }
+        self.bit_count -= 1;
+        (self.bit_buffer >> self.bit_count) & 1
+    }
+
+    fn read_bits(&mut self, count: u8) -> u32 {
+        let mut value = 0;
+        for _ in 0..count {
+            value = (value << 1) | self.read_bit();
+        }
+        value
+    }
+
+    fn read_signed_bits(&mut self, count: u8) -> i32 {
+        if count == 0 {
+            return 0;
+        }
+        let value = self.read_bits(count) as i32;
+        if value < 1 << (count - 1) {
+            value - (1 << count) + 1
+        } else {
+            value
+        }
+    }
+
+    fn read_huffman_symbol(&mut self, table: &JPEGHuffmanTable) -> Result<u8, Error> {
+        let mut code = 0i32;
+        for length in 1..=16 {
+            code = (code << 1) | self.read_bit() as i32;
+            if code <= table.max_code[length] {
+                return table.values.get((code + table.value_offset[length]) as usize)
+                    .copied()
+                    .ok_or(Error::InvalidData("Invalid JPEG Huffman code"));
+            }
+        }
+        Err(Error::InvalidData("Invalid JPEG Huffman code"))
+    }
+
+    fn restart(&mut self) {
+        self.bit_count = 0;
+        if self.data.get(self.position) == Some(&0xFF) && self.data.get(self.position + 1).is_some_and(|marker| (JPEG_RST0..=JPEG_RST7).contains(marker)) {
+            self.position += 2;
+        }
+    }
+}
+
+impl JPEGHeader {
+    fn read_segment(&mut self, marker: u8, reader: &mut JPEGReader) -> Result<(), Error> {
+        let mut segment = reader.read_segment()?;
+        match marker {
+            JPEG_DQT => {
+                while !segment.is_eof() {
+                    let precision_and_index = segment.read_u8()?;
+                    let Some(table) = self.quantization_tables.get_mut((precision_and_index & 0x0F) as usize) else {
+                        return Err(Error::InvalidData("Invalid JPEG quantization table index"));
+                    };
+                    for natural_index in ZIGZAG_TO_NATURAL {
+                        table[natural_index] = match precision_and_index >> 4 {
+                            0 => segment.read_u8()? as u16,
+                            1 => segment.read_u16()?,
+                            _ => return Err(Error::InvalidData("Invalid JPEG quantization table precision"))
+                        };
+                    }
+                }
+            }
+            JPEG_DHT => {
+                while !segment.is_eof() {
+                    let class_and_index = segment.read_u8()?;
+                    let counts = segment.read_bytes(16)?;
+                    let values = segment.read_bytes(counts.iter().map(|&count| count as usize).sum())?;
+                    let tables = match class_and_index >> 4 {
+                        0 => &mut self.dc_tables,
+                        1 => &mut self.ac_tables,
+                        _ => return Err(Error::InvalidData("Invalid JPEG Huffman table class"))
+                    };
+                    let Some(table) = tables.get_mut((class_and_index & 0x0F) as usize) else {
+                        return Err(Error::InvalidData("Invalid JPEG Huffman table index"));
+                    };
+                    *table = Some(JPEGHuffmanTable::new(counts, values)?);
+                }
+            }
+            JPEG_DRI => self.restart_interval = segment.read_u16()? as usize,
+            JPEG_APP0 => self.has_jfif |= segment.data.starts_with(b"JFIF\0"),
+            JPEG_APP14 if segment.data.starts_with(b"Adobe") && segment.data.len() >= 12 => self.adobe_transform = Some(segment.data[11]),
+            // NOTE: Everything else (other APPn segments, comments, ...) doesn't affect decoding.
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+fn decode_frame_header(marker: u8, reader: &mut JPEGReader) -> Result<JPEGFrame, Error> {
+    let mut segment = reader.read_segment()?;
+    if segment.read_u8()? != 8 {
+        return Err(Error::Unsupported("Unsupported JPEG sample precision"));
+    }
+    let height = segment.read_u16()?;
+    let width = segment.read_u16()?;
+    if width == 0 || height == 0 {
+        return Err(Error::Unsupported("Unsupported JPEG dimensions"));
+    }
+    // NOTE: Reject images that can't fit in a `Bitmap` before allocating their coefficients.
+    Bitmap::check_dimensions(width as u32, height as u32)?;
+    let component_count = segment.read_u8()?;
+    if !matches!(component_count, 1 | 3 | 4) {
+        return Err(Error::Unsupported("Unsupported JPEG component count"));
+    }
+
+    let mut components = Vec::with_capacity(component_count as usize);
+    for _ in 0..component_count {
+        let id = segment.read_u8()?;
+        let sampling_factors = segment.read_u8()?;
+        let quantization_table_index = segment.read_u8()? as usize;
+        let (horizontal_sampling_factor, vertical_sampling_factor) = ((sampling_factors >> 4) as usize, (sampling_factors & 0x0F) as usize);
+        if !(1..=4).contains(&horizontal_sampling_factor) || !(1..=4).contains(&vertical_sampling_factor) || quantization_table_index > 3 {
+            return Err(Error::InvalidData("Invalid JPEG component"));
+        }
+        if components.iter().any(|component: &JPEGComponent| component.id == id) {
+            return Err(Error::InvalidData("Duplicate JPEG component"));
+        }
+        components.push(JPEGComponent {
+            id,
+            horizontal_sampling_factor,
+            vertical_sampling_factor,
+            quantization_table_index,
+            dc_table_index: 0,
+            ac_table_index: 0,
+            blocks_per_line: 0,
+            blocks_per_column: 0,
+            coefficients: Vec::new(),
+            dc_predictor: 0
+        });
+    }
+
+    // NOTE: With a single component, there's no interleaving and every MCU is a single block.
+    if components.len() == 1 {
+        components[0].horizontal_sampling_factor = 1;
+        components[0].vertical_sampling_factor = 1;
+    }
+    let max_horizontal_sampling_factor = components.iter().map(|component| component.horizontal_sampling_factor).max().unwrap_or(1);
+    let max_vertical_sampling_factor = components.iter().map(|component| component.vertical_sampling_factor).max().unwrap_or(1);
+    let mcus_per_line = (width as usize).div_ceil(8 * max_horizontal_sampling_factor);
+    let mcus_per_column = (height as usize).div_ceil(8 * max_vertical_sampling_factor);
+    for component in &mut components {
+        component.blocks_per_line = mcus_per_line * component.horizontal_sampling_factor;
+        component.blocks_per_column = mcus_per_column * component.vertical_sampling_factor;
+    }
+
+    Ok(JPEGFrame {
+        is_progressive: marker == JPEG_SOF2,
+        width,
+        height,
+        max_horizontal_sampling_factor,
+        max_vertical_sampling_factor,
+        mcus_per_line,
+        mcus_per_column,
+        components
+    })
+}
+
+impl<'a> JPEGImageDecoderPlugin<'a> {
+    pub fn sniff(bytes: &[u8]) -> bool {
+        bytes.starts_with(&[0xFF, JPEG_SOI, 0xFF])
+    }
+
+    pub fn create(bytes: &'a [u8]) -> Result<Self, Error> {
+        if !Self::sniff(bytes) {
+            return Err(Error::InvalidHeader("Invalid JPEG signature"));
+        }
+
+        let mut reader = JPEGReader { data: bytes, position: 2 };
+        let mut header = JPEGHeader {
+            quantization_tables: [[0; 64]; 4],
+            dc_tables: [None, None, None, None],
+            ac_tables: [None, None, None, None],
+            restart_interval: 0,
+            has_jfif: false,
+            adobe_transform: None
+        };
+        let frame = loop {
+            match reader.read_marker()? {
+                marker @ (JPEG_SOF0 | JPEG_SOF1 | JPEG_SOF2) => break decode_frame_header(marker, &mut reader)?,
+                0xC3 | 0xC5..=0xC7 | 0xC9..=0xCB | 0xCD..=0xCF => return Err(Error::Unsupported("Unsupported JPEG coding process")),
+                JPEG_SOS | JPEG_EOI => return Err(Error::InvalidData("Missing JPEG frame header")),
+                JPEG_RST0..=JPEG_RST7 => {}
+                marker => header.read_segment(marker, &mut reader)?
+            }
+        };
+
+        Ok(Self {
+            context: JPEGLoadingContext {
+                bytes,
+                data_offset: reader.position,
+                header,
+                frame,
+                has_decoded_scans: false,
+                bitmaps: [None, None, None, None],
+                cmyk_bitmap: None
+            }
+        })
+    }
+}
+
+fn decode_baseline_block(reader: &mut JPEGBitReader, block: &mut [i16], predictor: &mut i32, dc_table: &JPEGHuffmanTable, ac_table: &JPEGHuffmanTable) -> Result<(), Error> {
+    let size = reader.read_huffman_symbol(dc_table)?;
+    if size > 16 {
+        return Err(Error::InvalidData("Invalid JPEG DC coefficient"));
+    }
+    *predictor = predictor.wrapping_add(reader.read_signed_bits(size));
+    block[0] = *predictor as i16;
+
+    let mut index = 1;
+    while index < 64 {
+        let symbol = reader.read_huffman_symbol(ac_table)?;
+        let (run, size) = ((symbol >> 4) as usize, symbol & 0x0F);
+        if size == 0 {
+            if run != 15 {
+                break;
+            }
+            index += 16;
+            continue;
+        }
+        index += run;
+        if index > 63 {
+            return Err(Error::InvalidData("Invalid JPEG AC coefficient"));
+        }
+        block[ZIGZAG_TO_NATURAL[index]] = reader.read_signed_bits(size) as i16;
+        index += 1;
+    }
+    Ok(())
+}
+
+fn decode_dc_first_block(reader: &mut JPEGBitReader, block: &mut [i16], predictor: &mut i32, dc_table: &JPEGHuffmanTable, shift: u8) -> Result<(), Error> {
+    let size = reader.read_huffman_symbol(dc_table)?;
+    if size > 16 {
+        return Err(Error::InvalidData("Invalid JPEG DC coefficient"));
+    }
+    *predictor = predictor.wrapping_add(reader.read_signed_bits(size));
+    block[0] = predictor.wrapping_shl(shift as u32) as i16;
+    Ok(())
+}
+
+fn decode_ac_first_block(reader: &mut JPEGBitReader, block: &mut [i16], eob_run: &mut u32, ac_table: &JPEGHuffmanTable, scan: &JPEGScan) -> Result<(), Error> {
+    if *eob_run > 0 {
+        *eob_run -= 1;
+        return Ok(());
+    }
+
+    let mut index = scan.spectral_selection_start;
+    while index <= scan.spectral_selection_end {
+        let symbol = reader.read_huffman_symbol(ac_table)?;
+        let (run, size) = ((symbol >> 4) as usize, symbol & 0x0F);
+        if size == 0 {
+            if run < 15 {
+                *eob_run = (1 << run) + reader.read_bits(run as u8) - 1;
+                break;
+            }
+            index += 16;
+            continue;
+        }
+        index += run;
+        if index > scan.spectral_selection_end {
+            return Err(Error::InvalidData("Invalid JPEG AC coefficient"));
+        }
+        block[ZIGZAG_TO_NATURAL[index]] = (reader.read_signed_bits(size) << scan.successive_approximation_low) as i16;
+        index += 1;
+    }
+    Ok(())
+}
+
+fn refine_nonzero_coefficient(reader: &mut JPEGBitReader, coefficient: &mut i16, bit: i16) {
+    if reader.read_bit() != 0 && *coefficient & bit == 0 {
+        if *coefficient > 0 {
+            *coefficient = coefficient.wrapping_add(bit);
+        } else {
+            *coefficient = coefficient.wrapping_sub(bit);
+        }
+    }
+}
+
+fn decode_ac_refinement_block(reader: &mut JPEGBitReader, block: &mut [i16], eob_run: &mut u32, ac_table: &JPEGHuffmanTable, scan: &JPEGScan) -> Result<(), Error> {
+    let bit = 1i16.wrapping_shl(scan.successive_approximation_low as u32);
+    let mut index = scan.spectral_selection_start;
+
+    if *eob_run > 0 {
+        *eob_run -= 1;
+        for index in index..=scan.spectral_selection_end {
+            let coefficient = &mut block[ZIGZAG_TO_NATURAL[index]];
+            if *coefficient != 0 {
+                refine_nonzero_coefficient(reader, coefficient, bit);
+            }
+        }
+        return Ok(());
+    }
+
+    // NOTE: See G.1.2.3 of the specification, zero runs skip over coefficients that already have a value,
+    //       but those still receive a correction bit.
+    while index <= scan.spectral_selection_end {
+        let symbol = reader.read_huffman_symbol(ac_table)?;
+        let (mut run, size) = ((symbol >> 4) as usize, symbol & 0x0F);
+        let mut value = 0;
+        if size == 0 {
+            if run < 15 {
+                *eob_run = (1 << run) + reader.read_bits(run as u8) - 1;
+                run = 64;
+            }
+        } else {
+            if size != 1 {
+                return Err(Error::InvalidData("Invalid JPEG AC refinement"));
+            }
+            value = if reader.read_bit() != 0 { bit } else { bit.wrapping_neg() };
+        }
+
+        while index <= scan.spectral_selection_end {
+            let coefficient = &mut block[ZIGZAG_TO_NATURAL[index]];
+            index += 1;
+            if *coefficient != 0 {
+                refine_nonzero_coefficient(reader, coefficient, bit);
+            } else {
+                if run == 0 {
+                    *coefficient = value;
+                    break;
+                }
+                run -= 1;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn idct_cosines(block_size: usize) -> Vec<f32> {
+    let mut cosines = vec![0.0; block_size * block_size];
+    for x in 0..block_size {
+        for u in 0..block_size {
+            let scale = if u == 0 { std::f32::consts::FRAC_1_SQRT_2 } else { 1.0 };
+            cosines[x * block_size + u] = scale * (((2 * x + 1) * u) as f32 * std::f32::consts::PI / (2 * block_size) as f32).cos();
+        }
+    }
+    cosines
+}
+
+/// Inverse DCT producing a `block_size` by `block_size` block out of the lowest frequencies,
+/// which is how we scale images down while decoding.
+fn inverse_dct_block(block: &[i16], quantization_table: &[u16; 64], cosines: &[f32], block_size: usize, output: &mut [u8], stride: usize) {
+    let mut dequantized = [0f32; 64];
+    for v in 0..block_size {
+        for u in 0..block_size {
+            dequantized[v * 8 + u] = block[v * 8 + u] as f32 * quantization_table[v * 8 + u] as f32;
+        }
+    }
+
+    let mut rows = [0f32; 64];
+    for v in 0..block_size {
+        for x in 0..block_size {
+            rows[v * 8 + x] = (0..block_size).map(|u| dequantized[v * 8 + u] * cosines[x * block_size + u]).sum();
+        }
+    }
+    for y in 0..block_size {
+        for x in 0..block_size {
+            let value: f32 = (0..block_size).map(|v| rows[v * 8 + x] * cosines[y * block_size + v]).sum();
+            output[y * stride + x] = (value / 4.0 + 128.0).round().clamp(0.0, 255.0) as u8;
+        }
+    }
+}
+
+fn clamp_to_u8(value: f32) -> u8 {
+    value.round().clamp(0.0, 255.0) as u8
+}
+
+fn ycbcr_to_rgb(y: u8, cb: u8, cr: u8) -> (u8, u8, u8) {
+    let (y, cb, cr) = (y as f32, cb as f32 - 128.0, cr as f32 - 128.0);
+    (
+        clamp_to_u8(y + 1.402 * cr),
+        clamp_to_u8(y - 0.344136 * cb - 0.714136 * cr),
+        clamp_to_u8(y + 1.772 * cb)
+    )
+}
+
+impl<'a> JPEGLoadingContext<'a> {
+    fn decode_scans(&mut self) -> Result<(), Error> {
+        for component in &mut self.frame.components {
+            let coefficient_count = component.blocks_per_line * component.blocks_per_column * 64;
+            if component.coefficients.try_reserve_exact(coefficient_count).is_err() {
+                return Err(Error::OutOfMemory);
+            }
+            component.coefficients.clear();
+            component.coefficients.resize(coefficient_count, 0);
+        }
+
+        let mut reader = JPEGReader { data: self.bytes, position: self.data_offset };
+        let mut has_decoded_scan = false;
+        loop {
+            // NOTE: Like most decoders, we show whatever we have of a truncated image.
+            if reader.is_eof() && has_decoded_scan {
+                break;
+            }
+            match reader.read_marker()? {
+                JPEG_SOS => {
+                    let scan = self.decode_scan_header(&mut reader)?;
+                    let data = reader.read_entropy_coded_data();
+                    self.decode_scan(&scan, data)?;
+                    has_decoded_scan = true;
+                }
+                JPEG_EOI => break,
+                0xC0..=0xC3 | 0xC5..=0xC7 | 0xC9..=0xCB | 0xCD..=0xCF => return Err(Error::InvalidData("Multiple JPEG frame headers")),
+                JPEG_RST0..=JPEG_RST7 => {}
+                marker => self.header.read_segment(marker, &mut reader)?
+            }
+        }
+        if !has_decoded_scan {
+            return Err(Error::InvalidData("JPEG without any scans"));
+        }
+        Ok(())
+    }
+
+    fn decode_scan_header(&mut self, reader: &mut JPEGReader) -> Result<JPEGScan, Error> {
+        let mut segment = reader.read_segment()?;
+        let component_count = segment.read_u8()? as usize;
+        if !(1..=4).contains(&component_count) {
+            return Err(Error::InvalidData("Invalid JPEG scan component count"));
+        }
+
+        let mut component_indices = Vec::with_capacity(component_count);
+        for _ in 0..component_count {
+            let id = segment.read_u8()?;
+            let table_indices = segment.read_u8()?;
+            let Some(index) = self.frame.components.iter().position(|component| component.id == id) else {
+                return Err(Error::InvalidData("Invalid JPEG scan component"));
+            };
+            if component_indices.contains(&index) || table_indices >> 4 > 3 || table_indices & 0x0F > 3 {
+                return Err(Error::InvalidData("Invalid JPEG scan component"));
+            }
+            let component = &mut self.frame.components[index];
+            component.dc_table_index = (table_indices >> 4) as usize;
+            component.ac_table_index = (table_indices & 0x0F) as usize;
+            component_indices.push(index);
+        }
+
+        let spectral_selection_start = segment.read_u8()? as usize;
+        let spectral_selection_end = segment.read_u8()? as usize;
+        let successive_approximation = segment.read_u8()?;
+        let scan = JPEGScan {
+            component_indices,
+            spectral_selection_start,
+            spectral_selection_end,
+            successive_approximation_high: successive_approximation >> 4,
+            successive_approximation_low: successive_approximation & 0x0F
+        };
+
+        if self.frame.is_progressive {
+            let is_dc_scan = scan.spectral_selection_start == 0;
+            if (is_dc_scan && scan.spectral_selection_end != 0)
+                || (!is_dc_scan && (scan.spectral_selection_end < scan.spectral_selection_start || scan.spectral_selection_end > 63 || scan.component_indices.len() != 1))
+                || scan.successive_approximation_low > 13 {
+                return Err(Error::InvalidData("Invalid JPEG progressive scan"));
+            }
+        }
+        Ok(scan)
+    }
+
+    fn decode_scan(&mut self, scan: &JPEGScan, data: &[u8]) -> Result<(), Error> {
+        let header = &self.header;
+        let frame = &mut self.frame;
+        let is_dc_scan = !frame.is_progressive || scan.spectral_selection_start == 0;
+        let is_ac_scan = !frame.is_progressive || scan.spectral_selection_start != 0;
+        let is_refinement = frame.is_progressive && scan.successive_approximation_high != 0;
+        for &index in &scan.component_indices {
+            let component = &mut frame.components[index];
+            if (is_dc_scan && !is_refinement && header.dc_tables[component.dc_table_index].is_none())
+                || (is_ac_scan && header.ac_tables[component.ac_table_index].is_none()) {
+                return Err(Error::InvalidData("Missing JPEG Huffman table"));
+            }
+            component.dc_predictor = 0;
+        }
+
+        // NOTE: A scan with a single component isn't interleaved and only covers the blocks inside the image.
+        let (mcus_per_line, mcu_count) = if let [index] = scan.component_indices[..] {
+            let component = &frame.components[index];
+            let blocks_per_line = (frame.width as usize * component.horizontal_sampling_factor).div_ceil(frame.max_horizontal_sampling_factor).div_ceil(8);
+            let blocks_per_column = (frame.height as usize * component.vertical_sampling_factor).div_ceil(frame.max_vertical_sampling_factor).div_ceil(8);
+            (blocks_per_line, blocks_per_line * blocks_per_column)
+        } else {
+            (frame.mcus_per_line, frame.mcus_per_line * frame.mcus_per_column)
+        };
+
+        let mut reader = JPEGBitReader { data, position: 0, bit_buffer: 0, bit_count: 0 };
+        let mut eob_run = 0;
+        for mcu in 0..mcu_count {
+            if header.restart_interval > 0 && mcu > 0 && mcu % header.restart_interval == 0 {
+                reader.restart();
+                eob_run = 0;
+                for &index in &scan.component_indices {
+                    frame.components[index].dc_predictor = 0;
+                }
+            }
+
+            let (mcu_x, mcu_y) = (mcu % mcus_per_line, mcu / mcus_per_line);
+            for &index in &scan.component_indices {
+                let component = &mut frame.components[index];
+                let (horizontal_blocks, vertical_blocks) = if scan.component_indices.len() == 1 {
+                    (1, 1)
+                } else {
+                    (component.horizontal_sampling_factor, component.vertical_sampling_factor)
+                };
+                for block_y in 0..vertical_blocks {
+                    for block_x in 0..horizontal_blocks {
+                        let block_index = (mcu_y * vertical_blocks + block_y) * component.blocks_per_line + mcu_x * horizontal_blocks + block_x;
+                        let block = &mut component.coefficients[block_index * 64..block_index * 64 + 64];
+                        let dc_table = header.dc_tables[component.dc_table_index].as_ref();
+                        let ac_table = header.ac_tables[component.ac_table_index].as_ref();
+                        match (frame.is_progressive, dc_table, ac_table) {
+                            (false, Some(dc_table), Some(ac_table)) => decode_baseline_block(&mut reader, block, &mut component.dc_predictor, dc_table, ac_table)?,
+                            (true, _, _) if is_dc_scan && is_refinement => {
+                                if reader.read_bit() != 0 {
+                                    block[0] |= 1i16.wrapping_shl(scan.successive_approximation_low as u32);
+                                }
+                            }
+                            (true, Some(dc_table), _) if is_dc_scan => decode_dc_first_block(&mut reader, block, &mut component.dc_predictor, dc_table, scan.successive_approximation_low)?,
+                            (true, _, Some(ac_table)) if is_refinement => decode_ac_refinement_block(&mut reader, block, &mut eob_run, ac_table, scan)?,
+                            (true, _, Some(ac_table)) => decode_ac_first_block(&mut reader, block, &mut eob_run, ac_table, scan)?,
+                            _ => return Err(Error::InvalidData("Missing JPEG Huffman table"))
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn color_transform(&self) -> JPEGColorTransform {
+        match (self.frame.components.len(), self.header.adobe_transform) {
+            (1, _) => JPEGColorTransform::Grayscale,
+            (3, Some(0)) => JPEGColorTransform::Rgb,
+            (3, Some(_)) => JPEGColorTransform::YCbCr,
+            (3, None) => {
+                let is_rgb = self.frame.components.iter().map(|component| component.id).eq(*b"RGB");
+                if is_rgb && !self.header.has_jfif { JPEGColorTransform::Rgb } else { JPEGColorTransform::YCbCr }
+            }
+            (_, Some(2)) => JPEGColorTransform::Ycck,
+            _ => JPEGColorTransform::Cmyk
+        }
+    }
+
+    fn scale_shift_for_ideal_size(&self, ideal_size: Option<IntSize>) -> usize {
+        let Some(ideal_size) = ideal_size.filter(|size| !size.is_empty()) else {
+            return 0;
+        };
+        // NOTE: Pick the smallest DCT scaling that still covers the requested size.
+        (1..=3).rev()
+            .find(|&shift| {
+                let scaled_size = self.scaled_size(shift);
+                scaled_size.width >= ideal_size.width && scaled_size.height >= ideal_size.height
+            })
+            .unwrap_or(0)
+    }
+
+    fn scaled_size(&self, shift: usize) -> IntSize {
+        IntSize {
+            width: (self.frame.width as i32 + (1 << shift) - 1) >> shift,
+            height: (self.frame.height as i32 + (1 << shift) - 1) >> shift
+        }
+    }
+
+    fn render_component(&self, component: &JPEGComponent, block_size: usize) -> Vec<u8> {
+        let quantization_table = &self.header.quantization_tables[component.quantization_table_index];
+        let cosines = idct_cosines(block_size);
+        let stride = component.blocks_per_line * block_size;
+        let mut plane = vec![0; stride * component.blocks_per_column * block_size];
+        for (block_index, block) in component.coefficients.chunks_exact(64).enumerate() {
+            let (block_x, block_y) = (block_index % component.blocks_per_line, block_index / component.blocks_per_line);
+            let offset = block_y * block_size * stride + block_x * block_size;
+            inverse_dct_block(block, quantization_table, &cosines, block_size, &mut plane[offset..], stride);
+        }
+        plane
+    }
+
+    fn for_each_pixel(&self, shift: usize, mut callback: impl FnMut(i32, i32, &[u8; 4])) {
+        let size = self.scaled_size(shift);
+        let block_size = 8 >> shift;
+        let planes: Vec<Vec<u8>> = self.frame.components.iter().map(|component| self.render_component(component, block_size)).collect();
+
+        let mut samples = [0u8; 4];
+        for y in 0..size.height as usize {
+            for x in 0..size.width as usize {
+                // NOTE: Subsampled components are upsampled by simply repeating their samples.
+                for (sample, (component, plane)) in samples.iter_mut().zip(self.frame.components.iter().zip(&planes)) {
+                    let component_x = x * component.horizontal_sampling_factor / self.frame.max_horizontal_sampling_factor;
+                    let component_y = y * component.vertical_sampling_factor / self.frame.max_vertical_sampling_factor;
+                    *sample = plane[component_y * component.blocks_per_line * block_size + component_x];
+                }
+                callback(x as i32, y as i32, &samples);
+            }
+        }
+    }
+
+    fn render(&self, shift: usize) -> Result<Bitmap, Error> {
+        let color_transform = self.color_transform();
+        if matches!(color_transform, JPEGColorTransform::Cmyk | JPEGColorTransform::Ycck) {
+            return self.render_cmyk(shift)?.to_low_quality_rgb();
+        }
+
+        let mut bitmap = Bitmap::new(BitmapFormat::BGRx8888, self.scaled_size(shift), 1)?;
+        self.for_each_pixel(shift, |x, y, samples| {
+            let color = match color_transform {
+                JPEGColorTransform::Grayscale => Color::from_rgb(samples[0], samples[0], samples[0]),
+                JPEGColorTransform::Rgb => Color::from_rgb(samples[0], samples[1], samples[2]),
+                _ => {
+                    let (red, green, blue) = ycbcr_to_rgb(samples[0], samples[1], samples[2]);
+                    Color::from_rgb(red, green, blue)
+                }
+            };
+            bitmap.set_pixel(x, y, color.color);
+        });
+        Ok(bitmap)
+    }
+
+    fn render_cmyk(&self, shift: usize) -> Result<CMYKBitmap, Error> {
+        let color_transform = self.color_transform();
+        // NOTE: Adobe applications store CMYK inverted, and YCCK is only ever signalled by them.
+        let is_inverted = self.header.adobe_transform.is_some();
+
+        let mut bitmap = CMYKBitmap::new(self.scaled_size(shift))?;
+        self.for_each_pixel(shift, |x, y, samples| {
+            let color = match color_transform {
+                JPEGColorTransform::Ycck => {
+                    let (red, green, blue) = ycbcr_to_rgb(samples[0], samples[1], samples[2]);
+                    CMYK { c: red, m: green, y: blue, k: 255 - samples[3] }
+                }
+                _ if is_inverted => CMYK { c: 255 - samples[0], m: 255 - samples[1], y: 255 - samples[2], k: 255 - samples[3] },
+                _ => CMYK { c: samples[0], m: samples[1], y: samples[2], k: samples[3] }
+            };
+            bitmap.set_pixel(x, y, color);
+        });
+        Ok(bitmap)
+    }
+
+    fn ensure_scans_decoded(&mut self) -> Result<(), Error> {
+        if !self.has_decoded_scans {
+            self.decode_scans()?;
+            self.has_decoded_scans = true;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> ImageDecoderPlugin for JPEGImageDecoderPlugin<'a> {
+    fn size(&self) -> IntSize {
+        self.context.scaled_size(0)
+    }
+
+    fn frame_with_ideal_size(&mut self, index: usize, ideal_size: Option<IntSize>) -> Result<ImageFrameDescriptor, Error> {
+        if index != 0 {
+            return Err(Error::FrameIndexOutOfRange);
+        }
+
+        let shift = self.context.scale_shift_for_ideal_size(ideal_size);
+        if let Some(bitmap) = &self.context.bitmaps[shift] {
+            return Ok(ImageFrameDescriptor {
+                image: bitmap.clone(),
+                duration: 0
+            });
+        }
+
+        self.context.ensure_scans_decoded()?;
+        let bitmap = self.context.render(shift)?;
+        self.context.bitmaps[shift] = Some(bitmap.clone());
+
+        Ok(ImageFrameDescriptor {
+            image: bitmap,
+            duration: 0
+        })
+    }
+
+    fn natural_frame_format(&self) -> NaturalFrameFormat {
+        match self.context.color_transform() {
+            JPEGColorTransform::Grayscale => NaturalFrameFormat::Grayscale,
+            JPEGColorTransform::Cmyk | JPEGColorTransform::Ycck => NaturalFrameFormat::CMYK,
+            _ => NaturalFrameFormat::RGB
+        }
+    }
+
+    fn cmyk_frame(&mut self, index: usize) -> Result<CMYKBitmap, Error> {
+        if index != 0 {
+            return Err(Error::FrameIndexOutOfRange);
+        }
+        if self.natural_frame_format() != NaturalFrameFormat::CMYK {
+            return Err(Error::Unsupported("JPEG is not in CMYK"));
+        }
+        if let Some(bitmap) = &self.context.cmyk_bitmap {
+            return Ok(bitmap.clone());
+        }
+
+        self.context.ensure_scans_decoded()?;
+        let bitmap = self.context.render_cmyk(0)?;
+        self.context.cmyk_bitmap = Some(bitmap.clone());
+        Ok(bitmap)
+    }
+}
+
+/// # Safety
+///
+/// `bytes` must point to `size` readable bytes that outlive the returned decoder,
+/// and `out_decoder` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn jpeg_image_decoder_plugin_new(bytes: *const u8, size: usize, out_decoder: *mut *mut c_void) -> ErrorCode {
+    ffi_call(|| unsafe {
+        if bytes.is_null() {
+            return Err(Error::InvalidArgument("Bytes are null"));
+        }
+        if out_decoder.is_null() {
+            return Err(Error::InvalidArgument("Output pointer is null"));
+        }
+        let bytes = std::slice::from_raw_parts(bytes, size);
+        let decoder = JPEGImageDecoderPlugin::create(bytes)?;
+        write_ffi_result(out_decoder, decoder_into_opaque(Box::new(decoder)))
+    })
+}
+
+/// # Safety
+///
+/// `opaque_decoder` must either be null or come from `jpeg_image_decoder_plugin_new` and not be freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn jpeg_image_decoder_plugin_free(opaque_decoder: *mut c_void) -> ErrorCode {
+    ffi_call(|| {
+        if !opaque_decoder.is_null() {
+            let decoder: Box<Box<dyn ImageDecoderPlugin>> = unsafe { Box::from_raw(opaque_decoder as *mut _) };
+            drop(decoder);
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::imagedecoderplugin::{image_decoder_plugin_cmyk_frame, image_decoder_plugin_free_cmyk_frame};
+
+    // NOTE: Unless noted otherwise, the images are 16x16 with the quadrants (200, 40, 40), (40, 200, 40),
+    //       (40, 40, 200) and (220, 220, 220), encoded by libjpeg at quality 95 without chroma subsampling.
+    const QUADRANTS: [(u8, u8, u8); 4] = [(200, 40, 40), (40, 200, 40), (40, 40, 200), (220, 220, 220)];
+
+    // NOTE: Baseline with 2x2 luma sampling.
+    const BASELINE_IMAGE: [u8; 349] = [
+        0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x10, 0x4A, 0x46, 0x49, 0x46, 0x00, 0x01, 0x01, 0x00, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00,
+        0xFF, 0xDB, 0x00, 0x43, 0x00, 0x02, 0x01, 0x01, 0x01, 0x01, 0x01, 0x02, 0x01, 0x01, 0x01, 0x02, 0x02, 0x02, 0x02, 0x02,
+        0x04, 0x03, 0x02, 0x02, 0x02, 0x02, 0x05, 0x04, 0x04, 0x03, 0x04, 0x06, 0x05, 0x06, 0x06, 0x06, 0x05, 0x06, 0x06, 0x06,
+        0x07, 0x09, 0x08, 0x06, 0x07, 0x09, 0x07, 0x06, 0x06, 0x08, 0x0B, 0x08, 0x09, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x06, 0x08,
+        0x0B, 0x0C, 0x0B, 0x0A, 0x0C, 0x09, 0x0A, 0x0A, 0x0A, 0xFF, 0xDB, 0x00, 0x43, 0x01, 0x02, 0x02, 0x02, 0x02, 0x02, 0x02,
+        0x05, 0x03, 0x03, 0x05, 0x0A, 0x07, 0x06, 0x07, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A,
+        0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A,
+        0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0xFF, 0xC0,
+        0x00, 0x11, 0x08, 0x00, 0x10, 0x00, 0x10, 0x03, 0x01, 0x22, 0x00, 0x02, 0x11, 0x01, 0x03, 0x11, 0x01, 0xFF, 0xC4, 0x00,
+        0x16, 0x00, 0x01, 0x01, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x08, 0x09,
+        0x0A, 0xFF, 0xC4, 0x00, 0x14, 0x10, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0xFF, 0xC4, 0x00, 0x14, 0x01, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0xC4, 0x00, 0x29, 0x11, 0x00, 0x01, 0x02, 0x02, 0x06, 0x0B, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x13, 0x11, 0x12, 0x07, 0x14, 0x00, 0x02, 0x03, 0x05, 0x41, 0x61, 0x08, 0x15, 0x16,
+        0x18, 0x21, 0x22, 0x25, 0x31, 0x43, 0x62, 0x63, 0xFF, 0xDA, 0x00, 0x0C, 0x03, 0x01, 0x00, 0x02, 0x11, 0x03, 0x11, 0x00,
+        0x3F, 0x00, 0x2F, 0x97, 0x09, 0x9E, 0xD4, 0x41, 0x18, 0x34, 0x6E, 0xD9, 0x39, 0x2E, 0xA8, 0x42, 0x17, 0xC0, 0xD4, 0x68,
+        0xFE, 0xD5, 0x95, 0x5D, 0x97, 0x6A, 0x23, 0xCD, 0xF1, 0xBC, 0x1E, 0xAE, 0xE4, 0x92, 0x92, 0x36, 0x26, 0x79, 0x85, 0x95,
+        0x93, 0x5A, 0x2F, 0x65, 0x76, 0x09, 0xC7, 0xFF, 0xD9
+    ];
+
+    // NOTE: Progressive with a DC first scan at half precision, AC first scans, and refinement scans for both.
+    const PROGRESSIVE_IMAGE: [u8; 406] = [
+        0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x10, 0x4A, 0x46, 0x49, 0x46, 0x00, 0x01, 0x01, 0x00, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00,
+        0xFF, 0xDB, 0x00, 0x43, 0x00, 0x02, 0x01, 0x01, 0x01, 0x01, 0x01, 0x02, 0x01, 0x01, 0x01, 0x02, 0x02, 0x02, 0x02, 0x02,
+        0x04, 0x03, 0x02, 0x02, 0x02, 0x02, 0x05, 0x04, 0x04, 0x03, 0x04, 0x06, 0x05, 0x06, 0x06, 0x06, 0x05, 0x06, 0x06, 0x06,
+        0x07, 0x09, 0x08, 0x06, 0x07, 0x09, 0x07, 0x06, 0x06, 0x08, 0x0B, 0x08, 0x09, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x06, 0x08,
+        0x0B, 0x0C, 0x0B, 0x0A, 0x0C, 0x09, 0x0A, 0x0A, 0x0A, 0xFF, 0xDB, 0x00, 0x43, 0x01, 0x02, 0x02, 0x02, 0x02, 0x02, 0x02,
+        0x05, 0x03, 0x03, 0x05, 0x0A, 0x07, 0x06, 0x07, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A,
+        0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A,
+        0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0xFF, 0xC2,
+        0x00, 0x11, 0x08, 0x00, 0x10, 0x00, 0x10, 0x03, 0x01, 0x11, 0x00, 0x02, 0x11, 0x01, 0x03, 0x11, 0x01, 0xFF, 0xC4, 0x00,
+        0x16, 0x00, 0x01, 0x01, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x07, 0x08,
+        0x09, 0xFF, 0xC4, 0x00, 0x18, 0x01, 0x00, 0x03, 0x01, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x06, 0x08, 0x09, 0x05, 0x07, 0xFF, 0xDA, 0x00, 0x0C, 0x03, 0x01, 0x00, 0x02, 0x10, 0x03, 0x10, 0x00, 0x00,
+        0x01, 0x2F, 0x09, 0x68, 0x17, 0x02, 0xE6, 0xCC, 0xCF, 0x42, 0xBB, 0x66, 0xA2, 0x2B, 0xFB, 0x5F, 0xFF, 0xC4, 0x00, 0x14,
+        0x10, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x20, 0xFF, 0xDA,
+        0x00, 0x08, 0x01, 0x01, 0x00, 0x01, 0x3F, 0x01, 0x1F, 0xFF, 0xC4, 0x00, 0x14, 0x11, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x20, 0xFF, 0xDA, 0x00, 0x08, 0x01, 0x02, 0x01, 0x01, 0x3F,
+        0x00, 0x1F, 0xFF, 0xC4, 0x00, 0x14, 0x11, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x20, 0xFF, 0xDA, 0x00, 0x08, 0x01, 0x03, 0x01, 0x01, 0x3F, 0x00, 0x1F, 0xFF, 0xC4, 0x00, 0x14, 0x10,
+        0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x20, 0xFF, 0xDA, 0x00,
+        0x08, 0x01, 0x01, 0x00, 0x01, 0x3F, 0x10, 0x1F, 0xFF, 0xDA, 0x00, 0x0C, 0x03, 0x01, 0x00, 0x02, 0x00, 0x03, 0x00, 0x00,
+        0x00, 0x10, 0x00, 0x0F, 0xFF, 0xD9
+    ];
+
+    // NOTE: Baseline with a restart marker after every MCU.
+    const RESTART_IMAGE: [u8; 316] = [
+        0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x10, 0x4A, 0x46, 0x49, 0x46, 0x00, 0x01, 0x01, 0x00, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00,
+        0xFF, 0xDB, 0x00, 0x43, 0x00, 0x02, 0x01, 0x01, 0x01, 0x01, 0x01, 0x02, 0x01, 0x01, 0x01, 0x02, 0x02, 0x02, 0x02, 0x02,
+        0x04, 0x03, 0x02, 0x02, 0x02, 0x02, 0x05, 0x04, 0x04, 0x03, 0x04, 0x06, 0x05, 0x06, 0x06, 0x06, 0x05, 0x06, 0x06, 0x06,
+        0x07, 0x09, 0x08, 0x06, 0x07, 0x09, 0x07, 0x06, 0x06, 0x08, 0x0B, 0x08, 0x09, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x06, 0x08,
+        0x0B, 0x0C, 0x0B, 0x0A, 0x0C, 0x09, 0x0A, 0x0A, 0x0A, 0xFF, 0xDB, 0x00, 0x43, 0x01, 0x02, 0x02, 0x02, 0x02, 0x02, 0x02,
+        0x05, 0x03, 0x03, 0x05, 0x0A, 0x07, 0x06, 0x07, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A,
+        0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A,
+        0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0xFF, 0xC0,
+        0x00, 0x11, 0x08, 0x00, 0x10, 0x00, 0x10, 0x03, 0x01, 0x11, 0x00, 0x02, 0x11, 0x01, 0x03, 0x11, 0x01, 0xFF, 0xC4, 0x00,
+        0x16, 0x00, 0x01, 0x01, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x09, 0x05,
+        0x08, 0xFF, 0xC4, 0x00, 0x14, 0x10, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0xFF, 0xC4, 0x00, 0x18, 0x01, 0x00, 0x02, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x09, 0x06, 0x07, 0x08, 0xFF, 0xC4, 0x00, 0x14, 0x11, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0xDD, 0x00, 0x04, 0x00, 0x01, 0xFF, 0xDA, 0x00,
+        0x0C, 0x03, 0x01, 0x00, 0x02, 0x11, 0x03, 0x11, 0x00, 0x3F, 0x00, 0xCB, 0xEA, 0x4C, 0xD0, 0x1F, 0xFF, 0xD0, 0xB0, 0xC5,
+        0x65, 0xE6, 0xFF, 0xD1, 0x39, 0xCD, 0x01, 0x0B, 0x7F, 0xFF, 0xD2, 0x5C, 0x00, 0x7F, 0xFF, 0xD9
+    ];
+
+    // NOTE: Baseline with 2x2 luma sampling and one scan per component.
+    const NON_INTERLEAVED_IMAGE: [u8; 413] = [
+        0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x10, 0x4A, 0x46, 0x49, 0x46, 0x00, 0x01, 0x01, 0x00, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00,
+        0xFF, 0xDB, 0x00, 0x43, 0x00, 0x02, 0x01, 0x01, 0x01, 0x01, 0x01, 0x02, 0x01, 0x01, 0x01, 0x02, 0x02, 0x02, 0x02, 0x02,
+        0x04, 0x03, 0x02, 0x02, 0x02, 0x02, 0x05, 0x04, 0x04, 0x03, 0x04, 0x06, 0x05, 0x06, 0x06, 0x06, 0x05, 0x06, 0x06, 0x06,
+        0x07, 0x09, 0x08, 0x06, 0x07, 0x09, 0x07, 0x06, 0x06, 0x08, 0x0B, 0x08, 0x09, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x06, 0x08,
+        0x0B, 0x0C, 0x0B, 0x0A, 0x0C, 0x09, 0x0A, 0x0A, 0x0A, 0xFF, 0xDB, 0x00, 0x43, 0x01, 0x02, 0x02, 0x02, 0x02, 0x02, 0x02,
+        0x05, 0x03, 0x03, 0x05, 0x0A, 0x07, 0x06, 0x07, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A,
+        0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A,
+        0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0xFF, 0xC0,
+        0x00, 0x11, 0x08, 0x00, 0x10, 0x00, 0x10, 0x03, 0x01, 0x22, 0x00, 0x02, 0x11, 0x01, 0x03, 0x11, 0x01, 0xFF, 0xC4, 0x00,
+        0x16, 0x00, 0x01, 0x01, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x08, 0x09,
+        0x0A, 0xFF, 0xC4, 0x00, 0x14, 0x10, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0xFF, 0xDA, 0x00, 0x08, 0x01, 0x01, 0x00, 0x00, 0x3F, 0x00, 0x2F, 0x97, 0x09, 0x9E, 0xD4, 0x43, 0xFF,
+        0xC4, 0x00, 0x14, 0x01, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0xFF, 0xC4, 0x00, 0x21, 0x11, 0x00, 0x01, 0x02, 0x04, 0x07, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x13, 0x11, 0x12, 0x00, 0x02, 0x07, 0x08, 0x14, 0x16, 0x25, 0x31, 0x43, 0x61, 0x63, 0xFF, 0xDA, 0x00, 0x08,
+        0x01, 0x02, 0x11, 0x00, 0x3F, 0x00, 0x56, 0x0B, 0x6E, 0xCA, 0x78, 0x2D, 0x50, 0x84, 0x2F, 0x03, 0x51, 0xA3, 0xF6, 0x99,
+        0x55, 0xDD, 0x6D, 0x1F, 0xFF, 0xC4, 0x00, 0x14, 0x01, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0xC4, 0x00, 0x21, 0x11, 0x00, 0x01, 0x02, 0x04, 0x07, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x13, 0x11, 0x12, 0x03, 0x05, 0x14, 0x41, 0x07, 0x15, 0x18, 0x21, 0x22, 0x61,
+        0x62, 0xFF, 0xDA, 0x00, 0x08, 0x01, 0x03, 0x11, 0x00, 0x3F, 0x00, 0x63, 0xCC, 0xE3, 0x50, 0x79, 0x77, 0x0A, 0x2A, 0x23,
+        0x5C, 0xCF, 0x30, 0xBA, 0x84, 0xD6, 0x8B, 0xD2, 0xBA, 0xC9, 0xBF, 0xFF, 0xD9
+    ];
+
+    // NOTE: 12x10 grayscale, 0x20 in the left 8 columns and 0xE0 in the rest, so the right blocks are partial.
+    const GRAYSCALE_IMAGE: [u8; 165] = [
+        0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x10, 0x4A, 0x46, 0x49, 0x46, 0x00, 0x01, 0x01, 0x00, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00,
+        0xFF, 0xDB, 0x00, 0x43, 0x00, 0x02, 0x01, 0x01, 0x01, 0x01, 0x01, 0x02, 0x01, 0x01, 0x01, 0x02, 0x02, 0x02, 0x02, 0x02,
+        0x04, 0x03, 0x02, 0x02, 0x02, 0x02, 0x05, 0x04, 0x04, 0x03, 0x04, 0x06, 0x05, 0x06, 0x06, 0x06, 0x05, 0x06, 0x06, 0x06,
+        0x07, 0x09, 0x08, 0x06, 0x07, 0x09, 0x07, 0x06, 0x06, 0x08, 0x0B, 0x08, 0x09, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x06, 0x08,
+        0x0B, 0x0C, 0x0B, 0x0A, 0x0C, 0x09, 0x0A, 0x0A, 0x0A, 0xFF, 0xC0, 0x00, 0x0B, 0x08, 0x00, 0x0A, 0x00, 0x0C, 0x01, 0x01,
+        0x11, 0x00, 0xFF, 0xC4, 0x00, 0x15, 0x00, 0x01, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x0A, 0x09, 0xFF, 0xC4, 0x00, 0x14, 0x10, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0xDA, 0x00, 0x08, 0x01, 0x01, 0x00, 0x00, 0x3F, 0x00, 0x8F, 0xE6, 0x00,
+        0x1F, 0xE6, 0x00, 0xFF, 0xD9
+    ];
+
+    // NOTE: RGB components tagged with an Adobe APP14 segment using transform 0.
+    const ADOBE_RGB_IMAGE: [u8; 185] = [
+        0xFF, 0xD8, 0xFF, 0xEE, 0x00, 0x0E, 0x41, 0x64, 0x6F, 0x62, 0x65, 0x00, 0x64, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0xDB,
+        0x00, 0x43, 0x00, 0x02, 0x01, 0x01, 0x01, 0x01, 0x01, 0x02, 0x01, 0x01, 0x01, 0x02, 0x02, 0x02, 0x02, 0x02, 0x04, 0x03,
+        0x02, 0x02, 0x02, 0x02, 0x05, 0x04, 0x04, 0x03, 0x04, 0x06, 0x05, 0x06, 0x06, 0x06, 0x05, 0x06, 0x06, 0x06, 0x07, 0x09,
+        0x08, 0x06, 0x07, 0x09, 0x07, 0x06, 0x06, 0x08, 0x0B, 0x08, 0x09, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x06, 0x08, 0x0B, 0x0C,
+        0x0B, 0x0A, 0x0C, 0x09, 0x0A, 0x0A, 0x0A, 0xFF, 0xC0, 0x00, 0x11, 0x08, 0x00, 0x10, 0x00, 0x10, 0x03, 0x52, 0x11, 0x00,
+        0x47, 0x11, 0x00, 0x42, 0x11, 0x00, 0xFF, 0xC4, 0x00, 0x17, 0x00, 0x01, 0x01, 0x01, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0A, 0x09, 0x00, 0x07, 0xFF, 0xC4, 0x00, 0x14, 0x10, 0x01, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0xDA, 0x00, 0x0C, 0x03, 0x52, 0x00,
+        0x47, 0x00, 0x42, 0x00, 0x00, 0x3F, 0x00, 0xA4, 0x09, 0x3E, 0x93, 0xE2, 0xFE, 0x50, 0x0C, 0xC2, 0xFE, 0x50, 0x05, 0xA0,
+        0x5A, 0x0E, 0xA0, 0xFF, 0xD9
+    ];
+
+    // NOTE: YCbCr components tagged with an Adobe APP14 segment using transform 1.
+    const ADOBE_YCBCR_IMAGE: [u8; 303] = [
+        0xFF, 0xD8, 0xFF, 0xEE, 0x00, 0x0E, 0x41, 0x64, 0x6F, 0x62, 0x65, 0x00, 0x64, 0x00, 0x00, 0x00, 0x00, 0x01, 0xFF, 0xDB,
+        0x00, 0x43, 0x00, 0x02, 0x01, 0x01, 0x01, 0x01, 0x01, 0x02, 0x01, 0x01, 0x01, 0x02, 0x02, 0x02, 0x02, 0x02, 0x04, 0x03,
+        0x02, 0x02, 0x02, 0x02, 0x05, 0x04, 0x04, 0x03, 0x04, 0x06, 0x05, 0x06, 0x06, 0x06, 0x05, 0x06, 0x06, 0x06, 0x07, 0x09,
+        0x08, 0x06, 0x07, 0x09, 0x07, 0x06, 0x06, 0x08, 0x0B, 0x08, 0x09, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x06, 0x08, 0x0B, 0x0C,
+        0x0B, 0x0A, 0x0C, 0x09, 0x0A, 0x0A, 0x0A, 0xFF, 0xDB, 0x00, 0x43, 0x01, 0x02, 0x02, 0x02, 0x02, 0x02, 0x02, 0x05, 0x03,
+        0x03, 0x05, 0x0A, 0x07, 0x06, 0x07, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A,
+        0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A,
+        0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0xFF, 0xC0, 0x00, 0x11,
+        0x08, 0x00, 0x10, 0x00, 0x10, 0x03, 0x01, 0x11, 0x00, 0x02, 0x11, 0x01, 0x03, 0x11, 0x01, 0xFF, 0xC4, 0x00, 0x16, 0x00,
+        0x01, 0x01, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x08, 0x09, 0x0A, 0xFF,
+        0xC4, 0x00, 0x14, 0x10, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0xFF, 0xC4, 0x00, 0x18, 0x01, 0x00, 0x03, 0x01, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x07, 0x09, 0x0A, 0x06, 0x08, 0xFF, 0xC4, 0x00, 0x14, 0x11, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0xDA, 0x00, 0x0C, 0x03, 0x01, 0x00, 0x02, 0x11, 0x03, 0x11,
+        0x00, 0x3F, 0x00, 0x2F, 0x82, 0x66, 0x80, 0x5C, 0x02, 0xE9, 0xB3, 0x4C, 0xF5, 0x0A, 0x3B, 0x61, 0xA8, 0x82, 0xBF, 0x6D,
+        0x1F, 0xFF, 0xD9
+    ];
+
+    // NOTE: 16x16 CMYK with the inks (200, 40, 40, 0), (40, 200, 40, 0), (40, 40, 200, 0) and (0, 0, 0, 160)
+    //       in the quadrants, stored inverted as Adobe applications do.
+    const ADOBE_CMYK_IMAGE: [u8; 195] = [
+        0xFF, 0xD8, 0xFF, 0xEE, 0x00, 0x0E, 0x41, 0x64, 0x6F, 0x62, 0x65, 0x00, 0x64, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0xDB,
+        0x00, 0x43, 0x00, 0x02, 0x01, 0x01, 0x01, 0x01, 0x01, 0x02, 0x01, 0x01, 0x01, 0x02, 0x02, 0x02, 0x02, 0x02, 0x04, 0x03,
+        0x02, 0x02, 0x02, 0x02, 0x05, 0x04, 0x04, 0x03, 0x04, 0x06, 0x05, 0x06, 0x06, 0x06, 0x05, 0x06, 0x06, 0x06, 0x07, 0x09,
+        0x08, 0x06, 0x07, 0x09, 0x07, 0x06, 0x06, 0x08, 0x0B, 0x08, 0x09, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x06, 0x08, 0x0B, 0x0C,
+        0x0B, 0x0A, 0x0C, 0x09, 0x0A, 0x0A, 0x0A, 0xFF, 0xC0, 0x00, 0x14, 0x08, 0x00, 0x10, 0x00, 0x10, 0x04, 0x43, 0x11, 0x00,
+        0x4D, 0x11, 0x00, 0x59, 0x11, 0x00, 0x4B, 0x11, 0x00, 0xFF, 0xC4, 0x00, 0x17, 0x00, 0x00, 0x03, 0x01, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x09, 0x0A, 0x08, 0xFF, 0xC4, 0x00, 0x14, 0x10, 0x01,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0xDA, 0x00, 0x0E,
+        0x04, 0x43, 0x00, 0x4D, 0x00, 0x59, 0x00, 0x4B, 0x00, 0x00, 0x3F, 0x00, 0x5B, 0x66, 0xB8, 0x6B, 0x87, 0xF8, 0xA8, 0x04,
+        0xBF, 0x80, 0x15, 0x00, 0x97, 0xF0, 0xD4, 0x0D, 0x40, 0xB2, 0x04, 0xBF, 0xBF, 0xFF, 0xD9
+    ];
+
+    // NOTE: Same inks, stored as is and without an Adobe APP14 segment.
+    const CMYK_IMAGE: [u8; 179] = [
+        0xFF, 0xD8, 0xFF, 0xDB, 0x00, 0x43, 0x00, 0x02, 0x01, 0x01, 0x01, 0x01, 0x01, 0x02, 0x01, 0x01, 0x01, 0x02, 0x02, 0x02,
+        0x02, 0x02, 0x04, 0x03, 0x02, 0x02, 0x02, 0x02, 0x05, 0x04, 0x04, 0x03, 0x04, 0x06, 0x05, 0x06, 0x06, 0x06, 0x05, 0x06,
+        0x06, 0x06, 0x07, 0x09, 0x08, 0x06, 0x07, 0x09, 0x07, 0x06, 0x06, 0x08, 0x0B, 0x08, 0x09, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A,
+        0x06, 0x08, 0x0B, 0x0C, 0x0B, 0x0A, 0x0C, 0x09, 0x0A, 0x0A, 0x0A, 0xFF, 0xC0, 0x00, 0x14, 0x08, 0x00, 0x10, 0x00, 0x10,
+        0x04, 0x43, 0x11, 0x00, 0x4D, 0x11, 0x00, 0x59, 0x11, 0x00, 0x4B, 0x11, 0x00, 0xFF, 0xC4, 0x00, 0x17, 0x00, 0x01, 0x01,
+        0x01, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0A, 0x00, 0x09, 0x08, 0xFF, 0xC4,
+        0x00, 0x14, 0x10, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0xFF, 0xDA, 0x00, 0x0E, 0x04, 0x43, 0x00, 0x4D, 0x00, 0x59, 0x00, 0x4B, 0x00, 0x00, 0x3F, 0x00, 0xD2, 0x06, 0x4F, 0xB2,
+        0x7C, 0x7F, 0xC5, 0xFC, 0xA0, 0x12, 0x42, 0xFE, 0x50, 0x09, 0xCB, 0xEE, 0x5F, 0x0D, 0xF2, 0x80, 0x7F, 0xFF, 0xD9
+    ];
+
+    // NOTE: Same inks, stored as YCCK with Adobe APP14 transform 2.
+    const YCCK_IMAGE: [u8; 313] = [
+        0xFF, 0xD8, 0xFF, 0xEE, 0x00, 0x0E, 0x41, 0x64, 0x6F, 0x62, 0x65, 0x00, 0x64, 0x00, 0x00, 0x00, 0x00, 0x02, 0xFF, 0xDB,
+        0x00, 0x43, 0x00, 0x02, 0x01, 0x01, 0x01, 0x01, 0x01, 0x02, 0x01, 0x01, 0x01, 0x02, 0x02, 0x02, 0x02, 0x02, 0x04, 0x03,
+        0x02, 0x02, 0x02, 0x02, 0x05, 0x04, 0x04, 0x03, 0x04, 0x06, 0x05, 0x06, 0x06, 0x06, 0x05, 0x06, 0x06, 0x06, 0x07, 0x09,
+        0x08, 0x06, 0x07, 0x09, 0x07, 0x06, 0x06, 0x08, 0x0B, 0x08, 0x09, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x06, 0x08, 0x0B, 0x0C,
+        0x0B, 0x0A, 0x0C, 0x09, 0x0A, 0x0A, 0x0A, 0xFF, 0xDB, 0x00, 0x43, 0x01, 0x02, 0x02, 0x02, 0x02, 0x02, 0x02, 0x05, 0x03,
+        0x03, 0x05, 0x0A, 0x07, 0x06, 0x07, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A,
+        0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A,
+        0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0x0A, 0xFF, 0xC0, 0x00, 0x14,
+        0x08, 0x00, 0x10, 0x00, 0x10, 0x04, 0x01, 0x11, 0x00, 0x02, 0x11, 0x01, 0x03, 0x11, 0x01, 0x04, 0x11, 0x00, 0xFF, 0xC4,
+        0x00, 0x17, 0x00, 0x00, 0x03, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x08, 0x09, 0x0A, 0xFF, 0xC4, 0x00, 0x14, 0x10, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0xC4, 0x00, 0x18, 0x01, 0x00, 0x03, 0x01, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x07, 0x09, 0x0A, 0x06, 0x08, 0xFF, 0xC4, 0x00, 0x14, 0x11, 0x01, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF, 0xDA, 0x00, 0x0E, 0x04, 0x01, 0x00,
+        0x02, 0x11, 0x03, 0x11, 0x04, 0x00, 0x00, 0x3F, 0x00, 0x57, 0xC1, 0x33, 0x40, 0x5F, 0xC3, 0x70, 0x0B, 0xA6, 0xCC, 0x26,
+        0x7A, 0x85, 0x1D, 0xB0, 0x08, 0xB9, 0x5F, 0xB6, 0x8C, 0xBF, 0xBF, 0xFF, 0xD9
+    ];
+
+    const INKS: [CMYK; 4] = [
+        CMYK { c: 200, m: 40, y: 40, k: 0 },
+        CMYK { c: 40, m: 200, y: 40, k: 0 },
+        CMYK { c: 40, m: 40, y: 200, k: 0 },
+        CMYK { c: 0, m: 0, y: 0, k: 160 }
+    ];
+
+    // NOTE: JPEG is lossy, so pixels only have to be close to the source colors.
+    const TOLERANCE: u8 = 8;
+
+    fn assert_quadrants(image: &Bitmap) {
+        let (width, height) = (image.size.width, image.size.height);
+        for y in 0..height {
+            for x in 0..width {
+                let expected = QUADRANTS[(y / (height / 2) * 2 + x / (width / 2)) as usize];
+                let color = Color::from(image.get_pixel(x, y));
+                let actual = (color.red(), color.green(), color.blue());
+                let is_close = [(actual.0, expected.0), (actual.1, expected.1), (actual.2, expected.2)].iter().all(|(a, b)| a.abs_diff(*b) <= TOLERANCE);
+                assert!(is_close, "pixel ({x}, {y}) of {width}x{height} is {actual:?}, expected {expected:?}");
+            }
+        }
+    }
+
+    fn decode(bytes: &[u8]) -> Bitmap {
+        JPEGImageDecoderPlugin::create(bytes).unwrap().frame(0).unwrap().image
+    }
+
+    #[test]
+    fn decodes_baseline_image() {
+        let decoder = JPEGImageDecoderPlugin::create(&BASELINE_IMAGE).unwrap();
+        assert_eq!((decoder.size().width, decoder.size().height), (16, 16));
+        assert_eq!(decoder.natural_frame_format(), NaturalFrameFormat::RGB);
+        assert_quadrants(&decode(&BASELINE_IMAGE));
+    }
+
+    #[test]
+    fn decodes_progressive_image_with_refinement_scans() {
+        assert_quadrants(&decode(&PROGRESSIVE_IMAGE));
+    }
+
+    #[test]
+    fn decodes_image_with_restart_markers() {
+        assert_quadrants(&decode(&RESTART_IMAGE));
+    }
+
+    #[test]
+    fn decodes_non_interleaved_scans() {
+        assert_quadrants(&decode(&NON_INTERLEAVED_IMAGE));
+    }
+
+    #[test]
+    fn decodes_grayscale_image_with_partial_blocks() {
+        let mut decoder = JPEGImageDecoderPlugin::create(&GRAYSCALE_IMAGE).unwrap();
+        assert_eq!(decoder.natural_frame_format(), NaturalFrameFormat::Grayscale);
+        let image = decoder.frame(0).unwrap().image;
+        assert_eq!((image.size.width, image.size.height), (12, 10));
+        for y in 0..10 {
+            for x in 0..12 {
+                let gray = Color::from(image.get_pixel(x, y)).red();
+                let expected: u8 = if x < 8 { 0x20 } else { 0xE0 };
+                assert!(gray.abs_diff(expected) <= TOLERANCE, "pixel ({x}, {y}) is {gray}");
+            }
+        }
+    }
+
+    #[test]
+    fn scales_dct_to_ideal_size() {
+        // NOTE: Without chroma subsampling, so that scaled chroma blocks do not bleed across the quadrants.
+        let mut decoder = JPEGImageDecoderPlugin::create(&ADOBE_RGB_IMAGE).unwrap();
+        for (ideal_size, expected_size) in [(16, 16), (9, 16), (8, 8), (5, 8), (4, 4), (2, 2), (1, 2)] {
+            let image = decoder.frame_with_ideal_size(0, Some(IntSize { width: ideal_size, height: ideal_size })).unwrap().image;
+            assert_eq!((image.size.width, image.size.height), (expected_size, expected_size), "ideal size {ideal_size}");
+            assert_quadrants(&image);
+        }
+    }
+
+    #[test]
+    fn applies_adobe_color_transform() {
+        assert_quadrants(&decode(&ADOBE_RGB_IMAGE));
+        assert_quadrants(&decode(&ADOBE_YCBCR_IMAGE));
+
+        // NOTE: Claiming the RGB components are YCbCr has to change the colors.
+        let mut data = ADOBE_RGB_IMAGE;
+        assert_eq!(&data[6..11], b"Adobe");
+        data[17] = 1;
+        let color = Color::from(decode(&data).get_pixel(0, 0));
+        assert_ne!((color.red(), color.green(), color.blue()), (200, 40, 40));
+    }
+
+    #[test]
+    fn rejects_oversized_dimensions_before_decoding() {
+        let mut data = BASELINE_IMAGE;
+        let frame_header = data.windows(2).position(|marker| marker == [0xFF, 0xC0]).unwrap();
+        data[frame_header + 5..frame_header + 9].copy_from_slice(&[0x9C, 0x40, 0x9C, 0x40]);
+        assert_eq!(JPEGImageDecoderPlugin::create(&data).err(), Some(Error::SizeOverflow));
+    }
+
+    #[test]
+    fn decodes_cmyk_frames() {
+        for (name, data) in [("Adobe CMYK", &ADOBE_CMYK_IMAGE[..]), ("CMYK", &CMYK_IMAGE[..]), ("YCCK", &YCCK_IMAGE[..])] {
+            let mut decoder = JPEGImageDecoderPlugin::create(data).unwrap();
+            assert_eq!(decoder.natural_frame_format(), NaturalFrameFormat::CMYK, "{name}");
+            assert_eq!(decoder.cmyk_frame(1).err(), Some(Error::FrameIndexOutOfRange), "{name}");
+            let image = decoder.cmyk_frame(0).unwrap();
+            assert_eq!((image.size.width, image.size.height), (16, 16), "{name}");
+            for y in 0..16 {
+                for x in 0..16 {
+                    let expected = INKS[(y / 8 * 2 + x / 8) as usize];
+                    let actual = image.get_pixel(x, y);
+                    let is_close = [(actual.c, expected.c), (actual.m, expected.m), (actual.y, expected.y), (actual.k, expected.k)].iter().all(|(a, b)| a.abs_diff(*b) <= TOLERANCE);
+                    assert!(is_close, "{name} pixel ({x}, {y}) is {actual:?}, expected {expected:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn renders_cmyk_frames_as_low_quality_rgb() {
+        for data in [&ADOBE_CMYK_IMAGE[..], &CMYK_IMAGE[..], &YCCK_IMAGE[..]] {
+            let mut decoder = JPEGImageDecoderPlugin::create(data).unwrap();
+            let expected = decoder.cmyk_frame(0).unwrap().to_low_quality_rgb().unwrap();
+            let image = decoder.frame(0).unwrap().image;
+            assert!(matches!(image.format, BitmapFormat::BGRx8888));
+            for y in 0..16 {
+                for x in 0..16 {
+                    assert_eq!(image.get_pixel(x, y), expected.get_pixel(x, y));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_cmyk_frame_of_rgb_image() {
+        let mut decoder = JPEGImageDecoderPlugin::create(&BASELINE_IMAGE).unwrap();
+        assert!(matches!(decoder.cmyk_frame(0), Err(Error::Unsupported(_))));
+    }
+
+    #[test]
+    fn decodes_cmyk_frame_across_the_ffi() {
+        unsafe {
+            let mut decoder = std::ptr::null_mut();
+            assert_eq!(jpeg_image_decoder_plugin_new(YCCK_IMAGE.as_ptr(), YCCK_IMAGE.len(), std::ptr::null_mut()), ErrorCode::InvalidArgument);
+            assert_eq!(jpeg_image_decoder_plugin_new(YCCK_IMAGE.as_ptr(), YCCK_IMAGE.len(), &mut decoder), ErrorCode::Success);
+            let mut frame = std::mem::MaybeUninit::uninit();
+            assert_eq!(image_decoder_plugin_cmyk_frame(decoder, 1, frame.as_mut_ptr()), ErrorCode::FrameIndexOutOfRange);
+            assert_eq!(image_decoder_plugin_cmyk_frame(decoder, 0, std::ptr::null_mut()), ErrorCode::InvalidArgument);
+            assert_eq!(image_decoder_plugin_cmyk_frame(decoder, 0, frame.as_mut_ptr()), ErrorCode::Success);
+            let frame = frame.assume_init();
+            assert_eq!((frame.size.width, frame.size.height, frame.pitch), (16, 16, 64));
+            assert_eq!(image_decoder_plugin_free_cmyk_frame(frame), ErrorCode::Success);
+            assert_eq!(jpeg_image_decoder_plugin_free(decoder), ErrorCode::Success);
+        }
+    }
+}
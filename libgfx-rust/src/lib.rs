@@ -1,11 +1,34 @@
 #![allow(dead_code)]
 
+pub mod imagedecoder;
 pub mod tgaloader;
+pub mod pngloader;
+pub mod gifloader;
+pub mod bmploader;
+pub mod ddsloader;
+pub mod icoloader;
+pub mod jpegloader;
+pub mod portableimageloader;
+pub mod qoiloader;
+pub mod tiffloader;
+pub mod tinyvgloader;
+pub mod webploader;
+pub mod qoiwriter;
+pub mod tgawriter;
 pub mod imagedecoderplugin;
 pub mod bitmap;
+pub mod cmykbitmap;
+pub mod vectorgraphic;
+pub mod error;
+mod inflate;
+mod rasterizer;
+mod webploaderlossless;
+mod webploaderlossy;
+#[cfg(test)]
+mod testsupport;
 
 #[repr(C)]
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub struct IntSize {
     pub width: i32,
     pub height: i32
@@ -17,9 +40,29 @@ impl IntSize {
     }
 }
 
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct IntPoint {
+    pub x: i32,
+    pub y: i32
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct FloatPoint {
+    pub x: f32,
+    pub y: f32
+}
+
+impl FloatPoint {
+    pub fn distance_to(&self, other: FloatPoint) -> f32 {
+        (self.x - other.x).hypot(self.y - other.y)
+    }
+}
+
 pub type ARGB = u32;
 
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone)]
 pub struct Color {
     color: ARGB
 }
@@ -36,6 +79,54 @@ impl Color {
     fn from_rgba(r: u8, g: u8, b: u8, a: u8) -> Self {
         Self { color: ((a as u32) << 24) | ((r as u32) << 16) | ((g as u32) << 8) | b as u32 }
     }
+
+    fn red(&self) -> u8 {
+        (self.color >> 16) as u8
+    }
+
+    fn green(&self) -> u8 {
+        (self.color >> 8) as u8
+    }
+
+    fn blue(&self) -> u8 {
+        self.color as u8
+    }
+
+    fn alpha(&self) -> u8 {
+        (self.color >> 24) as u8
+    }
+
+    fn with_alpha(&self, alpha: u8) -> Self {
+        Self { color: (self.color & 0x00FFFFFF) | ((alpha as u32) << 24) }
+    }
+
+    /// Converts a color with premultiplied alpha back to straight alpha, rounding to the nearest value.
+    fn unpremultiplied(&self) -> Self {
+        let alpha = self.alpha() as u32;
+        if alpha == 0 {
+            return Self::new();
+        }
+        let unpremultiply = |value: u8| ((value as u32 * 255 + alpha / 2) / alpha).min(255) as u8;
+        Self::from_rgba(unpremultiply(self.red()), unpremultiply(self.green()), unpremultiply(self.blue()), self.alpha())
+    }
+
+    /// Composites `source` over this color.
+    fn blend(&self, source: Color) -> Self {
+        if self.alpha() == 0 || source.alpha() == 255 {
+            return source;
+        }
+        if source.alpha() == 0 {
+            return *self;
+        }
+
+        let destination_alpha = self.alpha() as u32;
+        let source_alpha = source.alpha() as u32;
+        let d = 255 * (destination_alpha + source_alpha) - destination_alpha * source_alpha;
+        let mix = |destination: u8, source: u8| {
+            ((destination as u32 * destination_alpha * (255 - source_alpha) + source as u32 * 255 * source_alpha) / d) as u8
+        };
+        Self::from_rgba(mix(self.red(), source.red()), mix(self.green(), source.green()), mix(self.blue(), source.blue()), (d / 255) as u8)
+    }
 }
 
 impl From<ARGB> for Color {
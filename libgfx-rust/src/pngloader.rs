@@ -0,0 +1,963 @@
+use std::ffi::c_void;
+use crate::imagedecoderplugin::{decoder_into_opaque, ImageDecoderPlugin, ImageFrameDescriptor, NaturalFrameFormat};
+use crate::{ARGB, Color, IntSize};
+use crate::bitmap::{Bitmap, BitmapFormat};
+use crate::error::{Error, ErrorCode, ffi_call, write_ffi_result};
+use crate::inflate::zlib_decompress;
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+
+#[derive (Debug, PartialEq, Copy, Clone)]
+enum PNGColorType {
+    Greyscale = 0,
+    Truecolor = 2,
+    IndexedColor = 3,
+    GreyscaleWithAlpha = 4,
+    TruecolorWithAlpha = 6
+}
+
+impl PNGColorType {
+    fn channels(&self) -> usize {
+        match self {
+            PNGColorType::Greyscale | PNGColorType::IndexedColor => 1,
+            PNGColorType::GreyscaleWithAlpha => 2,
+            PNGColorType::Truecolor => 3,
+            PNGColorType::TruecolorWithAlpha => 4
+        }
+    }
+
+    fn is_valid_bit_depth(&self, bit_depth: u8) -> bool {
+        match self {
+            PNGColorType::Greyscale => matches!(bit_depth, 1 | 2 | 4 | 8 | 16),
+            PNGColorType::IndexedColor => matches!(bit_depth, 1 | 2 | 4 | 8),
+            _ => matches!(bit_depth, 8 | 16)
+        }
+    }
+}
+
+#[derive (Debug, Copy, Clone)]
+struct PNGHeader {
+    width: u32,
+    height: u32,
+    bit_depth: u8,
+    color_type: PNGColorType,
+    interlaced: bool
+}
+
+#[derive (Debug, Copy, Clone)]
+struct PNGChunk<'a> {
+    chunk_type: [u8; 4],
+    data: &'a [u8]
+}
+
+// NOTE: tRNS gives either a single transparent color, or alpha values for the palette entries.
+#[derive (Debug, Copy, Clone)]
+enum PNGTransparency {
+    None,
+    Greyscale(u16),
+    Truecolor(u16, u16, u16)
+}
+
+#[derive (Debug, PartialEq, Copy, Clone)]
+enum PNGDisposeOp {
+    None = 0,
+    Background = 1,
+    Previous = 2
+}
+
+#[derive (Debug, PartialEq, Copy, Clone)]
+enum PNGBlendOp {
+    Source = 0,
+    Over = 1
+}
+
+#[derive (Debug, Copy, Clone)]
+struct PNGFrameControl {
+    width: u32,
+    height: u32,
+    x_offset: u32,
+    y_offset: u32,
+    delay_numerator: u16,
+    delay_denominator: u16,
+    dispose_op: PNGDisposeOp,
+    blend_op: PNGBlendOp
+}
+
+impl PNGFrameControl {
+    fn duration(&self) -> i32 {
+        // NOTE: A zero denominator means the delay is in hundredths of a second.
+        let denominator = if self.delay_denominator == 0 { 100 } else { self.delay_denominator as u32 };
+        (self.delay_numerator as u32 * 1000 / denominator) as i32
+    }
+}
+
+struct PNGAnimationFrame<'a> {
+    control: PNGFrameControl,
+    image_data: Vec<&'a [u8]>
+}
+
+// NOTE: Frames have to be composited in order, so we keep the canvas around to make sequential access cheap.
+struct PNGAnimationState {
+    canvas: Bitmap,
+    next_frame_index: usize,
+    pending_dispose: Option<PNGFrameControl>,
+    previous_canvas: Option<Bitmap>
+}
+
+pub struct PNGImageDecoderPlugin<'a> {
+    context: PNGLoadingContext<'a>
+}
+
+struct PNGLoadingContext<'a> {
+    header: PNGHeader,
+    bytes: &'a [u8],
+    chunks: Vec<PNGChunk<'a>>,
+    palette: Vec<Color>,
+    transparency: PNGTransparency,
+    loop_count: u32,
+    animation_frames: Vec<PNGAnimationFrame<'a>>,
+    is_default_image_part_of_animation: bool,
+    animation_state: Option<PNGAnimationState>,
+    bitmap: Option<Bitmap>
+}
+
+impl<'a> PNGImageDecoderPlugin<'a> {
+    pub fn sniff(bytes: &[u8]) -> bool {
+        bytes.starts_with(&PNG_SIGNATURE)
+    }
+
+    pub fn create(bytes: &'a [u8]) -> Result<Self, Error> {
+        if !Self::sniff(bytes) {
+            return Err(Error::InvalidHeader("Invalid PNG signature"));
+        }
+        let chunks = read_chunks(&bytes[PNG_SIGNATURE.len()..])?;
+        let header = decode_png_header(&chunks)?;
+        let mut context = PNGLoadingContext {
+            header,
+            bytes,
+            chunks,
+            palette: Vec::new(),
+            transparency: PNGTransparency::None,
+            loop_count: 0,
+            animation_frames: Vec::new(),
+            is_default_image_part_of_animation: false,
+            animation_state: None,
+            bitmap: None
+        };
+        context.decode_palette_and_transparency()?;
+        context.decode_animation_chunks()?;
+        Ok(Self { context })
+    }
+}
+
+fn crc32(data: &[&[u8]]) -> u32 {
+    static TABLE: std::sync::OnceLock<[u32; 256]> = std::sync::OnceLock::new();
+    let table = TABLE.get_or_init(|| {
+        let mut table = [0u32; 256];
+        for (index, entry) in table.iter_mut().enumerate() {
+            let mut value = index as u32;
+            for _ in 0..8 {
+                value = if value & 1 != 0 { 0xEDB88320 ^ (value >> 1) } else { value >> 1 };
+            }
+            *entry = value;
+        }
+        table
+    });
+
+    let mut crc = 0xFFFFFFFFu32;
+    for bytes in data {
+        for byte in *bytes {
+            crc = table[((crc ^ *byte as u32) & 0xFF) as usize] ^ (crc >> 8);
+        }
+    }
+    !crc
+}
+
+fn read_u32_be(data: &[u8]) -> u32 {
+    u32::from_be_bytes([data[0], data[1], data[2], data[3]])
+}
+
+fn read_chunks(mut data: &[u8]) -> Result<Vec<PNGChunk<'_>>, Error> {
+    let mut chunks = Vec::new();
+    loop {
+        if data.len() < 12 {
+            return Err(Error::Truncated);
+        }
+        let length = read_u32_be(data) as usize;
+        if length > i32::MAX as usize {
+            return Err(Error::InvalidData("PNG chunk is too large"));
+        }
+        if data.len() < 12 + length {
+            return Err(Error::Truncated);
+        }
+        let chunk_type: [u8; 4] = data[4..8].try_into().unwrap();
+        let chunk_data = &data[8..8 + length];
+        let crc = read_u32_be(&data[8 + length..]);
+        if crc != crc32(&[&chunk_type, chunk_data]) {
+            return Err(Error::InvalidData("PNG chunk CRC mismatch"));
+        }
+        data = &data[12 + length..];
+
+        chunks.push(PNGChunk { chunk_type, data: chunk_data });
+        if &chunk_type == b"IEND" {
+            return Ok(chunks);
+        }
+    }
+}
+
+fn decode_png_header(chunks: &[PNGChunk]) -> Result<PNGHeader, Error> {
+    let Some(chunk) = chunks.first().filter(|chunk| &chunk.chunk_type == b"IHDR") else {
+        return Err(Error::InvalidHeader("PNG does not start with an IHDR chunk"));
+    };
+    if chunk.data.len() != 13 {
+        return Err(Error::InvalidHeader("Invalid PNG IHDR chunk size"));
+    }
+
+    let data = chunk.data;
+    let width = read_u32_be(data);
+    let height = read_u32_be(&data[4..]);
+    let bit_depth = data[8];
+    let color_type = match data[9] {
+        0 => PNGColorType::Greyscale,
+        2 => PNGColorType::Truecolor,
+        3 => PNGColorType::IndexedColor,
+        4 => PNGColorType::GreyscaleWithAlpha,
+        6 => PNGColorType::TruecolorWithAlpha,
+        _ => return Err(Error::InvalidHeader("Invalid PNG color type"))
+    };
+    if width == 0 || height == 0 || width > i32::MAX as u32 || height > i32::MAX as u32 {
+        return Err(Error::InvalidHeader("Invalid PNG dimensions"));
+    }
+    if !color_type.is_valid_bit_depth(bit_depth) {
+        return Err(Error::InvalidHeader("Invalid PNG bit depth for color type"));
+    }
+    if data[10] != 0 || data[11] != 0 {
+        return Err(Error::Unsupported("PNG compression or filter method"));
+    }
+    let interlaced = match data[12] {
+        0 => false,
+        1 => true,
+        _ => return Err(Error::InvalidHeader("Invalid PNG interlace method"))
+    };
+
+    Ok(PNGHeader { width, height, bit_depth, color_type, interlaced })
+}
+
+fn check_sequence_number(data: &[u8], expected_sequence_number: &mut u32) -> Result<(), Error> {
+    if read_u32_be(data) != *expected_sequence_number {
+        return Err(Error::InvalidData("Out of order PNG animation chunk"));
+    }
+    *expected_sequence_number += 1;
+    Ok(())
+}
+
+fn decode_frame_control(data: &[u8], header: &PNGHeader) -> Result<PNGFrameControl, Error> {
+    let dispose_op = match data[20] {
+        0 => PNGDisposeOp::None,
+        1 => PNGDisposeOp::Background,
+        2 => PNGDisposeOp::Previous,
+        _ => return Err(Error::InvalidData("Invalid PNG dispose op"))
+    };
+    let blend_op = match data[21] {
+        0 => PNGBlendOp::Source,
+        1 => PNGBlendOp::Over,
+        _ => return Err(Error::InvalidData("Invalid PNG blend op"))
+    };
+    let control = PNGFrameControl {
+        width: read_u32_be(data),
+        height: read_u32_be(&data[4..]),
+        x_offset: read_u32_be(&data[8..]),
+        y_offset: read_u32_be(&data[12..]),
+        delay_numerator: u16::from_be_bytes([data[16], data[17]]),
+        delay_denominator: u16::from_be_bytes([data[18], data[19]]),
+        dispose_op,
+        blend_op
+    };
+
+    if control.width == 0 || control.height == 0
+        || control.x_offset as u64 + control.width as u64 > header.width as u64
+        || control.y_offset as u64 + control.height as u64 > header.height as u64 {
+        return Err(Error::InvalidData("PNG animation frame is outside of the image"));
+    }
+    Ok(control)
+}
+
+impl<'a> PNGLoadingContext<'a> {
+    fn find_chunk(&self, chunk_type: &[u8; 4]) -> Option<&PNGChunk<'a>> {
+        self.chunks.iter().find(|chunk| &chunk.chunk_type == chunk_type)
+    }
+
+    fn decode_palette_and_transparency(&mut self) -> Result<(), Error> {
+        if let Some(chunk) = self.find_chunk(b"PLTE") {
+            if !chunk.data.len().is_multiple_of(3) || chunk.data.len() > 256 * 3 {
+                return Err(Error::InvalidData("Invalid PNG palette size"));
+            }
+            self.palette = chunk.data.chunks_exact(3).map(|entry| Color::from_rgb(entry[0], entry[1], entry[2])).collect();
+        } else if self.header.color_type == PNGColorType::IndexedColor {
+            return Err(Error::InvalidData("Indexed PNG without a palette"));
+        }
+
+        let Some(chunk) = self.find_chunk(b"tRNS") else {
+            return Ok(());
+        };
+        let data = chunk.data;
+        self.transparency = match self.header.color_type {
+            PNGColorType::Greyscale if data.len() >= 2 => PNGTransparency::Greyscale(u16::from_be_bytes([data[0], data[1]])),
+            PNGColorType::Truecolor if data.len() >= 6 => PNGTransparency::Truecolor(
+                u16::from_be_bytes([data[0], data[1]]),
+                u16::from_be_bytes([data[2], data[3]]),
+                u16::from_be_bytes([data[4], data[5]])
+            ),
+            PNGColorType::IndexedColor => {
+                for (entry, alpha) in self.palette.iter_mut().zip(data) {
+                    *entry = entry.with_alpha(*alpha);
+                }
+                PNGTransparency::None
+            }
+            _ => PNGTransparency::None
+        };
+        Ok(())
+    }
+
+    fn decode_animation_chunks(&mut self) -> Result<(), Error> {
+        let mut has_animation_control = false;
+        let mut has_seen_image_data = false;
+        let mut expected_sequence_number = 0;
+        let mut frames: Vec<PNGAnimationFrame<'a>> = Vec::new();
+
+        for chunk in &self.chunks {
+            match &chunk.chunk_type {
+                // NOTE: An acTL chunk after the image data does not make the image animated.
+                b"acTL" if !has_seen_image_data => {
+                    if chunk.data.len() != 8 {
+                        return Err(Error::InvalidData("Invalid PNG acTL chunk size"));
+                    }
+                    self.loop_count = read_u32_be(&chunk.data[4..]);
+                    has_animation_control = true;
+                }
+                b"fcTL" if has_animation_control => {
+                    if chunk.data.len() != 26 {
+                        return Err(Error::InvalidData("Invalid PNG fcTL chunk size"));
+                    }
+                    check_sequence_number(chunk.data, &mut expected_sequence_number)?;
+                    let control = decode_frame_control(&chunk.data[4..], &self.header)?;
+                    if !has_seen_image_data {
+                        if control.x_offset != 0 || control.y_offset != 0 || control.width != self.header.width || control.height != self.header.height {
+                            return Err(Error::InvalidData("First PNG animation frame does not cover the whole image"));
+                        }
+                        self.is_default_image_part_of_animation = true;
+                    }
+                    frames.push(PNGAnimationFrame { control, image_data: Vec::new() });
+                }
+                b"IDAT" => {
+                    has_seen_image_data = true;
+                    if self.is_default_image_part_of_animation && frames.len() == 1 {
+                        frames[0].image_data.push(chunk.data);
+                    }
+                }
+                b"fdAT" if has_animation_control => {
+                    if chunk.data.len() < 4 {
+                        return Err(Error::InvalidData("Invalid PNG fdAT chunk size"));
+                    }
+                    check_sequence_number(chunk.data, &mut expected_sequence_number)?;
+                    if frames.is_empty() || (self.is_default_image_part_of_animation && frames.len() == 1) {
+                        return Err(Error::InvalidData("PNG fdAT chunk without a frame to belong to"));
+                    }
+                    frames.last_mut().unwrap().image_data.push(&chunk.data[4..]);
+                }
+                _ => {}
+            }
+        }
+
+        if frames.iter().any(|frame| frame.image_data.is_empty()) {
+            return Err(Error::InvalidData("PNG animation frame without image data"));
+        }
+        self.animation_frames = frames;
+        Ok(())
+    }
+
+    fn render_animation_frame(&mut self, index: usize) -> Result<ImageFrameDescriptor, Error> {
+        let mut state = match self.animation_state.take() {
+            Some(state) if state.next_frame_index <= index => state,
+            _ => PNGAnimationState {
+                canvas: Bitmap::new(BitmapFormat::BGRA8888, IntSize { width: self.header.width as i32, height: self.header.height as i32 }, 1)?,
+                next_frame_index: 0,
+                pending_dispose: None,
+                previous_canvas: None
+            }
+        };
+
+        let mut duration = 0;
+        while state.next_frame_index <= index {
+            let frame = &self.animation_frames[state.next_frame_index];
+            let control = frame.control;
+
+            if let Some(previous_control) = state.pending_dispose.take() {
+                match previous_control.dispose_op {
+                    PNGDisposeOp::None => {}
+                    PNGDisposeOp::Background => {
+                        for y in previous_control.y_offset..previous_control.y_offset + previous_control.height {
+                            for x in previous_control.x_offset..previous_control.x_offset + previous_control.width {
+                                state.canvas.set_pixel(x as i32, y as i32, 0);
+                            }
+                        }
+                    }
+                    PNGDisposeOp::Previous => {
+                        if let Some(previous_canvas) = state.previous_canvas.take() {
+                            state.canvas = previous_canvas;
+                        }
+                    }
+                }
+            }
+            // NOTE: For the first frame, the canvas is fully transparent, so Previous behaves like Background as the spec requires.
+            if control.dispose_op == PNGDisposeOp::Previous {
+                state.previous_canvas = Some(state.canvas.clone());
+            }
+
+            let image = self.decode_image(control.width, control.height, &frame.image_data.concat())?;
+            for y in 0..control.height {
+                for x in 0..control.width {
+                    let source = image.get_pixel(x as i32, y as i32);
+                    let (canvas_x, canvas_y) = ((control.x_offset + x) as i32, (control.y_offset + y) as i32);
+                    let color = match control.blend_op {
+                        PNGBlendOp::Source => source,
+                        PNGBlendOp::Over => Color::from(state.canvas.get_pixel(canvas_x, canvas_y)).blend(Color::from(source)).color
+                    };
+                    state.canvas.set_pixel(canvas_x, canvas_y, color);
+                }
+            }
+
+            state.pending_dispose = Some(control);
+            state.next_frame_index += 1;
+            duration = control.duration();
+        }
+
+        let image = state.canvas.clone();
+        self.animation_state = Some(state);
+        Ok(ImageFrameDescriptor { image, duration })
+    }
+
+    fn image_data(&self) -> Vec<u8> {
+        self.chunks.iter().filter(|chunk| &chunk.chunk_type == b"IDAT").flat_map(|chunk| chunk.data).copied().collect()
+    }
+
+    fn bits_per_pixel(&self) -> usize {
+        self.header.color_type.channels() * self.header.bit_depth as usize
+    }
+
+    fn decode_image(&self, width: u32, height: u32, compressed_data: &[u8]) -> Result<Bitmap, Error> {
+        let bits_per_pixel = self.bits_per_pixel();
+        let passes: &[(u32, u32, u32, u32)] = if self.header.interlaced {
+            // NOTE: Adam7 passes as (x start, y start, x step, y step).
+            &[(0, 0, 8, 8), (4, 0, 8, 8), (0, 4, 4, 8), (2, 0, 4, 4), (0, 2, 2, 4), (1, 0, 2, 2), (0, 1, 1, 2)]
+        } else {
+            &[(0, 0, 1, 1)]
+        };
+
+        let pass_size = |(x_start, y_start, x_step, y_step): (u32, u32, u32, u32)| {
+            let pass_width = if width > x_start { (width - x_start).div_ceil(x_step) } else { 0 };
+            let pass_height = if height > y_start { (height - y_start).div_ceil(y_step) } else { 0 };
+            (pass_width as usize, pass_height as usize)
+        };
+        let mut expected_size = 0usize;
+        for pass in passes {
+            let (pass_width, pass_height) = pass_size(*pass);
+            if pass_width == 0 || pass_height == 0 {
+                continue;
+            }
+            let row_size = (pass_width * bits_per_pixel).div_ceil(8) + 1;
+            let Some(size) = row_size.checked_mul(pass_height).and_then(|size| size.checked_add(expected_size)) else {
+                return Err(Error::SizeOverflow);
+            };
+            expected_size = size;
+        }
+
+        let mut bitmap = Bitmap::new(BitmapFormat::BGRA8888, IntSize { width: width as i32, height: height as i32 }, 1)?;
+        let mut data = zlib_decompress(compressed_data, expected_size)?;
+        if data.len() < expected_size {
+            return Err(Error::Truncated);
+        }
+
+        let mut offset = 0;
+        for pass in passes {
+            let (pass_width, pass_height) = pass_size(*pass);
+            if pass_width == 0 || pass_height == 0 {
+                continue;
+            }
+            let row_size = (pass_width * bits_per_pixel).div_ceil(8);
+            let pass_data = &mut data[offset..offset + (row_size + 1) * pass_height];
+            unfilter_scanlines(pass_data, row_size, bits_per_pixel.div_ceil(8))?;
+
+            let (x_start, y_start, x_step, y_step) = *pass;
+            for (row, scanline) in pass_data.chunks_exact(row_size + 1).enumerate() {
+                let y = y_start + row as u32 * y_step;
+                for column in 0..pass_width {
+                    let x = x_start + column as u32 * x_step;
+                    let color = self.pixel_at(&scanline[1..], column)?;
+                    bitmap.set_pixel(x as i32, y as i32, color);
+                }
+            }
+            offset += (row_size + 1) * pass_height;
+        }
+
+        Ok(bitmap)
+    }
+
+    fn pixel_at(&self, scanline: &[u8], column: usize) -> Result<ARGB, Error> {
+        let bit_depth = self.header.bit_depth as usize;
+        let channels = self.header.color_type.channels();
+        let sample = |channel: usize| -> u16 {
+            let index = column * channels + channel;
+            match bit_depth {
+                16 => u16::from_be_bytes([scanline[index * 2], scanline[index * 2 + 1]]),
+                8 => scanline[index] as u16,
+                _ => {
+                    let bit_offset = index * bit_depth;
+                    let shift = 8 - bit_depth - bit_offset % 8;
+                    ((scanline[bit_offset / 8] >> shift) & ((1 << bit_depth) - 1)) as u16
+                }
+            }
+        };
+        // NOTE: Scale samples of any bit depth to 8 bits, keeping the top byte of 16-bit ones.
+        let to_u8 = |value: u16| -> u8 {
+            match bit_depth {
+                16 => (value >> 8) as u8,
+                8 => value as u8,
+                _ => (value as u32 * 255 / ((1 << bit_depth) - 1)) as u8
+            }
+        };
+
+        let color = match self.header.color_type {
+            PNGColorType::Greyscale => {
+                let gray = sample(0);
+                let alpha = match self.transparency {
+                    PNGTransparency::Greyscale(transparent_gray) if transparent_gray == gray => 0,
+                    _ => 0xFF
+                };
+                let gray = to_u8(gray);
+                Color::from_rgba(gray, gray, gray, alpha)
+            }
+            PNGColorType::Truecolor => {
+                let (red, green, blue) = (sample(0), sample(1), sample(2));
+                let alpha = match self.transparency {
+                    PNGTransparency::Truecolor(transparent_red, transparent_green, transparent_blue)
+                        if (transparent_red, transparent_green, transparent_blue) == (red, green, blue) => 0,
+                    _ => 0xFF
+                };
+                Color::from_rgba(to_u8(red), to_u8(green), to_u8(blue), alpha)
+            }
+            PNGColorType::IndexedColor => {
+                let Some(color) = self.palette.get(sample(0) as usize) else {
+                    return Err(Error::InvalidData("PNG palette index out of range"));
+                };
+                *color
+            }
+            PNGColorType::GreyscaleWithAlpha => {
+                let gray = to_u8(sample(0));
+                Color::from_rgba(gray, gray, gray, to_u8(sample(1)))
+            }
+            PNGColorType::TruecolorWithAlpha => Color::from_rgba(to_u8(sample(0)), to_u8(sample(1)), to_u8(sample(2)), to_u8(sample(3)))
+        };
+        Ok(color.color)
+    }
+}
+
+fn paeth_predictor(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let pa = (p - a as i16).abs();
+    let pb = (p - b as i16).abs();
+    let pc = (p - c as i16).abs();
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/// Reverses the per-scanline filters in place. Each scanline is preceded by its filter type byte.
+fn unfilter_scanlines(data: &mut [u8], row_size: usize, bytes_per_pixel: usize) -> Result<(), Error> {
+    let stride = row_size + 1;
+    let row_count = data.len() / stride;
+    for row in 0..row_count {
+        let (previous_rows, current_rows) = data.split_at_mut(row * stride);
+        let previous = if row == 0 { None } else { Some(&previous_rows[(row - 1) * stride + 1..]) };
+        let filter_type = current_rows[0];
+        let current = &mut current_rows[1..stride];
+        let above = |index: usize| previous.map_or(0, |previous| previous[index]);
+
+        match filter_type {
+            0 => {}
+            1 => {
+                for index in bytes_per_pixel..row_size {
+                    current[index] = current[index].wrapping_add(current[index - bytes_per_pixel]);
+                }
+            }
+            2 => {
+                for (index, byte) in current.iter_mut().enumerate() {
+                    *byte = byte.wrapping_add(above(index));
+                }
+            }
+            3 => {
+                for index in 0..row_size {
+                    let left = if index >= bytes_per_pixel { current[index - bytes_per_pixel] } else { 0 };
+                    current[index] = current[index].wrapping_add(((left as u16 + above(index) as u16) / 2) as u8);
+                }
+            }
+            4 => {
+                for index in 0..row_size {
+                    let left = if index >= bytes_per_pixel { current[index - bytes_per_pixel] } else { 0 };
+                    let upper_left = if index >= bytes_per_pixel { above(index - bytes_per_pixel) } else { 0 };
+                    current[index] = current[index].wrapping_add(paeth_predictor(left, above(index), upper_left));
+                }
+            }
+            _ => return Err(Error::InvalidData("Invalid PNG filter type"))
+        }
+    }
+    Ok(())
+}
+
+impl<'a> ImageDecoderPlugin for PNGImageDecoderPlugin<'a> {
+    fn size(&self) -> IntSize {
+        IntSize {
+            width: self.context.header.width as i32,
+            height: self.context.header.height as i32
+        }
+    }
+
+    fn is_animated(&self) -> bool {
+        !self.context.animation_frames.is_empty()
+    }
+
+    fn loop_count(&self) -> usize {
+        self.context.loop_count as usize
+    }
+
+    fn frame_count(&self) -> usize {
+        if !self.is_animated() {
+            return 1;
+        }
+        self.first_animated_frame_index() + self.context.animation_frames.len()
+    }
+
+    fn first_animated_frame_index(&self) -> usize {
+        if self.is_animated() && !self.context.is_default_image_part_of_animation {
+            1
+        } else {
+            0
+        }
+    }
+
+    fn frame_with_ideal_size(&mut self, index: usize, _: Option<IntSize>) -> Result<ImageFrameDescriptor, Error> {
+        if index >= self.frame_count() {
+            return Err(Error::FrameIndexOutOfRange);
+        }
+        if self.is_animated() && index >= self.first_animated_frame_index() {
+            return self.context.render_animation_frame(index - self.first_animated_frame_index());
+        }
+
+        if let Some(bitmap) = &self.context.bitmap {
+            return Ok(ImageFrameDescriptor {
+                image: bitmap.clone(),
+                duration: 0
+            });
+        }
+
+        let image_data = self.context.image_data();
+        if image_data.is_empty() {
+            return Err(Error::InvalidData("PNG without IDAT chunks"));
+        }
+        let bitmap = self.context.decode_image(self.context.header.width, self.context.header.height, &image_data)?;
+        self.context.bitmap = Some(bitmap.clone());
+
+        Ok(ImageFrameDescriptor {
+            image: bitmap,
+            duration: 0
+        })
+    }
+
+    fn natural_frame_format(&self) -> NaturalFrameFormat {
+        match self.context.header.color_type {
+            PNGColorType::Greyscale | PNGColorType::GreyscaleWithAlpha => NaturalFrameFormat::Grayscale,
+            _ => NaturalFrameFormat::RGB
+        }
+    }
+}
+
+/// # Safety
+///
+/// `bytes` must point to `size` readable bytes that outlive the returned decoder,
+/// and `out_decoder` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn png_image_decoder_plugin_new(bytes: *const u8, size: usize, out_decoder: *mut *mut c_void) -> ErrorCode {
+    ffi_call(|| unsafe {
+        if bytes.is_null() {
+            return Err(Error::InvalidArgument("Bytes are null"));
+        }
+        if out_decoder.is_null() {
+            return Err(Error::InvalidArgument("Output pointer is null"));
+        }
+        let bytes = std::slice::from_raw_parts(bytes, size);
+        let decoder = PNGImageDecoderPlugin::create(bytes)?;
+        write_ffi_result(out_decoder, decoder_into_opaque(Box::new(decoder)))
+    })
+}
+
+/// # Safety
+///
+/// `opaque_decoder` must either be null or come from `png_image_decoder_plugin_new` and not be freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn png_image_decoder_plugin_free(opaque_decoder: *mut c_void) -> ErrorCode {
+    ffi_call(|| {
+        if !opaque_decoder.is_null() {
+            let decoder: Box<Box<dyn ImageDecoderPlugin>> = unsafe { Box::from_raw(opaque_decoder as *mut _) };
+            drop(decoder);
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // NOTE: 2x2 RGBA stored uncompressed, with red, green, blue and half transparent white pixels.
+    const RGBA_IMAGE: [u8; 86] = [
+        0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A, 0x00, 0x00, 0x00, 0x0D, 0x49, 0x48, 0x44, 0x52, 0x00, 0x00, 0x00, 0x02,
+        0x00, 0x00, 0x00, 0x02, 0x08, 0x06, 0x00, 0x00, 0x00, 0x72, 0xB6, 0x0D, 0x24, 0x00, 0x00, 0x00, 0x1D, 0x49, 0x44, 0x41,
+        0x54, 0x78, 0x01, 0x01, 0x12, 0x00, 0xED, 0xFF, 0x00, 0xFF, 0x00, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0x00, 0x00,
+        0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x80, 0x49, 0x49, 0x09, 0x78, 0x4B, 0xD9, 0xCE, 0x03, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45,
+        0x4E, 0x44, 0xAE, 0x42, 0x60, 0x82
+    ];
+
+    // NOTE: 3x5 RGB using filter types 0 through 4 on consecutive scanlines, with the zlib stream split over two IDAT chunks.
+    const FILTERED_IMAGE: [u8; 115] = [
+        0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A, 0x00, 0x00, 0x00, 0x0D, 0x49, 0x48, 0x44, 0x52, 0x00, 0x00, 0x00, 0x03,
+        0x00, 0x00, 0x00, 0x05, 0x08, 0x02, 0x00, 0x00, 0x00, 0x0F, 0x13, 0xC1, 0xF5, 0x00, 0x00, 0x00, 0x0A, 0x49, 0x44, 0x41,
+        0x54, 0x78, 0xDA, 0x63, 0x60, 0x38, 0xC1, 0xA0, 0xB1, 0x8A, 0x31, 0xB4, 0xBD, 0x83, 0x76, 0x00, 0x00, 0x00, 0x24, 0x49,
+        0x44, 0x41, 0x54, 0xA0, 0x87, 0x89, 0x91, 0xEB, 0x84, 0x91, 0xC6, 0x23, 0x46, 0x20, 0x62, 0xE2, 0x62, 0x30, 0x82, 0x20,
+        0x66, 0x91, 0x94, 0x14, 0xC9, 0x8F, 0x52, 0x40, 0xC4, 0x02, 0x16, 0x60, 0x04, 0x22, 0x00, 0x1E, 0x45, 0x09, 0xC8, 0x4F,
+        0x09, 0x36, 0x18, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4E, 0x44, 0xAE, 0x42, 0x60, 0x82
+    ];
+
+    // NOTE: 5x5 Adam7 interlaced greyscale where each pixel is 10 * (y * 5 + x), with every pass using the Sub filter.
+    const INTERLACED_IMAGE: [u8; 97] = [
+        0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A, 0x00, 0x00, 0x00, 0x0D, 0x49, 0x48, 0x44, 0x52, 0x00, 0x00, 0x00, 0x05,
+        0x00, 0x00, 0x00, 0x05, 0x08, 0x00, 0x00, 0x00, 0x01, 0xDF, 0x03, 0x49, 0xAF, 0x00, 0x00, 0x00, 0x28, 0x49, 0x44, 0x41,
+        0x54, 0x78, 0xDA, 0x63, 0x64, 0x60, 0xD4, 0x60, 0x3C, 0xA1, 0xC1, 0x28, 0xC2, 0x78, 0x87, 0x31, 0x45, 0x44, 0x84, 0x91,
+        0x4B, 0x84, 0x31, 0x4F, 0x84, 0xF1, 0x92, 0x08, 0xA3, 0x11, 0x17, 0x10, 0x30, 0x4E, 0x03, 0x91, 0x00, 0x66, 0xBD, 0x05,
+        0x3E, 0x5D, 0xA2, 0x80, 0x16, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4E, 0x44, 0xAE, 0x42, 0x60, 0x82
+    ];
+
+    // NOTE: 4x2 with a 2-bit palette of red, green, blue and a fully transparent black.
+    const PALETTED_IMAGE: [u8; 109] = [
+        0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A, 0x00, 0x00, 0x00, 0x0D, 0x49, 0x48, 0x44, 0x52, 0x00, 0x00, 0x00, 0x04,
+        0x00, 0x00, 0x00, 0x02, 0x02, 0x03, 0x00, 0x00, 0x00, 0x02, 0xC6, 0x95, 0xF0, 0x00, 0x00, 0x00, 0x0C, 0x50, 0x4C, 0x54,
+        0x45, 0xFF, 0x00, 0x00, 0x00, 0xFF, 0x00, 0x00, 0x00, 0xFF, 0x00, 0x00, 0x00, 0xFB, 0xBE, 0x46, 0xE4, 0x00, 0x00, 0x00,
+        0x04, 0x74, 0x52, 0x4E, 0x53, 0xFF, 0xFF, 0xFF, 0x00, 0x40, 0x2A, 0xA9, 0xF4, 0x00, 0x00, 0x00, 0x0C, 0x49, 0x44, 0x41,
+        0x54, 0x78, 0xDA, 0x63, 0x90, 0x66, 0x78, 0x02, 0x00, 0x01, 0x39, 0x01, 0x00, 0x7B, 0x99, 0x42, 0x37, 0x00, 0x00, 0x00,
+        0x00, 0x49, 0x45, 0x4E, 0x44, 0xAE, 0x42, 0x60, 0x82
+    ];
+
+    // NOTE: 4x1 2-bit greyscale with the samples 0, 1, 2 and 3.
+    const GRAYSCALE_2_IMAGE: [u8; 67] = [
+        0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A, 0x00, 0x00, 0x00, 0x0D, 0x49, 0x48, 0x44, 0x52, 0x00, 0x00, 0x00, 0x04,
+        0x00, 0x00, 0x00, 0x01, 0x02, 0x00, 0x00, 0x00, 0x00, 0x96, 0xE7, 0x48, 0xB0, 0x00, 0x00, 0x00, 0x0A, 0x49, 0x44, 0x41,
+        0x54, 0x78, 0xDA, 0x63, 0x90, 0x06, 0x00, 0x00, 0x1D, 0x00, 0x1C, 0x23, 0x7C, 0x8F, 0xAC, 0x00, 0x00, 0x00, 0x00, 0x49,
+        0x45, 0x4E, 0x44, 0xAE, 0x42, 0x60, 0x82
+    ];
+
+    // NOTE: 2x1 16-bit greyscale with the samples 0x1234 and 0xABCD, where tRNS makes 0x1234 transparent.
+    const GRAYSCALE_16_IMAGE: [u8; 84] = [
+        0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A, 0x00, 0x00, 0x00, 0x0D, 0x49, 0x48, 0x44, 0x52, 0x00, 0x00, 0x00, 0x02,
+        0x00, 0x00, 0x00, 0x01, 0x10, 0x00, 0x00, 0x00, 0x00, 0x81, 0xD9, 0xFC, 0x15, 0x00, 0x00, 0x00, 0x02, 0x74, 0x52, 0x4E,
+        0x53, 0x12, 0x34, 0x2F, 0xD3, 0x49, 0x5E, 0x00, 0x00, 0x00, 0x0D, 0x49, 0x44, 0x41, 0x54, 0x78, 0xDA, 0x63, 0x10, 0x32,
+        0x59, 0x7D, 0x16, 0x00, 0x03, 0x0C, 0x01, 0xBF, 0xB1, 0xE7, 0xD4, 0x4D, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4E, 0x44,
+        0xAE, 0x42, 0x60, 0x82
+    ];
+
+    // NOTE: 2x1 RGB with the colors (10, 20, 30) and (40, 50, 60), where tRNS makes the first one transparent.
+    const TRUECOLOR_TRANSPARENT_IMAGE: [u8; 90] = [
+        0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A, 0x00, 0x00, 0x00, 0x0D, 0x49, 0x48, 0x44, 0x52, 0x00, 0x00, 0x00, 0x02,
+        0x00, 0x00, 0x00, 0x01, 0x08, 0x02, 0x00, 0x00, 0x00, 0x7B, 0x40, 0xE8, 0xDD, 0x00, 0x00, 0x00, 0x06, 0x74, 0x52, 0x4E,
+        0x53, 0x00, 0x0A, 0x00, 0x14, 0x00, 0x1E, 0xC5, 0x36, 0x29, 0xFF, 0x00, 0x00, 0x00, 0x0F, 0x49, 0x44, 0x41, 0x54, 0x78,
+        0xDA, 0x63, 0xE0, 0x12, 0x91, 0xD3, 0x30, 0xB2, 0x01, 0x00, 0x02, 0x37, 0x00, 0xD3, 0xE2, 0x2D, 0xED, 0x9F, 0x00, 0x00,
+        0x00, 0x00, 0x49, 0x45, 0x4E, 0x44, 0xAE, 0x42, 0x60, 0x82
+    ];
+
+    // NOTE: 1x1 greyscale whose IDAT inflates to 6 bytes instead of the expected 2.
+    const OVERSIZED_IMAGE: [u8; 69] = [
+        0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A, 0x00, 0x00, 0x00, 0x0D, 0x49, 0x48, 0x44, 0x52, 0x00, 0x00, 0x00, 0x01,
+        0x00, 0x00, 0x00, 0x01, 0x08, 0x00, 0x00, 0x00, 0x00, 0x3A, 0x7E, 0x9B, 0x55, 0x00, 0x00, 0x00, 0x0C, 0x49, 0x44, 0x41,
+        0x54, 0x78, 0xDA, 0x63, 0x68, 0x60, 0x00, 0x02, 0x00, 0x02, 0x86, 0x00, 0x81, 0x10, 0x4C, 0x20, 0x4A, 0x00, 0x00, 0x00,
+        0x00, 0x49, 0x45, 0x4E, 0x44, 0xAE, 0x42, 0x60, 0x82
+    ];
+    // NOTE: 3x1 APNG looping 3 times, where the IDAT is the first of four frames:
+    //       0: red, green, blue, 1/10 s, dispose none, blend source
+    //       1: half transparent white at x = 1, 5/100 s (zero denominator), dispose previous, blend over
+    //       2: half transparent black at x = 2, 250/1000 s, dispose background, blend source
+    //       3: opaque white at x = 0, 1/1 s, dispose none, blend over
+    const ANIMATED_IMAGE: [u8; 332] = [
+        0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A, 0x00, 0x00, 0x00, 0x0D, 0x49, 0x48, 0x44, 0x52, 0x00, 0x00, 0x00, 0x03,
+        0x00, 0x00, 0x00, 0x01, 0x08, 0x06, 0x00, 0x00, 0x00, 0x1B, 0xE0, 0x14, 0xB4, 0x00, 0x00, 0x00, 0x08, 0x61, 0x63, 0x54,
+        0x4C, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x03, 0xE5, 0xC4, 0x37, 0x6A, 0x00, 0x00, 0x00, 0x1A, 0x66, 0x63, 0x54,
+        0x4C, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x01, 0x00, 0x0A, 0x00, 0x00, 0x2E, 0xCB, 0x36, 0x21, 0x00, 0x00, 0x00, 0x12, 0x49, 0x44, 0x41, 0x54, 0x78,
+        0xDA, 0x63, 0xF8, 0xCF, 0xC0, 0xF0, 0x1F, 0x0C, 0x19, 0xFE, 0xFF, 0x07, 0x00, 0x23, 0xE9, 0x05, 0xFB, 0x8A, 0xE7, 0x4F,
+        0x9E, 0x00, 0x00, 0x00, 0x1A, 0x66, 0x63, 0x54, 0x4C, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00,
+        0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x05, 0x00, 0x00, 0x02, 0x01, 0xBD, 0xA4, 0x74, 0xC6, 0x00,
+        0x00, 0x00, 0x11, 0x66, 0x64, 0x41, 0x54, 0x00, 0x00, 0x00, 0x02, 0x78, 0xDA, 0x63, 0xF8, 0xFF, 0xFF, 0x7F, 0x03, 0x00,
+        0x09, 0x7C, 0x03, 0x7E, 0x82, 0xE9, 0xA7, 0x34, 0x00, 0x00, 0x00, 0x1A, 0x66, 0x63, 0x54, 0x4C, 0x00, 0x00, 0x00, 0x03,
+        0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFA, 0x03, 0xE8,
+        0x01, 0x00, 0xEA, 0xDA, 0x4B, 0x01, 0x00, 0x00, 0x00, 0x11, 0x66, 0x64, 0x41, 0x54, 0x00, 0x00, 0x00, 0x04, 0x78, 0xDA,
+        0x63, 0x60, 0x60, 0x60, 0x68, 0x00, 0x00, 0x00, 0x85, 0x00, 0x81, 0xAE, 0x04, 0x64, 0x58, 0x00, 0x00, 0x00, 0x1A, 0x66,
+        0x63, 0x54, 0x4C, 0x00, 0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x01, 0x00, 0x01, 0xBA, 0x02, 0xA4, 0xE0, 0x00, 0x00, 0x00, 0x0F, 0x66, 0x64, 0x41,
+        0x54, 0x00, 0x00, 0x00, 0x06, 0x78, 0xDA, 0x63, 0xF8, 0x0F, 0x04, 0x00, 0x09, 0xFB, 0x03, 0xFD, 0xED, 0x5D, 0xEA, 0x2C,
+        0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4E, 0x44, 0xAE, 0x42, 0x60, 0x82
+    ];
+
+    // NOTE: 1x1 APNG with a red default image that is not part of the animation, followed by a single blue frame.
+    const DEFAULT_IMAGE_NOT_ANIMATED_IMAGE: [u8; 157] = [
+        0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A, 0x00, 0x00, 0x00, 0x0D, 0x49, 0x48, 0x44, 0x52, 0x00, 0x00, 0x00, 0x01,
+        0x00, 0x00, 0x00, 0x01, 0x08, 0x06, 0x00, 0x00, 0x00, 0x1F, 0x15, 0xC4, 0x89, 0x00, 0x00, 0x00, 0x08, 0x61, 0x63, 0x54,
+        0x4C, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0xB4, 0x2D, 0xE9, 0xA0, 0x00, 0x00, 0x00, 0x0D, 0x49, 0x44, 0x41,
+        0x54, 0x78, 0xDA, 0x63, 0xF8, 0xCF, 0xC0, 0xF0, 0x1F, 0x00, 0x05, 0x00, 0x01, 0xFF, 0x56, 0xC7, 0x2F, 0x0D, 0x00, 0x00,
+        0x00, 0x1A, 0x66, 0x63, 0x54, 0x4C, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x02, 0x00, 0x00, 0x54, 0x6C, 0x61, 0x68, 0x00, 0x00, 0x00, 0x11,
+        0x66, 0x64, 0x41, 0x54, 0x00, 0x00, 0x00, 0x01, 0x78, 0xDA, 0x63, 0x60, 0x60, 0xF8, 0xFF, 0x1F, 0x00, 0x03, 0x02, 0x01,
+        0xFF, 0x56, 0x44, 0x92, 0x1C, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4E, 0x44, 0xAE, 0x42, 0x60, 0x82
+    ];
+
+    #[test]
+    fn decodes_rgba_image() {
+        let mut decoder = PNGImageDecoderPlugin::create(&RGBA_IMAGE).unwrap();
+        assert_eq!((decoder.size().width, decoder.size().height), (2, 2));
+        assert_eq!(decoder.natural_frame_format(), NaturalFrameFormat::RGB);
+        let image = decoder.frame(0).unwrap().image;
+        assert_eq!(image.pixels(), [0xFFFF0000, 0xFF00FF00, 0xFF0000FF, 0x80FFFFFF]);
+    }
+
+    #[test]
+    fn reverses_every_filter_type_across_idat_chunks() {
+        let mut decoder = PNGImageDecoderPlugin::create(&FILTERED_IMAGE).unwrap();
+        let image = decoder.frame(0).unwrap().image;
+        for y in 0..5 {
+            for x in 0..3 {
+                let expected = Color::from_rgb((x * 40 + y * 10) as u8, (200 - x * 30) as u8, (y * 50 + x) as u8).color;
+                assert_eq!(image.get_pixel(x, y), expected, "pixel ({x}, {y})");
+            }
+        }
+    }
+
+    #[test]
+    fn decodes_adam7_interlaced_image() {
+        let mut decoder = PNGImageDecoderPlugin::create(&INTERLACED_IMAGE).unwrap();
+        assert_eq!(decoder.natural_frame_format(), NaturalFrameFormat::Grayscale);
+        let image = decoder.frame(0).unwrap().image;
+        let expected: Vec<ARGB> = (0..25).map(|index| {
+            let gray = index as u8 * 10;
+            Color::from_rgb(gray, gray, gray).color
+        }).collect();
+        assert_eq!(image.pixels(), expected);
+    }
+
+    #[test]
+    fn decodes_paletted_image_with_transparency() {
+        let mut decoder = PNGImageDecoderPlugin::create(&PALETTED_IMAGE).unwrap();
+        let image = decoder.frame(0).unwrap().image;
+        assert_eq!(image.pixels(), [
+            0xFFFF0000, 0xFF00FF00, 0xFF0000FF, 0x00000000,
+            0x00000000, 0xFF0000FF, 0xFF00FF00, 0xFFFF0000
+        ]);
+    }
+
+    #[test]
+    fn scales_low_bit_depth_samples() {
+        let mut decoder = PNGImageDecoderPlugin::create(&GRAYSCALE_2_IMAGE).unwrap();
+        let image = decoder.frame(0).unwrap().image;
+        assert_eq!(image.pixels(), [0xFF000000, 0xFF555555, 0xFFAAAAAA, 0xFFFFFFFF]);
+    }
+
+    #[test]
+    fn applies_single_color_transparency() {
+        let mut decoder = PNGImageDecoderPlugin::create(&GRAYSCALE_16_IMAGE).unwrap();
+        assert_eq!(decoder.frame(0).unwrap().image.pixels(), [0x00121212, 0xFFABABAB]);
+
+        let mut decoder = PNGImageDecoderPlugin::create(&TRUECOLOR_TRANSPARENT_IMAGE).unwrap();
+        assert_eq!(decoder.frame(0).unwrap().image.pixels(), [0x000A141E, 0xFF28323C]);
+    }
+
+    #[test]
+    fn rejects_bad_chunk_checksum() {
+        let mut data = RGBA_IMAGE;
+        data[29] ^= 1;
+        assert_eq!(PNGImageDecoderPlugin::create(&data).err(), Some(Error::InvalidData("PNG chunk CRC mismatch")));
+    }
+
+    #[test]
+    fn rejects_image_data_larger_than_the_image() {
+        let mut decoder = PNGImageDecoderPlugin::create(&OVERSIZED_IMAGE).unwrap();
+        assert_eq!(decoder.frame(0).err(), Some(Error::InvalidData("Decompressed data is larger than expected")));
+    }
+
+    #[test]
+    fn rejects_missing_frames() {
+        let mut decoder = PNGImageDecoderPlugin::create(&RGBA_IMAGE).unwrap();
+        assert_eq!(decoder.frame(1).err(), Some(Error::FrameIndexOutOfRange));
+    }
+
+    #[test]
+    fn decodes_animation_control() {
+        let decoder = PNGImageDecoderPlugin::create(&ANIMATED_IMAGE).unwrap();
+        assert!(decoder.is_animated());
+        assert_eq!(decoder.frame_count(), 4);
+        assert_eq!(decoder.loop_count(), 3);
+        assert_eq!(decoder.first_animated_frame_index(), 0);
+
+        let decoder = PNGImageDecoderPlugin::create(&RGBA_IMAGE).unwrap();
+        assert!(!decoder.is_animated());
+        assert_eq!(decoder.frame_count(), 1);
+    }
+
+    #[test]
+    fn composites_animation_frames() {
+        let mut decoder = PNGImageDecoderPlugin::create(&ANIMATED_IMAGE).unwrap();
+        let expected: [(i32, [ARGB; 3]); 4] = [
+            (100, [0xFFFF0000, 0xFF00FF00, 0xFF0000FF]),
+            // NOTE: Half transparent white blended over green.
+            (50, [0xFFFF0000, 0xFF80FF80, 0xFF0000FF]),
+            // NOTE: The previous frame was restored before replacing the blue pixel.
+            (250, [0xFFFF0000, 0xFF00FF00, 0x80000000]),
+            // NOTE: The replaced pixel was cleared to transparent black before this frame.
+            (1000, [0xFFFFFFFF, 0xFF00FF00, 0x00000000])
+        ];
+        for (index, (duration, frame_pixels)) in expected.iter().enumerate() {
+            let frame = decoder.frame(index).unwrap();
+            assert_eq!(frame.duration, *duration, "frame {index}");
+            assert_eq!(frame.image.pixels(), frame_pixels, "frame {index}");
+        }
+    }
+
+    #[test]
+    fn renders_animation_frames_out_of_order() {
+        let mut decoder = PNGImageDecoderPlugin::create(&ANIMATED_IMAGE).unwrap();
+        assert_eq!(decoder.frame(3).unwrap().image.pixels(), [0xFFFFFFFF, 0xFF00FF00, 0x00000000]);
+        assert_eq!(decoder.frame(1).unwrap().image.pixels(), [0xFFFF0000, 0xFF80FF80, 0xFF0000FF]);
+        assert_eq!(decoder.frame(4).err(), Some(Error::FrameIndexOutOfRange));
+    }
+
+    #[test]
+    fn keeps_default_image_outside_of_animation() {
+        let mut decoder = PNGImageDecoderPlugin::create(&DEFAULT_IMAGE_NOT_ANIMATED_IMAGE).unwrap();
+        assert_eq!(decoder.frame_count(), 2);
+        assert_eq!(decoder.first_animated_frame_index(), 1);
+        assert_eq!(decoder.loop_count(), 0);
+
+        let frame = decoder.frame(0).unwrap();
+        assert_eq!((frame.duration, frame.image.pixels()), (0, vec![0xFFFF0000]));
+        let frame = decoder.frame(1).unwrap();
+        assert_eq!((frame.duration, frame.image.pixels()), (500, vec![0xFF0000FF]));
+    }
+}
@@ -0,0 +1,418 @@
+use std::ffi::c_void;
+use crate::imagedecoderplugin::{decoder_into_opaque, ImageDecoderPlugin, ImageFrameDescriptor, NaturalFrameFormat};
+use crate::{Color, IntSize};
+use crate::bitmap::{Bitmap, BitmapFormat};
+use crate::error::{Error, ErrorCode, ffi_call, write_ffi_result};
+
+#[derive (Debug, PartialEq, Copy, Clone)]
+enum PortableImageFormat {
+    Bitmap,
+    Graymap,
+    Pixmap,
+    ArbitraryMap
+}
+
+#[derive (Debug, Copy, Clone)]
+struct PortableImageHeader {
+    format: PortableImageFormat,
+    is_raw: bool,
+    width: u32,
+    height: u32,
+    max_value: u16,
+    depth: u8
+}
+
+impl PortableImageHeader {
+    fn has_alpha(&self) -> bool {
+        self.depth == 2 || self.depth == 4
+    }
+
+    fn is_grayscale(&self) -> bool {
+        self.depth <= 2
+    }
+}
+
+pub struct PortableImageDecoderPlugin<'a> {
+    context: PortableImageLoadingContext<'a>
+}
+
+struct PortableImageLoadingContext<'a> {
+    header: PortableImageHeader,
+    bytes: &'a [u8],
+    data_offset: usize,
+    bitmap: Option<Bitmap>
+}
+
+struct PortableImageReader<'a> {
+    data: &'a [u8],
+    position: usize
+}
+
+impl<'a> PortableImageReader<'a> {
+    fn read_u8(&mut self) -> Result<u8, Error> {
+        let Some(byte) = self.data.get(self.position) else {
+            return Err(Error::Truncated);
+        };
+        self.position += 1;
+        Ok(*byte)
+    }
+
+    fn skip_whitespace_and_comments(&mut self) {
+        while let Some(&byte) = self.data.get(self.position) {
+            if byte == b'#' {
+                while self.data.get(self.position).is_some_and(|&byte| byte != b'\n' && byte != b'\r') {
+                    self.position += 1;
+                }
+            } else if byte.is_ascii_whitespace() {
+                self.position += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn read_token(&mut self) -> Result<&'a [u8], Error> {
+        self.skip_whitespace_and_comments();
+        let start = self.position;
+        while self.data.get(self.position).is_some_and(|byte| !byte.is_ascii_whitespace()) {
+            self.position += 1;
+        }
+        if start == self.position {
+            return Err(Error::Truncated);
+        }
+        Ok(&self.data[start..self.position])
+    }
+
+    fn read_number(&mut self) -> Result<u32, Error> {
+        let token = self.read_token()?;
+        let mut number = 0u32;
+        for &digit in token {
+            if !digit.is_ascii_digit() {
+                return Err(Error::InvalidData("Expected a number"));
+            }
+            number = number.checked_mul(10)
+                .and_then(|number| number.checked_add((digit - b'0') as u32))
+                .ok_or(Error::InvalidData("Number is too large"))?;
+        }
+        Ok(number)
+    }
+
+    fn read_bit(&mut self) -> Result<bool, Error> {
+        // NOTE: Plain PBM samples don't have to be separated by whitespace.
+        self.skip_whitespace_and_comments();
+        match self.read_u8()? {
+            b'0' => Ok(false),
+            b'1' => Ok(true),
+            _ => Err(Error::InvalidData("Expected a PBM bit"))
+        }
+    }
+}
+
+fn read_pam_header(reader: &mut PortableImageReader) -> Result<(u32, u32, u32, u8), Error> {
+    let (mut width, mut height, mut max_value, mut depth) = (None, None, None, None);
+    let mut tuple_depth = None;
+    loop {
+        match reader.read_token()? {
+            b"WIDTH" => width = Some(reader.read_number()?),
+            b"HEIGHT" => height = Some(reader.read_number()?),
+            b"MAXVAL" => max_value = Some(reader.read_number()?),
+            b"DEPTH" => depth = Some(reader.read_number()?),
+            b"TUPLTYPE" => {
+                tuple_depth = Some(match reader.read_token()? {
+                    b"BLACKANDWHITE" | b"GRAYSCALE" => 1,
+                    b"BLACKANDWHITE_ALPHA" | b"GRAYSCALE_ALPHA" => 2,
+                    b"RGB" => 3,
+                    b"RGB_ALPHA" => 4,
+                    _ => return Err(Error::Unsupported("Unsupported PAM tuple type"))
+                });
+            }
+            b"ENDHDR" => break,
+            _ => return Err(Error::InvalidHeader("Invalid PAM header field"))
+        }
+    }
+
+    let (Some(width), Some(height), Some(max_value), Some(depth)) = (width, height, max_value, depth) else {
+        return Err(Error::InvalidHeader("Incomplete PAM header"));
+    };
+    // NOTE: Without a tuple type, we go by the depth alone.
+    if !(1..=4).contains(&depth) || tuple_depth.is_some_and(|tuple_depth| tuple_depth != depth) {
+        return Err(Error::Unsupported("Unsupported PAM depth"));
+    }
+    Ok((width, height, max_value, depth as u8))
+}
+
+fn decode_portable_image_header(bytes: &[u8]) -> Result<(PortableImageHeader, usize), Error> {
+    if bytes.len() < 2 {
+        return Err(Error::Truncated);
+    }
+    let (format, is_raw) = match (bytes[0], bytes[1]) {
+        (b'P', b'1') => (PortableImageFormat::Bitmap, false),
+        (b'P', b'2') => (PortableImageFormat::Graymap, false),
+        (b'P', b'3') => (PortableImageFormat::Pixmap, false),
+        (b'P', b'4') => (PortableImageFormat::Bitmap, true),
+        (b'P', b'5') => (PortableImageFormat::Graymap, true),
+        (b'P', b'6') => (PortableImageFormat::Pixmap, true),
+        (b'P', b'7') => (PortableImageFormat::ArbitraryMap, true),
+        _ => return Err(Error::InvalidHeader("Invalid Netpbm magic"))
+    };
+
+    let mut reader = PortableImageReader { data: bytes, position: 2 };
+    let (width, height, max_value, depth) = match format {
+        PortableImageFormat::Bitmap => (reader.read_number()?, reader.read_number()?, 1, 1),
+        PortableImageFormat::Graymap => (reader.read_number()?, reader.read_number()?, reader.read_number()?, 1),
+        PortableImageFormat::Pixmap => (reader.read_number()?, reader.read_number()?, reader.read_number()?, 3),
+        PortableImageFormat::ArbitraryMap => read_pam_header(&mut reader)?
+    };
+    if width == 0 || height == 0 || width > i32::MAX as u32 || height > i32::MAX as u32 {
+        return Err(Error::InvalidHeader("Invalid Netpbm dimensions"));
+    }
+    if max_value == 0 || max_value > u16::MAX as u32 {
+        return Err(Error::InvalidHeader("Invalid Netpbm maximum value"));
+    }
+
+    // NOTE: Exactly one whitespace character separates the header from the raster.
+    if is_raw && !reader.read_u8()?.is_ascii_whitespace() {
+        return Err(Error::InvalidHeader("Missing whitespace after Netpbm header"));
+    }
+
+    let header = PortableImageHeader {
+        format,
+        is_raw,
+        width,
+        height,
+        max_value: max_value as u16,
+        depth
+    };
+    Ok((header, reader.position))
+}
+
+impl<'a> PortableImageDecoderPlugin<'a> {
+    pub fn sniff(bytes: &[u8]) -> bool {
+        bytes.len() >= 3 && bytes[0] == b'P' && (b'1'..=b'7').contains(&bytes[1]) && bytes[2].is_ascii_whitespace()
+    }
+
+    pub fn create(bytes: &'a [u8]) -> Result<Self, Error> {
+        let (header, data_offset) = decode_portable_image_header(bytes)?;
+        Ok(Self {
+            context: PortableImageLoadingContext {
+                header,
+                bytes,
+                data_offset,
+                bitmap: None
+            }
+        })
+    }
+}
+
+impl<'a> PortableImageLoadingContext<'a> {
+    fn read_sample(&self, reader: &mut PortableImageReader) -> Result<u8, Error> {
+        let value = if !self.header.is_raw {
+            reader.read_number()?
+        } else if self.header.max_value > 255 {
+            u16::from_be_bytes([reader.read_u8()?, reader.read_u8()?]) as u32
+        } else {
+            reader.read_u8()? as u32
+        };
+
+        // NOTE: Out of range samples are clamped instead of rejecting the whole image.
+        let max_value = self.header.max_value as u32;
+        Ok(((value.min(max_value) * 255 + max_value / 2) / max_value) as u8)
+    }
+
+    fn decode_bitmap_rows(&self, bitmap: &mut Bitmap, reader: &mut PortableImageReader) -> Result<(), Error> {
+        let (width, height) = (self.header.width as usize, self.header.height as usize);
+        for y in 0..height {
+            let row = if self.header.is_raw {
+                let Some(row) = reader.data.get(reader.position..reader.position + width.div_ceil(8)) else {
+                    return Err(Error::Truncated);
+                };
+                reader.position += row.len();
+                Some(row)
+            } else {
+                None
+            };
+
+            for x in 0..width {
+                let is_black = match row {
+                    Some(row) => row[x / 8] & (0x80 >> (x % 8)) != 0,
+                    None => reader.read_bit()?
+                };
+                let value = if is_black { 0 } else { 255 };
+                bitmap.set_pixel(x as i32, y as i32, Color::from_rgb(value, value, value).color);
+            }
+        }
+        Ok(())
+    }
+
+    fn decode_pixels(&self) -> Result<Bitmap, Error> {
+        let format = if self.header.has_alpha() { BitmapFormat::BGRA8888 } else { BitmapFormat::BGRx8888 };
+        let mut bitmap = Bitmap::new(format, self.size(), 1)?;
+        let mut reader = PortableImageReader { data: self.bytes, position: self.data_offset };
+
+        if self.header.format == PortableImageFormat::Bitmap {
+            self.decode_bitmap_rows(&mut bitmap, &mut reader)?;
+            return Ok(bitmap);
+        }
+
+        for y in 0..self.header.height as i32 {
+            for x in 0..self.header.width as i32 {
+                let color = match self.header.depth {
+                    1 => {
+                        let gray = self.read_sample(&mut reader)?;
+                        Color::from_rgb(gray, gray, gray)
+                    }
+                    2 => {
+                        let gray = self.read_sample(&mut reader)?;
+                        Color::from_rgba(gray, gray, gray, self.read_sample(&mut reader)?)
+                    }
+                    3 => Color::from_rgb(self.read_sample(&mut reader)?, self.read_sample(&mut reader)?, self.read_sample(&mut reader)?),
+                    _ => Color::from_rgba(self.read_sample(&mut reader)?, self.read_sample(&mut reader)?, self.read_sample(&mut reader)?, self.read_sample(&mut reader)?)
+                };
+                bitmap.set_pixel(x, y, color.color);
+            }
+        }
+        Ok(bitmap)
+    }
+
+    fn size(&self) -> IntSize {
+        IntSize {
+            width: self.header.width as i32,
+            height: self.header.height as i32
+        }
+    }
+}
+
+impl<'a> ImageDecoderPlugin for PortableImageDecoderPlugin<'a> {
+    fn size(&self) -> IntSize {
+        self.context.size()
+    }
+
+    fn frame_with_ideal_size(&mut self, index: usize, _: Option<IntSize>) -> Result<ImageFrameDescriptor, Error> {
+        if index != 0 {
+            return Err(Error::FrameIndexOutOfRange);
+        }
+
+        if let Some(bitmap) = &self.context.bitmap {
+            return Ok(ImageFrameDescriptor {
+                image: bitmap.clone(),
+                duration: 0
+            });
+        }
+
+        let bitmap = self.context.decode_pixels()?;
+        self.context.bitmap = Some(bitmap.clone());
+
+        Ok(ImageFrameDescriptor {
+            image: bitmap,
+            duration: 0
+        })
+    }
+
+    fn natural_frame_format(&self) -> NaturalFrameFormat {
+        if self.context.header.is_grayscale() {
+            NaturalFrameFormat::Grayscale
+        } else {
+            NaturalFrameFormat::RGB
+        }
+    }
+}
+
+/// # Safety
+///
+/// `bytes` must point to `size` readable bytes that outlive the returned decoder,
+/// and `out_decoder` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn portable_image_decoder_plugin_new(bytes: *const u8, size: usize, out_decoder: *mut *mut c_void) -> ErrorCode {
+    ffi_call(|| unsafe {
+        if bytes.is_null() {
+            return Err(Error::InvalidArgument("Bytes are null"));
+        }
+        if out_decoder.is_null() {
+            return Err(Error::InvalidArgument("Output pointer is null"));
+        }
+        let bytes = std::slice::from_raw_parts(bytes, size);
+        let decoder = PortableImageDecoderPlugin::create(bytes)?;
+        write_ffi_result(out_decoder, decoder_into_opaque(Box::new(decoder)))
+    })
+}
+
+/// # Safety
+///
+/// `opaque_decoder` must either be null or come from `portable_image_decoder_plugin_new` and not be freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn portable_image_decoder_plugin_free(opaque_decoder: *mut c_void) -> ErrorCode {
+    ffi_call(|| {
+        if !opaque_decoder.is_null() {
+            let decoder: Box<Box<dyn ImageDecoderPlugin>> = unsafe { Box::from_raw(opaque_decoder as *mut _) };
+            drop(decoder);
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ARGB;
+
+    const BLACK: ARGB = 0xFF000000;
+    const WHITE: ARGB = 0xFFFFFFFF;
+
+    fn decode(bytes: &[u8]) -> Result<Vec<ARGB>, Error> {
+        let mut decoder = PortableImageDecoderPlugin::create(bytes)?;
+        let image = decoder.frame(0)?.image;
+        Ok((0..image.size.height).flat_map(|y| (0..image.size.width).map(move |x| (x, y))).map(|(x, y)| image.get_pixel(x, y)).collect())
+    }
+
+    #[test]
+    fn decodes_plain_pbm_with_comments_and_packed_bits() {
+        assert_eq!(decode(b"P1\n# A comment\n3 2 # Another one\n1 0 1\n010\n"), Ok(vec![BLACK, WHITE, BLACK, WHITE, BLACK, WHITE]));
+    }
+
+    #[test]
+    fn decodes_raw_pbm_with_padded_rows() {
+        assert_eq!(decode(b"P4 10 2\n\xC0\x40\x01\x80"), Ok(vec![
+            BLACK, BLACK, WHITE, WHITE, WHITE, WHITE, WHITE, WHITE, WHITE, BLACK,
+            WHITE, WHITE, WHITE, WHITE, WHITE, WHITE, WHITE, BLACK, BLACK, WHITE
+        ]));
+    }
+
+    #[test]
+    fn scales_and_clamps_samples_to_maximum_value() {
+        assert_eq!(decode(b"P2\n4 1\n10\n0 5 10 12\n"), Ok(vec![BLACK, 0xFF808080, WHITE, WHITE]));
+        // NOTE: Maximum values above 255 use two bytes per sample in raw files.
+        assert_eq!(decode(b"P5 2 1 1000\n\x01\xF4\x03\xE8"), Ok(vec![0xFF808080, WHITE]));
+    }
+
+    #[test]
+    fn decodes_plain_and_raw_ppm() {
+        assert_eq!(decode(b"P3 2 1 255\n255 0 0  16 32 48\n"), Ok(vec![0xFFFF0000, 0xFF102030]));
+        assert_eq!(decode(b"P6 2 1 255\n\xFF\x00\x00\x10\x20\x30"), Ok(vec![0xFFFF0000, 0xFF102030]));
+    }
+
+    #[test]
+    fn decodes_pam_tuple_types() {
+        assert_eq!(decode(b"P7\nWIDTH 2\nHEIGHT 1\nDEPTH 2\nMAXVAL 255\nTUPLTYPE GRAYSCALE_ALPHA\nENDHDR\n\x40\x80\xFF\x00"), Ok(vec![0x80404040, 0x00FFFFFF]));
+        assert_eq!(decode(b"P7\nWIDTH 1\nHEIGHT 1\nDEPTH 4\nMAXVAL 255\nTUPLTYPE RGB_ALPHA\nENDHDR\n\x10\x20\x30\x80"), Ok(vec![0x80102030]));
+        // NOTE: Without a tuple type, the depth alone decides the layout.
+        assert_eq!(decode(b"P7\nWIDTH 1\nHEIGHT 1\nDEPTH 3\nMAXVAL 255\nENDHDR\n\x10\x20\x30"), Ok(vec![0xFF102030]));
+        assert_eq!(decode(b"P7\nWIDTH 1\nHEIGHT 1\nDEPTH 3\nMAXVAL 255\nTUPLTYPE GRAYSCALE\nENDHDR\n\x10\x20\x30").err(), Some(Error::Unsupported("Unsupported PAM depth")));
+    }
+
+    #[test]
+    fn reports_natural_frame_format() {
+        let format = |bytes: &[u8]| PortableImageDecoderPlugin::create(bytes).unwrap().natural_frame_format();
+        assert_eq!(format(b"P4 1 1\n\x00"), NaturalFrameFormat::Grayscale);
+        assert_eq!(format(b"P5 1 1 255\n\x00"), NaturalFrameFormat::Grayscale);
+        assert_eq!(format(b"P6 1 1 255\n\x00\x00\x00"), NaturalFrameFormat::RGB);
+    }
+
+    #[test]
+    fn rejects_malformed_files() {
+        assert_eq!(decode(b"P5 1 1 255").err(), Some(Error::Truncated));
+        assert_eq!(decode(b"P5 0 1 255\n").err(), Some(Error::InvalidHeader("Invalid Netpbm dimensions")));
+        assert_eq!(decode(b"P2 1 1 0\n0").err(), Some(Error::InvalidHeader("Invalid Netpbm maximum value")));
+        assert_eq!(decode(b"P6 2 1 255\n\xFF\x00\x00\x10").err(), Some(Error::Truncated));
+        assert_eq!(decode(b"P1 2 1\n0 2").err(), Some(Error::InvalidData("Expected a PBM bit")));
+    }
+}
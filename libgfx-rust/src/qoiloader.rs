@@ -0,0 +1,249 @@
+use std::ffi::c_void;
+use crate::imagedecoderplugin::{decoder_into_opaque, ImageDecoderPlugin, ImageFrameDescriptor};
+use crate::{Color, IntSize};
+use crate::bitmap::{Bitmap, BitmapFormat};
+use crate::error::{Error, ErrorCode, ffi_call, write_ffi_result};
+
+pub(crate) const QOI_MAGIC: &[u8; 4] = b"qoif";
+pub(crate) const QOI_HEADER_SIZE: usize = 14;
+pub(crate) const QOI_END_MARKER: [u8; 8] = [0, 0, 0, 0, 0, 0, 0, 1];
+
+pub(crate) const QOI_OP_INDEX: u8 = 0x00;
+pub(crate) const QOI_OP_DIFF: u8 = 0x40;
+pub(crate) const QOI_OP_LUMA: u8 = 0x80;
+pub(crate) const QOI_OP_RUN: u8 = 0xC0;
+pub(crate) const QOI_OP_RGB: u8 = 0xFE;
+pub(crate) const QOI_OP_RGBA: u8 = 0xFF;
+pub(crate) const QOI_MASK_2: u8 = 0xC0;
+
+#[derive (Debug, Copy, Clone)]
+struct QOIHeader {
+    width: u32,
+    height: u32,
+    channels: u8,
+    colorspace: u8
+}
+
+pub struct QOIImageDecoderPlugin<'a> {
+    context: QOILoadingContext<'a>
+}
+
+struct QOILoadingContext<'a> {
+    header: QOIHeader,
+    bytes: &'a [u8],
+    bitmap: Option<Bitmap>
+}
+
+pub(crate) fn qoi_color_hash(color: Color) -> usize {
+    (color.red() as usize * 3 + color.green() as usize * 5 + color.blue() as usize * 7 + color.alpha() as usize * 11) % 64
+}
+
+impl<'a> QOIImageDecoderPlugin<'a> {
+    pub fn sniff(bytes: &[u8]) -> bool {
+        bytes.starts_with(QOI_MAGIC)
+    }
+
+    pub fn create(bytes: &'a [u8]) -> Result<Self, Error> {
+        if bytes.len() < QOI_HEADER_SIZE {
+            return Err(Error::Truncated);
+        }
+        if !Self::sniff(bytes) {
+            return Err(Error::InvalidHeader("Invalid QOI magic"));
+        }
+
+        let header = QOIHeader {
+            width: u32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]),
+            height: u32::from_be_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]),
+            channels: bytes[12],
+            colorspace: bytes[13]
+        };
+        if header.width == 0 || header.height == 0 || header.width > i32::MAX as u32 || header.height > i32::MAX as u32 {
+            return Err(Error::InvalidHeader("Invalid QOI dimensions"));
+        }
+        if !matches!(header.channels, 3 | 4) {
+            return Err(Error::InvalidHeader("Invalid QOI channel count"));
+        }
+        if header.colorspace > 1 {
+            return Err(Error::InvalidHeader("Invalid QOI colorspace"));
+        }
+
+        Ok(Self {
+            context: QOILoadingContext {
+                header,
+                bytes,
+                bitmap: None
+            }
+        })
+    }
+}
+
+impl<'a> QOILoadingContext<'a> {
+    fn decode_pixels(&self) -> Result<Bitmap, Error> {
+        let (width, height) = (self.header.width as i32, self.header.height as i32);
+        let mut bitmap = Bitmap::new(BitmapFormat::BGRA8888, IntSize { width, height }, 1)?;
+
+        let data = &self.bytes[QOI_HEADER_SIZE..];
+        let mut position = 0;
+        let mut next_byte = || -> Result<u8, Error> {
+            let Some(byte) = data.get(position) else {
+                return Err(Error::Truncated);
+            };
+            position += 1;
+            Ok(*byte)
+        };
+
+        let mut index = [Color::new(); 64];
+        let mut color = Color::from_rgba(0, 0, 0, 255);
+        let mut run = 0;
+        for y in 0..height {
+            for x in 0..width {
+                if run > 0 {
+                    run -= 1;
+                } else {
+                    let tag = next_byte()?;
+                    match tag {
+                        QOI_OP_RGB => color = Color::from_rgba(next_byte()?, next_byte()?, next_byte()?, color.alpha()),
+                        QOI_OP_RGBA => color = Color::from_rgba(next_byte()?, next_byte()?, next_byte()?, next_byte()?),
+                        _ => match tag & QOI_MASK_2 {
+                            QOI_OP_INDEX => color = index[tag as usize],
+                            QOI_OP_DIFF => {
+                                let difference = |shift: u8| ((tag >> shift) & 0x03).wrapping_sub(2);
+                                color = Color::from_rgba(
+                                    color.red().wrapping_add(difference(4)),
+                                    color.green().wrapping_add(difference(2)),
+                                    color.blue().wrapping_add(difference(0)),
+                                    color.alpha()
+                                );
+                            }
+                            QOI_OP_LUMA => {
+                                let green_difference = (tag & 0x3F).wrapping_sub(32);
+                                let byte = next_byte()?;
+                                color = Color::from_rgba(
+                                    color.red().wrapping_add(green_difference).wrapping_add(byte >> 4).wrapping_sub(8),
+                                    color.green().wrapping_add(green_difference),
+                                    color.blue().wrapping_add(green_difference).wrapping_add(byte & 0x0F).wrapping_sub(8),
+                                    color.alpha()
+                                );
+                            }
+                            _ => run = tag & 0x3F
+                        }
+                    }
+                    index[qoi_color_hash(color)] = color;
+                }
+                bitmap.set_pixel(x, y, color.color);
+            }
+        }
+
+        // NOTE: We don't insist on the end marker, as the pixel count already tells us where the image ends.
+        Ok(bitmap)
+    }
+}
+
+impl<'a> ImageDecoderPlugin for QOIImageDecoderPlugin<'a> {
+    fn size(&self) -> IntSize {
+        IntSize {
+            width: self.context.header.width as i32,
+            height: self.context.header.height as i32
+        }
+    }
+
+    fn frame_with_ideal_size(&mut self, index: usize, _: Option<IntSize>) -> Result<ImageFrameDescriptor, Error> {
+        if index != 0 {
+            return Err(Error::FrameIndexOutOfRange);
+        }
+
+        if let Some(bitmap) = &self.context.bitmap {
+            return Ok(ImageFrameDescriptor {
+                image: bitmap.clone(),
+                duration: 0
+            });
+        }
+
+        let bitmap = self.context.decode_pixels()?;
+        self.context.bitmap = Some(bitmap.clone());
+
+        Ok(ImageFrameDescriptor {
+            image: bitmap,
+            duration: 0
+        })
+    }
+}
+
+/// # Safety
+///
+/// `bytes` must point to `size` readable bytes that outlive the returned decoder,
+/// and `out_decoder` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn qoi_image_decoder_plugin_new(bytes: *const u8, size: usize, out_decoder: *mut *mut c_void) -> ErrorCode {
+    ffi_call(|| unsafe {
+        if bytes.is_null() {
+            return Err(Error::InvalidArgument("Bytes are null"));
+        }
+        if out_decoder.is_null() {
+            return Err(Error::InvalidArgument("Output pointer is null"));
+        }
+        let bytes = std::slice::from_raw_parts(bytes, size);
+        let decoder = QOIImageDecoderPlugin::create(bytes)?;
+        write_ffi_result(out_decoder, decoder_into_opaque(Box::new(decoder)))
+    })
+}
+
+/// # Safety
+///
+/// `opaque_decoder` must either be null or come from `qoi_image_decoder_plugin_new` and not be freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn qoi_image_decoder_plugin_free(opaque_decoder: *mut c_void) -> ErrorCode {
+    ffi_call(|| {
+        if !opaque_decoder.is_null() {
+            let decoder: Box<Box<dyn ImageDecoderPlugin>> = unsafe { Box::from_raw(opaque_decoder as *mut _) };
+            drop(decoder);
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::ARGB;
+
+    // NOTE: 8x2 image from the reference encoder that uses every op: a run of two, a diff, a luma, an index,
+    //       an RGB and an RGBA op, followed by a run of nine pixels that crosses into the second row.
+    pub(crate) const ENCODED_IMAGE: [u8; 37] = [
+        0x71, 0x6F, 0x69, 0x66, 0x00, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x02, 0x04, 0x00, 0xC1, 0x79, 0xAA, 0x87, 0x31, 0xFE, 0xC8, 0x64, 0x32, 0xFF, 0xC8, 0x64, 0x32, 0x80, 0xC8, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01
+    ];
+
+    pub(crate) const PIXELS: [ARGB; 16] = [
+        0xFF000000, 0xFF000000, 0xFF0100FF, 0xFF0B0A08, 0xFF0100FF, 0xFFC86432, 0x80C86432, 0x80C86432,
+        0x80C86432, 0x80C86432, 0x80C86432, 0x80C86432, 0x80C86432, 0x80C86432, 0x80C86432, 0x80C86432
+    ];
+
+    #[test]
+    fn decodes_every_op() {
+        let mut decoder = QOIImageDecoderPlugin::create(&ENCODED_IMAGE).unwrap();
+        assert_eq!((decoder.size().width, decoder.size().height), (8, 2));
+        assert_eq!(decoder.frame(0).unwrap().image.pixels(), PIXELS);
+    }
+
+    #[test]
+    fn does_not_require_end_marker() {
+        let data = &ENCODED_IMAGE[..ENCODED_IMAGE.len() - QOI_END_MARKER.len()];
+        let mut decoder = QOIImageDecoderPlugin::create(data).unwrap();
+        assert_eq!(decoder.frame(0).unwrap().image.pixels(), PIXELS);
+    }
+
+    #[test]
+    fn rejects_truncated_pixel_data() {
+        // NOTE: Cut into the RGBA op, so the last ten pixels are missing.
+        let mut decoder = QOIImageDecoderPlugin::create(&ENCODED_IMAGE[..25]).unwrap();
+        assert_eq!(decoder.frame(0).err(), Some(Error::Truncated));
+    }
+
+    #[test]
+    fn rejects_invalid_header() {
+        let mut data = ENCODED_IMAGE;
+        data[12] = 2;
+        assert_eq!(QOIImageDecoderPlugin::create(&data).err(), Some(Error::InvalidHeader("Invalid QOI channel count")));
+        assert_eq!(QOIImageDecoderPlugin::create(&ENCODED_IMAGE[..QOI_HEADER_SIZE - 1]).err(), Some(Error::Truncated));
+    }
+}
@@ -0,0 +1,183 @@
+use bytes::BufMut;
+use crate::Color;
+use crate::bitmap::{Bitmap, BitmapFormat};
+use crate::error::{Error, ErrorCode, ffi_call, write_ffi_result};
+use crate::imagedecoderplugin::{FFIBitmap, FFIBuffer};
+use crate::qoiloader::{QOI_END_MARKER, QOI_HEADER_SIZE, QOI_MAGIC, QOI_OP_DIFF, QOI_OP_INDEX, QOI_OP_LUMA, QOI_OP_RGB, QOI_OP_RGBA, QOI_OP_RUN, qoi_color_hash};
+
+const QOI_MAX_RUN_LENGTH: u8 = 62;
+
+pub struct QOIWriter;
+
+impl QOIWriter {
+    pub fn encode(bitmap: &Bitmap) -> Result<Vec<u8>, Error> {
+        let width = bitmap.physical_width();
+        let height = bitmap.physical_height();
+        // NOTE: Bitmaps without an alpha channel are tagged as such, the pixel data is encoded the same either way.
+        let channels = if matches!(bitmap.format, BitmapFormat::BGRx8888) { 3 } else { 4 };
+
+        let mut data = Vec::new();
+        if data.try_reserve(QOI_HEADER_SIZE + width as usize * height as usize + QOI_END_MARKER.len()).is_err() {
+            return Err(Error::OutOfMemory);
+        }
+        data.put_slice(QOI_MAGIC);
+        data.put_u32(width as u32);
+        data.put_u32(height as u32);
+        data.put_u8(channels);
+        data.put_u8(0); // sRGB with linear alpha
+
+        let mut index = [Color::new(); 64];
+        let mut previous = Color::from_rgba(0, 0, 0, 255);
+        let mut run = 0u8;
+        for y in 0..height {
+            for x in 0..width {
+                let color = Color::from(bitmap.get_pixel(x, y));
+                if color.color == previous.color {
+                    run += 1;
+                    if run == QOI_MAX_RUN_LENGTH {
+                        data.put_u8(QOI_OP_RUN | (run - 1));
+                        run = 0;
+                    }
+                    continue;
+                }
+
+                if run > 0 {
+                    data.put_u8(QOI_OP_RUN | (run - 1));
+                    run = 0;
+                }
+                write_color(&mut data, &mut index, previous, color);
+                previous = color;
+            }
+        }
+        if run > 0 {
+            data.put_u8(QOI_OP_RUN | (run - 1));
+        }
+
+        data.put_slice(&QOI_END_MARKER);
+        Ok(data)
+    }
+}
+
+fn write_color(data: &mut Vec<u8>, index: &mut [Color; 64], previous: Color, color: Color) {
+    let hash = qoi_color_hash(color);
+    if index[hash].color == color.color {
+        data.put_u8(QOI_OP_INDEX | hash as u8);
+        return;
+    }
+    index[hash] = color;
+
+    if color.alpha() != previous.alpha() {
+        data.put_slice(&[QOI_OP_RGBA, color.red(), color.green(), color.blue(), color.alpha()]);
+        return;
+    }
+
+    let red_difference = color.red().wrapping_sub(previous.red()) as i8;
+    let green_difference = color.green().wrapping_sub(previous.green()) as i8;
+    let blue_difference = color.blue().wrapping_sub(previous.blue()) as i8;
+    let red_green_difference = red_difference.wrapping_sub(green_difference);
+    let blue_green_difference = blue_difference.wrapping_sub(green_difference);
+
+    let is_small_difference = |difference: i8| (-2..=1).contains(&difference);
+    if is_small_difference(red_difference) && is_small_difference(green_difference) && is_small_difference(blue_difference) {
+        data.put_u8(QOI_OP_DIFF | ((red_difference + 2) as u8) << 4 | ((green_difference + 2) as u8) << 2 | (blue_difference + 2) as u8);
+    } else if (-32..=31).contains(&green_difference) && (-8..=7).contains(&red_green_difference) && (-8..=7).contains(&blue_green_difference) {
+        data.put_u8(QOI_OP_LUMA | (green_difference + 32) as u8);
+        data.put_u8(((red_green_difference + 8) as u8) << 4 | (blue_green_difference + 8) as u8);
+    } else {
+        data.put_slice(&[QOI_OP_RGB, color.red(), color.green(), color.blue()]);
+    }
+}
+
+/// Encodes `bitmap` as QOI. The resulting buffer has to be released with `ffi_buffer_free`.
+///
+/// # Safety
+///
+/// `bitmap` must point to a valid `FFIBitmap` with readable pixel data, and `out_buffer` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn qoi_writer_encode(bitmap: *const FFIBitmap, out_buffer: *mut FFIBuffer) -> ErrorCode {
+    ffi_call(|| unsafe {
+        let Some(bitmap) = bitmap.as_ref() else {
+            return Err(Error::InvalidArgument("Bitmap is null"));
+        };
+        if out_buffer.is_null() {
+            return Err(Error::InvalidArgument("Output pointer is null"));
+        }
+        let data = QOIWriter::encode(&bitmap.to_bitmap()?)?;
+        write_ffi_result(out_buffer, FFIBuffer::from(data))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ARGB, IntSize};
+    use crate::imagedecoderplugin::{ffi_buffer_free, ImageDecoderPlugin};
+    use crate::qoiloader::QOIImageDecoderPlugin;
+    use crate::qoiloader::tests::{ENCODED_IMAGE, PIXELS};
+
+    fn test_bitmap(format: BitmapFormat, width: i32, pixels: &[ARGB]) -> Bitmap {
+        let height = pixels.len() as i32 / width;
+        let mut bitmap = Bitmap::new(format, IntSize { width, height }, 1).unwrap();
+        for (index, pixel) in pixels.iter().enumerate() {
+            bitmap.set_pixel(index as i32 % width, index as i32 / width, *pixel);
+        }
+        bitmap
+    }
+
+    #[test]
+    fn encodes_every_op_like_the_reference_encoder() {
+        assert_eq!(QOIWriter::encode(&test_bitmap(BitmapFormat::RGBA8888, 8, &PIXELS)).unwrap(), ENCODED_IMAGE);
+        assert_eq!(QOIWriter::encode(&test_bitmap(BitmapFormat::BGRA8888, 8, &PIXELS)).unwrap(), ENCODED_IMAGE);
+    }
+
+    #[test]
+    fn round_trips_through_the_decoder() {
+        // NOTE: Scattered colors with occasional alpha changes, followed by a run longer than a single run op holds.
+        let pixels: Vec<ARGB> = (0..64u32).map(|index| {
+            let (red, green, blue) = (index * 37 % 256, index * index % 256, (index / 4) * 9 % 256);
+            let alpha = if index % 7 == 0 { 0x40 } else { 0xFF };
+            alpha << 24 | red << 16 | green << 8 | blue
+        }).chain(std::iter::repeat_n(0x12345678, 70)).collect();
+
+        for format in [BitmapFormat::RGBA8888, BitmapFormat::BGRA8888] {
+            let encoded = QOIWriter::encode(&test_bitmap(format, 2, &pixels)).unwrap();
+            assert_eq!(encoded[12], 4);
+            let mut decoder = QOIImageDecoderPlugin::create(&encoded).unwrap();
+            let image = decoder.frame(0).unwrap().image;
+            let decoded: Vec<ARGB> = (0..image.size.height).flat_map(|y| (0..2).map(move |x| (x, y))).map(|(x, y)| image.get_pixel(x, y)).collect();
+            assert_eq!(decoded, pixels);
+        }
+    }
+
+    #[test]
+    fn tags_bitmaps_without_alpha_as_rgb() {
+        let encoded = QOIWriter::encode(&test_bitmap(BitmapFormat::BGRx8888, 2, &[0xFF102030, 0xFF405060])).unwrap();
+        assert_eq!(encoded[12], 3);
+    }
+
+    #[test]
+    fn splits_runs_longer_than_62_pixels() {
+        let encoded = QOIWriter::encode(&test_bitmap(BitmapFormat::BGRA8888, 100, &[0xFF000000; 100])).unwrap();
+        assert_eq!(encoded[QOI_HEADER_SIZE..encoded.len() - QOI_END_MARKER.len()], [QOI_OP_RUN | 61, QOI_OP_RUN | 37]);
+    }
+
+    #[test]
+    fn encodes_across_the_ffi() {
+        let bitmap = test_bitmap(BitmapFormat::RGBA8888, 8, &PIXELS);
+        let ffi_bitmap = FFIBitmap {
+            format: bitmap.format,
+            size: bitmap.size,
+            scale: bitmap.scale,
+            pitch: bitmap.pitch,
+            data: FFIBuffer { data: bitmap.data.as_ptr() as *mut u8, size: bitmap.data.len(), capacity: bitmap.data.len() }
+        };
+        unsafe {
+            assert_eq!(qoi_writer_encode(&ffi_bitmap, std::ptr::null_mut()), ErrorCode::InvalidArgument);
+            let mut buffer = std::mem::MaybeUninit::uninit();
+            assert_eq!(qoi_writer_encode(&ffi_bitmap, buffer.as_mut_ptr()), ErrorCode::Success);
+            let buffer = buffer.assume_init();
+            assert_eq!(std::slice::from_raw_parts(buffer.data, buffer.size), ENCODED_IMAGE);
+            assert_eq!(ffi_buffer_free(buffer), ErrorCode::Success);
+        }
+    }
+}
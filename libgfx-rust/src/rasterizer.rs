@@ -0,0 +1,269 @@
+use crate::{Color, FloatPoint, IntSize};
+use crate::bitmap::Bitmap;
+
+// NOTE: Each pixel row is sampled at this many heights, horizontal coverage is computed exactly.
+const SUBSAMPLES_PER_ROW: usize = 4;
+
+#[derive (Debug, Copy, Clone, PartialEq)]
+pub(crate) enum WindingRule {
+    NonZero,
+    EvenOdd
+}
+
+#[derive (Debug, Copy, Clone)]
+struct Edge {
+    top: FloatPoint,
+    bottom: FloatPoint,
+    direction: i32
+}
+
+/// An anti-aliasing scanline rasterizer for polygons in device coordinates.
+pub(crate) struct Rasterizer {
+    size: IntSize,
+    edges: Vec<Edge>
+}
+
+impl Rasterizer {
+    pub(crate) fn new(size: IntSize) -> Self {
+        Self { size, edges: Vec::new() }
+    }
+
+    /// Adds a polygon, which is implicitly closed.
+    pub(crate) fn add_polygon(&mut self, points: &[FloatPoint]) {
+        for (index, &from) in points.iter().enumerate() {
+            let to = points[(index + 1) % points.len()];
+            if from.y == to.y || !from.x.is_finite() || !from.y.is_finite() || !to.x.is_finite() || !to.y.is_finite() {
+                continue;
+            }
+            if from.y < to.y {
+                self.edges.push(Edge { top: from, bottom: to, direction: 1 });
+            } else {
+                self.edges.push(Edge { top: to, bottom: from, direction: -1 });
+            }
+        }
+    }
+
+    /// Adds the outline of a polyline with round joins and caps. Needs to be filled with `WindingRule::NonZero`.
+    pub(crate) fn add_stroke(&mut self, points: &[FloatPoint], is_closed: bool, width: f32) {
+        let half_width = width / 2.0;
+        if half_width <= 0.0 || points.is_empty() {
+            return;
+        }
+
+        let segment_count = if is_closed { points.len() } else { points.len() - 1 };
+        for index in 0..segment_count {
+            let (from, to) = (points[index], points[(index + 1) % points.len()]);
+            let length = from.distance_to(to);
+            if length == 0.0 {
+                continue;
+            }
+            let normal_x = -(to.y - from.y) / length * half_width;
+            let normal_y = (to.x - from.x) / length * half_width;
+            self.add_polygon(&[
+                FloatPoint { x: from.x + normal_x, y: from.y + normal_y },
+                FloatPoint { x: to.x + normal_x, y: to.y + normal_y },
+                FloatPoint { x: to.x - normal_x, y: to.y - normal_y },
+                FloatPoint { x: from.x - normal_x, y: from.y - normal_y }
+            ]);
+        }
+
+        // NOTE: The circles have to wind the same way as the quads above, so that overlaps don't cancel out.
+        let circle_point_count = (half_width * std::f32::consts::TAU).ceil().clamp(8.0, 128.0) as usize;
+        for &center in points {
+            let circle: Vec<FloatPoint> = (0..circle_point_count).map(|index| {
+                let (sin, cos) = (-(index as f32) * std::f32::consts::TAU / circle_point_count as f32).sin_cos();
+                FloatPoint { x: center.x + cos * half_width, y: center.y + sin * half_width }
+            }).collect();
+            self.add_polygon(&circle);
+        }
+    }
+
+    /// Composites the covered area onto `bitmap`, asking `color_at` for the color at each pixel center.
+    pub(crate) fn fill(&self, bitmap: &mut Bitmap, winding_rule: WindingRule, color_at: &dyn Fn(FloatPoint) -> Color) {
+        let width = self.size.width as usize;
+        let mut coverage = vec![0f32; width];
+        let mut crossings: Vec<(f32, i32)> = Vec::new();
+        let sample_weight = 1.0 / SUBSAMPLES_PER_ROW as f32;
+
+        let top = self.edges.iter().map(|edge| edge.top.y).fold(f32::INFINITY, f32::min).floor().max(0.0);
+        let bottom = self.edges.iter().map(|edge| edge.bottom.y).fold(f32::NEG_INFINITY, f32::max).ceil().min(self.size.height as f32);
+        if top >= bottom {
+            return;
+        }
+
+        for y in top as i32..bottom as i32 {
+            coverage.fill(0.0);
+            for subsample in 0..SUBSAMPLES_PER_ROW {
+                let sample_y = y as f32 + (subsample as f32 + 0.5) * sample_weight;
+                crossings.clear();
+                for edge in &self.edges {
+                    if edge.top.y <= sample_y && sample_y < edge.bottom.y {
+                        let t = (sample_y - edge.top.y) / (edge.bottom.y - edge.top.y);
+                        crossings.push((edge.top.x + (edge.bottom.x - edge.top.x) * t, edge.direction));
+                    }
+                }
+                crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+                let mut winding = 0;
+                for pair in crossings.windows(2) {
+                    winding += pair[0].1;
+                    let is_inside = match winding_rule {
+                        WindingRule::NonZero => winding != 0,
+                        WindingRule::EvenOdd => winding % 2 != 0
+                    };
+                    if is_inside {
+                        add_span(&mut coverage, pair[0].0, pair[1].0, sample_weight);
+                    }
+                }
+            }
+
+            for (x, &pixel_coverage) in coverage.iter().enumerate() {
+                if pixel_coverage <= 0.0 {
+                    continue;
+                }
+                let color = color_at(FloatPoint { x: x as f32 + 0.5, y: y as f32 + 0.5 });
+                let alpha = (color.alpha() as f32 * pixel_coverage.min(1.0)).round() as u8;
+                let destination = Color::from(bitmap.get_pixel(x as i32, y));
+                bitmap.set_pixel(x as i32, y, destination.blend(color.with_alpha(alpha)).color);
+            }
+        }
+    }
+}
+
+fn add_span(coverage: &mut [f32], from: f32, to: f32, weight: f32) {
+    let from = from.clamp(0.0, coverage.len() as f32);
+    let to = to.clamp(0.0, coverage.len() as f32);
+    if from >= to {
+        return;
+    }
+
+    let first_pixel = from as usize;
+    let last_pixel = to as usize;
+    if first_pixel == last_pixel {
+        coverage[first_pixel] += (to - from) * weight;
+        return;
+    }
+    coverage[first_pixel] += (first_pixel as f32 + 1.0 - from) * weight;
+    for pixel in &mut coverage[first_pixel + 1..last_pixel] {
+        *pixel += weight;
+    }
+    if last_pixel < coverage.len() {
+        coverage[last_pixel] += (to - last_pixel as f32) * weight;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitmap::BitmapFormat;
+
+    fn square(left: f32, top: f32, size: f32) -> [FloatPoint; 4] {
+        [
+            FloatPoint { x: left, y: top },
+            FloatPoint { x: left + size, y: top },
+            FloatPoint { x: left + size, y: top + size },
+            FloatPoint { x: left, y: top + size }
+        ]
+    }
+
+    /// Fills with opaque white and returns the resulting alpha of each pixel, in rows.
+    fn coverage(rasterizer: &Rasterizer, winding_rule: WindingRule) -> Vec<Vec<u8>> {
+        let mut bitmap = Bitmap::new(BitmapFormat::BGRA8888, rasterizer.size, 1).unwrap();
+        rasterizer.fill(&mut bitmap, winding_rule, &|_| Color::from(0xFFFFFFFF));
+        (0..rasterizer.size.height).map(|y| (0..rasterizer.size.width).map(|x| Color::from(bitmap.get_pixel(x, y)).alpha()).collect()).collect()
+    }
+
+    #[test]
+    fn fills_pixel_aligned_polygons_exactly() {
+        let mut rasterizer = Rasterizer::new(IntSize { width: 4, height: 4 });
+        rasterizer.add_polygon(&square(1.0, 1.0, 2.0));
+        assert_eq!(coverage(&rasterizer, WindingRule::NonZero), [[0, 0, 0, 0], [0, 255, 255, 0], [0, 255, 255, 0], [0, 0, 0, 0]]);
+    }
+
+    #[test]
+    fn covers_partial_pixels_by_area() {
+        let mut rasterizer = Rasterizer::new(IntSize { width: 3, height: 2 });
+        rasterizer.add_polygon(&[
+            FloatPoint { x: 0.5, y: 0.0 },
+            FloatPoint { x: 2.25, y: 0.0 },
+            FloatPoint { x: 2.25, y: 0.5 },
+            FloatPoint { x: 0.5, y: 0.5 }
+        ]);
+        assert_eq!(coverage(&rasterizer, WindingRule::NonZero), [[64, 128, 32], [0, 0, 0]]);
+    }
+
+    #[test]
+    fn applies_winding_rules() {
+        // NOTE: Both squares wind the same way, so the inner one is a hole only with the even-odd rule.
+        let mut rasterizer = Rasterizer::new(IntSize { width: 4, height: 4 });
+        rasterizer.add_polygon(&square(0.0, 0.0, 4.0));
+        rasterizer.add_polygon(&square(1.0, 1.0, 2.0));
+        assert_eq!(coverage(&rasterizer, WindingRule::NonZero), [[255; 4]; 4]);
+        assert_eq!(coverage(&rasterizer, WindingRule::EvenOdd), [[255; 4], [255, 0, 0, 255], [255, 0, 0, 255], [255; 4]]);
+
+        // NOTE: Winding the inner square the other way cancels it out with both rules.
+        let mut rasterizer = Rasterizer::new(IntSize { width: 4, height: 4 });
+        rasterizer.add_polygon(&square(0.0, 0.0, 4.0));
+        let mut inner = square(1.0, 1.0, 2.0);
+        inner.reverse();
+        rasterizer.add_polygon(&inner);
+        assert_eq!(coverage(&rasterizer, WindingRule::NonZero), [[255; 4], [255, 0, 0, 255], [255, 0, 0, 255], [255; 4]]);
+    }
+
+    #[test]
+    fn clips_to_bitmap() {
+        let mut rasterizer = Rasterizer::new(IntSize { width: 2, height: 2 });
+        rasterizer.add_polygon(&square(-5.0, 1.0, 6.0));
+        rasterizer.add_polygon(&[FloatPoint { x: f32::NAN, y: 0.0 }, FloatPoint { x: 1.0, y: 1.0 }]);
+        assert_eq!(coverage(&rasterizer, WindingRule::NonZero), [[0, 0], [255, 0]]);
+    }
+
+    #[test]
+    fn strokes_with_round_joins_and_caps() {
+        let mut rasterizer = Rasterizer::new(IntSize { width: 9, height: 9 });
+        rasterizer.add_stroke(&[FloatPoint { x: 2.0, y: 2.0 }, FloatPoint { x: 6.0, y: 2.0 }, FloatPoint { x: 6.0, y: 6.0 }], false, 2.0);
+        let coverage = coverage(&rasterizer, WindingRule::NonZero);
+
+        // NOTE: Where the segments and the join overlap, they must add up instead of cancelling out.
+        for (x, y) in [(2, 1), (4, 2), (5, 1), (5, 2), (6, 2), (6, 4), (5, 5)] {
+            assert_eq!(coverage[y][x], 255, "pixel ({x}, {y})");
+        }
+        for (x, y) in [(3, 0), (4, 3), (3, 5), (7, 3), (8, 2)] {
+            assert_eq!(coverage[y][x], 0, "pixel ({x}, {y})");
+        }
+        // NOTE: The caps and the outside of the join are quarter circles.
+        for (x, y) in [(1, 1), (1, 2), (5, 6), (6, 6), (6, 1)] {
+            assert!((160..=240).contains(&coverage[y][x]), "pixel ({x}, {y}) has coverage {}", coverage[y][x]);
+        }
+    }
+
+    #[test]
+    fn strokes_closed_polylines_back_to_the_start() {
+        let points = square(1.0, 1.0, 4.0);
+        let mut rasterizer = Rasterizer::new(IntSize { width: 6, height: 6 });
+        rasterizer.add_stroke(&points, true, 1.0);
+        assert_eq!(coverage(&rasterizer, WindingRule::NonZero)[3][0..2], [128, 128]);
+
+        let mut rasterizer = Rasterizer::new(IntSize { width: 6, height: 6 });
+        rasterizer.add_stroke(&points, false, 1.0);
+        assert_eq!(coverage(&rasterizer, WindingRule::NonZero)[3][0..2], [0, 0]);
+
+        let mut rasterizer = Rasterizer::new(IntSize { width: 6, height: 6 });
+        rasterizer.add_stroke(&points, true, 0.0);
+        assert!(rasterizer.edges.is_empty());
+    }
+
+    #[test]
+    fn blends_onto_bitmap() {
+        let mut bitmap = Bitmap::new(BitmapFormat::BGRA8888, IntSize { width: 2, height: 1 }, 1).unwrap();
+        bitmap.set_pixel(0, 0, 0xFFFF0000);
+        bitmap.set_pixel(1, 0, 0xFFFF0000);
+        let mut rasterizer = Rasterizer::new(IntSize { width: 2, height: 1 });
+        rasterizer.add_polygon(&square(0.0, 0.0, 1.0));
+        rasterizer.fill(&mut bitmap, WindingRule::NonZero, &|point| {
+            assert_eq!(point, FloatPoint { x: 0.5, y: 0.5 });
+            Color::from(0x800000FF)
+        });
+        assert_eq!([bitmap.get_pixel(0, 0), bitmap.get_pixel(1, 0)], [0xFF7F0080, 0xFFFF0000]);
+    }
+}
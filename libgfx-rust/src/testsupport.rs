@@ -0,0 +1,14 @@
+use crate::Color;
+use crate::bitmap::Bitmap;
+
+/// Checks the size of `image`, and that every pixel has the [red, green, blue, alpha] value `expected` returns for it.
+pub(crate) fn assert_pixels(image: &Bitmap, width: i32, height: i32, expected: impl Fn(u32, u32) -> [u8; 4]) {
+    assert_eq!((image.size.width, image.size.height), (width, height));
+    for y in 0..height {
+        for x in 0..width {
+            let color = Color::from(image.get_pixel(x, y));
+            let actual = [color.red(), color.green(), color.blue(), color.alpha()];
+            assert_eq!(actual, expected(x as u32, y as u32), "pixel ({x}, {y})");
+        }
+    }
+}
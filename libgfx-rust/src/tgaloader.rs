@@ -1,12 +1,14 @@
 #![allow(dead_code)]
 
+use std::collections::BTreeMap;
 use std::ffi::c_void;
 use std::io::Read;
 use bytes::buf::Buf;
 use static_assertions::const_assert;
-use crate::imagedecoderplugin::{ImageDecoderPlugin, ImageFrameDescriptor};
-use crate::{Color, IntSize};
+use crate::imagedecoderplugin::{decoder_into_opaque, ImageDecoderPlugin, ImageFrameDescriptor, Metadata, NaturalFrameFormat};
+use crate::{ARGB, Color, IntSize};
 use crate::bitmap::{Bitmap, BitmapFormat};
+use crate::error::{Error, ErrorCode, ffi_call, write_ffi_result};
 
 #[derive (Debug, PartialEq, Copy, Clone)]
 enum TGADataType {
@@ -38,6 +40,53 @@ pub struct TGAHeader {
 }
 const_assert!(std::mem::size_of::<TGAHeader>() == 18);
 
+const TGA_FOOTER_SIZE: usize = 26;
+const TGA_FOOTER_SIGNATURE: &[u8; 18] = b"TRUEVISION-XFILE.\0";
+const TGA_EXTENSION_AREA_SIZE: usize = 495;
+
+#[derive (Debug, PartialEq, Copy, Clone)]
+pub enum TGAAlphaAttributeType {
+    NoAlpha = 0,
+    UndefinedIgnore = 1,
+    UndefinedRetain = 2,
+    Alpha = 3,
+    PremultipliedAlpha = 4
+}
+
+#[derive (Debug, PartialEq, Copy, Clone)]
+pub struct TGATimestamp {
+    pub month: u16,
+    pub day: u16,
+    pub year: u16,
+    pub hour: u16,
+    pub minute: u16,
+    pub second: u16
+}
+
+#[derive (Debug, Clone)]
+pub struct TGAExtensionArea {
+    pub author_name: String,
+    pub author_comments: Vec<String>,
+    pub timestamp: Option<TGATimestamp>,
+    pub job_name: String,
+    pub job_time: (u16, u16, u16),
+    pub software_id: String,
+    pub software_version: Option<(u16, char)>,
+    pub key_color: ARGB,
+    pub pixel_aspect_ratio: Option<(u16, u16)>,
+    pub gamma: Option<(u16, u16)>,
+    pub color_correction_offset: u32,
+    pub postage_stamp_offset: u32,
+    pub scan_line_offset: u32,
+    pub alpha_attribute_type: Option<TGAAlphaAttributeType>
+}
+
+#[derive (Debug, Clone)]
+pub struct TGADeveloperField<'a> {
+    pub tag: u16,
+    pub data: &'a [u8]
+}
+
 struct TGAPixelPacketHeader {
     raw: bool,
     pixels_count: u8,
@@ -51,19 +100,60 @@ struct TGALoadingContext<'a> {
     header: TGAHeader,
     reader: bytes::buf::Reader<&'a [u8]>,
     bytes: &'a[u8],
+    image_id: &'a [u8],
+    color_map: Vec<Color>,
+    extension_area: Option<TGAExtensionArea>,
+    developer_fields: Vec<TGADeveloperField<'a>>,
     bitmap: Option<Bitmap>
 }
 
 impl<'a> TGAImageDecoderPlugin<'a> {
-    pub fn create(bytes: &'a[u8]) -> Result<Self, String> {
-        if !Self::validate_before_create(bytes) {
-            return Err("Invalid TGA file".to_string());
-        }
+    pub fn create(bytes: &'a[u8]) -> Result<Self, Error> {
+        Self::validate_before_create(bytes)?;
         let mut decoder = Self::new(bytes);
         decoder.decode_tga_header()?;
+        decoder.decode_tga_footer();
         Ok(decoder)
     }
 
+    /// Only TGA 2.0 files can be recognized, as the original format has no magic number.
+    pub fn sniff(bytes: &[u8]) -> bool {
+        bytes.ends_with(TGA_FOOTER_SIGNATURE) && Self::validate_before_create(bytes).is_ok()
+    }
+
+    pub fn image_id(&self) -> &'a [u8] {
+        self.context.image_id
+    }
+
+    pub fn extension_area(&self) -> Option<&TGAExtensionArea> {
+        self.context.extension_area.as_ref()
+    }
+
+    pub fn developer_fields(&self) -> &[TGADeveloperField<'a>] {
+        &self.context.developer_fields
+    }
+
+    pub fn postage_stamp(&mut self) -> Result<Option<Bitmap>, Error> {
+        let postage_stamp_offset = match &self.context.extension_area {
+            Some(extension_area) if extension_area.postage_stamp_offset != 0 => extension_area.postage_stamp_offset as usize,
+            _ => return Ok(None)
+        };
+        let Some(&[width, height]) = self.context.bytes.get(postage_stamp_offset..postage_stamp_offset + 2) else {
+            return Err(Error::InvalidData("Invalid TGA postage stamp offset"));
+        };
+        if width == 0 || height == 0 {
+            return Ok(None);
+        }
+
+        // NOTE: The postage stamp uses the same pixel format as the image, but is never compressed.
+        let mut bitmap = self.context.create_bitmap(IntSize { width: width as i32, height: height as i32 })?;
+        let image_reader = std::mem::replace(&mut self.context.reader, Buf::reader(&self.context.bytes[postage_stamp_offset + 2..]));
+        let result = self.context.decode_uncompressed_pixels(&mut bitmap);
+        self.context.reader = image_reader;
+        result?;
+        Ok(Some(bitmap))
+    }
+
     fn drop(&mut self) {
         panic!("TGAImageDecoderPlugin: drop");
     }
@@ -87,62 +177,384 @@ impl<'a> TGAImageDecoderPlugin<'a> {
                 },
                 reader: Buf::reader(bytes),
                 bytes,
+                image_id: &[],
+                color_map: Vec::new(),
+                extension_area: None,
+                developer_fields: Vec::new(),
                 bitmap: None
             }
         }
     }
 
-    fn validate_before_create(bytes: &[u8]) -> bool {
+    pub fn validate_before_create(bytes: &[u8]) -> Result<(), Error> {
         let mut header_data = [0u8; std::mem::size_of::<TGAHeader>()];
         let mut reader = Buf::reader(bytes);
-        if reader.read_exact(&mut header_data).is_err() {
-            return false;
-        }
-        let header: TGAHeader = unsafe { std::mem::transmute(header_data) };
-        if Self::ensure_header_validity(&header, bytes.len()).is_err() {
-            return false;
+        reader.read_exact(&mut header_data)?;
+        let header = Self::header_from_bytes(header_data)?;
+        Self::ensure_header_validity(&header, bytes.len())
+    }
+
+    fn header_from_bytes(header_data: [u8; 18]) -> Result<TGAHeader, Error> {
+        // NOTE: Reject unknown data types before transmuting, as they are not valid TGADataType values.
+        if !matches!(header_data[2], 0 | 1 | 2 | 3 | 9 | 10 | 11 | 32 | 33) {
+            return Err(Error::InvalidHeader("Invalid TGA data type"));
         }
-        true
+        Ok(unsafe { std::mem::transmute::<[u8; 18], TGAHeader>(header_data) })
     }
 
-    fn ensure_header_validity(header: &TGAHeader, whole_image_size: usize) -> Result<(), String> {
-        if (header.bits_per_pixel % 8) != 0 || header.bits_per_pixel < 8 || header.bits_per_pixel > 32 {
-            return Err("Invalid bits per pixel".to_string());
+    fn ensure_header_validity(header: &TGAHeader, whole_image_size: usize) -> Result<(), Error> {
+        if !matches!(header.bits_per_pixel, 8 | 15 | 16 | 24 | 32) {
+            return Err(Error::InvalidHeader("Invalid TGA bits per pixel"));
         }
         let bytes_remaining = whole_image_size - std::mem::size_of::<TGAHeader>();
-        if header.data_type_code == TGADataType::UncompressedRGB && (bytes_remaining < header.width as usize *  header.height as usize * header.bits_per_pixel as usize / 8) {
-            return Err("Invalid image size".to_string());
+        let bytes_per_pixel = (header.bits_per_pixel as usize).div_ceil(8);
+        if header.data_type_code == TGADataType::UncompressedRGB && (bytes_remaining < header.width as usize *  header.height as usize * bytes_per_pixel) {
+            return Err(Error::Truncated);
         }
         Ok(())
     }
 
-    fn decode_tga_header(&mut self) -> Result<(), String> {
+    fn decode_tga_header(&mut self) -> Result<(), Error> {
         let mut header_data = [0u8; std::mem::size_of::<TGAHeader>()];
-        if let Err(e) = self.context.reader.read_exact(&mut header_data)  {
-            return Err(e.to_string());
-        }
-        self.context.header = unsafe { std::mem::transmute(header_data) };
+        self.context.reader.read_exact(&mut header_data)?;
+        self.context.header = Self::header_from_bytes(header_data)?;
         Self::ensure_header_validity(&self.context.header, self.context.bytes.len())?;
+
+        // NOTE: The image ID field sits between the header and the color map.
+        let image_id_length = self.context.header.id_length as usize;
+        let image_id_offset = std::mem::size_of::<TGAHeader>();
+        let Some(image_id) = self.context.bytes.get(image_id_offset..image_id_offset + image_id_length) else {
+            return Err(Error::Truncated);
+        };
+        self.context.image_id = image_id;
+        self.context.reader.get_mut().advance(image_id_length);
+
+        if self.context.header.color_map_type == 1 {
+            self.decode_tga_color_map()?;
+        }
+        Ok(())
+    }
+
+    fn decode_tga_color_map(&mut self) -> Result<(), Error> {
+        let color_map_length = self.context.header.color_map_length;
+        let color_map_depth = self.context.header.color_map_depth;
+        let attribute_bits = self.context.attribute_bits();
+        let entry_size = match color_map_depth {
+            15 | 16 => 2,
+            24 => 3,
+            32 => 4,
+            _ => return Err(Error::InvalidHeader("Invalid TGA color map depth"))
+        };
+        if color_map_length < 0 {
+            return Err(Error::InvalidHeader("Invalid TGA color map length"));
+        }
+
+        let mut color_map = Vec::with_capacity(color_map_length as usize);
+        for _ in 0..color_map_length {
+            let color = match entry_size {
+                2 => read_a1r5g5b5_pixel_from_reader(&mut self.context.reader, color_map_depth == 16 && attribute_bits > 0)?,
+                _ => read_pixel_from_reader(&mut self.context.reader, entry_size)?
+            };
+            color_map.push(color);
+        }
+        self.context.color_map = color_map;
         Ok(())
     }
+
+    fn decode_tga_footer(&mut self) {
+        let bytes = self.context.bytes;
+        // NOTE: Only TGA 2.0 files have a footer, anything else is an original TGA file.
+        if bytes.len() < std::mem::size_of::<TGAHeader>() + TGA_FOOTER_SIZE || !bytes.ends_with(TGA_FOOTER_SIGNATURE) {
+            return;
+        }
+        let mut footer = &bytes[bytes.len() - TGA_FOOTER_SIZE..];
+        let extension_area_offset = footer.get_u32_le() as usize;
+        let developer_directory_offset = footer.get_u32_le() as usize;
+
+        // NOTE: Both areas only hold optional metadata, so a broken one is treated as missing rather than making the image unreadable.
+        if extension_area_offset != 0 {
+            self.context.extension_area = Self::decode_tga_extension_area(bytes, extension_area_offset).ok();
+        }
+        if developer_directory_offset != 0 {
+            self.context.developer_fields = Self::decode_tga_developer_directory(bytes, developer_directory_offset).unwrap_or_default();
+        }
+    }
+
+    fn decode_tga_extension_area(bytes: &[u8], offset: usize) -> Result<TGAExtensionArea, Error> {
+        let Some(mut area) = bytes.get(offset..).filter(|area| area.len() >= TGA_EXTENSION_AREA_SIZE) else {
+            return Err(Error::InvalidData("Invalid TGA extension area offset"));
+        };
+        let extension_size = area.get_u16_le() as usize;
+        if extension_size < TGA_EXTENSION_AREA_SIZE {
+            return Err(Error::InvalidData("Invalid TGA extension area size"));
+        }
+
+        let author_name = read_tga_string(&mut area, 41);
+        let author_comments = (0..4).map(|_| read_tga_string(&mut area, 81)).collect();
+        let timestamp = TGATimestamp {
+            month: area.get_u16_le(),
+            day: area.get_u16_le(),
+            year: area.get_u16_le(),
+            hour: area.get_u16_le(),
+            minute: area.get_u16_le(),
+            second: area.get_u16_le()
+        };
+        let job_name = read_tga_string(&mut area, 41);
+        let job_time = (area.get_u16_le(), area.get_u16_le(), area.get_u16_le());
+        let software_id = read_tga_string(&mut area, 41);
+        let software_version_number = area.get_u16_le();
+        let software_version_letter = match area.get_u8() {
+            0 => ' ',
+            letter => letter as char
+        };
+        let key_color = area.get_u32_le();
+        let pixel_aspect_ratio = (area.get_u16_le(), area.get_u16_le());
+        let gamma = (area.get_u16_le(), area.get_u16_le());
+        let color_correction_offset = area.get_u32_le();
+        let postage_stamp_offset = area.get_u32_le();
+        let scan_line_offset = area.get_u32_le();
+        let alpha_attribute_type = match area.get_u8() {
+            0 => Some(TGAAlphaAttributeType::NoAlpha),
+            1 => Some(TGAAlphaAttributeType::UndefinedIgnore),
+            2 => Some(TGAAlphaAttributeType::UndefinedRetain),
+            3 => Some(TGAAlphaAttributeType::Alpha),
+            4 => Some(TGAAlphaAttributeType::PremultipliedAlpha),
+            _ => None
+        };
+
+        // NOTE: Zeroed out fields mean the value was not specified by the writer.
+        Ok(TGAExtensionArea {
+            author_name,
+            author_comments,
+            timestamp: if timestamp.month == 0 && timestamp.day == 0 && timestamp.year == 0 { None } else { Some(timestamp) },
+            job_name,
+            job_time,
+            software_id,
+            software_version: if software_version_number == 0 {
+                None
+            } else {
+                Some((software_version_number, software_version_letter))
+            },
+            key_color,
+            pixel_aspect_ratio: if pixel_aspect_ratio.1 == 0 { None } else { Some(pixel_aspect_ratio) },
+            gamma: if gamma.1 == 0 { None } else { Some(gamma) },
+            color_correction_offset,
+            postage_stamp_offset,
+            scan_line_offset,
+            alpha_attribute_type
+        })
+    }
+
+    fn decode_tga_developer_directory(bytes: &'a [u8], offset: usize) -> Result<Vec<TGADeveloperField<'a>>, Error> {
+        let Some(mut directory) = bytes.get(offset..).filter(|directory| directory.len() >= 2) else {
+            return Err(Error::InvalidData("Invalid TGA developer directory offset"));
+        };
+        let tags_count = directory.get_u16_le() as usize;
+        if directory.len() < tags_count * 10 {
+            return Err(Error::Truncated);
+        }
+
+        let mut fields = Vec::with_capacity(tags_count);
+        for _ in 0..tags_count {
+            let tag = directory.get_u16_le();
+            let field_offset = directory.get_u32_le() as usize;
+            let field_size = directory.get_u32_le() as usize;
+            let Some(data) = bytes.get(field_offset..).and_then(|data| data.get(..field_size)) else {
+                return Err(Error::InvalidData("Invalid TGA developer field"));
+            };
+            fields.push(TGADeveloperField { tag, data });
+        }
+        Ok(fields)
+    }
+}
+
+fn read_tga_string(data: &mut &[u8], length: usize) -> String {
+    let field = &data[..length];
+    data.advance(length);
+    let field = field.split(|byte| *byte == 0).next().unwrap_or_default();
+    String::from_utf8_lossy(field).trim_end().to_string()
+}
+
+impl Metadata for TGAExtensionArea {
+    fn main_tags(&self) -> BTreeMap<&'static str, String> {
+        let mut tags = BTreeMap::new();
+        if !self.author_name.is_empty() {
+            tags.insert("Author", self.author_name.clone());
+        }
+        let comments = self.author_comments.iter().filter(|line| !line.is_empty()).cloned().collect::<Vec<_>>();
+        if !comments.is_empty() {
+            tags.insert("Comment", comments.join("\n"));
+        }
+        if let Some(timestamp) = self.timestamp {
+            tags.insert("DateTime", format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}", timestamp.year, timestamp.month, timestamp.day, timestamp.hour, timestamp.minute, timestamp.second));
+        }
+        if !self.job_name.is_empty() {
+            tags.insert("JobName", self.job_name.clone());
+        }
+        if !self.software_id.is_empty() {
+            let software = match self.software_version {
+                Some((number, letter)) => format!("{} {}.{:02}{}", self.software_id, number / 100, number % 100, letter).trim_end().to_string(),
+                None => self.software_id.clone()
+            };
+            tags.insert("Software", software);
+        }
+        if let Some((numerator, denominator)) = self.gamma {
+            tags.insert("Gamma", format!("{}", numerator as f32 / denominator as f32));
+        }
+        tags
+    }
 }
 
-fn read_pixel_from_reader(reader: &mut bytes::buf::Reader<&[u8]>, bytes_size: usize) -> Result<Color, String> {
-    // NOTE: We support 24-bit color pixels and 32-bit color pixels
+impl<'a> TGALoadingContext<'a> {
+    fn is_color_mapped(&self) -> bool {
+        matches!(self.header.data_type_code, TGADataType::UncompressedColorMapped | TGADataType::RunLengthEncodedColorMapped)
+    }
+
+    fn is_black_and_white(&self) -> bool {
+        matches!(self.header.data_type_code, TGADataType::UncompressedBlackAndWhite | TGADataType::CompressedBlackAndWhite)
+    }
+
+    fn attribute_bits(&self) -> u8 {
+        // NOTE: Bits 0-3 of the image descriptor hold the number of attribute (alpha) bits per pixel.
+        self.header.image_descriptor & 0x0F
+    }
+
+    fn is_right_to_left(&self) -> bool {
+        (self.header.image_descriptor & 0x10) != 0
+    }
+
+    fn is_top_to_bottom(&self) -> bool {
+        (self.header.image_descriptor & 0x20) != 0
+    }
+
+    fn create_bitmap(&self, size: IntSize) -> Result<Bitmap, Error> {
+        let bits_per_pixel = self.header.bits_per_pixel;
+        let pixel_depth = if self.is_color_mapped() {
+            if bits_per_pixel != 8 && bits_per_pixel != 16 {
+                return Err(Error::Unsupported("TGA color map indices must be 8 or 16 bits"));
+            }
+            self.header.color_map_depth
+        } else if self.is_black_and_white() {
+            if bits_per_pixel != 8 && bits_per_pixel != 16 {
+                return Err(Error::Unsupported("TGA grayscale pixels must be 8 or 16 bits"));
+            }
+            // NOTE: 16-bit black-and-white pixels carry an alpha byte after the gray value,
+            // so they map to the same bitmap formats as 24 and 32-bit true-color pixels.
+            if bits_per_pixel == 8 { 24 } else { 32 }
+        } else {
+            match bits_per_pixel {
+                // NOTE: Without attribute bits, the top bit of a 16-bit pixel is unused (X1R5G5B5).
+                16 if self.attribute_bits() == 0 => 15,
+                15 | 16 | 24 | 32 => bits_per_pixel,
+                _ => return Err(Error::Unsupported("TGA true-color pixels must be 15, 16, 24 or 32 bits"))
+            }
+        };
+
+        match pixel_depth {
+            15 | 24 => Bitmap::new(BitmapFormat::BGRx8888, size, 1),
+            16 | 32 if !self.has_meaningful_alpha() => Bitmap::new(BitmapFormat::BGRx8888, size, 1),
+            16 | 32 => Bitmap::new(BitmapFormat::BGRA8888, size, 1),
+            _ => Err(Error::InvalidHeader("Invalid TGA color map depth"))
+        }
+    }
+
+    fn set_pixel_at_index(&self, bitmap: &mut Bitmap, pixel_index: usize, color: Color) {
+        // NOTE: The x and y origin fields are only screen positioning hints,
+        // the pixel ordering is given by the image descriptor bits 4 and 5.
+        let width = bitmap.size.width;
+        let height = bitmap.size.height;
+        let row = pixel_index as i32 / width;
+        let col = pixel_index as i32 % width;
+        let actual_row = if self.is_top_to_bottom() { row } else { height - 1 - row };
+        let actual_col = if self.is_right_to_left() { width - 1 - col } else { col };
+        bitmap.set_pixel(actual_col, actual_row, color.color);
+    }
+
+    fn decode_uncompressed_pixels(&mut self, bitmap: &mut Bitmap) -> Result<(), Error> {
+        let pixel_count = bitmap.size.width as usize * bitmap.size.height as usize;
+        for pixel_index in 0..pixel_count {
+            let pixel = self.read_pixel()?;
+            self.set_pixel_at_index(bitmap, pixel_index, pixel);
+        }
+        Ok(())
+    }
+
+    fn decode_run_length_encoded_pixels(&mut self, bitmap: &mut Bitmap) -> Result<(), Error> {
+        let mut pixel_index = 0usize;
+        let pixel_count = bitmap.size.width as usize * bitmap.size.height as usize;
+        while pixel_index < pixel_count {
+            let pixel_packet_header = read_pixel_packet_header(&mut self.reader)?;
+            assert!(pixel_packet_header.pixels_count > 0);
+
+            let mut pixel = self.read_pixel()?;
+            let max_pixel_index = std::cmp::min(pixel_index + pixel_packet_header.pixels_count as usize, pixel_count);
+            for current_pixel_index in pixel_index..max_pixel_index {
+                self.set_pixel_at_index(bitmap, current_pixel_index, pixel);
+                if pixel_packet_header.raw && current_pixel_index + 1 < max_pixel_index {
+                    let next_pixel = self.read_pixel()?;
+                    pixel = next_pixel;
+                }
+            }
+            pixel_index += pixel_packet_header.pixels_count as usize;
+        }
+        Ok(())
+    }
+
+    fn alpha_attribute_type(&self) -> Option<TGAAlphaAttributeType> {
+        self.extension_area.as_ref().and_then(|extension_area| extension_area.alpha_attribute_type)
+    }
+
+    fn has_meaningful_alpha(&self) -> bool {
+        // NOTE: Without an extension area we have no choice but to trust the pixel depth.
+        !matches!(self.alpha_attribute_type(), Some(TGAAlphaAttributeType::NoAlpha | TGAAlphaAttributeType::UndefinedIgnore | TGAAlphaAttributeType::UndefinedRetain))
+    }
+
+    fn read_pixel(&mut self) -> Result<Color, Error> {
+        let pixel = self.read_raw_pixel()?;
+        Ok(match self.alpha_attribute_type() {
+            Some(TGAAlphaAttributeType::NoAlpha | TGAAlphaAttributeType::UndefinedIgnore) => pixel.with_alpha(0xFF),
+            Some(TGAAlphaAttributeType::PremultipliedAlpha) => pixel.unpremultiplied(),
+            _ => pixel
+        })
+    }
+
+    fn read_raw_pixel(&mut self) -> Result<Color, Error> {
+        let bytes_per_pixel = (self.header.bits_per_pixel as usize).div_ceil(8);
+        if self.is_black_and_white() {
+            return read_grayscale_pixel_from_reader(&mut self.reader, bytes_per_pixel);
+        }
+        if !self.is_color_mapped() {
+            if bytes_per_pixel == 2 {
+                let has_alpha = self.header.bits_per_pixel == 16 && self.attribute_bits() > 0;
+                return read_a1r5g5b5_pixel_from_reader(&mut self.reader, has_alpha);
+            }
+            return read_pixel_from_reader(&mut self.reader, bytes_per_pixel);
+        }
+
+        let mut index_data = [0u8; 2];
+        self.reader.read_exact(&mut index_data[..bytes_per_pixel])?;
+        // NOTE: Pixel values index the color map starting at the color map origin.
+        let index = u16::from_le_bytes(index_data) as usize;
+        let first_entry_index = self.header.color_map_origin as usize;
+        match index.checked_sub(first_entry_index).and_then(|index| self.color_map.get(index)) {
+            Some(color) => Ok(*color),
+            None => Err(Error::InvalidData("TGA color map index out of range"))
+        }
+    }
+}
+
+fn read_pixel_from_reader(reader: &mut bytes::buf::Reader<&[u8]>, bytes_size: usize) -> Result<Color, Error> {
+    // NOTE: We support 24-bit color pixels and 32-bit color pixels, 15 and 16-bit ones are handled separately
     match bytes_size {
         3 => {
             let mut color_data: [u8; 3] = [0u8; 3];
-            if let Err(e) = reader.read_exact(&mut color_data) {
-                return Err(e.to_string());
-            }
+            reader.read_exact(&mut color_data)?;
             Ok(Color::from_rgb(color_data[2], color_data[1], color_data[0]))
         }
         4 => {
             let mut color_data: [u8; 4] = [0u8; 4];
-            if let Err(e) = reader.read_exact(&mut color_data) {
-                return Err(e.to_string());
-            }
-            Ok(Color::from_rgba(color_data[3], color_data[2], color_data[1], color_data[0]))
+            reader.read_exact(&mut color_data)?;
+            Ok(Color::from_rgba(color_data[2], color_data[1], color_data[0], color_data[3]))
         }
         _ => {
             unreachable!("Invalid bytes size");
@@ -150,11 +562,30 @@ fn read_pixel_from_reader(reader: &mut bytes::buf::Reader<&[u8]>, bytes_size: us
     }
 }
 
-fn read_pixel_packet_header(reader: &mut bytes::buf::Reader<&[u8]>) -> Result<TGAPixelPacketHeader, String> {
+fn read_grayscale_pixel_from_reader(reader: &mut bytes::buf::Reader<&[u8]>, bytes_size: usize) -> Result<Color, Error> {
+    // NOTE: We support 8-bit gray pixels and 16-bit gray pixels with an alpha byte
+    let mut color_data: [u8; 2] = [0u8, 0xFF];
+    reader.read_exact(&mut color_data[..bytes_size])?;
+    let gray = color_data[0];
+    Ok(Color::from_rgba(gray, gray, gray, color_data[1]))
+}
+
+fn read_a1r5g5b5_pixel_from_reader(reader: &mut bytes::buf::Reader<&[u8]>, has_alpha: bool) -> Result<Color, Error> {
+    let mut color_data: [u8; 2] = [0u8; 2];
+    reader.read_exact(&mut color_data)?;
+    let value = u16::from_le_bytes(color_data);
+    // NOTE: Expand 5-bit channels to 8 bits by replicating the top bits into the bottom ones.
+    let expand = |channel: u16| -> u8 {
+        let channel = (channel & 0x1F) as u8;
+        (channel << 3) | (channel >> 2)
+    };
+    let alpha = if !has_alpha || (value & 0x8000) != 0 { 0xFF } else { 0 };
+    Ok(Color::from_rgba(expand(value >> 10), expand(value >> 5), expand(value), alpha))
+}
+
+fn read_pixel_packet_header(reader: &mut bytes::buf::Reader<&[u8]>) -> Result<TGAPixelPacketHeader, Error> {
     let mut header_data: [u8; 1] = [0u8; 1];
-    if let Err(e) = reader.read_exact(&mut header_data) {
-        return Err(e.to_string());
-    }
+    reader.read_exact(&mut header_data)?;
     let raw = (header_data[0] & 0x80) == 0;
     let mut pixels_count = header_data[0] & 0x7F;
     // NOTE: Run-length-encoded/Raw pixel packets cannot encode zero pixels,
@@ -175,94 +606,42 @@ impl<'a> ImageDecoderPlugin for TGAImageDecoderPlugin<'a> {
         }
     }
 
-    fn frame_with_ideal_size(&mut self, index: usize, _: Option<IntSize>) -> Result<ImageFrameDescriptor, String> {
+    fn frame_with_ideal_size(&mut self, index: usize, _: Option<IntSize>) -> Result<ImageFrameDescriptor, Error> {
 
-        let bits_per_pixel = self.context.header.bits_per_pixel;
         let color_map = self.context.header.color_map_type;
         let data_type =  self.context.header.data_type_code;
         let width = self.context.header.width;
         let height =  self.context.header.height;
-        let x_origin =  self.context.header.x_origin;
-        let mut y_origin =  self.context.header.y_origin;
 
         if index != 0 {
-            return Err("TAImageDecoderPlugin: frame index must be 0".to_string());
+            return Err(Error::FrameIndexOutOfRange);
         }
 
         if color_map > 1 {
-            return Err("TAImageDecoderPlugin: Invalid color map type".to_string());
+            return Err(Error::InvalidHeader("Invalid TGA color map type"));
         }
 
-        if self.context.bitmap.is_some() {
+        if let Some(bitmap) = &self.context.bitmap {
             return Ok(ImageFrameDescriptor {
-                image: self.context.bitmap.as_mut().unwrap().clone(),
+                image: bitmap.clone(),
                 duration: 0
             });
         }
 
-        let mut bitmap = match bits_per_pixel {
-            24 => Bitmap::new(BitmapFormat::BGRx8888, IntSize { width: width as i32, height: height as i32 }, 1)?,
-            32 => Bitmap::new(BitmapFormat::BGRA8888, IntSize { width: width as i32, height: height as i32 }, 1)?,
-            _ => {
-                // FIXME: Implement other TGA bit depths
-                return Err("TGAImageDecoderPlugin: Can only handle 24 and 32 bits per pixel".to_string())
-            }
-        };
-
-        // FIXME: Try to understand the Image origin (instead of X and Y origin coordinates)
-        // based on the Image descriptor, Field 5.6, bits 4 and 5.
-
-        // NOTE: If Y origin is set to a negative number, just assume the generating software
-        // meant that we start with Y origin at the top height of the picture.
-        // At least this is the observed behavior when generating some pictures in GIMP.
-        if y_origin < 0 {
-            y_origin = height as i16;
-        }
-        if y_origin != 0 && y_origin != height as i16 {
-            return Err("TGAImageDecoderPlugin: Can only handle Y origin which is 0 or the entire height".to_string());
-        }
-        if x_origin != 0 && x_origin != width as i16 {
-            return Err("TGAImageDecoderPlugin: Can only handle X origin which is 0 or the entire width".to_string());
+        if self.context.is_color_mapped() && color_map != 1 {
+            return Err(Error::InvalidHeader("Color-mapped TGA without a color map"));
         }
 
-        assert_eq!(bits_per_pixel % 8, 0);
-        let bytes_per_pixel = bits_per_pixel / 8;
+        let mut bitmap = self.context.create_bitmap(IntSize { width: width as i32, height: height as i32 })?;
 
         match data_type {
-            TGADataType::UncompressedRGB => {
-                for row in 0..height as i32 {
-                    for col in 0..width as i32 {
-                        let actual_row = if y_origin >= height as i16 { row } else { height as i32 - 1 - row };
-                        let actual_col = if x_origin <= width as i16 { col } else { width as i32 - 1 - col };
-                        let pixel = read_pixel_from_reader(&mut self.context.reader, bytes_per_pixel as usize)?;
-                        bitmap.set_pixel(actual_col, actual_row, pixel.color);
-                    }
-                }
+            TGADataType::UncompressedColorMapped | TGADataType::UncompressedRGB | TGADataType::UncompressedBlackAndWhite => {
+                self.context.decode_uncompressed_pixels(&mut bitmap)?;
             },
-            TGADataType::RunLengthEncodedRGB => {
-                let mut pixel_index = 0usize;
-                let pixel_count = height as usize * width as usize;
-                while pixel_index < pixel_count {
-                    let pixel_packet_header = read_pixel_packet_header(&mut self.context.reader)?;
-                    assert!(pixel_packet_header.pixels_count > 0);
-
-                    let mut pixel = read_pixel_from_reader(&mut self.context.reader, bytes_per_pixel as usize)?;
-                    let max_pixel_index = std::cmp::min(pixel_index + pixel_packet_header.pixels_count as usize, pixel_count);
-                    for current_pixel_index in pixel_index..max_pixel_index {
-                        let row = current_pixel_index as i32 / width as i32;
-                        let col = current_pixel_index as i32 % width as i32;
-                        let actual_row = if y_origin >= height as i16 { row } else { height as i32 - 1 - row };
-                        let actual_col = if x_origin <= width as i16 { col } else { width as i32 - 1 - col };
-                        bitmap.set_pixel(actual_col, actual_row, pixel.color);
-                        if pixel_packet_header.raw && current_pixel_index + 1 < max_pixel_index {
-                            let next_pixel = read_pixel_from_reader(&mut self.context.reader, bytes_per_pixel as usize)?;
-                            pixel = next_pixel;
-                        }
-                    }
-                    pixel_index += pixel_packet_header.pixels_count as usize;
-                }
+            TGADataType::RunLengthEncodedColorMapped | TGADataType::RunLengthEncodedRGB | TGADataType::CompressedBlackAndWhite => {
+                self.context.decode_run_length_encoded_pixels(&mut bitmap)?;
             }
-            _ => { return Err("TGAImageDecoderPlugin: Unsupported TGA data type".to_string()); }
+            _ => { return Err(Error::Unsupported("TGA data type")); }
         }
 
         self.context.bitmap = Some(bitmap.clone());
@@ -272,28 +651,439 @@ impl<'a> ImageDecoderPlugin for TGAImageDecoderPlugin<'a> {
             duration: 0
         })
     }
+
+    fn metadata(&self) -> Option<&dyn Metadata> {
+        self.context.extension_area.as_ref().map(|extension_area| extension_area as &dyn Metadata)
+    }
+
+    fn natural_frame_format(&self) -> NaturalFrameFormat {
+        if self.context.is_black_and_white() {
+            NaturalFrameFormat::Grayscale
+        } else {
+            NaturalFrameFormat::RGB
+        }
+    }
 }
 
+/// # Safety
+///
+/// `bytes` must point to `size` readable bytes that outlive the returned decoder,
+/// and `out_decoder` must be valid for writes.
 #[no_mangle]
-pub unsafe extern "C" fn tga_image_decoder_plugin_new<'a>(bytes: *const u8, size: usize) -> *mut c_void {
-    let bytes = unsafe {
-        assert!(!bytes.is_null());
-        std::slice::from_raw_parts(bytes, size)
-    };
-    match TGAImageDecoderPlugin::create(bytes) {
-        Ok(decoder) => {
-            let interface: Box<dyn ImageDecoderPlugin> = Box::new(decoder);
-            let boxed_interface = Box::new(interface);
-            Box::into_raw(boxed_interface) as *mut c_void
-        },
-        Err(_) => std::ptr::null_mut()
-    }
+pub unsafe extern "C" fn tga_image_decoder_plugin_new(bytes: *const u8, size: usize, out_decoder: *mut *mut c_void) -> ErrorCode {
+    ffi_call(|| unsafe {
+        if bytes.is_null() {
+            return Err(Error::InvalidArgument("Bytes are null"));
+        }
+        if out_decoder.is_null() {
+            return Err(Error::InvalidArgument("Output pointer is null"));
+        }
+        let bytes = std::slice::from_raw_parts(bytes, size);
+        let decoder = TGAImageDecoderPlugin::create(bytes)?;
+        write_ffi_result(out_decoder, decoder_into_opaque(Box::new(decoder)))
+    })
 }
 
+/// # Safety
+///
+/// `opaque_decoder` must either be null or come from `tga_image_decoder_plugin_new` and not be freed yet.
 #[no_mangle]
-pub extern "C" fn tga_image_decoder_plugin_free(opaque_decoder: *mut c_void) {
-    if !opaque_decoder.is_null() {
-        let decoder: Box<Box<dyn ImageDecoderPlugin>> = unsafe { Box::from_raw(opaque_decoder as *mut _) };
-        drop(decoder);
+pub unsafe extern "C" fn tga_image_decoder_plugin_free(opaque_decoder: *mut c_void) -> ErrorCode {
+    ffi_call(|| {
+        if !opaque_decoder.is_null() {
+            let decoder: Box<Box<dyn ImageDecoderPlugin>> = unsafe { Box::from_raw(opaque_decoder as *mut _) };
+            drop(decoder);
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ARGB;
+    use bytes::BufMut;
+    use std::ffi::CStr;
+    use crate::error::libgfx_last_error_message;
+    use crate::imagedecoderplugin::{image_decoder_plugin_frame, image_decoder_plugin_free_frame, image_decoder_plugin_size};
+
+    // NOTE: 2x2 stored bottom-up, with 8-bit indices into a 24-bit color map of red, green and blue.
+    const COLOR_MAPPED_24_IMAGE: [u8; 31] = [
+        0x00, 0x01, 0x01, 0x00, 0x00, 0x03, 0x00, 0x18, 0x00, 0x00, 0x00, 0x00, 0x02, 0x00, 0x02, 0x00, 0x08, 0x00, 0x00, 0x00,
+        0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0x00, 0x02, 0x01, 0x00, 0x00
+    ];
+    // NOTE: 2x2 run-length encoded, with a color map starting at index 2 that holds a half transparent red and a transparent white.
+    const COLOR_MAPPED_32_RLE_IMAGE: [u8; 30] = [
+        0x00, 0x01, 0x09, 0x02, 0x00, 0x02, 0x00, 0x20, 0x00, 0x00, 0x00, 0x00, 0x02, 0x00, 0x02, 0x00, 0x08, 0x00, 0x00, 0x00,
+        0xFF, 0x80, 0xFF, 0xFF, 0xFF, 0x00, 0x82, 0x02, 0x00, 0x03
+    ];
+    // NOTE: 2x1 with a 15-bit color map.
+    const COLOR_MAPPED_15_IMAGE: [u8; 24] = [
+        0x00, 0x01, 0x01, 0x00, 0x00, 0x02, 0x00, 0x0F, 0x00, 0x00, 0x00, 0x00, 0x02, 0x00, 0x01, 0x00, 0x08, 0x00, 0x01, 0x41,
+        0xE0, 0x03, 0x00, 0x01
+    ];
+    // NOTE: 2x1 with 16-bit indices into a 16-bit color map, whose attribute bit makes the second entry transparent.
+    const COLOR_MAPPED_16_IMAGE: [u8; 26] = [
+        0x00, 0x01, 0x01, 0x00, 0x00, 0x02, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x02, 0x00, 0x01, 0x00, 0x10, 0x01, 0xFF, 0xFF,
+        0x00, 0x7C, 0x01, 0x00, 0x00, 0x00
+    ];
+    // NOTE: 2x1 uncompressed 32-bit, with a half transparent dark color and an opaque blue pixel.
+    const TRUE_COLOR_32_IMAGE: [u8; 26] = [
+        0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x00, 0x01, 0x00, 0x20, 0x08, 0x10, 0x20,
+        0x30, 0x80, 0xFF, 0x00, 0x00, 0xFF
+    ];
+    // NOTE: 2x2 8-bit grayscale stored bottom-up, going from black to white.
+    const GRAYSCALE_8_IMAGE: [u8; 22] = [
+        0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x00, 0x02, 0x00, 0x08, 0x00, 0x00, 0x40,
+        0x80, 0xFF
+    ];
+    // NOTE: 2x1 run-length encoded 16-bit grayscale, a single run of half transparent gray.
+    const GRAYSCALE_16_RLE_IMAGE: [u8; 21] = [
+        0x00, 0x00, 0x0B, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x00, 0x01, 0x00, 0x10, 0x08, 0x81, 0x60,
+        0x80
+    ];
+    // NOTE: 2x1 16-bit without attribute bits, a red pixel with a clear top bit and a blue one with it set.
+    const X1R5G5B5_IMAGE: [u8; 22] = [
+        0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x00, 0x01, 0x00, 0x10, 0x00, 0x00, 0x7C,
+        0x1F, 0x80
+    ];
+    // NOTE: The same pixels as above, but with one attribute bit in the image descriptor.
+    const A1R5G5B5_IMAGE: [u8; 22] = [
+        0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x00, 0x01, 0x00, 0x10, 0x01, 0x00, 0x7C,
+        0x1F, 0x80
+    ];
+    // NOTE: 2x1 run-length encoded 15-bit, with a stray top bit that has to be ignored.
+    const R5G5B5_RLE_IMAGE: [u8; 21] = [
+        0x00, 0x00, 0x0A, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x00, 0x01, 0x00, 0x0F, 0x00, 0x81, 0x01,
+        0xC1
+    ];
+    // NOTE: 2x2 24-bit holding red, green, blue and white in storage order, with origin fields that must not matter.
+    const ORIENTATION_IMAGE: [u8; 30] = [
+        0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x07, 0x00, 0x05, 0x00, 0x02, 0x00, 0x02, 0x00, 0x18, 0x00, 0x00, 0x00,
+        0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0x00, 0xFF, 0xFF, 0xFF
+    ];
+    // NOTE: The same pixels, run-length encoded with a raw packet that crosses the first scanline.
+    const ORIENTATION_RLE_IMAGE: [u8; 32] = [
+        0x00, 0x00, 0x0A, 0x00, 0x00, 0x00, 0x00, 0x00, 0x07, 0x00, 0x05, 0x00, 0x02, 0x00, 0x02, 0x00, 0x18, 0x00, 0x02, 0x00,
+        0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0x00, 0x80, 0xFF, 0xFF, 0xFF
+    ];
+    // NOTE: 2x1 color-mapped, with a 5 byte image ID between the header and the color map.
+    const IMAGE_ID_IMAGE: [u8; 31] = [
+        0x05, 0x01, 0x01, 0x00, 0x00, 0x02, 0x00, 0x18, 0x00, 0x00, 0x00, 0x00, 0x02, 0x00, 0x01, 0x00, 0x08, 0x00, 0x68, 0x65,
+        0x6C, 0x6C, 0x6F, 0x00, 0x00, 0xFF, 0xFF, 0x00, 0x00, 0x01, 0x00
+    ];
+
+    fn pixel(image: &Bitmap, x: i32, y: i32) -> ARGB {
+        let offset = y as usize * image.pitch as usize + x as usize * 4;
+        u32::from_be_bytes(image.data[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn decodes_32_bit_pixels_in_bgra_order() {
+        let mut decoder = TGAImageDecoderPlugin::create(&TRUE_COLOR_32_IMAGE).unwrap();
+        let image = decoder.frame(0).unwrap().image;
+        assert_eq!([pixel(&image, 0, 0), pixel(&image, 1, 0)], [0x80302010, 0xFF0000FF]);
+    }
+
+    #[test]
+    fn decodes_color_map_with_24_bit_entries() {
+        let mut decoder = TGAImageDecoderPlugin::create(&COLOR_MAPPED_24_IMAGE).unwrap();
+        let image = decoder.frame(0).unwrap().image;
+        assert_eq!([pixel(&image, 0, 0), pixel(&image, 1, 0), pixel(&image, 0, 1), pixel(&image, 1, 1)], [0xFFFF0000, 0xFFFF0000, 0xFF0000FF, 0xFF00FF00]);
+    }
+
+    #[test]
+    fn decodes_run_length_encoded_color_map_with_32_bit_entries_and_an_origin() {
+        let mut decoder = TGAImageDecoderPlugin::create(&COLOR_MAPPED_32_RLE_IMAGE).unwrap();
+        let image = decoder.frame(0).unwrap().image;
+        assert_eq!([pixel(&image, 0, 0), pixel(&image, 1, 0), pixel(&image, 0, 1), pixel(&image, 1, 1)], [0x80FF0000, 0x00FFFFFF, 0x80FF0000, 0x80FF0000]);
+    }
+
+    #[test]
+    fn decodes_color_map_with_15_bit_entries() {
+        let mut decoder = TGAImageDecoderPlugin::create(&COLOR_MAPPED_15_IMAGE).unwrap();
+        let image = decoder.frame(0).unwrap().image;
+        assert_eq!([pixel(&image, 0, 0), pixel(&image, 1, 0)], [0xFF844208, 0xFF00FF00]);
+    }
+
+    #[test]
+    fn decodes_color_map_with_16_bit_entries_and_indices() {
+        let mut decoder = TGAImageDecoderPlugin::create(&COLOR_MAPPED_16_IMAGE).unwrap();
+        let image = decoder.frame(0).unwrap().image;
+        assert_eq!([pixel(&image, 0, 0), pixel(&image, 1, 0)], [0x00FF0000, 0xFFFFFFFF]);
+    }
+
+    #[test]
+    fn rejects_color_map_index_out_of_range() {
+        let mut data = COLOR_MAPPED_24_IMAGE;
+        data[data.len() - 1] = 3;
+        let mut decoder = TGAImageDecoderPlugin::create(&data).unwrap();
+        assert_eq!(decoder.frame(0).err(), Some(Error::InvalidData("TGA color map index out of range")));
+    }
+
+    #[test]
+    fn decodes_8_bit_grayscale() {
+        let mut decoder = TGAImageDecoderPlugin::create(&GRAYSCALE_8_IMAGE).unwrap();
+        assert_eq!(decoder.natural_frame_format(), NaturalFrameFormat::Grayscale);
+        let image = decoder.frame(0).unwrap().image;
+        assert_eq!([pixel(&image, 0, 0), pixel(&image, 1, 0), pixel(&image, 0, 1), pixel(&image, 1, 1)], [0xFF808080, 0xFFFFFFFF, 0xFF000000, 0xFF404040]);
+    }
+
+    #[test]
+    fn decodes_run_length_encoded_16_bit_grayscale_with_alpha() {
+        let mut decoder = TGAImageDecoderPlugin::create(&GRAYSCALE_16_RLE_IMAGE).unwrap();
+        assert_eq!(decoder.natural_frame_format(), NaturalFrameFormat::Grayscale);
+        let image = decoder.frame(0).unwrap().image;
+        assert_eq!([pixel(&image, 0, 0), pixel(&image, 1, 0)], [0x80606060, 0x80606060]);
+    }
+
+    #[test]
+    fn reports_rgb_natural_frame_format_for_color_images() {
+        let decoder = TGAImageDecoderPlugin::create(&TRUE_COLOR_32_IMAGE).unwrap();
+        assert_eq!(decoder.natural_frame_format(), NaturalFrameFormat::RGB);
+    }
+
+    #[test]
+    fn ignores_top_bit_of_16_bit_pixels_without_attribute_bits() {
+        let mut decoder = TGAImageDecoderPlugin::create(&X1R5G5B5_IMAGE).unwrap();
+        let image = decoder.frame(0).unwrap().image;
+        assert!(matches!(image.format, BitmapFormat::BGRx8888));
+        assert_eq!([pixel(&image, 0, 0), pixel(&image, 1, 0)], [0xFFFF0000, 0xFF0000FF]);
+    }
+
+    #[test]
+    fn decodes_alpha_bit_of_16_bit_pixels_with_an_attribute_bit() {
+        let mut decoder = TGAImageDecoderPlugin::create(&A1R5G5B5_IMAGE).unwrap();
+        let image = decoder.frame(0).unwrap().image;
+        assert!(matches!(image.format, BitmapFormat::BGRA8888));
+        assert_eq!([pixel(&image, 0, 0), pixel(&image, 1, 0)], [0x00FF0000, 0xFF0000FF]);
+    }
+
+    #[test]
+    fn decodes_run_length_encoded_15_bit_pixels() {
+        let mut decoder = TGAImageDecoderPlugin::create(&R5G5B5_RLE_IMAGE).unwrap();
+        let image = decoder.frame(0).unwrap().image;
+        assert_eq!([pixel(&image, 0, 0), pixel(&image, 1, 0)], [0xFF844208, 0xFF844208]);
+    }
+
+    fn assert_decodes_in_every_orientation(data: &[u8]) {
+        // NOTE: Image descriptor bit 4 means right-to-left and bit 5 top-to-bottom, pixels are listed as (0, 0), (1, 0), (0, 1), (1, 1).
+        let orientations: [(u8, [ARGB; 4]); 4] = [
+            (0x00, [0xFF0000FF, 0xFFFFFFFF, 0xFFFF0000, 0xFF00FF00]),
+            (0x10, [0xFFFFFFFF, 0xFF0000FF, 0xFF00FF00, 0xFFFF0000]),
+            (0x20, [0xFFFF0000, 0xFF00FF00, 0xFF0000FF, 0xFFFFFFFF]),
+            (0x30, [0xFF00FF00, 0xFFFF0000, 0xFFFFFFFF, 0xFF0000FF])
+        ];
+        for (image_descriptor, expected) in orientations {
+            let mut data = data.to_vec();
+            data[17] = image_descriptor;
+            let mut decoder = TGAImageDecoderPlugin::create(&data).unwrap();
+            let image = decoder.frame(0).unwrap().image;
+            assert_eq!([pixel(&image, 0, 0), pixel(&image, 1, 0), pixel(&image, 0, 1), pixel(&image, 1, 1)], expected, "image descriptor {image_descriptor:#04X}");
+        }
+    }
+
+    #[test]
+    fn decodes_uncompressed_pixels_in_every_orientation() {
+        assert_decodes_in_every_orientation(&ORIENTATION_IMAGE);
+    }
+
+    #[test]
+    fn decodes_run_length_encoded_pixels_in_every_orientation() {
+        assert_decodes_in_every_orientation(&ORIENTATION_RLE_IMAGE);
+    }
+
+    fn put_tga_string(data: &mut Vec<u8>, string: &str, length: usize) {
+        data.put_slice(string.as_bytes());
+        data.put_bytes(0, length - string.len());
+    }
+
+    // NOTE: 1x1 32-bit TGA 2.0 file, with a filled in extension area, one developer field and an optional postage stamp.
+    fn tga_2_0_image(pixel: [u8; 4], alpha_attribute_type: u8, postage_stamp: &[u8]) -> Vec<u8> {
+        let mut data = vec![0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x01, 0x00, 0x20, 0x28];
+        data.put_slice(&pixel);
+        let developer_field_offset = data.len() as u32;
+        data.put_slice(b"dev");
+        let postage_stamp_offset = if postage_stamp.is_empty() { 0 } else { data.len() as u32 };
+        data.put_slice(postage_stamp);
+
+        let extension_area_offset = data.len() as u32;
+        data.put_u16_le(TGA_EXTENSION_AREA_SIZE as u16);
+        put_tga_string(&mut data, "Jane Doe", 41);
+        for comment in ["First line", "Second line", "", ""] {
+            put_tga_string(&mut data, comment, 81);
+        }
+        for value in [12, 31, 1999, 23, 59, 58] {
+            data.put_u16_le(value);
+        }
+        put_tga_string(&mut data, "Job", 41);
+        for value in [1, 2, 3] {
+            data.put_u16_le(value);
+        }
+        put_tga_string(&mut data, "Paint", 41);
+        data.put_u16_le(213);
+        data.put_u8(b'b');
+        data.put_u32_le(0xFF102030);
+        data.put_u16_le(4);
+        data.put_u16_le(3);
+        data.put_u16_le(22);
+        data.put_u16_le(10);
+        data.put_u32_le(0);
+        data.put_u32_le(postage_stamp_offset);
+        data.put_u32_le(0);
+        data.put_u8(alpha_attribute_type);
+
+        let developer_directory_offset = data.len() as u32;
+        data.put_u16_le(1);
+        data.put_u16_le(0x8000);
+        data.put_u32_le(developer_field_offset);
+        data.put_u32_le(3);
+
+        data.put_u32_le(extension_area_offset);
+        data.put_u32_le(developer_directory_offset);
+        data.put_slice(TGA_FOOTER_SIGNATURE);
+        data
+    }
+
+    #[test]
+    fn decodes_extension_area_and_developer_directory() {
+        let data = tga_2_0_image([0x20, 0x40, 0x60, 0x80], 3, &[]);
+        let decoder = TGAImageDecoderPlugin::create(&data).unwrap();
+        let extension_area = decoder.extension_area().unwrap();
+        assert_eq!(extension_area.author_name, "Jane Doe");
+        assert_eq!(extension_area.timestamp, Some(TGATimestamp { month: 12, day: 31, year: 1999, hour: 23, minute: 59, second: 58 }));
+        assert_eq!(extension_area.job_time, (1, 2, 3));
+        assert_eq!(extension_area.software_version, Some((213, 'b')));
+        assert_eq!(extension_area.key_color, 0xFF102030);
+        assert_eq!(extension_area.pixel_aspect_ratio, Some((4, 3)));
+        assert_eq!(extension_area.postage_stamp_offset, 0);
+        assert_eq!(extension_area.alpha_attribute_type, Some(TGAAlphaAttributeType::Alpha));
+
+        let developer_fields = decoder.developer_fields();
+        assert_eq!(developer_fields.len(), 1);
+        assert_eq!((developer_fields[0].tag, developer_fields[0].data), (0x8000, &b"dev"[..]));
+
+        let tags = decoder.metadata().unwrap().main_tags();
+        assert_eq!(tags.get("Author").map(String::as_str), Some("Jane Doe"));
+        assert_eq!(tags.get("Comment").map(String::as_str), Some("First line\nSecond line"));
+        assert_eq!(tags.get("DateTime").map(String::as_str), Some("1999-12-31 23:59:58"));
+        assert_eq!(tags.get("JobName").map(String::as_str), Some("Job"));
+        assert_eq!(tags.get("Software").map(String::as_str), Some("Paint 2.13b"));
+        assert_eq!(tags.get("Gamma").map(String::as_str), Some("2.2"));
+    }
+
+    #[test]
+    fn applies_alpha_attribute_type() {
+        // NOTE: UndefinedRetain keeps the alpha bits in the bitmap, but the bitmap format says to ignore them.
+        let expected_pixels: [(u8, bool, ARGB); 5] = [
+            (0, false, 0xFF604020),
+            (1, false, 0xFF604020),
+            (2, false, 0x80604020),
+            (3, true, 0x80604020),
+            (4, true, 0x80BF8040)
+        ];
+        for (alpha_attribute_type, has_alpha, expected) in expected_pixels {
+            let data = tga_2_0_image([0x20, 0x40, 0x60, 0x80], alpha_attribute_type, &[]);
+            let mut decoder = TGAImageDecoderPlugin::create(&data).unwrap();
+            let image = decoder.frame(0).unwrap().image;
+            assert_eq!(matches!(image.format, BitmapFormat::BGRA8888), has_alpha, "alpha attribute type {alpha_attribute_type}");
+            assert_eq!(pixel(&image, 0, 0), expected, "alpha attribute type {alpha_attribute_type}");
+        }
+    }
+
+    #[test]
+    fn ignores_broken_extension_area_and_developer_directory() {
+        let mut data = tga_2_0_image([0x20, 0x40, 0x60, 0x80], 3, &[]);
+        let footer_offset = data.len() - TGA_FOOTER_SIZE;
+        let out_of_range_offset = data.len() as u32 + 100;
+        data[footer_offset..footer_offset + 4].copy_from_slice(&out_of_range_offset.to_le_bytes());
+        data[footer_offset + 4..footer_offset + 8].copy_from_slice(&out_of_range_offset.to_le_bytes());
+
+        let mut decoder = TGAImageDecoderPlugin::create(&data).unwrap();
+        assert!(decoder.extension_area().is_none());
+        assert!(decoder.developer_fields().is_empty());
+        assert!(decoder.metadata().is_none());
+        assert_eq!(pixel(&decoder.frame(0).unwrap().image, 0, 0), 0x80604020);
+    }
+
+    #[test]
+    fn exposes_image_id() {
+        let mut decoder = TGAImageDecoderPlugin::create(&IMAGE_ID_IMAGE).unwrap();
+        assert_eq!(decoder.image_id(), b"hello");
+        let image = decoder.frame(0).unwrap().image;
+        assert_eq!([pixel(&image, 0, 0), pixel(&image, 1, 0)], [0xFF0000FF, 0xFFFF0000]);
+
+        let decoder = TGAImageDecoderPlugin::create(&TRUE_COLOR_32_IMAGE).unwrap();
+        assert!(decoder.image_id().is_empty());
+    }
+
+    #[test]
+    fn rejects_truncated_image_id() {
+        assert_eq!(TGAImageDecoderPlugin::create(&IMAGE_ID_IMAGE[..20]).err(), Some(Error::Truncated));
+    }
+
+    #[test]
+    fn decodes_postage_stamp() {
+        // NOTE: A 2x1 postage stamp, stored bottom-up in the image's pixel format.
+        let data = tga_2_0_image([0x20, 0x40, 0x60, 0x80], 3, &[0x02, 0x01, 0xFF, 0x00, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0x40]);
+        let mut decoder = TGAImageDecoderPlugin::create(&data).unwrap();
+        let postage_stamp = decoder.postage_stamp().unwrap().unwrap();
+        assert_eq!((postage_stamp.size.width, postage_stamp.size.height), (2, 1));
+        assert_eq!([pixel(&postage_stamp, 0, 0), pixel(&postage_stamp, 1, 0)], [0xFF0000FF, 0x4000FF00]);
+        // NOTE: Decoding the postage stamp must not disturb the image decoding.
+        assert_eq!(pixel(&decoder.frame(0).unwrap().image, 0, 0), 0x80604020);
+    }
+
+    #[test]
+    fn treats_missing_or_empty_postage_stamp_as_none() {
+        let data = tga_2_0_image([0x20, 0x40, 0x60, 0x80], 3, &[]);
+        assert!(TGAImageDecoderPlugin::create(&data).unwrap().postage_stamp().unwrap().is_none());
+        assert!(TGAImageDecoderPlugin::create(&TRUE_COLOR_32_IMAGE).unwrap().postage_stamp().unwrap().is_none());
+
+        let data = tga_2_0_image([0x20, 0x40, 0x60, 0x80], 3, &[0x00, 0x00]);
+        assert!(TGAImageDecoderPlugin::create(&data).unwrap().postage_stamp().unwrap().is_none());
+    }
+
+    #[test]
+    fn rejects_out_of_range_postage_stamp() {
+        let mut data = tga_2_0_image([0x20, 0x40, 0x60, 0x80], 3, &[0x00, 0x00]);
+        let footer_offset = data.len() - TGA_FOOTER_SIZE;
+        let extension_area_offset = u32::from_le_bytes(data[footer_offset..footer_offset + 4].try_into().unwrap()) as usize;
+        // NOTE: The postage stamp offset is stored 486 bytes into the extension area.
+        let out_of_range_offset = data.len() as u32 + 100;
+        data[extension_area_offset + 486..extension_area_offset + 490].copy_from_slice(&out_of_range_offset.to_le_bytes());
+        assert_eq!(TGAImageDecoderPlugin::create(&data).unwrap().postage_stamp().err(), Some(Error::InvalidData("Invalid TGA postage stamp offset")));
+    }
+
+    #[test]
+    fn rejects_truncated_run_length_encoded_image() {
+        let mut decoder = TGAImageDecoderPlugin::create(&ORIENTATION_RLE_IMAGE[..ORIENTATION_RLE_IMAGE.len() - 1]).unwrap();
+        assert_eq!(decoder.frame(0).err(), Some(Error::Truncated));
+    }
+
+    #[test]
+    fn rejects_truncated_uncompressed_image() {
+        assert_eq!(TGAImageDecoderPlugin::create(&ORIENTATION_IMAGE[..ORIENTATION_IMAGE.len() - 1]).err(), Some(Error::Truncated));
+    }
+
+    #[test]
+    fn reports_errors_across_the_ffi() {
+        unsafe {
+            let mut decoder = std::ptr::null_mut();
+            assert_eq!(tga_image_decoder_plugin_new(ORIENTATION_IMAGE.as_ptr(), ORIENTATION_IMAGE.len(), std::ptr::null_mut()), ErrorCode::InvalidArgument);
+            assert_eq!(tga_image_decoder_plugin_new(ORIENTATION_IMAGE.as_ptr(), 10, &mut decoder), ErrorCode::Truncated);
+            assert!(decoder.is_null());
+            assert_eq!(CStr::from_ptr(libgfx_last_error_message()).to_str(), Ok("Unexpected end of data"));
+
+            assert_eq!(tga_image_decoder_plugin_new(ORIENTATION_IMAGE.as_ptr(), ORIENTATION_IMAGE.len(), &mut decoder), ErrorCode::Success);
+            let mut size = IntSize { width: 0, height: 0 };
+            assert_eq!(image_decoder_plugin_size(decoder, &mut size), ErrorCode::Success);
+            assert_eq!((size.width, size.height), (2, 2));
+            let mut frame = std::mem::MaybeUninit::uninit();
+            assert_eq!(image_decoder_plugin_frame(decoder, 1, frame.as_mut_ptr()), ErrorCode::FrameIndexOutOfRange);
+            assert_eq!(image_decoder_plugin_frame(decoder, 0, frame.as_mut_ptr()), ErrorCode::Success);
+            assert_eq!(image_decoder_plugin_free_frame(frame.assume_init()), ErrorCode::Success);
+            assert_eq!(tga_image_decoder_plugin_free(decoder), ErrorCode::Success);
+        }
     }
 }
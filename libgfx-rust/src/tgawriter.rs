@@ -0,0 +1,252 @@
+use bytes::BufMut;
+use crate::{ARGB, Color};
+use crate::bitmap::Bitmap;
+use crate::error::Error;
+
+const TGA_EXTENSION_AREA_SIZE: u16 = 495;
+const TGA_FOOTER_SIGNATURE: &[u8; 18] = b"TRUEVISION-XFILE.\0";
+const TGA_MAX_PACKET_LENGTH: usize = 128;
+
+#[derive (Debug, PartialEq, Copy, Clone)]
+pub enum TGADataEncoding {
+    Uncompressed,
+    RunLengthEncoded
+}
+
+#[derive (Debug, PartialEq, Copy, Clone)]
+pub enum TGAOrigin {
+    TopLeft,
+    BottomLeft
+}
+
+#[derive (Debug, Copy, Clone)]
+pub struct TGAWriterOptions {
+    pub data_encoding: TGADataEncoding,
+    pub bits_per_pixel: u8,
+    pub origin: TGAOrigin,
+    pub write_footer: bool
+}
+
+impl Default for TGAWriterOptions {
+    fn default() -> Self {
+        Self {
+            data_encoding: TGADataEncoding::Uncompressed,
+            bits_per_pixel: 32,
+            origin: TGAOrigin::TopLeft,
+            write_footer: false
+        }
+    }
+}
+
+pub struct TGAWriter;
+
+impl TGAWriter {
+    pub fn encode(bitmap: &Bitmap, options: &TGAWriterOptions) -> Result<Vec<u8>, Error> {
+        if options.bits_per_pixel != 24 && options.bits_per_pixel != 32 {
+            return Err(Error::Unsupported("TGA can only be written with 24 or 32 bits per pixel"));
+        }
+        let width = bitmap.physical_width();
+        let height = bitmap.physical_height();
+        if width > u16::MAX as i32 || height > u16::MAX as i32 {
+            return Err(Error::SizeOverflow);
+        }
+
+        let bytes_per_pixel = options.bits_per_pixel as usize / 8;
+        let mut data = Vec::with_capacity(18 + width as usize * height as usize * bytes_per_pixel);
+
+        let data_type_code = match options.data_encoding {
+            TGADataEncoding::Uncompressed => 2,
+            TGADataEncoding::RunLengthEncoded => 10
+        };
+        // NOTE: Bits 0-3 are the attribute bits count, bit 5 is set for top-left origin.
+        let attribute_bits = if options.bits_per_pixel == 32 { 8 } else { 0 };
+        let image_descriptor = match options.origin {
+            TGAOrigin::TopLeft => attribute_bits | 0x20,
+            TGAOrigin::BottomLeft => attribute_bits
+        };
+
+        data.put_u8(0); // ID length
+        data.put_u8(0); // Color map type
+        data.put_u8(data_type_code);
+        data.put_i16_le(0); // Color map origin
+        data.put_i16_le(0); // Color map length
+        data.put_u8(0); // Color map depth
+        data.put_i16_le(0); // X origin
+        data.put_i16_le(0); // Y origin
+        data.put_u16_le(width as u16);
+        data.put_u16_le(height as u16);
+        data.put_u8(options.bits_per_pixel);
+        data.put_u8(image_descriptor);
+
+        for row in 0..height {
+            let y = match options.origin {
+                TGAOrigin::TopLeft => row,
+                TGAOrigin::BottomLeft => height - 1 - row
+            };
+            let scanline = (0..width).map(|x| bitmap.get_pixel(x, y)).collect::<Vec<ARGB>>();
+            match options.data_encoding {
+                TGADataEncoding::Uncompressed => {
+                    for pixel in scanline {
+                        write_pixel(&mut data, pixel, bytes_per_pixel);
+                    }
+                }
+                TGADataEncoding::RunLengthEncoded => write_run_length_encoded_scanline(&mut data, &scanline, bytes_per_pixel)
+            }
+        }
+
+        if options.write_footer {
+            let extension_area_offset = data.len() as u32;
+            write_extension_area(&mut data, options.bits_per_pixel == 32);
+            data.put_u32_le(extension_area_offset);
+            data.put_u32_le(0); // Developer directory offset
+            data.put_slice(TGA_FOOTER_SIGNATURE);
+        }
+
+        Ok(data)
+    }
+}
+
+fn write_pixel(data: &mut Vec<u8>, pixel: ARGB, bytes_per_pixel: usize) {
+    let color = Color::from(pixel);
+    data.put_slice(&[color.blue(), color.green(), color.red(), color.alpha()][..bytes_per_pixel]);
+}
+
+fn write_run_length_encoded_scanline(data: &mut Vec<u8>, scanline: &[ARGB], bytes_per_pixel: usize) {
+    // NOTE: Packets never cross scanline boundaries, as recommended by the TGA 2.0 specification.
+    let mut index = 0;
+    while index < scanline.len() {
+        let run_length = scanline[index..].iter().take(TGA_MAX_PACKET_LENGTH).take_while(|pixel| **pixel == scanline[index]).count();
+        if run_length > 1 {
+            data.put_u8(0x80 | (run_length - 1) as u8);
+            write_pixel(data, scanline[index], bytes_per_pixel);
+            index += run_length;
+            continue;
+        }
+
+        // NOTE: A raw packet stops right before the next run of identical pixels.
+        let mut raw_length = 1;
+        while index + raw_length < scanline.len() && raw_length < TGA_MAX_PACKET_LENGTH {
+            let next = index + raw_length;
+            if next + 1 < scanline.len() && scanline[next] == scanline[next + 1] {
+                break;
+            }
+            raw_length += 1;
+        }
+        data.put_u8((raw_length - 1) as u8);
+        for pixel in &scanline[index..index + raw_length] {
+            write_pixel(data, *pixel, bytes_per_pixel);
+        }
+        index += raw_length;
+    }
+}
+
+fn write_extension_area(data: &mut Vec<u8>, has_alpha: bool) {
+    let start = data.len();
+    data.put_u16_le(TGA_EXTENSION_AREA_SIZE);
+    // NOTE: Leave every field unspecified, except for the attributes type that ends the area.
+    data.resize(start + TGA_EXTENSION_AREA_SIZE as usize - 1, 0);
+    data.put_u8(if has_alpha { 3 } else { 0 });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::IntSize;
+    use crate::bitmap::BitmapFormat;
+    use crate::imagedecoderplugin::ImageDecoderPlugin;
+    use crate::tgaloader::{TGAAlphaAttributeType, TGAImageDecoderPlugin};
+
+    // NOTE: Both rows hold a run of two pixels that would join into a single run if packets could cross scanlines.
+    const PIXELS: [[ARGB; 3]; 2] = [
+        [0xFFFF0000, 0x8000FF00, 0x8000FF00],
+        [0x8000FF00, 0x8000FF00, 0xFF0000FF]
+    ];
+
+    fn test_bitmap() -> Bitmap {
+        let mut bitmap = Bitmap::new(BitmapFormat::BGRA8888, IntSize { width: 3, height: 2 }, 1).unwrap();
+        for (y, row) in PIXELS.iter().enumerate() {
+            for (x, pixel) in row.iter().enumerate() {
+                bitmap.set_pixel(x as i32, y as i32, *pixel);
+            }
+        }
+        bitmap
+    }
+
+    #[test]
+    fn round_trips_through_the_decoder() {
+        let bitmap = test_bitmap();
+        for data_encoding in [TGADataEncoding::Uncompressed, TGADataEncoding::RunLengthEncoded] {
+            for bits_per_pixel in [24, 32] {
+                for origin in [TGAOrigin::TopLeft, TGAOrigin::BottomLeft] {
+                    for write_footer in [false, true] {
+                        let options = TGAWriterOptions { data_encoding, bits_per_pixel, origin, write_footer };
+                        let data = TGAWriter::encode(&bitmap, &options).unwrap();
+                        let mut decoder = TGAImageDecoderPlugin::create(&data).unwrap();
+                        let image = decoder.frame(0).unwrap().image;
+                        for (y, row) in PIXELS.iter().enumerate() {
+                            for (x, pixel) in row.iter().enumerate() {
+                                let expected = if bits_per_pixel == 24 { pixel | 0xFF000000 } else { *pixel };
+                                assert_eq!(image.get_pixel(x as i32, y as i32), expected, "{options:?} at ({x}, {y})");
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn writes_origin_and_attribute_bits() {
+        let bitmap = test_bitmap();
+        let image_descriptor = |bits_per_pixel, origin| {
+            let options = TGAWriterOptions { bits_per_pixel, origin, ..Default::default() };
+            TGAWriter::encode(&bitmap, &options).unwrap()[17]
+        };
+        assert_eq!(image_descriptor(32, TGAOrigin::TopLeft), 0x28);
+        assert_eq!(image_descriptor(32, TGAOrigin::BottomLeft), 0x08);
+        assert_eq!(image_descriptor(24, TGAOrigin::TopLeft), 0x20);
+        assert_eq!(image_descriptor(24, TGAOrigin::BottomLeft), 0x00);
+    }
+
+    #[test]
+    fn run_length_packets_stop_at_scanline_boundaries() {
+        let options = TGAWriterOptions { data_encoding: TGADataEncoding::RunLengthEncoded, bits_per_pixel: 24, ..Default::default() };
+        let data = TGAWriter::encode(&test_bitmap(), &options).unwrap();
+        assert_eq!(data[18..], [
+            0x00, 0x00, 0x00, 0xFF, 0x81, 0x00, 0xFF, 0x00,
+            0x81, 0x00, 0xFF, 0x00, 0x00, 0xFF, 0x00, 0x00
+        ]);
+    }
+
+    #[test]
+    fn run_length_packets_hold_at_most_128_pixels() {
+        let mut bitmap = Bitmap::new(BitmapFormat::BGRx8888, IntSize { width: 131, height: 1 }, 1).unwrap();
+        for x in 0..130 {
+            bitmap.set_pixel(x, 0, 0xFF123456);
+        }
+        let options = TGAWriterOptions { data_encoding: TGADataEncoding::RunLengthEncoded, bits_per_pixel: 24, ..Default::default() };
+        let data = TGAWriter::encode(&bitmap, &options).unwrap();
+        assert_eq!(data[18..], [0xFF, 0x56, 0x34, 0x12, 0x81, 0x56, 0x34, 0x12, 0x00, 0x00, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn writes_footer_with_alpha_attribute_type() {
+        let bitmap = test_bitmap();
+        for (bits_per_pixel, alpha_attribute_type) in [(24, TGAAlphaAttributeType::NoAlpha), (32, TGAAlphaAttributeType::Alpha)] {
+            let options = TGAWriterOptions { bits_per_pixel, write_footer: true, ..Default::default() };
+            let data = TGAWriter::encode(&bitmap, &options).unwrap();
+            assert!(data.ends_with(TGA_FOOTER_SIGNATURE));
+            let decoder = TGAImageDecoderPlugin::create(&data).unwrap();
+            assert_eq!(decoder.extension_area().unwrap().alpha_attribute_type, Some(alpha_attribute_type));
+        }
+
+        let data = TGAWriter::encode(&bitmap, &TGAWriterOptions::default()).unwrap();
+        assert!(TGAImageDecoderPlugin::create(&data).unwrap().extension_area().is_none());
+    }
+
+    #[test]
+    fn rejects_unsupported_bits_per_pixel() {
+        let options = TGAWriterOptions { bits_per_pixel: 16, ..Default::default() };
+        assert!(matches!(TGAWriter::encode(&test_bitmap(), &options), Err(Error::Unsupported(_))));
+    }
+}
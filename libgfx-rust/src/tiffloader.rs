@@ -0,0 +1,1161 @@
+use std::collections::{BTreeMap, HashSet};
+use std::ffi::c_void;
+use crate::imagedecoderplugin::{decoder_into_opaque, ImageDecoderPlugin, ImageFrameDescriptor, NaturalFrameFormat};
+use crate::{Color, IntSize};
+use crate::bitmap::{Bitmap, BitmapFormat};
+use crate::cmykbitmap::{CMYK, CMYKBitmap};
+use crate::error::{Error, ErrorCode, ffi_call, write_ffi_result};
+use crate::inflate::zlib_decompress;
+
+// NOTE: See https://www.itu.int/itudoc/itu-t/com16/tiff-fx/docs/tiff6.pdf for the format.
+const TIFF_HEADER_SIZE: usize = 8;
+const TIFF_VERSION: u16 = 42;
+const BIGTIFF_VERSION: u16 = 43;
+const TIFF_IFD_ENTRY_SIZE: usize = 12;
+
+const TIFF_TAG_NEW_SUBFILE_TYPE: u16 = 254;
+const TIFF_TAG_IMAGE_WIDTH: u16 = 256;
+const TIFF_TAG_IMAGE_LENGTH: u16 = 257;
+const TIFF_TAG_BITS_PER_SAMPLE: u16 = 258;
+const TIFF_TAG_COMPRESSION: u16 = 259;
+const TIFF_TAG_PHOTOMETRIC_INTERPRETATION: u16 = 262;
+const TIFF_TAG_FILL_ORDER: u16 = 266;
+const TIFF_TAG_STRIP_OFFSETS: u16 = 273;
+const TIFF_TAG_SAMPLES_PER_PIXEL: u16 = 277;
+const TIFF_TAG_ROWS_PER_STRIP: u16 = 278;
+const TIFF_TAG_STRIP_BYTE_COUNTS: u16 = 279;
+const TIFF_TAG_PLANAR_CONFIGURATION: u16 = 284;
+const TIFF_TAG_PREDICTOR: u16 = 317;
+const TIFF_TAG_COLOR_MAP: u16 = 320;
+const TIFF_TAG_TILE_WIDTH: u16 = 322;
+const TIFF_TAG_TILE_LENGTH: u16 = 323;
+const TIFF_TAG_TILE_OFFSETS: u16 = 324;
+const TIFF_TAG_TILE_BYTE_COUNTS: u16 = 325;
+const TIFF_TAG_INK_SET: u16 = 332;
+const TIFF_TAG_EXTRA_SAMPLES: u16 = 338;
+const TIFF_TAG_SAMPLE_FORMAT: u16 = 339;
+
+const TIFF_TYPE_BYTE: u16 = 1;
+const TIFF_TYPE_SHORT: u16 = 3;
+const TIFF_TYPE_LONG: u16 = 4;
+
+const TIFF_EXTRA_SAMPLE_ASSOCIATED_ALPHA: u32 = 1;
+const TIFF_EXTRA_SAMPLE_UNASSOCIATED_ALPHA: u32 = 2;
+
+const LZW_CLEAR_CODE: usize = 256;
+const LZW_END_OF_INFORMATION: usize = 257;
+const LZW_FIRST_CODE: usize = 258;
+const LZW_MIN_CODE_SIZE: u32 = 9;
+const LZW_MAX_CODE_SIZE: u32 = 12;
+
+#[derive (Debug, Copy, Clone, PartialEq)]
+enum TIFFByteOrder {
+    LittleEndian,
+    BigEndian
+}
+
+impl TIFFByteOrder {
+    fn u16_from_bytes(self, bytes: [u8; 2]) -> u16 {
+        match self {
+            TIFFByteOrder::LittleEndian => u16::from_le_bytes(bytes),
+            TIFFByteOrder::BigEndian => u16::from_be_bytes(bytes)
+        }
+    }
+
+    fn u16_to_bytes(self, value: u16) -> [u8; 2] {
+        match self {
+            TIFFByteOrder::LittleEndian => value.to_le_bytes(),
+            TIFFByteOrder::BigEndian => value.to_be_bytes()
+        }
+    }
+
+    fn u32_from_bytes(self, bytes: [u8; 4]) -> u32 {
+        match self {
+            TIFFByteOrder::LittleEndian => u32::from_le_bytes(bytes),
+            TIFFByteOrder::BigEndian => u32::from_be_bytes(bytes)
+        }
+    }
+}
+
+#[derive (Debug, Copy, Clone, PartialEq)]
+enum TIFFCompression {
+    None,
+    Lzw,
+    Deflate,
+    PackBits
+}
+
+#[derive (Debug, Copy, Clone, PartialEq)]
+enum TIFFPhotometricInterpretation {
+    WhiteIsZero,
+    BlackIsZero,
+    Rgb,
+    Palette,
+    Cmyk
+}
+
+impl TIFFPhotometricInterpretation {
+    fn color_channel_count(self) -> usize {
+        match self {
+            TIFFPhotometricInterpretation::Rgb => 3,
+            TIFFPhotometricInterpretation::Cmyk => 4,
+            _ => 1
+        }
+    }
+}
+
+/// One image of the file, described by an image file directory (IFD).
+#[derive (Debug, Clone)]
+struct TIFFPage {
+    width: u32,
+    height: u32,
+    bits_per_sample: u32,
+    samples_per_pixel: usize,
+    compression: TIFFCompression,
+    photometric_interpretation: TIFFPhotometricInterpretation,
+    has_alpha: bool,
+    is_alpha_premultiplied: bool,
+    is_planar: bool,
+    is_tiled: bool,
+    uses_horizontal_predictor: bool,
+    is_fill_order_reversed: bool,
+    // NOTE: Strips are treated as tiles spanning the whole width of the image.
+    chunk_width: u32,
+    chunk_height: u32,
+    chunk_offsets: Vec<u32>,
+    chunk_byte_counts: Vec<u32>,
+    color_map: Vec<u32>
+}
+
+impl TIFFPage {
+    fn from_tags(tags: &BTreeMap<u16, Vec<u32>>) -> Result<Self, Error> {
+        let value = |tag: u16| tags.get(&tag).and_then(|values| values.first().copied());
+
+        let (Some(width), Some(height)) = (value(TIFF_TAG_IMAGE_WIDTH), value(TIFF_TAG_IMAGE_LENGTH)) else {
+            return Err(Error::InvalidData("TIFF page has no dimensions"));
+        };
+        if width == 0 || height == 0 || width > i32::MAX as u32 || height > i32::MAX as u32 {
+            return Err(Error::InvalidData("Invalid TIFF dimensions"));
+        }
+
+        let samples_per_pixel = value(TIFF_TAG_SAMPLES_PER_PIXEL).unwrap_or(1) as usize;
+        if samples_per_pixel == 0 {
+            return Err(Error::InvalidData("Invalid TIFF samples per pixel"));
+        }
+
+        let bits_per_sample = value(TIFF_TAG_BITS_PER_SAMPLE).unwrap_or(1);
+        if tags.get(&TIFF_TAG_BITS_PER_SAMPLE).is_some_and(|values| values.iter().any(|bits| *bits != bits_per_sample)) {
+            return Err(Error::Unsupported("TIFF samples of different sizes"));
+        }
+        if !matches!(bits_per_sample, 1 | 2 | 4 | 8 | 16) {
+            return Err(Error::Unsupported("TIFF bit depth"));
+        }
+        if tags.get(&TIFF_TAG_SAMPLE_FORMAT).is_some_and(|values| values.iter().any(|format| *format != 1)) {
+            return Err(Error::Unsupported("TIFF sample format"));
+        }
+
+        let compression = match value(TIFF_TAG_COMPRESSION).unwrap_or(1) {
+            1 => TIFFCompression::None,
+            5 => TIFFCompression::Lzw,
+            8 | 32946 => TIFFCompression::Deflate,
+            32773 => TIFFCompression::PackBits,
+            _ => return Err(Error::Unsupported("TIFF compression"))
+        };
+
+        let photometric_interpretation = match value(TIFF_TAG_PHOTOMETRIC_INTERPRETATION) {
+            Some(0) => TIFFPhotometricInterpretation::WhiteIsZero,
+            Some(1) => TIFFPhotometricInterpretation::BlackIsZero,
+            Some(2) => TIFFPhotometricInterpretation::Rgb,
+            Some(3) => TIFFPhotometricInterpretation::Palette,
+            Some(5) => {
+                if value(TIFF_TAG_INK_SET).unwrap_or(1) != 1 {
+                    return Err(Error::Unsupported("TIFF ink set"));
+                }
+                TIFFPhotometricInterpretation::Cmyk
+            }
+            Some(_) => return Err(Error::Unsupported("TIFF photometric interpretation")),
+            None => return Err(Error::InvalidData("TIFF page has no photometric interpretation"))
+        };
+        let color_channel_count = photometric_interpretation.color_channel_count();
+        if samples_per_pixel < color_channel_count {
+            return Err(Error::InvalidData("Too few TIFF samples per pixel"));
+        }
+
+        // NOTE: Only the first extra sample can be alpha, the others are ignored. Some writers leave out the
+        //       ExtraSamples tag when there is a single alpha sample, so that is what we assume in that case.
+        let extra_sample = value(TIFF_TAG_EXTRA_SAMPLES)
+            .or((samples_per_pixel == color_channel_count + 1).then_some(TIFF_EXTRA_SAMPLE_UNASSOCIATED_ALPHA));
+        let has_alpha = samples_per_pixel > color_channel_count
+            && matches!(extra_sample, Some(TIFF_EXTRA_SAMPLE_ASSOCIATED_ALPHA | TIFF_EXTRA_SAMPLE_UNASSOCIATED_ALPHA));
+        let is_alpha_premultiplied = has_alpha && extra_sample == Some(TIFF_EXTRA_SAMPLE_ASSOCIATED_ALPHA);
+
+        let is_planar = match value(TIFF_TAG_PLANAR_CONFIGURATION).unwrap_or(1) {
+            1 => false,
+            2 => true,
+            _ => return Err(Error::InvalidData("Invalid TIFF planar configuration"))
+        };
+
+        let uses_horizontal_predictor = match value(TIFF_TAG_PREDICTOR).unwrap_or(1) {
+            1 => false,
+            2 => {
+                if !matches!(bits_per_sample, 8 | 16) {
+                    return Err(Error::Unsupported("TIFF predictor bit depth"));
+                }
+                true
+            }
+            3 => return Err(Error::Unsupported("TIFF floating point predictor")),
+            _ => return Err(Error::InvalidData("Invalid TIFF predictor"))
+        };
+
+        let is_fill_order_reversed = match value(TIFF_TAG_FILL_ORDER).unwrap_or(1) {
+            1 => false,
+            2 => true,
+            _ => return Err(Error::InvalidData("Invalid TIFF fill order"))
+        };
+
+        let is_tiled = tags.contains_key(&TIFF_TAG_TILE_WIDTH);
+        let (chunk_width, chunk_height, chunk_offsets, chunk_byte_counts) = if is_tiled {
+            let (Some(tile_width), Some(tile_height)) = (value(TIFF_TAG_TILE_WIDTH), value(TIFF_TAG_TILE_LENGTH)) else {
+                return Err(Error::InvalidData("TIFF tile has no height"));
+            };
+            (tile_width, tile_height, tags.get(&TIFF_TAG_TILE_OFFSETS), tags.get(&TIFF_TAG_TILE_BYTE_COUNTS))
+        } else {
+            let rows_per_strip = value(TIFF_TAG_ROWS_PER_STRIP).unwrap_or(u32::MAX).min(height);
+            (width, rows_per_strip, tags.get(&TIFF_TAG_STRIP_OFFSETS), tags.get(&TIFF_TAG_STRIP_BYTE_COUNTS))
+        };
+        if chunk_width == 0 || chunk_height == 0 {
+            return Err(Error::InvalidData("Invalid TIFF tile size"));
+        }
+        Bitmap::check_dimensions(chunk_width, chunk_height)?;
+        let (Some(chunk_offsets), Some(chunk_byte_counts)) = (chunk_offsets, chunk_byte_counts) else {
+            return Err(Error::InvalidData("TIFF page has no image data"));
+        };
+
+        let mut page = Self {
+            width,
+            height,
+            bits_per_sample,
+            samples_per_pixel,
+            compression,
+            photometric_interpretation,
+            has_alpha,
+            is_alpha_premultiplied,
+            is_planar,
+            is_tiled,
+            uses_horizontal_predictor,
+            is_fill_order_reversed,
+            chunk_width,
+            chunk_height,
+            chunk_offsets: Vec::new(),
+            chunk_byte_counts: Vec::new(),
+            color_map: Vec::new()
+        };
+
+        let Some(chunk_count) = page.chunks_across().checked_mul(page.chunks_down()).and_then(|count| count.checked_mul(page.plane_count())) else {
+            return Err(Error::SizeOverflow);
+        };
+        if chunk_offsets.len() < chunk_count || chunk_byte_counts.len() < chunk_count {
+            return Err(Error::InvalidData("TIFF page is missing image data"));
+        }
+        page.chunk_offsets = chunk_offsets[..chunk_count].to_vec();
+        page.chunk_byte_counts = chunk_byte_counts[..chunk_count].to_vec();
+
+        if photometric_interpretation == TIFFPhotometricInterpretation::Palette {
+            let Some(color_map) = tags.get(&TIFF_TAG_COLOR_MAP) else {
+                return Err(Error::InvalidData("TIFF palette page has no color map"));
+            };
+            if color_map.len() != 3 << bits_per_sample {
+                return Err(Error::InvalidData("Invalid TIFF color map size"));
+            }
+            page.color_map = color_map.clone();
+        }
+
+        Ok(page)
+    }
+
+    fn size(&self) -> IntSize {
+        IntSize {
+            width: self.width as i32,
+            height: self.height as i32
+        }
+    }
+
+    fn chunks_across(&self) -> usize {
+        (self.width as usize).div_ceil(self.chunk_width as usize)
+    }
+
+    fn chunks_down(&self) -> usize {
+        (self.height as usize).div_ceil(self.chunk_height as usize)
+    }
+
+    fn plane_count(&self) -> usize {
+        if self.is_planar { self.samples_per_pixel } else { 1 }
+    }
+
+    fn natural_frame_format(&self) -> NaturalFrameFormat {
+        match self.photometric_interpretation {
+            TIFFPhotometricInterpretation::WhiteIsZero | TIFFPhotometricInterpretation::BlackIsZero => NaturalFrameFormat::Grayscale,
+            TIFFPhotometricInterpretation::Cmyk => NaturalFrameFormat::CMYK,
+            _ => NaturalFrameFormat::RGB
+        }
+    }
+
+    /// The number of samples we actually use per pixel, the color channels followed by alpha.
+    fn channel_count(&self) -> usize {
+        self.photometric_interpretation.color_channel_count() + self.has_alpha as usize
+    }
+
+    fn scale_sample(&self, value: u16) -> u8 {
+        match self.bits_per_sample {
+            8 => value as u8,
+            16 => ((value as u32 + 128) / 257) as u8,
+            bits => (value as u32 * 255 / ((1 << bits) - 1)) as u8
+        }
+    }
+}
+
+pub struct TIFFImageDecoderPlugin<'a> {
+    context: TIFFLoadingContext<'a>
+}
+
+struct TIFFLoadingContext<'a> {
+    bytes: &'a [u8],
+    byte_order: TIFFByteOrder,
+    pages: Vec<TIFFPage>,
+    // NOTE: Multi-page files tend to be large scans, so we only hold on to the page decoded last.
+    bitmap: Option<(usize, Bitmap)>,
+    cmyk_bitmap: Option<(usize, CMYKBitmap)>
+}
+
+impl<'a> TIFFImageDecoderPlugin<'a> {
+    pub fn sniff(bytes: &[u8]) -> bool {
+        matches!(bytes.get(..4), Some([b'I', b'I', 42 | 43, 0] | [b'M', b'M', 0, 42 | 43]))
+    }
+
+    pub fn create(bytes: &'a [u8]) -> Result<Self, Error> {
+        if bytes.len() < TIFF_HEADER_SIZE {
+            return Err(Error::Truncated);
+        }
+        if !Self::sniff(bytes) {
+            return Err(Error::InvalidHeader("Invalid TIFF magic"));
+        }
+
+        let byte_order = if bytes[0] == b'I' { TIFFByteOrder::LittleEndian } else { TIFFByteOrder::BigEndian };
+        let mut context = TIFFLoadingContext {
+            bytes,
+            byte_order,
+            pages: Vec::new(),
+            bitmap: None,
+            cmyk_bitmap: None
+        };
+        match context.read_u16(2)? {
+            TIFF_VERSION => {}
+            BIGTIFF_VERSION => return Err(Error::Unsupported("BigTIFF")),
+            _ => return Err(Error::InvalidHeader("Invalid TIFF version"))
+        }
+        context.read_pages()?;
+
+        Ok(Self { context })
+    }
+}
+
+impl<'a> TIFFLoadingContext<'a> {
+    fn read_u16(&self, offset: usize) -> Result<u16, Error> {
+        let Some(bytes) = self.bytes.get(offset..offset + 2) else {
+            return Err(Error::Truncated);
+        };
+        Ok(self.byte_order.u16_from_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn read_u32(&self, offset: usize) -> Result<u32, Error> {
+        let Some(bytes) = self.bytes.get(offset..offset + 4) else {
+            return Err(Error::Truncated);
+        };
+        Ok(self.byte_order.u32_from_bytes(bytes.try_into().unwrap()))
+    }
+
+    /// Reads the integer tags of the IFD at `offset`, returning them with the offset of the next IFD.
+    fn read_ifd(&self, offset: usize) -> Result<(BTreeMap<u16, Vec<u32>>, u32), Error> {
+        let entry_count = self.read_u16(offset)? as usize;
+        let mut tags = BTreeMap::new();
+        for index in 0..entry_count {
+            let entry_offset = offset + 2 + index * TIFF_IFD_ENTRY_SIZE;
+            let tag = self.read_u16(entry_offset)?;
+            let field_type = self.read_u16(entry_offset + 2)?;
+            let count = self.read_u32(entry_offset + 4)? as usize;
+            let value_size = match field_type {
+                TIFF_TYPE_BYTE => 1,
+                TIFF_TYPE_SHORT => 2,
+                TIFF_TYPE_LONG => 4,
+                // NOTE: None of the tags we use have values of other types.
+                _ => continue
+            };
+
+            // NOTE: Values that fit are stored in the entry itself, otherwise it holds their offset.
+            let size = count * value_size;
+            let value_offset = if size <= 4 { entry_offset + 8 } else { self.read_u32(entry_offset + 8)? as usize };
+            // NOTE: Broken entries are skipped, if we needed them that gets reported once the page is parsed.
+            if value_offset.checked_add(size).is_none_or(|end| end > self.bytes.len()) {
+                continue;
+            }
+
+            let mut values = Vec::new();
+            if values.try_reserve_exact(count).is_err() {
+                return Err(Error::OutOfMemory);
+            }
+            for value_index in 0..count {
+                let value_offset = value_offset + value_index * value_size;
+                values.push(match value_size {
+                    1 => self.bytes[value_offset] as u32,
+                    2 => self.read_u16(value_offset)? as u32,
+                    _ => self.read_u32(value_offset)?
+                });
+            }
+            tags.insert(tag, values);
+        }
+
+        let next_offset = self.read_u32(offset + 2 + entry_count * TIFF_IFD_ENTRY_SIZE)?;
+        Ok((tags, next_offset))
+    }
+
+    fn read_pages(&mut self) -> Result<(), Error> {
+        let mut visited_offsets = HashSet::new();
+        let mut offset = self.read_u32(4)?;
+        while offset != 0 {
+            if !visited_offsets.insert(offset) {
+                return Err(Error::InvalidData("TIFF page chain contains a loop"));
+            }
+            let (tags, next_offset) = self.read_ifd(offset as usize)?;
+            // NOTE: Reduced-resolution versions of other pages (e.g. thumbnails) are not pages of their own.
+            let new_subfile_type = tags.get(&TIFF_TAG_NEW_SUBFILE_TYPE).and_then(|values| values.first().copied()).unwrap_or(0);
+            if new_subfile_type & 1 == 0 {
+                self.pages.push(TIFFPage::from_tags(&tags)?);
+            }
+            offset = next_offset;
+        }
+
+        if self.pages.is_empty() {
+            return Err(Error::InvalidData("TIFF file has no pages"));
+        }
+        Ok(())
+    }
+
+    fn decompress_chunk(&self, page: &TIFFPage, index: usize, expected_size: usize) -> Result<Vec<u8>, Error> {
+        let offset = page.chunk_offsets[index] as usize;
+        let Some(data) = self.bytes.get(offset..offset + page.chunk_byte_counts[index] as usize) else {
+            return Err(Error::Truncated);
+        };
+
+        let reversed_data: Vec<u8>;
+        let data = if page.is_fill_order_reversed {
+            reversed_data = data.iter().map(|byte| byte.reverse_bits()).collect();
+            &reversed_data
+        } else {
+            data
+        };
+
+        let mut decompressed_data = match page.compression {
+            TIFFCompression::None => data.to_vec(),
+            TIFFCompression::Lzw => decompress_lzw(data, expected_size)?,
+            TIFFCompression::Deflate => zlib_decompress(data, expected_size)?,
+            TIFFCompression::PackBits => decompress_packbits(data, expected_size)?
+        };
+        if decompressed_data.len() < expected_size {
+            return Err(Error::Truncated);
+        }
+        decompressed_data.truncate(expected_size);
+        Ok(decompressed_data)
+    }
+
+    /// Decodes all chunks of `page` into `channel_count()` samples per pixel, interleaved regardless of the planar configuration.
+    fn decode_samples(&self, page: &TIFFPage) -> Result<Vec<u16>, Error> {
+        let width = page.width as usize;
+        let height = page.height as usize;
+        let channel_count = page.channel_count();
+        let Some(sample_count) = width.checked_mul(height).and_then(|pixel_count| pixel_count.checked_mul(channel_count)) else {
+            return Err(Error::SizeOverflow);
+        };
+        let mut samples = Vec::new();
+        if samples.try_reserve_exact(sample_count).is_err() {
+            return Err(Error::OutOfMemory);
+        }
+        samples.resize(sample_count, 0u16);
+
+        let chunk_width = page.chunk_width as usize;
+        let chunk_height = page.chunk_height as usize;
+        let samples_per_chunk_pixel = if page.is_planar { 1 } else { page.samples_per_pixel };
+        let bytes_per_chunk_row = (chunk_width * samples_per_chunk_pixel * page.bits_per_sample as usize).div_ceil(8);
+
+        // NOTE: Planes past the ones we use (i.e. extra samples other than alpha) don't need to be decoded at all.
+        for plane in 0..page.plane_count().min(channel_count) {
+            let used_samples_per_chunk_pixel = samples_per_chunk_pixel.min(channel_count - plane);
+            for chunk_y in 0..page.chunks_down() {
+                for chunk_x in 0..page.chunks_across() {
+                    let index = (plane * page.chunks_down() + chunk_y) * page.chunks_across() + chunk_x;
+                    let top = chunk_y * chunk_height;
+                    let left = chunk_x * chunk_width;
+                    // NOTE: Tiles are always padded to their full size, but the last strip only holds the remaining rows.
+                    let row_count = if page.is_tiled { chunk_height } else { chunk_height.min(height - top) };
+                    let mut data = self.decompress_chunk(page, index, bytes_per_chunk_row * row_count)?;
+
+                    for (row_index, row) in data.chunks_exact_mut(bytes_per_chunk_row).enumerate() {
+                        let y = top + row_index;
+                        if y >= height {
+                            break;
+                        }
+                        if page.uses_horizontal_predictor {
+                            undo_horizontal_predictor(row, samples_per_chunk_pixel, page.bits_per_sample, self.byte_order);
+                        }
+                        for column in 0..chunk_width.min(width - left) {
+                            let pixel_offset = ((y * width) + left + column) * channel_count + plane;
+                            for sample in 0..used_samples_per_chunk_pixel {
+                                samples[pixel_offset + sample] = read_sample(row, column * samples_per_chunk_pixel + sample, page.bits_per_sample, self.byte_order);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(samples)
+    }
+
+    fn render(&self, page: &TIFFPage) -> Result<Bitmap, Error> {
+        let format = if page.has_alpha { BitmapFormat::BGRA8888 } else { BitmapFormat::BGRx8888 };
+        let mut bitmap = Bitmap::new(format, page.size(), 1)?;
+        let samples = self.decode_samples(page)?;
+
+        let width = page.width as usize;
+        let color_count = 1usize << page.bits_per_sample;
+        for (index, pixel) in samples.chunks_exact(page.channel_count()).enumerate() {
+            let (red, green, blue) = match page.photometric_interpretation {
+                TIFFPhotometricInterpretation::WhiteIsZero => {
+                    let gray = 255 - page.scale_sample(pixel[0]);
+                    (gray, gray, gray)
+                }
+                TIFFPhotometricInterpretation::BlackIsZero => {
+                    let gray = page.scale_sample(pixel[0]);
+                    (gray, gray, gray)
+                }
+                TIFFPhotometricInterpretation::Rgb => (page.scale_sample(pixel[0]), page.scale_sample(pixel[1]), page.scale_sample(pixel[2])),
+                TIFFPhotometricInterpretation::Palette => {
+                    // NOTE: Color map entries are always 16-bit.
+                    let entry = |channel: usize| ((page.color_map[channel * color_count + pixel[0] as usize] + 128) / 257) as u8;
+                    (entry(0), entry(1), entry(2))
+                }
+                TIFFPhotometricInterpretation::Cmyk => CMYK {
+                    c: page.scale_sample(pixel[0]),
+                    m: page.scale_sample(pixel[1]),
+                    y: page.scale_sample(pixel[2]),
+                    k: page.scale_sample(pixel[3])
+                }.to_low_quality_rgb()
+            };
+
+            let mut color = Color::from_rgb(red, green, blue);
+            if page.has_alpha {
+                color = color.with_alpha(page.scale_sample(pixel[pixel.len() - 1]));
+                if page.is_alpha_premultiplied {
+                    color = color.unpremultiplied();
+                }
+            }
+            bitmap.set_pixel((index % width) as i32, (index / width) as i32, color.color);
+        }
+
+        Ok(bitmap)
+    }
+
+    fn render_cmyk(&self, page: &TIFFPage) -> Result<CMYKBitmap, Error> {
+        let mut bitmap = CMYKBitmap::new(page.size())?;
+        let samples = self.decode_samples(page)?;
+
+        let width = page.width as usize;
+        for (index, pixel) in samples.chunks_exact(page.channel_count()).enumerate() {
+            let color = CMYK {
+                c: page.scale_sample(pixel[0]),
+                m: page.scale_sample(pixel[1]),
+                y: page.scale_sample(pixel[2]),
+                k: page.scale_sample(pixel[3])
+            };
+            bitmap.set_pixel((index % width) as i32, (index / width) as i32, color);
+        }
+
+        Ok(bitmap)
+    }
+}
+
+fn read_sample(row: &[u8], index: usize, bits_per_sample: u32, byte_order: TIFFByteOrder) -> u16 {
+    match bits_per_sample {
+        8 => row[index] as u16,
+        16 => byte_order.u16_from_bytes([row[index * 2], row[index * 2 + 1]]),
+        bits => {
+            // NOTE: Samples smaller than a byte are packed starting from the most significant bit.
+            let bit_offset = index * bits as usize;
+            let shift = 8 - bits - (bit_offset % 8) as u32;
+            ((row[bit_offset / 8] >> shift) as u16) & ((1 << bits) - 1)
+        }
+    }
+}
+
+fn undo_horizontal_predictor(row: &mut [u8], samples_per_pixel: usize, bits_per_sample: u32, byte_order: TIFFByteOrder) {
+    if bits_per_sample == 8 {
+        for index in samples_per_pixel..row.len() {
+            row[index] = row[index].wrapping_add(row[index - samples_per_pixel]);
+        }
+        return;
+    }
+
+    let read = |row: &[u8], index: usize| byte_order.u16_from_bytes([row[index * 2], row[index * 2 + 1]]);
+    for index in samples_per_pixel..row.len() / 2 {
+        let value = read(row, index).wrapping_add(read(row, index - samples_per_pixel));
+        row[index * 2..index * 2 + 2].copy_from_slice(&byte_order.u16_to_bytes(value));
+    }
+}
+
+fn decompress_packbits(data: &[u8], expected_size: usize) -> Result<Vec<u8>, Error> {
+    let mut output = Vec::new();
+    if output.try_reserve_exact(expected_size).is_err() {
+        return Err(Error::OutOfMemory);
+    }
+
+    let mut offset = 0;
+    while output.len() < expected_size && offset < data.len() {
+        let header = data[offset] as i8;
+        offset += 1;
+        match header {
+            0..=127 => {
+                let length = header as usize + 1;
+                let Some(literal) = data.get(offset..offset + length) else {
+                    return Err(Error::Truncated);
+                };
+                output.extend_from_slice(literal);
+                offset += length;
+            }
+            -127..=-1 => {
+                let Some(&byte) = data.get(offset) else {
+                    return Err(Error::Truncated);
+                };
+                output.extend(std::iter::repeat_n(byte, (1 - header as i32) as usize));
+                offset += 1;
+            }
+            // NOTE: -128 is a no-op.
+            _ => {}
+        }
+    }
+
+    Ok(output)
+}
+
+#[derive (Debug, Copy, Clone)]
+struct LZWEntry {
+    prefix: u16,
+    byte: u8,
+    first_byte: u8,
+    length: u16
+}
+
+struct LZWReader<'a> {
+    data: &'a [u8],
+    offset: usize,
+    bit_buffer: u32,
+    bit_count: u32
+}
+
+impl<'a> LZWReader<'a> {
+    /// Reads the next code, most significant bit first. Returns `None` once the data runs out.
+    fn read_code(&mut self, code_size: u32) -> Option<usize> {
+        while self.bit_count < code_size {
+            let byte = *self.data.get(self.offset)?;
+            self.offset += 1;
+            self.bit_buffer = (self.bit_buffer << 8) | byte as u32;
+            self.bit_count += 8;
+        }
+        self.bit_count -= code_size;
+        let code = (self.bit_buffer >> self.bit_count) & ((1 << code_size) - 1);
+        self.bit_buffer &= (1 << self.bit_count) - 1;
+        Some(code as usize)
+    }
+}
+
+fn decompress_lzw(data: &[u8], expected_size: usize) -> Result<Vec<u8>, Error> {
+    // NOTE: This is how libtiff tells apart data written by its pre-5.0 LZW encoder, which used a different bit order.
+    if data.len() >= 2 && data[0] == 0 && data[1] & 1 != 0 {
+        return Err(Error::Unsupported("Old-style TIFF LZW"));
+    }
+
+    let mut output = Vec::new();
+    if output.try_reserve_exact(expected_size).is_err() {
+        return Err(Error::OutOfMemory);
+    }
+    let mut table: Vec<LZWEntry> = (0..LZW_FIRST_CODE).map(|code| LZWEntry { prefix: 0, byte: code as u8, first_byte: code as u8, length: 1 }).collect();
+
+    let mut reader = LZWReader { data, offset: 0, bit_buffer: 0, bit_count: 0 };
+    let mut code_size = LZW_MIN_CODE_SIZE;
+    let mut previous_code: Option<usize> = None;
+    // NOTE: Some encoders leave out the end of information code, so running out of data is fine too.
+    while output.len() < expected_size {
+        let Some(code) = reader.read_code(code_size) else {
+            break;
+        };
+        if code == LZW_CLEAR_CODE {
+            table.truncate(LZW_FIRST_CODE);
+            code_size = LZW_MIN_CODE_SIZE;
+            previous_code = None;
+            continue;
+        }
+        if code == LZW_END_OF_INFORMATION {
+            break;
+        }
+
+        if let Some(previous_code) = previous_code {
+            if code > table.len() {
+                return Err(Error::InvalidData("Invalid TIFF LZW code"));
+            }
+            // NOTE: A code may refer to the entry it is about to create, which always starts with the previous string.
+            let first_byte = table.get(code).unwrap_or(&table[previous_code]).first_byte;
+            if table.len() < 1 << LZW_MAX_CODE_SIZE {
+                let previous_entry = table[previous_code];
+                table.push(LZWEntry {
+                    prefix: previous_code as u16,
+                    byte: first_byte,
+                    first_byte: previous_entry.first_byte,
+                    length: previous_entry.length + 1
+                });
+            }
+        }
+        let Some(entry) = table.get(code) else {
+            return Err(Error::InvalidData("Invalid TIFF LZW code"));
+        };
+
+        let start = output.len();
+        output.resize(start + entry.length as usize, 0);
+        let mut current_code = code;
+        for position in (start..output.len()).rev() {
+            output[position] = table[current_code].byte;
+            current_code = table[current_code].prefix as usize;
+        }
+        previous_code = Some(code);
+
+        // NOTE: TIFF switches to the next code size one code early.
+        if table.len() + 1 >= 1 << code_size && code_size < LZW_MAX_CODE_SIZE {
+            code_size += 1;
+        }
+    }
+
+    Ok(output)
+}
+
+impl<'a> ImageDecoderPlugin for TIFFImageDecoderPlugin<'a> {
+    fn size(&self) -> IntSize {
+        self.context.pages[0].size()
+    }
+
+    fn frame_count(&self) -> usize {
+        self.context.pages.len()
+    }
+
+    fn frame_with_ideal_size(&mut self, index: usize, _: Option<IntSize>) -> Result<ImageFrameDescriptor, Error> {
+        let Some(page) = self.context.pages.get(index) else {
+            return Err(Error::FrameIndexOutOfRange);
+        };
+
+        if let Some((bitmap_index, bitmap)) = &self.context.bitmap {
+            if *bitmap_index == index {
+                return Ok(ImageFrameDescriptor {
+                    image: bitmap.clone(),
+                    duration: 0
+                });
+            }
+        }
+
+        let bitmap = self.context.render(page)?;
+        self.context.bitmap = Some((index, bitmap.clone()));
+
+        Ok(ImageFrameDescriptor {
+            image: bitmap,
+            duration: 0
+        })
+    }
+
+    // NOTE: Pages can differ, so we only report Grayscale or CMYK when every page is, as RGB works for all of them.
+    fn natural_frame_format(&self) -> NaturalFrameFormat {
+        let format = self.context.pages[0].natural_frame_format();
+        if self.context.pages.iter().all(|page| page.natural_frame_format() == format) {
+            format
+        } else {
+            NaturalFrameFormat::RGB
+        }
+    }
+
+    fn cmyk_frame(&mut self, index: usize) -> Result<CMYKBitmap, Error> {
+        let Some(page) = self.context.pages.get(index) else {
+            return Err(Error::FrameIndexOutOfRange);
+        };
+        if page.natural_frame_format() != NaturalFrameFormat::CMYK {
+            return Err(Error::Unsupported("TIFF page is not in CMYK"));
+        }
+
+        if let Some((bitmap_index, bitmap)) = &self.context.cmyk_bitmap {
+            if *bitmap_index == index {
+                return Ok(bitmap.clone());
+            }
+        }
+
+        let bitmap = self.context.render_cmyk(page)?;
+        self.context.cmyk_bitmap = Some((index, bitmap.clone()));
+        Ok(bitmap)
+    }
+}
+
+/// # Safety
+///
+/// `bytes` must point to `size` readable bytes that outlive the returned decoder,
+/// and `out_decoder` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn tiff_image_decoder_plugin_new(bytes: *const u8, size: usize, out_decoder: *mut *mut c_void) -> ErrorCode {
+    ffi_call(|| unsafe {
+        if bytes.is_null() {
+            return Err(Error::InvalidArgument("Bytes are null"));
+        }
+        if out_decoder.is_null() {
+            return Err(Error::InvalidArgument("Output pointer is null"));
+        }
+        let bytes = std::slice::from_raw_parts(bytes, size);
+        let decoder = TIFFImageDecoderPlugin::create(bytes)?;
+        write_ffi_result(out_decoder, decoder_into_opaque(Box::new(decoder)))
+    })
+}
+
+/// # Safety
+///
+/// `opaque_decoder` must either be null or come from `tiff_image_decoder_plugin_new` and not be freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn tiff_image_decoder_plugin_free(opaque_decoder: *mut c_void) -> ErrorCode {
+    ffi_call(|| {
+        if !opaque_decoder.is_null() {
+            let decoder: Box<Box<dyn ImageDecoderPlugin>> = unsafe { Box::from_raw(opaque_decoder as *mut _) };
+            drop(decoder);
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testsupport::assert_pixels;
+    use crate::Color;
+
+    // NOTE: Big-endian, with a 12x9 grayscale page, an 8x6 RGB page, a 4x3 reduced-resolution RGB page and a
+    //       5x4 CMYK page, all uncompressed.
+    const MULTI_PAGE_IMAGE: [u8; 900] = [
+        0x4D, 0x4D, 0x00, 0x2A, 0x00, 0x00, 0x00, 0x74, 0x14, 0x14, 0x14, 0x14, 0xDC, 0xDC, 0xDC, 0xDC, 0x14, 0x14, 0x14, 0x14,
+        0x14, 0x14, 0x14, 0x14, 0xDC, 0xDC, 0xDC, 0xDC, 0x14, 0x14, 0x14, 0x14, 0x14, 0x14, 0x14, 0x14, 0xDC, 0xDC, 0xDC, 0xDC,
+        0x14, 0x14, 0x14, 0x14, 0xDC, 0xDC, 0xDC, 0xDC, 0x14, 0x14, 0x14, 0x14, 0xDC, 0xDC, 0xDC, 0xDC, 0xDC, 0xDC, 0xDC, 0xDC,
+        0x14, 0x14, 0x14, 0x14, 0xDC, 0xDC, 0xDC, 0xDC, 0xDC, 0xDC, 0xDC, 0xDC, 0x14, 0x14, 0x14, 0x14, 0xDC, 0xDC, 0xDC, 0xDC,
+        0x14, 0x14, 0x14, 0x14, 0xDC, 0xDC, 0xDC, 0xDC, 0x14, 0x14, 0x14, 0x14, 0x14, 0x14, 0x14, 0x14, 0xDC, 0xDC, 0xDC, 0xDC,
+        0x14, 0x14, 0x14, 0x14, 0x14, 0x14, 0x14, 0x14, 0xDC, 0xDC, 0xDC, 0xDC, 0x14, 0x14, 0x14, 0x14, 0x00, 0x0A, 0x00, 0xFE,
+        0x00, 0x04, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x01, 0x00, 0x0C,
+        0x00, 0x00, 0x01, 0x01, 0x00, 0x03, 0x00, 0x00, 0x00, 0x01, 0x00, 0x09, 0x00, 0x00, 0x01, 0x02, 0x00, 0x03, 0x00, 0x00,
+        0x00, 0x01, 0x00, 0x08, 0x00, 0x00, 0x01, 0x03, 0x00, 0x03, 0x00, 0x00, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x01, 0x06,
+        0x00, 0x03, 0x00, 0x00, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x01, 0x11, 0x00, 0x04, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00,
+        0x00, 0x08, 0x01, 0x15, 0x00, 0x03, 0x00, 0x00, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x01, 0x16, 0x00, 0x03, 0x00, 0x00,
+        0x00, 0x01, 0x00, 0x09, 0x00, 0x00, 0x01, 0x17, 0x00, 0x04, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x6C, 0x00, 0x00,
+        0x01, 0x88, 0x00, 0x00, 0x00, 0x10, 0x00, 0x08, 0x20, 0x00, 0x10, 0x30, 0x00, 0x18, 0x40, 0x00, 0x20, 0x50, 0x00, 0x28,
+        0x60, 0x00, 0x30, 0x70, 0x00, 0x38, 0x00, 0x14, 0x08, 0x10, 0x14, 0x10, 0x20, 0x14, 0x18, 0x30, 0x14, 0x20, 0x40, 0x14,
+        0x28, 0x50, 0x14, 0x30, 0x60, 0x14, 0x38, 0x70, 0x14, 0x40, 0x00, 0x28, 0x10, 0x10, 0x28, 0x18, 0x20, 0x28, 0x20, 0x30,
+        0x28, 0x28, 0x40, 0x28, 0x30, 0x50, 0x28, 0x38, 0x60, 0x28, 0x40, 0x70, 0x28, 0x48, 0x00, 0x3C, 0x18, 0x10, 0x3C, 0x20,
+        0x20, 0x3C, 0x28, 0x30, 0x3C, 0x30, 0x40, 0x3C, 0x38, 0x50, 0x3C, 0x40, 0x60, 0x3C, 0x48, 0x70, 0x3C, 0x50, 0x00, 0x50,
+        0x20, 0x10, 0x50, 0x28, 0x20, 0x50, 0x30, 0x30, 0x50, 0x38, 0x40, 0x50, 0x40, 0x50, 0x50, 0x48, 0x60, 0x50, 0x50, 0x70,
+        0x50, 0x58, 0x00, 0x64, 0x28, 0x10, 0x64, 0x30, 0x20, 0x64, 0x38, 0x30, 0x64, 0x40, 0x40, 0x64, 0x48, 0x50, 0x64, 0x50,
+        0x60, 0x64, 0x58, 0x70, 0x64, 0x60, 0x00, 0x08, 0x00, 0x08, 0x00, 0x08, 0x00, 0x0A, 0x00, 0xFE, 0x00, 0x04, 0x00, 0x00,
+        0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x01, 0x00, 0x08, 0x00, 0x00, 0x01, 0x01,
+        0x00, 0x03, 0x00, 0x00, 0x00, 0x01, 0x00, 0x06, 0x00, 0x00, 0x01, 0x02, 0x00, 0x03, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00,
+        0x01, 0x82, 0x01, 0x03, 0x00, 0x03, 0x00, 0x00, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x01, 0x06, 0x00, 0x03, 0x00, 0x00,
+        0x00, 0x01, 0x00, 0x02, 0x00, 0x00, 0x01, 0x11, 0x00, 0x04, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0xF2, 0x01, 0x15,
+        0x00, 0x03, 0x00, 0x00, 0x00, 0x01, 0x00, 0x03, 0x00, 0x00, 0x01, 0x16, 0x00, 0x03, 0x00, 0x00, 0x00, 0x01, 0x00, 0x06,
+        0x00, 0x00, 0x01, 0x17, 0x00, 0x04, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x90, 0x00, 0x00, 0x02, 0x30, 0x00, 0x00,
+        0x00, 0x10, 0x00, 0x08, 0x20, 0x00, 0x10, 0x30, 0x00, 0x18, 0x00, 0x14, 0x08, 0x10, 0x14, 0x10, 0x20, 0x14, 0x18, 0x30,
+        0x14, 0x20, 0x00, 0x28, 0x10, 0x10, 0x28, 0x18, 0x20, 0x28, 0x20, 0x30, 0x28, 0x28, 0x00, 0x08, 0x00, 0x08, 0x00, 0x08,
+        0x00, 0x0A, 0x00, 0xFE, 0x00, 0x04, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x01, 0x00, 0x00, 0x03, 0x00, 0x00,
+        0x00, 0x01, 0x00, 0x04, 0x00, 0x00, 0x01, 0x01, 0x00, 0x03, 0x00, 0x00, 0x00, 0x01, 0x00, 0x03, 0x00, 0x00, 0x01, 0x02,
+        0x00, 0x03, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x02, 0x2A, 0x01, 0x03, 0x00, 0x03, 0x00, 0x00, 0x00, 0x01, 0x00, 0x01,
+        0x00, 0x00, 0x01, 0x06, 0x00, 0x03, 0x00, 0x00, 0x00, 0x01, 0x00, 0x02, 0x00, 0x00, 0x01, 0x11, 0x00, 0x04, 0x00, 0x00,
+        0x00, 0x01, 0x00, 0x00, 0x02, 0x06, 0x01, 0x15, 0x00, 0x03, 0x00, 0x00, 0x00, 0x01, 0x00, 0x03, 0x00, 0x00, 0x01, 0x16,
+        0x00, 0x03, 0x00, 0x00, 0x00, 0x01, 0x00, 0x03, 0x00, 0x00, 0x01, 0x17, 0x00, 0x04, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00,
+        0x00, 0x24, 0x00, 0x00, 0x03, 0x06, 0x00, 0x00, 0x80, 0x00, 0x1E, 0x00, 0x80, 0x0A, 0x3C, 0x00, 0x80, 0x14, 0x5A, 0x00,
+        0x80, 0x1E, 0x78, 0x00, 0x80, 0x28, 0x00, 0x28, 0x80, 0x0A, 0x1E, 0x28, 0x80, 0x14, 0x3C, 0x28, 0x80, 0x1E, 0x5A, 0x28,
+        0x80, 0x28, 0x78, 0x28, 0x80, 0x32, 0x00, 0x50, 0x80, 0x14, 0x1E, 0x50, 0x80, 0x1E, 0x3C, 0x50, 0x80, 0x28, 0x5A, 0x50,
+        0x80, 0x32, 0x78, 0x50, 0x80, 0x3C, 0x00, 0x78, 0x80, 0x1E, 0x1E, 0x78, 0x80, 0x28, 0x3C, 0x78, 0x80, 0x32, 0x5A, 0x78,
+        0x80, 0x3C, 0x78, 0x78, 0x80, 0x46, 0x00, 0x08, 0x00, 0x08, 0x00, 0x08, 0x00, 0x08, 0x00, 0x0A, 0x00, 0xFE, 0x00, 0x04,
+        0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x01, 0x00, 0x05, 0x00, 0x00,
+        0x01, 0x01, 0x00, 0x03, 0x00, 0x00, 0x00, 0x01, 0x00, 0x04, 0x00, 0x00, 0x01, 0x02, 0x00, 0x03, 0x00, 0x00, 0x00, 0x04,
+        0x00, 0x00, 0x02, 0xFE, 0x01, 0x03, 0x00, 0x03, 0x00, 0x00, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x01, 0x06, 0x00, 0x03,
+        0x00, 0x00, 0x00, 0x01, 0x00, 0x05, 0x00, 0x00, 0x01, 0x11, 0x00, 0x04, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x02, 0xAE,
+        0x01, 0x15, 0x00, 0x03, 0x00, 0x00, 0x00, 0x01, 0x00, 0x04, 0x00, 0x00, 0x01, 0x16, 0x00, 0x03, 0x00, 0x00, 0x00, 0x01,
+        0x00, 0x04, 0x00, 0x00, 0x01, 0x17, 0x00, 0x04, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x50, 0x00, 0x00, 0x00, 0x00
+    ];
+
+    // NOTE: 16x12 RGB, Deflate compressed with the horizontal predictor in strips of five rows.
+    const PREDICTOR_IMAGE: [u8; 268] = [
+        0x49, 0x49, 0x2A, 0x00, 0x82, 0x00, 0x00, 0x00, 0x78, 0x9C, 0x63, 0x60, 0x60, 0x10, 0x60, 0xE0, 0x20, 0x1E, 0x31, 0x88,
+        0x90, 0xA0, 0x18, 0xA4, 0x5E, 0x43, 0x80, 0x34, 0xF5, 0x36, 0x12, 0xA4, 0xA9, 0x0F, 0x50, 0x20, 0x49, 0x3D, 0x00, 0xAF,
+        0xC5, 0x08, 0x21, 0x00, 0x78, 0x9C, 0x63, 0x48, 0xD1, 0x10, 0x60, 0xE0, 0x20, 0x1E, 0x31, 0x54, 0x18, 0x90, 0xA6, 0xBE,
+        0xC7, 0x82, 0x34, 0xF5, 0x0B, 0x1C, 0x48, 0x53, 0xBF, 0xC5, 0x83, 0x24, 0xF5, 0x00, 0x36, 0x1F, 0x0A, 0xDD, 0x78, 0x9C,
+        0x63, 0x38, 0x11, 0x20, 0xC0, 0xC0, 0x41, 0x3C, 0x62, 0xB8, 0x13, 0x41, 0x92, 0x7A, 0x00, 0x25, 0x5B, 0x05, 0x1D, 0x00,
+        0x08, 0x00, 0x08, 0x00, 0x08, 0x00, 0x08, 0x00, 0x00, 0x00, 0x2C, 0x00, 0x00, 0x00, 0x4E, 0x00, 0x00, 0x00, 0x23, 0x00,
+        0x00, 0x00, 0x22, 0x00, 0x00, 0x00, 0x15, 0x00, 0x00, 0x00, 0x0B, 0x00, 0xFE, 0x00, 0x04, 0x00, 0x01, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x03, 0x00, 0x01, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x01, 0x01, 0x03, 0x00,
+        0x01, 0x00, 0x00, 0x00, 0x0C, 0x00, 0x00, 0x00, 0x02, 0x01, 0x03, 0x00, 0x03, 0x00, 0x00, 0x00, 0x64, 0x00, 0x00, 0x00,
+        0x03, 0x01, 0x03, 0x00, 0x01, 0x00, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x06, 0x01, 0x03, 0x00, 0x01, 0x00, 0x00, 0x00,
+        0x02, 0x00, 0x00, 0x00, 0x11, 0x01, 0x04, 0x00, 0x03, 0x00, 0x00, 0x00, 0x6A, 0x00, 0x00, 0x00, 0x15, 0x01, 0x03, 0x00,
+        0x01, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x16, 0x01, 0x03, 0x00, 0x01, 0x00, 0x00, 0x00, 0x05, 0x00, 0x00, 0x00,
+        0x17, 0x01, 0x04, 0x00, 0x03, 0x00, 0x00, 0x00, 0x76, 0x00, 0x00, 0x00, 0x3D, 0x01, 0x03, 0x00, 0x01, 0x00, 0x00, 0x00,
+        0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
+    ];
+
+    // NOTE: 16x12 grayscale, PackBits compressed in strips of four rows.
+    const PACKBITS_IMAGE: [u8; 248] = [
+        0x49, 0x49, 0x2A, 0x00, 0x7A, 0x00, 0x00, 0x00, 0xFD, 0x14, 0xFD, 0xDC, 0xFD, 0x14, 0xFD, 0xDC, 0xFD, 0x14, 0xFD, 0xDC,
+        0xFD, 0x14, 0xFD, 0xDC, 0xFD, 0x14, 0xFD, 0xDC, 0xFD, 0x14, 0xF9, 0xDC, 0xFD, 0x14, 0xFD, 0xDC, 0xFD, 0x14, 0xFD, 0xDC,
+        0xFD, 0x14, 0xFD, 0xDC, 0xFD, 0x14, 0xFD, 0xDC, 0xFD, 0x14, 0xFD, 0xDC, 0xF9, 0x14, 0xFD, 0xDC, 0xFD, 0x14, 0xFD, 0xDC,
+        0xFD, 0x14, 0xFD, 0xDC, 0xFD, 0x14, 0xFD, 0xDC, 0xFD, 0x14, 0xFD, 0xDC, 0xFD, 0x14, 0xF9, 0xDC, 0xFD, 0x14, 0xFD, 0xDC,
+        0xFD, 0x14, 0xFD, 0xDC, 0xFD, 0x14, 0xFD, 0xDC, 0xFD, 0x14, 0xFD, 0xDC, 0xFD, 0x14, 0xFD, 0xDC, 0xFD, 0x14, 0x08, 0x00,
+        0x00, 0x00, 0x26, 0x00, 0x00, 0x00, 0x44, 0x00, 0x00, 0x00, 0x1E, 0x00, 0x00, 0x00, 0x1E, 0x00, 0x00, 0x00, 0x1E, 0x00,
+        0x00, 0x00, 0x0A, 0x00, 0xFE, 0x00, 0x04, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x03, 0x00,
+        0x01, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x01, 0x01, 0x03, 0x00, 0x01, 0x00, 0x00, 0x00, 0x0C, 0x00, 0x00, 0x00,
+        0x02, 0x01, 0x03, 0x00, 0x01, 0x00, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x03, 0x01, 0x03, 0x00, 0x01, 0x00, 0x00, 0x00,
+        0x05, 0x80, 0x00, 0x00, 0x06, 0x01, 0x03, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x11, 0x01, 0x04, 0x00,
+        0x03, 0x00, 0x00, 0x00, 0x62, 0x00, 0x00, 0x00, 0x15, 0x01, 0x03, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00,
+        0x16, 0x01, 0x03, 0x00, 0x01, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x17, 0x01, 0x04, 0x00, 0x03, 0x00, 0x00, 0x00,
+        0x6E, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
+    ];
+
+    // NOTE: 20x18 RGB with unassociated alpha, LZW compressed in 16x16 tiles.
+    const TILED_IMAGE: [u8; 800] = [
+        0x49, 0x49, 0x2A, 0x00, 0x8A, 0x02, 0x00, 0x00, 0x80, 0x00, 0x00, 0x06, 0x47, 0xFC, 0x0A, 0x09, 0x06, 0x82, 0xC0, 0xE1,
+        0x30, 0x41, 0xE4, 0x2A, 0x1B, 0x0C, 0x87, 0x44, 0x60, 0x87, 0x88, 0x1A, 0x02, 0x28, 0x64, 0x8B, 0x45, 0x62, 0xF1, 0x98,
+        0xC2, 0xD2, 0x2B, 0x08, 0x90, 0x42, 0xA4, 0x31, 0x09, 0x23, 0xFE, 0x1F, 0x26, 0x89, 0x3F, 0xE3, 0x72, 0xB8, 0xD4, 0xB6,
+        0x3B, 0x1F, 0x91, 0x4C, 0x60, 0xF3, 0x29, 0x44, 0x96, 0x4F, 0x37, 0x85, 0x4B, 0x23, 0x13, 0xA8, 0xE2, 0x02, 0x3D, 0x18,
+        0x91, 0xC2, 0xE8, 0x50, 0x89, 0xC4, 0xDA, 0x53, 0x3C, 0xA4, 0x45, 0x67, 0xE8, 0x0A, 0x0D, 0x36, 0x53, 0x45, 0x9A, 0xD4,
+        0x69, 0x33, 0xB9, 0x74, 0xFA, 0x61, 0x33, 0xAC, 0x50, 0xE9, 0xF5, 0xB9, 0x2D, 0x4E, 0x7B, 0x1B, 0xA5, 0x80, 0x0A, 0x10,
+        0x7B, 0x1C, 0x16, 0xCB, 0x62, 0xB2, 0x43, 0x2C, 0xA3, 0xCB, 0x5D, 0xB6, 0xD5, 0x13, 0xB1, 0xC5, 0xAE, 0x27, 0x8B, 0x9D,
+        0xD6, 0x3B, 0x71, 0xB4, 0x59, 0xAD, 0x37, 0xAB, 0xE5, 0xB2, 0xDF, 0x26, 0xB7, 0x60, 0x2E, 0x13, 0xBB, 0xB5, 0xCB, 0x09,
+        0x77, 0xA0, 0x59, 0xF1, 0x57, 0xBB, 0xCD, 0xFB, 0x05, 0x8F, 0xC7, 0x63, 0xAE, 0x98, 0x7C, 0x36, 0x57, 0x27, 0x3E, 0xBC,
+        0x62, 0xEF, 0x97, 0x9C, 0x6E, 0x07, 0x23, 0x81, 0xCB, 0xE8, 0x70, 0xB9, 0x75, 0xA6, 0x67, 0x19, 0x9A, 0xCE, 0xDF, 0xF3,
+        0xF7, 0xFD, 0x16, 0x53, 0x5B, 0x98, 0xC4, 0xE9, 0xF6, 0x59, 0x0C, 0xF6, 0x83, 0x47, 0xB7, 0xC4, 0x53, 0x14, 0x10, 0x7D,
+        0xDC, 0x17, 0x7A, 0x00, 0xDF, 0xEF, 0x47, 0x9C, 0x2E, 0x24, 0x33, 0x8B, 0x2A, 0xDD, 0xC5, 0xB9, 0x27, 0x8E, 0x5F, 0x36,
+        0x3B, 0xC9, 0xE0, 0x6F, 0x3A, 0x5B, 0xEE, 0x9F, 0x0F, 0x8D, 0xD7, 0x93, 0x71, 0xF9, 0x93, 0xBE, 0x77, 0x2B, 0xB9, 0xCF,
+        0xA0, 0x70, 0x7A, 0x7D, 0x1E, 0xA7, 0x67, 0xB1, 0xD6, 0xF3, 0x7A, 0x7B, 0x7D, 0xEF, 0x67, 0xAF, 0xD6, 0xB4, 0xE8, 0x78,
+        0xBC, 0xBE, 0x4F, 0x27, 0xA3, 0xED, 0xC7, 0xF4, 0x7B, 0xBB, 0xBF, 0xAF, 0x02, 0x02, 0x02, 0x00, 0x80, 0x2D, 0x00, 0x06,
+        0x44, 0x04, 0x0A, 0x09, 0x06, 0x82, 0xC0, 0xD0, 0x00, 0x08, 0x64, 0x36, 0x1D, 0x0F, 0x88, 0x44, 0x62, 0x51, 0x38, 0x6C,
+        0x22, 0x2D, 0x0A, 0x84, 0x45, 0x23, 0x51, 0xB8, 0xE4, 0x32, 0x2F, 0x07, 0x8C, 0x42, 0xA3, 0xB2, 0x39, 0x24, 0x56, 0x43,
+        0x20, 0x94, 0x42, 0xE4, 0xB2, 0xB8, 0xDC, 0x7E, 0x13, 0x29, 0x96, 0x4C, 0x62, 0x72, 0xE9, 0x74, 0xCA, 0x6D, 0x0F, 0x5A,
+        0x14, 0x20, 0xF3, 0xA8, 0x2C, 0xF2, 0x73, 0x04, 0x9B, 0xD0, 0x40, 0x13, 0xF9, 0xEC, 0xEE, 0x8D, 0x2A, 0xA1, 0x4C, 0xA8,
+        0x94, 0xB9, 0xF4, 0xF2, 0x93, 0x36, 0xA6, 0x51, 0xE8, 0x94, 0xFA, 0x55, 0x36, 0xA5, 0x4E, 0xAA, 0x4B, 0x2A, 0x34, 0x5A,
+        0xE5, 0x66, 0xB4, 0xA0, 0x83, 0xD8, 0x20, 0xB6, 0x25, 0xA5, 0x8A, 0xBD, 0x2B, 0xB2, 0xD8, 0x6D, 0x56, 0x3A, 0x05, 0x9E,
+        0x49, 0x69, 0xB6, 0x5C, 0x6E, 0x16, 0xEB, 0x7D, 0x92, 0xED, 0x6B, 0xBA, 0x48, 0xE0, 0x20, 0x00, 0x80, 0x00, 0x14, 0x06,
+        0x47, 0xFC, 0x0A, 0x09, 0x06, 0x82, 0xC0, 0xE1, 0x30, 0x41, 0xE4, 0x2A, 0x1B, 0x0C, 0x87, 0x44, 0x60, 0x87, 0x88, 0x1A,
+        0x02, 0x28, 0x64, 0x8B, 0x45, 0x62, 0xF1, 0x98, 0xC2, 0xD2, 0x2B, 0x08, 0x90, 0x42, 0xA4, 0x31, 0x09, 0x23, 0xFE, 0x1F,
+        0x26, 0x89, 0x3F, 0xE3, 0x72, 0xB8, 0xD4, 0xB6, 0x3B, 0x1F, 0x00, 0x4C, 0x66, 0x53, 0x39, 0xA4, 0xD6, 0x6D, 0x37, 0x9C,
+        0x4E, 0x67, 0x53, 0xB9, 0xE4, 0xF6, 0x7D, 0x3F, 0xA0, 0x50, 0x68, 0x54, 0x3A, 0x25, 0x16, 0x8D, 0x47, 0xA4, 0x52, 0x69,
+        0x54, 0xBA, 0x65, 0x36, 0x9D, 0x4F, 0xA8, 0x54, 0x6A, 0x55, 0x3A, 0xA5, 0x56, 0xAD, 0x57, 0xAC, 0x54, 0x60, 0x20, 0x00,
+        0x80, 0x2D, 0x14, 0x06, 0x44, 0x04, 0x0A, 0x09, 0x06, 0x82, 0xC0, 0xD0, 0x00, 0x08, 0x64, 0x36, 0x1D, 0x0F, 0x88, 0x44,
+        0x62, 0x51, 0x38, 0x6C, 0x22, 0x2D, 0x0A, 0x84, 0x45, 0x23, 0x51, 0xB8, 0xE4, 0x76, 0x3D, 0x1F, 0x90, 0x48, 0x64, 0x52,
+        0x39, 0x24, 0x96, 0x4D, 0x27, 0x94, 0x4A, 0x65, 0x52, 0xB9, 0x64, 0xB6, 0x5D, 0x2F, 0x98, 0x4C, 0x66, 0x53, 0x39, 0xA4,
+        0xD6, 0x6D, 0x37, 0x9C, 0x4E, 0x67, 0x53, 0xA8, 0x08, 0x00, 0x08, 0x00, 0x08, 0x00, 0x08, 0x00, 0x08, 0x00, 0x08, 0x00,
+        0x00, 0x00, 0x28, 0x01, 0x00, 0x00, 0xB4, 0x01, 0x00, 0x00, 0x1C, 0x02, 0x00, 0x00, 0x1F, 0x01, 0x00, 0x00, 0x8B, 0x00,
+        0x00, 0x00, 0x67, 0x00, 0x00, 0x00, 0x45, 0x00, 0x00, 0x00, 0x0C, 0x00, 0xFE, 0x00, 0x04, 0x00, 0x01, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x03, 0x00, 0x01, 0x00, 0x00, 0x00, 0x14, 0x00, 0x00, 0x00, 0x01, 0x01, 0x03, 0x00,
+        0x01, 0x00, 0x00, 0x00, 0x12, 0x00, 0x00, 0x00, 0x02, 0x01, 0x03, 0x00, 0x04, 0x00, 0x00, 0x00, 0x62, 0x02, 0x00, 0x00,
+        0x03, 0x01, 0x03, 0x00, 0x01, 0x00, 0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x06, 0x01, 0x03, 0x00, 0x01, 0x00, 0x00, 0x00,
+        0x02, 0x00, 0x00, 0x00, 0x15, 0x01, 0x03, 0x00, 0x01, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x42, 0x01, 0x03, 0x00,
+        0x01, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x43, 0x01, 0x03, 0x00, 0x01, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00,
+        0x44, 0x01, 0x04, 0x00, 0x04, 0x00, 0x00, 0x00, 0x6A, 0x02, 0x00, 0x00, 0x45, 0x01, 0x04, 0x00, 0x04, 0x00, 0x00, 0x00,
+        0x7A, 0x02, 0x00, 0x00, 0x52, 0x01, 0x03, 0x00, 0x01, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
+    ];
+
+    // NOTE: Big-endian 14x6 RGB with associated alpha, LZW compressed in strips of four rows.
+    const PREMULTIPLIED_IMAGE: [u8; 296] = [
+        0x4D, 0x4D, 0x00, 0x2A, 0x00, 0x00, 0x00, 0x9E, 0x80, 0x00, 0x00, 0x06, 0x47, 0xFC, 0x0A, 0x09, 0x06, 0x82, 0xC0, 0xE1,
+        0x30, 0x41, 0xE4, 0x2A, 0x1B, 0x0C, 0x87, 0x44, 0x62, 0x03, 0x24, 0x04, 0x36, 0x29, 0x16, 0x8A, 0x80, 0x22, 0x90, 0x88,
+        0xE4, 0x2A, 0x3B, 0x10, 0x90, 0x3F, 0xE1, 0xF2, 0x28, 0x94, 0x92, 0x2F, 0x1A, 0x8C, 0xC9, 0xE3, 0x71, 0xE9, 0x64, 0x1E,
+        0x5B, 0x24, 0x90, 0xC8, 0xE6, 0x51, 0x19, 0x54, 0xA6, 0x6D, 0x1F, 0x85, 0xCE, 0x61, 0x13, 0x39, 0x8C, 0x96, 0x31, 0x3F,
+        0x94, 0x46, 0x20, 0x20, 0x80, 0x00, 0x00, 0x06, 0x47, 0xFC, 0x0A, 0x09, 0x06, 0x82, 0xC0, 0xE1, 0x30, 0x41, 0xE4, 0x2A,
+        0x1B, 0x0C, 0x87, 0x44, 0x62, 0x03, 0x24, 0x04, 0x36, 0x29, 0x16, 0x8A, 0x80, 0x22, 0x90, 0x88, 0xE4, 0x2A, 0x3B, 0x10,
+        0x90, 0x3F, 0xE1, 0xF2, 0x28, 0x94, 0x92, 0x2F, 0x1A, 0x8C, 0xC9, 0xE2, 0x90, 0x10, 0x00, 0x08, 0x00, 0x08, 0x00, 0x08,
+        0x00, 0x08, 0x00, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x54, 0x00, 0x00, 0x00, 0x4C, 0x00, 0x00, 0x00, 0x32, 0x00, 0x0B,
+        0x00, 0xFE, 0x00, 0x04, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x01,
+        0x00, 0x0E, 0x00, 0x00, 0x01, 0x01, 0x00, 0x03, 0x00, 0x00, 0x00, 0x01, 0x00, 0x06, 0x00, 0x00, 0x01, 0x02, 0x00, 0x03,
+        0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x86, 0x01, 0x03, 0x00, 0x03, 0x00, 0x00, 0x00, 0x01, 0x00, 0x05, 0x00, 0x00,
+        0x01, 0x06, 0x00, 0x03, 0x00, 0x00, 0x00, 0x01, 0x00, 0x02, 0x00, 0x00, 0x01, 0x11, 0x00, 0x04, 0x00, 0x00, 0x00, 0x02,
+        0x00, 0x00, 0x00, 0x8E, 0x01, 0x15, 0x00, 0x03, 0x00, 0x00, 0x00, 0x01, 0x00, 0x04, 0x00, 0x00, 0x01, 0x16, 0x00, 0x03,
+        0x00, 0x00, 0x00, 0x01, 0x00, 0x04, 0x00, 0x00, 0x01, 0x17, 0x00, 0x04, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x96,
+        0x01, 0x52, 0x00, 0x03, 0x00, 0x00, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
+    ];
+
+    // NOTE: Two 6x5 CMYK pages, the first uncompressed and the second PackBits compressed with separate planes.
+    const CMYK_IMAGE: [u8; 516] = [
+        0x49, 0x49, 0x2A, 0x00, 0x88, 0x00, 0x00, 0x00, 0x00, 0x00, 0x80, 0x00, 0x1E, 0x00, 0x80, 0x0A, 0x3C, 0x00, 0x80, 0x14,
+        0x5A, 0x00, 0x80, 0x1E, 0x78, 0x00, 0x80, 0x28, 0x96, 0x00, 0x80, 0x32, 0x00, 0x28, 0x80, 0x0A, 0x1E, 0x28, 0x80, 0x14,
+        0x3C, 0x28, 0x80, 0x1E, 0x5A, 0x28, 0x80, 0x28, 0x78, 0x28, 0x80, 0x32, 0x96, 0x28, 0x80, 0x3C, 0x00, 0x50, 0x80, 0x14,
+        0x1E, 0x50, 0x80, 0x1E, 0x3C, 0x50, 0x80, 0x28, 0x5A, 0x50, 0x80, 0x32, 0x78, 0x50, 0x80, 0x3C, 0x96, 0x50, 0x80, 0x46,
+        0x00, 0x78, 0x80, 0x1E, 0x1E, 0x78, 0x80, 0x28, 0x3C, 0x78, 0x80, 0x32, 0x5A, 0x78, 0x80, 0x3C, 0x78, 0x78, 0x80, 0x46,
+        0x96, 0x78, 0x80, 0x50, 0x00, 0xA0, 0x80, 0x28, 0x1E, 0xA0, 0x80, 0x32, 0x3C, 0xA0, 0x80, 0x3C, 0x5A, 0xA0, 0x80, 0x46,
+        0x78, 0xA0, 0x80, 0x50, 0x96, 0xA0, 0x80, 0x5A, 0x08, 0x00, 0x08, 0x00, 0x08, 0x00, 0x08, 0x00, 0x0A, 0x00, 0xFE, 0x00,
+        0x04, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x03, 0x00, 0x01, 0x00, 0x00, 0x00, 0x06, 0x00,
+        0x00, 0x00, 0x01, 0x01, 0x03, 0x00, 0x01, 0x00, 0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x02, 0x01, 0x03, 0x00, 0x04, 0x00,
+        0x00, 0x00, 0x80, 0x00, 0x00, 0x00, 0x03, 0x01, 0x03, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x06, 0x01,
+        0x03, 0x00, 0x01, 0x00, 0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x11, 0x01, 0x04, 0x00, 0x01, 0x00, 0x00, 0x00, 0x08, 0x00,
+        0x00, 0x00, 0x15, 0x01, 0x03, 0x00, 0x01, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x16, 0x01, 0x03, 0x00, 0x01, 0x00,
+        0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x17, 0x01, 0x04, 0x00, 0x01, 0x00, 0x00, 0x00, 0x78, 0x00, 0x00, 0x00, 0x7A, 0x01,
+        0x00, 0x00, 0x1D, 0x00, 0x1E, 0x3C, 0x5A, 0x78, 0x96, 0x00, 0x1E, 0x3C, 0x5A, 0x78, 0x96, 0x00, 0x1E, 0x3C, 0x5A, 0x78,
+        0x96, 0x00, 0x1E, 0x3C, 0x5A, 0x78, 0x96, 0x00, 0x1E, 0x3C, 0x5A, 0x78, 0x96, 0x00, 0xFB, 0x00, 0xFB, 0x28, 0xFB, 0x50,
+        0xFB, 0x78, 0xFB, 0xA0, 0xE3, 0x80, 0x1D, 0x00, 0x0A, 0x14, 0x1E, 0x28, 0x32, 0x0A, 0x14, 0x1E, 0x28, 0x32, 0x3C, 0x14,
+        0x1E, 0x28, 0x32, 0x3C, 0x46, 0x1E, 0x28, 0x32, 0x3C, 0x46, 0x50, 0x28, 0x32, 0x3C, 0x46, 0x50, 0x5A, 0x00, 0x08, 0x00,
+        0x08, 0x00, 0x08, 0x00, 0x08, 0x00, 0x06, 0x01, 0x00, 0x00, 0x26, 0x01, 0x00, 0x00, 0x30, 0x01, 0x00, 0x00, 0x32, 0x01,
+        0x00, 0x00, 0x1F, 0x00, 0x00, 0x00, 0x0A, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00, 0x0B, 0x00,
+        0xFE, 0x00, 0x04, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x03, 0x00, 0x01, 0x00, 0x00, 0x00,
+        0x06, 0x00, 0x00, 0x00, 0x01, 0x01, 0x03, 0x00, 0x01, 0x00, 0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x02, 0x01, 0x03, 0x00,
+        0x04, 0x00, 0x00, 0x00, 0x52, 0x01, 0x00, 0x00, 0x03, 0x01, 0x03, 0x00, 0x01, 0x00, 0x00, 0x00, 0x05, 0x80, 0x00, 0x00,
+        0x06, 0x01, 0x03, 0x00, 0x01, 0x00, 0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x11, 0x01, 0x04, 0x00, 0x04, 0x00, 0x00, 0x00,
+        0x5A, 0x01, 0x00, 0x00, 0x15, 0x01, 0x03, 0x00, 0x01, 0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x16, 0x01, 0x03, 0x00,
+        0x01, 0x00, 0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x17, 0x01, 0x04, 0x00, 0x04, 0x00, 0x00, 0x00, 0x6A, 0x01, 0x00, 0x00,
+        0x1C, 0x01, 0x03, 0x00, 0x01, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00
+    ];
+
+    fn rgb(x: u32, y: u32) -> [u8; 4] {
+        [(x * 16) as u8, (y * 20) as u8, ((x + y) * 8) as u8, 255]
+    }
+
+    fn gray(x: u32, y: u32) -> [u8; 4] {
+        let gray = ((x / 4 + y / 3) % 2 * 200 + 20) as u8;
+        [gray, gray, gray, 255]
+    }
+
+    fn cmyk(x: u32, y: u32) -> CMYK {
+        CMYK {
+            c: (x * 30) as u8,
+            m: (y * 40) as u8,
+            y: 128,
+            k: ((x + y) * 10) as u8
+        }
+    }
+
+    fn assert_cmyk_pixels(bitmap: &CMYKBitmap, width: i32, height: i32) {
+        assert_eq!((bitmap.size.width, bitmap.size.height), (width, height));
+        for y in 0..height {
+            for x in 0..width {
+                assert_eq!(bitmap.get_pixel(x, y), cmyk(x as u32, y as u32), "pixel ({x}, {y})");
+            }
+        }
+    }
+
+    fn decode(bytes: &[u8]) -> Bitmap {
+        TIFFImageDecoderPlugin::create(bytes).unwrap().frame(0).unwrap().image
+    }
+
+    #[test]
+    fn decodes_each_page_as_a_frame() {
+        let mut decoder = TIFFImageDecoderPlugin::create(&MULTI_PAGE_IMAGE).unwrap();
+        assert_eq!((decoder.size().width, decoder.size().height), (12, 9));
+        assert_eq!(decoder.frame_count(), 3);
+        assert_eq!(decoder.natural_frame_format(), NaturalFrameFormat::RGB);
+
+        assert_pixels(&decoder.frame(0).unwrap().image, 12, 9, gray);
+        let frame = decoder.frame(1).unwrap();
+        assert!(matches!(frame.image.format, BitmapFormat::BGRx8888));
+        assert_pixels(&frame.image, 8, 6, rgb);
+        assert_pixels(&decoder.frame(2).unwrap().image, 5, 4, |x, y| {
+            let (red, green, blue) = cmyk(x, y).to_low_quality_rgb();
+            [red, green, blue, 255]
+        });
+        assert_pixels(&decoder.frame(1).unwrap().image, 8, 6, rgb);
+        assert_eq!(decoder.frame(3).err(), Some(Error::FrameIndexOutOfRange));
+    }
+
+    #[test]
+    fn decodes_cmyk_frames_of_cmyk_pages_only() {
+        let mut decoder = TIFFImageDecoderPlugin::create(&MULTI_PAGE_IMAGE).unwrap();
+        assert_cmyk_pixels(&decoder.cmyk_frame(2).unwrap(), 5, 4);
+        assert_eq!(decoder.cmyk_frame(1).err(), Some(Error::Unsupported("TIFF page is not in CMYK")));
+        assert_eq!(decoder.cmyk_frame(3).err(), Some(Error::FrameIndexOutOfRange));
+
+        let mut decoder = TIFFImageDecoderPlugin::create(&CMYK_IMAGE).unwrap();
+        assert_eq!(decoder.natural_frame_format(), NaturalFrameFormat::CMYK);
+        assert_cmyk_pixels(&decoder.cmyk_frame(0).unwrap(), 6, 5);
+        assert_cmyk_pixels(&decoder.cmyk_frame(1).unwrap(), 6, 5);
+        assert_cmyk_pixels(&decoder.cmyk_frame(0).unwrap(), 6, 5);
+    }
+
+    #[test]
+    fn decodes_deflate_with_horizontal_predictor() {
+        assert_pixels(&decode(&PREDICTOR_IMAGE), 16, 12, rgb);
+    }
+
+    #[test]
+    fn decodes_packbits() {
+        let mut decoder = TIFFImageDecoderPlugin::create(&PACKBITS_IMAGE).unwrap();
+        assert_eq!(decoder.natural_frame_format(), NaturalFrameFormat::Grayscale);
+        assert_pixels(&decoder.frame(0).unwrap().image, 16, 12, gray);
+    }
+
+    #[test]
+    fn decodes_lzw_tiles_with_alpha() {
+        let image = decode(&TILED_IMAGE);
+        assert!(matches!(image.format, BitmapFormat::BGRA8888));
+        assert_pixels(&image, 20, 18, |x, y| [(x / 5 * 60) as u8, (y / 6 * 80) as u8, 100, if x < 10 { 255 } else { 128 }]);
+    }
+
+    #[test]
+    fn unpremultiplies_associated_alpha() {
+        let image = decode(&PREMULTIPLIED_IMAGE);
+        assert_eq!((image.size.width, image.size.height), (14, 6));
+        for y in 0..6 {
+            for x in 0..14 {
+                let color = Color::from(image.get_pixel(x, y));
+                let expected = [(x / 5 * 60) as u8, (y / 6 * 80) as u8, 100u8];
+                let actual = [color.red(), color.green(), color.blue()];
+                for (actual, expected) in actual.iter().zip(expected) {
+                    assert!(actual.abs_diff(expected) <= 1, "pixel ({x}, {y}): {actual} != {expected}");
+                }
+                assert_eq!(color.alpha(), if x < 10 { 255 } else { 128 });
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_files() {
+        assert_eq!(TIFFImageDecoderPlugin::create(&PACKBITS_IMAGE[..4]).err(), Some(Error::Truncated));
+
+        let mut data = PACKBITS_IMAGE;
+        data[2] = 43;
+        assert_eq!(TIFFImageDecoderPlugin::create(&data).err(), Some(Error::Unsupported("BigTIFF")));
+
+        // NOTE: Make the first strip run past the end of the file.
+        let mut decoder = TIFFImageDecoderPlugin::create(&PREDICTOR_IMAGE).unwrap();
+        decoder.context.pages[0].chunk_byte_counts[0] = PREDICTOR_IMAGE.len() as u32;
+        assert_eq!(decoder.frame(0).err(), Some(Error::Truncated));
+
+        unsafe {
+            assert_eq!(tiff_image_decoder_plugin_new(PACKBITS_IMAGE.as_ptr(), PACKBITS_IMAGE.len(), std::ptr::null_mut()), ErrorCode::InvalidArgument);
+        }
+    }
+
+    #[test]
+    fn rejects_pages_with_too_many_chunks() {
+        // NOTE: 1x1 tiles across the largest possible page, with a separate plane for each of the samples.
+        let tags = BTreeMap::from([
+            (TIFF_TAG_IMAGE_WIDTH, vec![i32::MAX as u32]),
+            (TIFF_TAG_IMAGE_LENGTH, vec![i32::MAX as u32]),
+            (TIFF_TAG_BITS_PER_SAMPLE, vec![8]),
+            (TIFF_TAG_PHOTOMETRIC_INTERPRETATION, vec![1]),
+            (TIFF_TAG_SAMPLES_PER_PIXEL, vec![u32::MAX]),
+            (TIFF_TAG_PLANAR_CONFIGURATION, vec![2]),
+            (TIFF_TAG_TILE_WIDTH, vec![1]),
+            (TIFF_TAG_TILE_LENGTH, vec![1]),
+            (TIFF_TAG_TILE_OFFSETS, vec![0]),
+            (TIFF_TAG_TILE_BYTE_COUNTS, vec![0])
+        ]);
+        assert_eq!(TIFFPage::from_tags(&tags).err(), Some(Error::SizeOverflow));
+    }
+}
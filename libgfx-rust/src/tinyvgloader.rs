@@ -0,0 +1,699 @@
+use std::ffi::c_void;
+use crate::imagedecoderplugin::{decoder_into_opaque, ImageDecoderPlugin, ImageFrameDescriptor, NaturalFrameFormat, VectorImageFrameDescriptor};
+use crate::{ARGB, Color, FloatPoint, IntSize};
+use crate::bitmap::Bitmap;
+use crate::error::{Error, ErrorCode, ffi_call, write_ffi_result};
+use crate::vectorgraphic::{DrawCommand, Path, PathSegment, Style, VectorGraphic};
+
+// NOTE: See https://tinyvg.tech/download/specification.pdf for the format.
+const TINYVG_MAGIC: [u8; 2] = [0x72, 0x56];
+const TINYVG_VERSION: u8 = 1;
+
+const TINYVG_COMMAND_END_OF_DOCUMENT: u8 = 0;
+const TINYVG_COMMAND_FILL_POLYGON: u8 = 1;
+const TINYVG_COMMAND_FILL_RECTANGLES: u8 = 2;
+const TINYVG_COMMAND_FILL_PATH: u8 = 3;
+const TINYVG_COMMAND_DRAW_LINES: u8 = 4;
+const TINYVG_COMMAND_DRAW_LINE_LOOP: u8 = 5;
+const TINYVG_COMMAND_DRAW_LINE_STRIP: u8 = 6;
+const TINYVG_COMMAND_DRAW_LINE_PATH: u8 = 7;
+const TINYVG_COMMAND_OUTLINE_FILL_POLYGON: u8 = 8;
+const TINYVG_COMMAND_OUTLINE_FILL_RECTANGLES: u8 = 9;
+const TINYVG_COMMAND_OUTLINE_FILL_PATH: u8 = 10;
+const TINYVG_COMMAND_TEXT_HINT: u8 = 11;
+
+const TINYVG_PATH_LINE: u8 = 0;
+const TINYVG_PATH_HORIZONTAL_LINE: u8 = 1;
+const TINYVG_PATH_VERTICAL_LINE: u8 = 2;
+const TINYVG_PATH_CUBIC_BEZIER: u8 = 3;
+const TINYVG_PATH_ARC_CIRCLE: u8 = 4;
+const TINYVG_PATH_ARC_ELLIPSE: u8 = 5;
+const TINYVG_PATH_CLOSE: u8 = 6;
+const TINYVG_PATH_QUADRATIC_BEZIER: u8 = 7;
+
+#[derive (Debug, Copy, Clone, PartialEq)]
+enum TinyVGColorEncoding {
+    Rgba8888,
+    Rgb565,
+    RgbaF32,
+    Custom
+}
+
+#[derive (Debug, Copy, Clone, PartialEq)]
+enum TinyVGCoordinateRange {
+    Default,
+    Reduced,
+    Enhanced
+}
+
+#[derive (Debug, Copy, Clone, PartialEq)]
+enum TinyVGStyleKind {
+    Flat,
+    LinearGradient,
+    RadialGradient
+}
+
+struct TinyVGReader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    scale: u8,
+    coordinate_range: TinyVGCoordinateRange,
+    color_table: Vec<ARGB>
+}
+
+impl<'a> TinyVGReader<'a> {
+    fn read_bytes(&mut self, count: usize) -> Result<&'a [u8], Error> {
+        if self.bytes.len() - self.offset < count {
+            return Err(Error::Truncated);
+        }
+        let bytes = &self.bytes[self.offset..self.offset + count];
+        self.offset += count;
+        Ok(bytes)
+    }
+
+    fn read_u8(&mut self) -> Result<u8, Error> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    fn read_var_uint(&mut self) -> Result<u32, Error> {
+        let mut value = 0u32;
+        for index in 0..5 {
+            let byte = self.read_u8()?;
+            value |= ((byte & 0x7F) as u32).checked_shl(7 * index).unwrap_or(0);
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(Error::InvalidData("TinyVG VarUInt is too long"))
+    }
+
+    /// Reads a count, which is stored minus one.
+    fn read_count(&mut self) -> Result<usize, Error> {
+        Ok(self.read_var_uint()? as usize + 1)
+    }
+
+    fn read_coordinate(&mut self) -> Result<i32, Error> {
+        Ok(match self.coordinate_range {
+            TinyVGCoordinateRange::Default => i16::from_le_bytes(self.read_bytes(2)?.try_into().unwrap()) as i32,
+            TinyVGCoordinateRange::Reduced => self.read_u8()? as i8 as i32,
+            TinyVGCoordinateRange::Enhanced => i32::from_le_bytes(self.read_bytes(4)?.try_into().unwrap())
+        })
+    }
+
+    fn read_unit(&mut self) -> Result<f32, Error> {
+        Ok(self.read_coordinate()? as f32 / (1u32 << self.scale) as f32)
+    }
+
+    fn read_point(&mut self) -> Result<FloatPoint, Error> {
+        Ok(FloatPoint { x: self.read_unit()?, y: self.read_unit()? })
+    }
+
+    fn read_color(&mut self, encoding: TinyVGColorEncoding) -> Result<ARGB, Error> {
+        Ok(match encoding {
+            TinyVGColorEncoding::Rgba8888 => {
+                let bytes = self.read_bytes(4)?;
+                Color::from_rgba(bytes[0], bytes[1], bytes[2], bytes[3]).color
+            }
+            TinyVGColorEncoding::Rgb565 => {
+                let value = u16::from_le_bytes(self.read_bytes(2)?.try_into().unwrap()) as u32;
+                let expand = |value: u32, max: u32| ((value * 255 + max / 2) / max) as u8;
+                Color::from_rgb(expand(value & 0x1F, 31), expand((value >> 5) & 0x3F, 63), expand(value >> 11, 31)).color
+            }
+            TinyVGColorEncoding::RgbaF32 => {
+                let mut channels = [0u8; 4];
+                for channel in &mut channels {
+                    let value = f32::from_le_bytes(self.read_bytes(4)?.try_into().unwrap());
+                    *channel = (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+                }
+                Color::from_rgba(channels[0], channels[1], channels[2], channels[3]).color
+            }
+            TinyVGColorEncoding::Custom => return Err(Error::Unsupported("TinyVG custom color encoding"))
+        })
+    }
+
+    fn read_color_index(&mut self) -> Result<ARGB, Error> {
+        let index = self.read_var_uint()? as usize;
+        self.color_table.get(index).copied().ok_or(Error::InvalidData("TinyVG color index out of range"))
+    }
+
+    fn read_style(&mut self, kind: u8) -> Result<Style, Error> {
+        let kind = match kind {
+            0 => TinyVGStyleKind::Flat,
+            1 => TinyVGStyleKind::LinearGradient,
+            2 => TinyVGStyleKind::RadialGradient,
+            _ => return Err(Error::InvalidData("Invalid TinyVG style kind"))
+        };
+        if kind == TinyVGStyleKind::Flat {
+            return Ok(Style::Color(self.read_color_index()?));
+        }
+
+        let (start, end) = (self.read_point()?, self.read_point()?);
+        let (start_color, end_color) = (self.read_color_index()?, self.read_color_index()?);
+        Ok(match kind {
+            TinyVGStyleKind::LinearGradient => Style::LinearGradient { start, end, start_color, end_color },
+            _ => Style::RadialGradient { center: start, edge: end, center_color: start_color, edge_color: end_color }
+        })
+    }
+
+    fn read_polygon(&mut self, point_count: usize, is_closed: bool) -> Result<Path, Error> {
+        let mut path = Path::new();
+        path.move_to(self.read_point()?);
+        for _ in 1..point_count {
+            path.line_to(self.read_point()?);
+        }
+        if is_closed {
+            path.close();
+        }
+        Ok(path)
+    }
+
+    fn read_rectangle(&mut self) -> Result<Path, Error> {
+        let (x, y) = (self.read_unit()?, self.read_unit()?);
+        let (width, height) = (self.read_unit()?, self.read_unit()?);
+        let mut path = Path::new();
+        path.move_to(FloatPoint { x, y });
+        path.line_to(FloatPoint { x: x + width, y });
+        path.line_to(FloatPoint { x: x + width, y: y + height });
+        path.line_to(FloatPoint { x, y: y + height });
+        path.close();
+        Ok(path)
+    }
+
+    fn read_lines(&mut self, line_count: usize) -> Result<Path, Error> {
+        let mut path = Path::new();
+        for _ in 0..line_count {
+            path.move_to(self.read_point()?);
+            path.line_to(self.read_point()?);
+        }
+        Ok(path)
+    }
+
+    /// Returns the whole path for filling, and the pieces to stroke with the line width each one was drawn with.
+    fn read_path(&mut self, segment_count: usize, line_width: f32) -> Result<(Path, Vec<(Path, f32)>), Error> {
+        let mut command_counts = Vec::new();
+        for _ in 0..segment_count {
+            command_counts.push(self.read_count()?);
+        }
+
+        let mut path = Path::new();
+        let mut strokes = Vec::new();
+        let mut stroke = Path::new();
+        let mut stroke_width = line_width;
+        for command_count in command_counts {
+            let segment_start = self.read_point()?;
+            path.move_to(segment_start);
+            stroke.move_to(segment_start);
+            let mut is_stroke_at_segment_start = true;
+            for _ in 0..command_count {
+                let tag = self.read_u8()?;
+                if tag & 0x10 != 0 {
+                    // NOTE: A new line width applies from this command on, so the stroke continues as a new piece.
+                    let width = self.read_unit()?;
+                    let has_stroke = stroke.segments().iter().any(|segment| !matches!(segment, PathSegment::MoveTo(_)));
+                    if width != stroke_width && has_stroke {
+                        strokes.push((std::mem::take(&mut stroke), stroke_width));
+                        stroke.move_to(path.current_point());
+                        is_stroke_at_segment_start = false;
+                    }
+                    stroke_width = width;
+                }
+                let segment_count_before = path.segments().len();
+                match tag & 0x07 {
+                    TINYVG_PATH_LINE => path.line_to(self.read_point()?),
+                    TINYVG_PATH_HORIZONTAL_LINE => {
+                        let x = self.read_unit()?;
+                        path.line_to(FloatPoint { x, y: path.current_point().y });
+                    }
+                    TINYVG_PATH_VERTICAL_LINE => {
+                        let y = self.read_unit()?;
+                        path.line_to(FloatPoint { x: path.current_point().x, y });
+                    }
+                    TINYVG_PATH_CUBIC_BEZIER => {
+                        let (first_control, second_control, end) = (self.read_point()?, self.read_point()?, self.read_point()?);
+                        path.cubic_bezier_curve_to(first_control, second_control, end);
+                    }
+                    TINYVG_PATH_ARC_CIRCLE => {
+                        let flags = self.read_u8()?;
+                        let radius = self.read_unit()?;
+                        let end = self.read_point()?;
+                        // NOTE: TinyVG's sweep flag means counter-clockwise, which is the opposite of SVG's.
+                        path.elliptical_arc_to(end, FloatPoint { x: radius, y: radius }, 0.0, flags & 1 != 0, flags & 2 == 0);
+                    }
+                    TINYVG_PATH_ARC_ELLIPSE => {
+                        let flags = self.read_u8()?;
+                        let radii = FloatPoint { x: self.read_unit()?, y: self.read_unit()? };
+                        let rotation = self.read_unit()?.to_radians();
+                        let end = self.read_point()?;
+                        path.elliptical_arc_to(end, radii, rotation, flags & 1 != 0, flags & 2 == 0);
+                    }
+                    TINYVG_PATH_CLOSE => {
+                        path.close();
+                        // NOTE: A piece that starts within the segment has to close back to the start of the segment.
+                        if is_stroke_at_segment_start {
+                            stroke.close();
+                        } else {
+                            stroke.line_to(segment_start);
+                        }
+                        continue;
+                    }
+                    TINYVG_PATH_QUADRATIC_BEZIER => {
+                        let (control, end) = (self.read_point()?, self.read_point()?);
+                        path.quadratic_bezier_curve_to(control, end);
+                    }
+                    _ => unreachable!()
+                }
+                stroke.append_segments(&path.segments()[segment_count_before..]);
+            }
+        }
+        strokes.push((stroke, stroke_width));
+        Ok((path, strokes))
+    }
+
+    fn skip_text_hint(&mut self) -> Result<(), Error> {
+        self.read_point()?;
+        self.read_unit()?;
+        self.read_unit()?;
+        let text_length = self.read_var_uint()? as usize;
+        self.read_bytes(text_length)?;
+        let glyph_count = self.read_var_uint()?;
+        for _ in 0..glyph_count {
+            self.read_unit()?;
+            self.read_unit()?;
+        }
+        Ok(())
+    }
+
+    fn read_commands(&mut self) -> Result<Vec<DrawCommand>, Error> {
+        let mut commands = Vec::new();
+        loop {
+            let command_byte = self.read_u8()?;
+            let primary_style_kind = command_byte >> 6;
+            match command_byte & 0x3F {
+                TINYVG_COMMAND_END_OF_DOCUMENT => return Ok(commands),
+                TINYVG_COMMAND_FILL_POLYGON => {
+                    let point_count = self.read_count()?;
+                    let fill = self.read_style(primary_style_kind)?;
+                    let path = self.read_polygon(point_count, true)?;
+                    commands.push(DrawCommand { path, fill: Some(fill), stroke: None, stroke_width: 0.0 });
+                }
+                TINYVG_COMMAND_FILL_RECTANGLES => {
+                    let rectangle_count = self.read_count()?;
+                    let fill = self.read_style(primary_style_kind)?;
+                    for _ in 0..rectangle_count {
+                        let path = self.read_rectangle()?;
+                        commands.push(DrawCommand { path, fill: Some(fill), stroke: None, stroke_width: 0.0 });
+                    }
+                }
+                TINYVG_COMMAND_FILL_PATH => {
+                    let segment_count = self.read_count()?;
+                    let fill = self.read_style(primary_style_kind)?;
+                    let (path, _) = self.read_path(segment_count, 0.0)?;
+                    commands.push(DrawCommand { path, fill: Some(fill), stroke: None, stroke_width: 0.0 });
+                }
+                command @ (TINYVG_COMMAND_DRAW_LINES | TINYVG_COMMAND_DRAW_LINE_LOOP | TINYVG_COMMAND_DRAW_LINE_STRIP | TINYVG_COMMAND_DRAW_LINE_PATH) => {
+                    let count = self.read_count()?;
+                    let stroke = self.read_style(primary_style_kind)?;
+                    let stroke_width = self.read_unit()?;
+                    let strokes = match command {
+                        TINYVG_COMMAND_DRAW_LINES => vec![(self.read_lines(count)?, stroke_width)],
+                        TINYVG_COMMAND_DRAW_LINE_LOOP => vec![(self.read_polygon(count, true)?, stroke_width)],
+                        TINYVG_COMMAND_DRAW_LINE_STRIP => vec![(self.read_polygon(count, false)?, stroke_width)],
+                        _ => self.read_path(count, stroke_width)?.1
+                    };
+                    for (path, stroke_width) in strokes {
+                        commands.push(DrawCommand { path, fill: None, stroke: Some(stroke), stroke_width });
+                    }
+                }
+                command @ (TINYVG_COMMAND_OUTLINE_FILL_POLYGON | TINYVG_COMMAND_OUTLINE_FILL_RECTANGLES | TINYVG_COMMAND_OUTLINE_FILL_PATH) => {
+                    // NOTE: Outline fills pack the count and the line style kind into a single byte.
+                    let count_and_style_kind = self.read_u8()?;
+                    let count = (count_and_style_kind & 0x3F) as usize + 1;
+                    let fill = self.read_style(primary_style_kind)?;
+                    let stroke = self.read_style(count_and_style_kind >> 6)?;
+                    let stroke_width = self.read_unit()?;
+                    if command == TINYVG_COMMAND_OUTLINE_FILL_PATH {
+                        let (path, strokes) = self.read_path(count, stroke_width)?;
+                        if let [(_, stroke_width)] = strokes[..] {
+                            commands.push(DrawCommand { path, fill: Some(fill), stroke: Some(stroke), stroke_width });
+                        } else {
+                            // NOTE: Fill the whole path first, as the pieces with differing line widths are not closed on their own.
+                            commands.push(DrawCommand { path, fill: Some(fill), stroke: None, stroke_width: 0.0 });
+                            for (path, stroke_width) in strokes {
+                                commands.push(DrawCommand { path, fill: None, stroke: Some(stroke), stroke_width });
+                            }
+                        }
+                        continue;
+                    }
+                    let paths = match command {
+                        TINYVG_COMMAND_OUTLINE_FILL_POLYGON => vec![self.read_polygon(count, true)?],
+                        _ => (0..count).map(|_| self.read_rectangle()).collect::<Result<_, _>>()?
+                    };
+                    for path in paths {
+                        commands.push(DrawCommand { path, fill: Some(fill), stroke: Some(stroke), stroke_width });
+                    }
+                }
+                TINYVG_COMMAND_TEXT_HINT => self.skip_text_hint()?,
+                _ => return Err(Error::InvalidData("Unknown TinyVG command"))
+            }
+        }
+    }
+}
+
+pub struct TinyVGImageDecoderPlugin {
+    context: TinyVGLoadingContext
+}
+
+struct TinyVGLoadingContext {
+    graphic: VectorGraphic,
+    bitmap: Option<Bitmap>
+}
+
+impl TinyVGImageDecoderPlugin {
+    pub fn sniff(bytes: &[u8]) -> bool {
+        bytes.starts_with(&TINYVG_MAGIC)
+    }
+
+    pub fn create(bytes: &[u8]) -> Result<Self, Error> {
+        if !Self::sniff(bytes) {
+            return Err(Error::InvalidHeader("Invalid TinyVG magic"));
+        }
+
+        let mut reader = TinyVGReader { bytes, offset: 2, scale: 0, coordinate_range: TinyVGCoordinateRange::Default, color_table: Vec::new() };
+        if reader.read_u8()? != TINYVG_VERSION {
+            return Err(Error::Unsupported("TinyVG version"));
+        }
+
+        let properties = reader.read_u8()?;
+        reader.scale = properties & 0x0F;
+        let color_encoding = match (properties >> 4) & 0x03 {
+            0 => TinyVGColorEncoding::Rgba8888,
+            1 => TinyVGColorEncoding::Rgb565,
+            2 => TinyVGColorEncoding::RgbaF32,
+            _ => TinyVGColorEncoding::Custom
+        };
+        reader.coordinate_range = match properties >> 6 {
+            0 => TinyVGCoordinateRange::Default,
+            1 => TinyVGCoordinateRange::Reduced,
+            2 => TinyVGCoordinateRange::Enhanced,
+            _ => return Err(Error::InvalidHeader("Invalid TinyVG coordinate range"))
+        };
+
+        // NOTE: The size is stored as unsigned, unlike all the other coordinates.
+        let (width, height) = match reader.coordinate_range {
+            TinyVGCoordinateRange::Default => (u16::from_le_bytes(reader.read_bytes(2)?.try_into().unwrap()) as u32, u16::from_le_bytes(reader.read_bytes(2)?.try_into().unwrap()) as u32),
+            TinyVGCoordinateRange::Reduced => (reader.read_u8()? as u32, reader.read_u8()? as u32),
+            TinyVGCoordinateRange::Enhanced => (u32::from_le_bytes(reader.read_bytes(4)?.try_into().unwrap()), u32::from_le_bytes(reader.read_bytes(4)?.try_into().unwrap()))
+        };
+        if width == 0 || height == 0 || width > i32::MAX as u32 || height > i32::MAX as u32 {
+            return Err(Error::InvalidHeader("Invalid TinyVG dimensions"));
+        }
+
+        let color_count = reader.read_var_uint()?;
+        for _ in 0..color_count {
+            let color = reader.read_color(color_encoding)?;
+            reader.color_table.push(color);
+        }
+
+        let commands = reader.read_commands()?;
+        let graphic = VectorGraphic {
+            size: IntSize { width: width as i32, height: height as i32 },
+            commands
+        };
+
+        Ok(Self {
+            context: TinyVGLoadingContext {
+                graphic,
+                bitmap: None
+            }
+        })
+    }
+}
+
+impl ImageDecoderPlugin for TinyVGImageDecoderPlugin {
+    fn size(&self) -> IntSize {
+        self.context.graphic.size
+    }
+
+    fn frame_with_ideal_size(&mut self, index: usize, ideal_size: Option<IntSize>) -> Result<ImageFrameDescriptor, Error> {
+        if index != 0 {
+            return Err(Error::FrameIndexOutOfRange);
+        }
+
+        let size = ideal_size.filter(|size| !size.is_empty()).unwrap_or(self.context.graphic.size);
+        if let Some(bitmap) = &self.context.bitmap {
+            if bitmap.size == size {
+                return Ok(ImageFrameDescriptor {
+                    image: bitmap.clone(),
+                    duration: 0
+                });
+            }
+        }
+
+        let bitmap = self.context.graphic.rasterize(size)?;
+        self.context.bitmap = Some(bitmap.clone());
+
+        Ok(ImageFrameDescriptor {
+            image: bitmap,
+            duration: 0
+        })
+    }
+
+    fn natural_frame_format(&self) -> NaturalFrameFormat {
+        NaturalFrameFormat::Vector
+    }
+
+    fn vector_frame(&mut self, frame_index: usize) -> Result<VectorImageFrameDescriptor, Error> {
+        if frame_index != 0 {
+            return Err(Error::FrameIndexOutOfRange);
+        }
+
+        Ok(VectorImageFrameDescriptor {
+            graphic: self.context.graphic.clone(),
+            duration: 0
+        })
+    }
+}
+
+/// # Safety
+///
+/// `bytes` must point to `size` readable bytes that outlive the returned decoder,
+/// and `out_decoder` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn tinyvg_image_decoder_plugin_new(bytes: *const u8, size: usize, out_decoder: *mut *mut c_void) -> ErrorCode {
+    ffi_call(|| unsafe {
+        if bytes.is_null() {
+            return Err(Error::InvalidArgument("Bytes are null"));
+        }
+        if out_decoder.is_null() {
+            return Err(Error::InvalidArgument("Output pointer is null"));
+        }
+        let bytes = std::slice::from_raw_parts(bytes, size);
+        let decoder = TinyVGImageDecoderPlugin::create(bytes)?;
+        write_ffi_result(out_decoder, decoder_into_opaque(Box::new(decoder)))
+    })
+}
+
+/// # Safety
+///
+/// `opaque_decoder` must either be null or come from `tinyvg_image_decoder_plugin_new` and not be freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn tinyvg_image_decoder_plugin_free(opaque_decoder: *mut c_void) -> ErrorCode {
+    ffi_call(|| {
+        if !opaque_decoder.is_null() {
+            let decoder: Box<Box<dyn ImageDecoderPlugin>> = unsafe { Box::from_raw(opaque_decoder as *mut _) };
+            drop(decoder);
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // NOTE: 16x16 with a red rectangle over the top left quadrant and a blue triangle (8, 8), (16, 8), (16, 16).
+    const SHAPES_IMAGE: [u8; 44] = [
+        0x72, 0x56, 0x01, 0x00, 0x10, 0x00, 0x10, 0x00, 0x02, 0xFF, 0x00, 0x00, 0xFF, 0x00, 0x00, 0xFF, 0xFF, 0x02, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x08, 0x00, 0x08, 0x00, 0x01, 0x02, 0x01, 0x08, 0x00, 0x08, 0x00, 0x10, 0x00, 0x08, 0x00, 0x10,
+        0x00, 0x10, 0x00, 0x00
+    ];
+
+    // NOTE: 16x16 with a red circle of radius 6 around (8, 8) made of two arcs, and a blue shape of a quadratic
+    //       and a cubic Bézier curve from (0, 16) over (4, 12) to (8, 16).
+    const PATH_IMAGE: [u8; 74] = [
+        0x72, 0x56, 0x01, 0x00, 0x10, 0x00, 0x10, 0x00, 0x02, 0xFF, 0x00, 0x00, 0xFF, 0x00, 0x00, 0xFF, 0xFF, 0x03, 0x00, 0x00,
+        0x02, 0x08, 0x00, 0x02, 0x00, 0x04, 0x00, 0x06, 0x00, 0x08, 0x00, 0x0E, 0x00, 0x04, 0x00, 0x06, 0x00, 0x08, 0x00, 0x02,
+        0x00, 0x06, 0x03, 0x00, 0x01, 0x02, 0x00, 0x00, 0x10, 0x00, 0x07, 0x00, 0x00, 0x0C, 0x00, 0x04, 0x00, 0x0C, 0x00, 0x03,
+        0x06, 0x00, 0x0C, 0x00, 0x08, 0x00, 0x0E, 0x00, 0x08, 0x00, 0x10, 0x00, 0x06, 0x00
+    ];
+
+    // NOTE: 16x16 with a red ellipse with radii 7 and 3 around (8, 8) made of two elliptical arcs.
+    const ELLIPSE_IMAGE: [u8; 47] = [
+        0x72, 0x56, 0x01, 0x00, 0x10, 0x00, 0x10, 0x00, 0x01, 0xFF, 0x00, 0x00, 0xFF, 0x03, 0x00, 0x00, 0x02, 0x01, 0x00, 0x08,
+        0x00, 0x05, 0x00, 0x07, 0x00, 0x03, 0x00, 0x00, 0x00, 0x0F, 0x00, 0x08, 0x00, 0x05, 0x00, 0x07, 0x00, 0x03, 0x00, 0x00,
+        0x00, 0x01, 0x00, 0x08, 0x00, 0x06, 0x00
+    ];
+
+    // NOTE: 16x16 with a red line path from (2, 4) to (14, 4) at width 2, then switching to width 6 down to (14, 12).
+    const LINE_WIDTH_IMAGE: [u8; 32] = [
+        0x72, 0x56, 0x01, 0x00, 0x10, 0x00, 0x10, 0x00, 0x01, 0xFF, 0x00, 0x00, 0xFF, 0x07, 0x00, 0x00, 0x02, 0x00, 0x01, 0x02,
+        0x00, 0x04, 0x00, 0x01, 0x0E, 0x00, 0x12, 0x06, 0x00, 0x0C, 0x00, 0x00
+    ];
+
+    // NOTE: 16x16 with the square (4, 4), (12, 12) filled red and outlined blue, with width 1 for the top edge
+    //       and width 3 from the right edge on, including the closing left edge.
+    const OUTLINE_FILL_PATH_IMAGE: [u8; 47] = [
+        0x72, 0x56, 0x01, 0x00, 0x10, 0x00, 0x10, 0x00, 0x02, 0xFF, 0x00, 0x00, 0xFF, 0x00, 0x00, 0xFF, 0xFF, 0x0A, 0x00, 0x00,
+        0x01, 0x01, 0x00, 0x03, 0x04, 0x00, 0x04, 0x00, 0x00, 0x0C, 0x00, 0x04, 0x00, 0x10, 0x03, 0x00, 0x0C, 0x00, 0x0C, 0x00,
+        0x00, 0x04, 0x00, 0x0C, 0x00, 0x06, 0x00
+    ];
+
+    // NOTE: 16x16 with a black to white linear gradient from (0, 0) to (16, 0) over the top half, and a white to
+    //       black radial gradient around (8, 12) with radius 8 over the bottom half.
+    const GRADIENT_IMAGE: [u8; 58] = [
+        0x72, 0x56, 0x01, 0x00, 0x10, 0x00, 0x10, 0x00, 0x02, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x42, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x08, 0x00, 0x82, 0x00, 0x08,
+        0x00, 0x0C, 0x00, 0x10, 0x00, 0x0C, 0x00, 0x01, 0x00, 0x00, 0x00, 0x08, 0x00, 0x10, 0x00, 0x08, 0x00, 0x00
+    ];
+
+    fn pixel(image: &Bitmap, x: i32, y: i32) -> Color {
+        Color::from(image.get_pixel(x, y))
+    }
+
+    fn is_red(color: Color) -> bool {
+        (color.alpha(), color.red(), color.green(), color.blue()) == (255, 255, 0, 0)
+    }
+
+    fn is_blue(color: Color) -> bool {
+        (color.alpha(), color.red(), color.green(), color.blue()) == (255, 0, 0, 255)
+    }
+
+    fn render(bytes: &[u8]) -> Bitmap {
+        TinyVGImageDecoderPlugin::create(bytes).unwrap().frame(0).unwrap().image
+    }
+
+    #[test]
+    fn decodes_filled_rectangles_and_polygons() {
+        let mut decoder = TinyVGImageDecoderPlugin::create(&SHAPES_IMAGE).unwrap();
+        assert_eq!((decoder.size().width, decoder.size().height), (16, 16));
+        assert_eq!(decoder.natural_frame_format(), NaturalFrameFormat::Vector);
+        let image = decoder.frame(0).unwrap().image;
+        assert!(is_red(pixel(&image, 2, 2)));
+        assert!(is_blue(pixel(&image, 14, 10)));
+        assert_eq!(pixel(&image, 12, 4).alpha(), 0);
+        assert_eq!(pixel(&image, 9, 14).alpha(), 0);
+    }
+
+    #[test]
+    fn rasterizes_at_ideal_size() {
+        let mut decoder = TinyVGImageDecoderPlugin::create(&SHAPES_IMAGE).unwrap();
+        let image = decoder.frame_with_ideal_size(0, Some(IntSize { width: 32, height: 64 })).unwrap().image;
+        assert_eq!((image.size.width, image.size.height), (32, 64));
+        assert!(is_red(pixel(&image, 4, 4)));
+        assert!(is_blue(pixel(&image, 28, 40)));
+        assert_eq!(pixel(&image, 4, 40).alpha(), 0);
+    }
+
+    #[test]
+    fn decodes_arcs_and_curves() {
+        let image = render(&PATH_IMAGE);
+        for (x, y) in [(8, 8), (3, 8), (12, 8), (8, 3), (8, 12)] {
+            assert!(is_red(pixel(&image, x, y)), "({x}, {y})");
+        }
+        for (x, y) in [(1, 8), (14, 8), (8, 0), (13, 13)] {
+            assert_eq!(pixel(&image, x, y).alpha(), 0, "({x}, {y})");
+        }
+        assert!(is_blue(pixel(&image, 2, 15)));
+        assert_eq!(pixel(&image, 0, 12).alpha(), 0);
+
+        let image = render(&ELLIPSE_IMAGE);
+        for (x, y) in [(8, 8), (2, 8), (13, 8), (8, 6), (8, 9)] {
+            assert!(is_red(pixel(&image, x, y)), "({x}, {y})");
+        }
+        for (x, y) in [(0, 8), (15, 8), (8, 4), (8, 11)] {
+            assert_eq!(pixel(&image, x, y).alpha(), 0, "({x}, {y})");
+        }
+    }
+
+    #[test]
+    fn exposes_paths_as_vector_frame() {
+        let mut decoder = TinyVGImageDecoderPlugin::create(&PATH_IMAGE).unwrap();
+        assert_eq!(decoder.vector_frame(1).err(), Some(Error::FrameIndexOutOfRange));
+        let graphic = decoder.vector_frame(0).unwrap().graphic;
+        assert_eq!((graphic.size.width, graphic.size.height), (16, 16));
+        assert_eq!(graphic.commands.len(), 2);
+
+        let circle = &graphic.commands[0];
+        assert_eq!((circle.fill, circle.stroke), (Some(Style::Color(Color::from_rgb(255, 0, 0).color)), None));
+        let segments = circle.path.segments();
+        assert_eq!(segments.first(), Some(&PathSegment::MoveTo(FloatPoint { x: 8.0, y: 2.0 })));
+        assert_eq!(segments.last(), Some(&PathSegment::ClosePath));
+        // NOTE: Arcs are converted to one cubic Bézier curve per quarter.
+        assert_eq!(segments.iter().filter(|segment| matches!(segment, PathSegment::CubicBezierCurveTo { .. })).count(), 4);
+
+        let curves = graphic.commands[1].path.segments();
+        assert_eq!(curves.len(), 4);
+        assert_eq!(curves[1], PathSegment::QuadraticBezierCurveTo { control: FloatPoint { x: 0.0, y: 12.0 }, end: FloatPoint { x: 4.0, y: 12.0 } });
+        assert!(matches!(curves[2], PathSegment::CubicBezierCurveTo { end: FloatPoint { x: 8.0, y: 16.0 }, .. }));
+    }
+
+    #[test]
+    fn honors_line_width_changes_within_paths() {
+        let mut decoder = TinyVGImageDecoderPlugin::create(&LINE_WIDTH_IMAGE).unwrap();
+        let graphic = decoder.vector_frame(0).unwrap().graphic;
+        let widths: Vec<f32> = graphic.commands.iter().map(|command| command.stroke_width).collect();
+        assert_eq!(widths, [2.0, 6.0]);
+        assert!(graphic.commands.iter().all(|command| command.fill.is_none()));
+
+        let image = render(&LINE_WIDTH_IMAGE);
+        assert!(is_red(pixel(&image, 8, 4)));
+        assert_eq!(pixel(&image, 8, 6).alpha(), 0);
+        assert!(is_red(pixel(&image, 12, 8)));
+        assert_eq!(pixel(&image, 10, 8).alpha(), 0);
+    }
+
+    #[test]
+    fn fills_outline_path_before_stroking_each_line_width() {
+        let mut decoder = TinyVGImageDecoderPlugin::create(&OUTLINE_FILL_PATH_IMAGE).unwrap();
+        let graphic = decoder.vector_frame(0).unwrap().graphic;
+        let commands: Vec<(bool, bool, f32)> = graphic.commands.iter().map(|command| (command.fill.is_some(), command.stroke.is_some(), command.stroke_width)).collect();
+        assert_eq!(commands, [(true, false, 0.0), (false, true, 1.0), (false, true, 3.0)]);
+
+        let image = render(&OUTLINE_FILL_PATH_IMAGE);
+        assert!(is_red(pixel(&image, 8, 8)));
+        assert!(is_blue(pixel(&image, 8, 11)));
+        assert!(is_blue(pixel(&image, 11, 8)));
+        // NOTE: The left edge closes back to the start of the segment, not to where the width changed.
+        assert!(is_blue(pixel(&image, 3, 8)));
+        assert_eq!(pixel(&image, 8, 1).alpha(), 0);
+    }
+
+    #[test]
+    fn decodes_gradients() {
+        let image = render(&GRADIENT_IMAGE);
+        let linear: Vec<u8> = (0..16).map(|x| pixel(&image, x, 2).red()).collect();
+        assert!(linear[0] < 32 && linear[15] > 223, "{linear:?}");
+        assert!(linear.windows(2).all(|pair| pair[0] < pair[1]), "{linear:?}");
+
+        let radial: Vec<u8> = (0..16).map(|x| pixel(&image, x, 12).red()).collect();
+        assert!(radial[8] > 223 && radial[0] < 32, "{radial:?}");
+        assert!(radial[..8].windows(2).all(|pair| pair[0] < pair[1]), "{radial:?}");
+        assert!(radial[8..].windows(2).all(|pair| pair[0] > pair[1]), "{radial:?}");
+        assert_eq!(radial[7], radial[8]);
+    }
+
+    #[test]
+    fn rejects_malformed_files() {
+        assert_eq!(TinyVGImageDecoderPlugin::create(&SHAPES_IMAGE[..20]).err(), Some(Error::Truncated));
+        let mut data = SHAPES_IMAGE;
+        data[2] = 2;
+        assert!(matches!(TinyVGImageDecoderPlugin::create(&data), Err(Error::Unsupported(_))));
+        // NOTE: Point the rectangle at a third color, the color table only has two.
+        let mut data = SHAPES_IMAGE;
+        data[19] = 2;
+        assert_eq!(TinyVGImageDecoderPlugin::create(&data).err(), Some(Error::InvalidData("TinyVG color index out of range")));
+        unsafe {
+            assert_eq!(tinyvg_image_decoder_plugin_new(SHAPES_IMAGE.as_ptr(), SHAPES_IMAGE.len(), std::ptr::null_mut()), ErrorCode::InvalidArgument);
+        }
+    }
+}
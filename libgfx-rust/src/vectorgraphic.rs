@@ -0,0 +1,445 @@
+use crate::{ARGB, Color, FloatPoint, IntSize};
+use crate::bitmap::{Bitmap, BitmapFormat};
+use crate::error::Error;
+use crate::rasterizer::{Rasterizer, WindingRule};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PathSegment {
+    MoveTo(FloatPoint),
+    LineTo(FloatPoint),
+    QuadraticBezierCurveTo { control: FloatPoint, end: FloatPoint },
+    CubicBezierCurveTo { first_control: FloatPoint, second_control: FloatPoint, end: FloatPoint },
+    ClosePath
+}
+
+/// A path made of lines and Bézier curves. Arcs are converted to cubic Bézier curves when added.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Path {
+    segments: Vec<PathSegment>,
+    current_point: FloatPoint,
+    subpath_start: FloatPoint
+}
+
+impl Path {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn segments(&self) -> &[PathSegment] {
+        &self.segments
+    }
+
+    pub fn current_point(&self) -> FloatPoint {
+        self.current_point
+    }
+
+    pub fn move_to(&mut self, point: FloatPoint) {
+        self.segments.push(PathSegment::MoveTo(point));
+        self.current_point = point;
+        self.subpath_start = point;
+    }
+
+    pub fn line_to(&mut self, point: FloatPoint) {
+        self.segments.push(PathSegment::LineTo(point));
+        self.current_point = point;
+    }
+
+    pub fn quadratic_bezier_curve_to(&mut self, control: FloatPoint, end: FloatPoint) {
+        self.segments.push(PathSegment::QuadraticBezierCurveTo { control, end });
+        self.current_point = end;
+    }
+
+    pub fn cubic_bezier_curve_to(&mut self, first_control: FloatPoint, second_control: FloatPoint, end: FloatPoint) {
+        self.segments.push(PathSegment::CubicBezierCurveTo { first_control, second_control, end });
+        self.current_point = end;
+    }
+
+    /// Adds an arc like SVG's `A` command, see "Elliptical arc implementation notes" in the SVG specification.
+    pub fn elliptical_arc_to(&mut self, end: FloatPoint, radii: FloatPoint, x_axis_rotation: f32, large_arc: bool, sweep: bool) {
+        let start = self.current_point;
+        if start == end {
+            return;
+        }
+        let (mut radius_x, mut radius_y) = (radii.x.abs(), radii.y.abs());
+        if radius_x == 0.0 || radius_y == 0.0 {
+            self.line_to(end);
+            return;
+        }
+
+        let (sin_phi, cos_phi) = x_axis_rotation.sin_cos();
+        let half_dx = (start.x - end.x) / 2.0;
+        let half_dy = (start.y - end.y) / 2.0;
+        let x1 = cos_phi * half_dx + sin_phi * half_dy;
+        let y1 = -sin_phi * half_dx + cos_phi * half_dy;
+
+        // NOTE: Radii that are too small to reach the end point get scaled up.
+        let lambda = (x1 * x1) / (radius_x * radius_x) + (y1 * y1) / (radius_y * radius_y);
+        if lambda > 1.0 {
+            radius_x *= lambda.sqrt();
+            radius_y *= lambda.sqrt();
+        }
+
+        let numerator = radius_x * radius_x * radius_y * radius_y - radius_x * radius_x * y1 * y1 - radius_y * radius_y * x1 * x1;
+        let denominator = radius_x * radius_x * y1 * y1 + radius_y * radius_y * x1 * x1;
+        let mut coefficient = (numerator / denominator).max(0.0).sqrt();
+        if large_arc == sweep {
+            coefficient = -coefficient;
+        }
+        let center_x1 = coefficient * radius_x * y1 / radius_y;
+        let center_y1 = -coefficient * radius_y * x1 / radius_x;
+        let center = FloatPoint {
+            x: cos_phi * center_x1 - sin_phi * center_y1 + (start.x + end.x) / 2.0,
+            y: sin_phi * center_x1 + cos_phi * center_y1 + (start.y + end.y) / 2.0
+        };
+
+        let start_angle = ((y1 - center_y1) / radius_y).atan2((x1 - center_x1) / radius_x);
+        let end_angle = ((-y1 - center_y1) / radius_y).atan2((-x1 - center_x1) / radius_x);
+        let mut delta = end_angle - start_angle;
+        if sweep && delta < 0.0 {
+            delta += std::f32::consts::TAU;
+        } else if !sweep && delta > 0.0 {
+            delta -= std::f32::consts::TAU;
+        }
+
+        // NOTE: Each cubic Bézier curve approximates at most a quarter of the ellipse.
+        let curve_count = (delta.abs() / std::f32::consts::FRAC_PI_2).ceil().max(1.0) as usize;
+        let step = delta / curve_count as f32;
+        let handle_length = 4.0 / 3.0 * (step / 4.0).tan();
+        let map = |x: f32, y: f32| FloatPoint {
+            x: center.x + radius_x * cos_phi * x - radius_y * sin_phi * y,
+            y: center.y + radius_x * sin_phi * x + radius_y * cos_phi * y
+        };
+        for index in 0..curve_count {
+            let (sin_from, cos_from) = (start_angle + step * index as f32).sin_cos();
+            let (sin_to, cos_to) = (start_angle + step * (index + 1) as f32).sin_cos();
+            let segment_end = if index + 1 == curve_count { end } else { map(cos_to, sin_to) };
+            self.cubic_bezier_curve_to(
+                map(cos_from - handle_length * sin_from, sin_from + handle_length * cos_from),
+                map(cos_to + handle_length * sin_to, sin_to - handle_length * cos_to),
+                segment_end
+            );
+        }
+    }
+
+    pub fn close(&mut self) {
+        self.segments.push(PathSegment::ClosePath);
+        self.current_point = self.subpath_start;
+    }
+
+    pub(crate) fn append_segments(&mut self, segments: &[PathSegment]) {
+        for segment in segments {
+            match *segment {
+                PathSegment::MoveTo(point) => self.move_to(point),
+                PathSegment::LineTo(point) => self.line_to(point),
+                PathSegment::QuadraticBezierCurveTo { control, end } => self.quadratic_bezier_curve_to(control, end),
+                PathSegment::CubicBezierCurveTo { first_control, second_control, end } => self.cubic_bezier_curve_to(first_control, second_control, end),
+                PathSegment::ClosePath => self.close()
+            }
+        }
+    }
+
+    /// Splits the path into polylines after applying `transform`, returning whether each one is closed.
+    pub(crate) fn flatten(&self, transform: &dyn Fn(FloatPoint) -> FloatPoint) -> Vec<(Vec<FloatPoint>, bool)> {
+        let mut polylines = Vec::new();
+        let mut current: Vec<FloatPoint> = Vec::new();
+        let mut finish = |current: &mut Vec<FloatPoint>, is_closed: bool| {
+            if current.len() > 1 {
+                polylines.push((std::mem::take(current), is_closed));
+            }
+            current.clear();
+        };
+
+        for segment in &self.segments {
+            // NOTE: A path that doesn't start with a move implicitly starts at the origin.
+            if current.is_empty() && !matches!(segment, PathSegment::MoveTo(_) | PathSegment::ClosePath) {
+                current.push(transform(FloatPoint::default()));
+            }
+            let last = current.last().copied().unwrap_or_default();
+            match *segment {
+                PathSegment::MoveTo(point) => {
+                    finish(&mut current, false);
+                    current.push(transform(point));
+                }
+                PathSegment::LineTo(point) => current.push(transform(point)),
+                PathSegment::QuadraticBezierCurveTo { control, end } => {
+                    let (control, end) = (transform(control), transform(end));
+                    let steps = curve_steps(&[last, control, end]);
+                    for step in 1..=steps {
+                        let t = step as f32 / steps as f32;
+                        let s = 1.0 - t;
+                        current.push(FloatPoint {
+                            x: s * s * last.x + 2.0 * s * t * control.x + t * t * end.x,
+                            y: s * s * last.y + 2.0 * s * t * control.y + t * t * end.y
+                        });
+                    }
+                }
+                PathSegment::CubicBezierCurveTo { first_control, second_control, end } => {
+                    let (first_control, second_control, end) = (transform(first_control), transform(second_control), transform(end));
+                    let steps = curve_steps(&[last, first_control, second_control, end]);
+                    for step in 1..=steps {
+                        let t = step as f32 / steps as f32;
+                        let s = 1.0 - t;
+                        current.push(FloatPoint {
+                            x: s * s * s * last.x + 3.0 * s * s * t * first_control.x + 3.0 * s * t * t * second_control.x + t * t * t * end.x,
+                            y: s * s * s * last.y + 3.0 * s * s * t * first_control.y + 3.0 * s * t * t * second_control.y + t * t * t * end.y
+                        });
+                    }
+                }
+                PathSegment::ClosePath => {
+                    let start = current.first().copied();
+                    finish(&mut current, true);
+                    if let Some(start) = start {
+                        current.push(start);
+                    }
+                }
+            }
+        }
+        finish(&mut current, false);
+        polylines
+    }
+}
+
+fn curve_steps(points: &[FloatPoint]) -> usize {
+    let length: f32 = points.windows(2).map(|pair| pair[0].distance_to(pair[1])).sum();
+    (length.sqrt() * 2.0).ceil().clamp(1.0, 256.0) as usize
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Style {
+    Color(ARGB),
+    LinearGradient { start: FloatPoint, end: FloatPoint, start_color: ARGB, end_color: ARGB },
+    RadialGradient { center: FloatPoint, edge: FloatPoint, center_color: ARGB, edge_color: ARGB }
+}
+
+impl Style {
+    fn transformed(&self, transform: &dyn Fn(FloatPoint) -> FloatPoint) -> Self {
+        match *self {
+            Style::Color(color) => Style::Color(color),
+            Style::LinearGradient { start, end, start_color, end_color } => Style::LinearGradient { start: transform(start), end: transform(end), start_color, end_color },
+            Style::RadialGradient { center, edge, center_color, edge_color } => Style::RadialGradient { center: transform(center), edge: transform(edge), center_color, edge_color }
+        }
+    }
+
+    fn color_at(&self, point: FloatPoint) -> Color {
+        let (position, from, to) = match *self {
+            Style::Color(color) => return Color::from(color),
+            Style::LinearGradient { start, end, start_color, end_color } => {
+                let (dx, dy) = (end.x - start.x, end.y - start.y);
+                let length_squared = dx * dx + dy * dy;
+                let position = if length_squared > 0.0 { ((point.x - start.x) * dx + (point.y - start.y) * dy) / length_squared } else { 0.0 };
+                (position, start_color, end_color)
+            }
+            Style::RadialGradient { center, edge, center_color, edge_color } => {
+                let radius = center.distance_to(edge);
+                let position = if radius > 0.0 { center.distance_to(point) / radius } else { 0.0 };
+                (position, center_color, edge_color)
+            }
+        };
+
+        let position = position.clamp(0.0, 1.0);
+        let (from, to) = (Color::from(from), Color::from(to));
+        let mix = |from: u8, to: u8| (from as f32 + (to as f32 - from as f32) * position).round() as u8;
+        Color::from_rgba(mix(from.red(), to.red()), mix(from.green(), to.green()), mix(from.blue(), to.blue()), mix(from.alpha(), to.alpha()))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DrawCommand {
+    pub path: Path,
+    pub fill: Option<Style>,
+    pub stroke: Option<Style>,
+    pub stroke_width: f32
+}
+
+/// A resolution independent image, made of filled and stroked paths.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VectorGraphic {
+    pub size: IntSize,
+    pub commands: Vec<DrawCommand>
+}
+
+impl VectorGraphic {
+    /// Renders the graphic stretched to `size`.
+    pub fn rasterize(&self, size: IntSize) -> Result<Bitmap, Error> {
+        let mut bitmap = Bitmap::new(BitmapFormat::BGRA8888, size, 1)?;
+        let scale_x = size.width as f32 / self.size.width as f32;
+        let scale_y = size.height as f32 / self.size.height as f32;
+        let transform = |point: FloatPoint| FloatPoint { x: point.x * scale_x, y: point.y * scale_y };
+
+        for command in &self.commands {
+            let polylines = command.path.flatten(&transform);
+            if let Some(fill) = &command.fill {
+                let mut rasterizer = Rasterizer::new(size);
+                for (points, _) in &polylines {
+                    rasterizer.add_polygon(points);
+                }
+                let fill = fill.transformed(&transform);
+                rasterizer.fill(&mut bitmap, WindingRule::EvenOdd, &|point| fill.color_at(point));
+            }
+            if let Some(stroke) = &command.stroke {
+                // NOTE: Non-uniform scaling would distort the pen, we just use the average scale factor.
+                let width = command.stroke_width * (scale_x + scale_y) / 2.0;
+                let mut rasterizer = Rasterizer::new(size);
+                for (points, is_closed) in &polylines {
+                    rasterizer.add_stroke(points, *is_closed, width);
+                }
+                let stroke = stroke.transformed(&transform);
+                rasterizer.fill(&mut bitmap, WindingRule::NonZero, &|point| stroke.color_at(point));
+            }
+        }
+        Ok(bitmap)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(x: f32, y: f32) -> FloatPoint {
+        FloatPoint { x, y }
+    }
+
+    fn flatten(path: &Path) -> Vec<(Vec<FloatPoint>, bool)> {
+        path.flatten(&|point| point)
+    }
+
+    fn arc(start: FloatPoint, end: FloatPoint, radii: FloatPoint, x_axis_rotation: f32, large_arc: bool, sweep: bool) -> Vec<FloatPoint> {
+        let mut path = Path::new();
+        path.move_to(start);
+        path.elliptical_arc_to(end, radii, x_axis_rotation, large_arc, sweep);
+        assert_eq!(path.current_point(), end);
+        let mut polylines = flatten(&path);
+        assert_eq!(polylines.len(), 1);
+        let (points, _) = polylines.remove(0);
+        assert_eq!((points[0], points[points.len() - 1]), (start, end));
+        points
+    }
+
+    fn assert_on_circle(points: &[FloatPoint], center: FloatPoint, radius: f32) {
+        for point in points {
+            assert!((center.distance_to(*point) - radius).abs() < 0.01, "{point:?} is not on the circle around {center:?}");
+        }
+    }
+
+    #[test]
+    fn picks_arc_by_large_arc_and_sweep_flags() {
+        let (start, end, radii) = (point(0.0, 0.0), point(1.0, 1.0), point(1.0, 1.0));
+        let small_sweep = arc(start, end, radii, 0.0, false, true);
+        let small = arc(start, end, radii, 0.0, false, false);
+        let large_sweep = arc(start, end, radii, 0.0, true, true);
+        let large = arc(start, end, radii, 0.0, true, false);
+
+        assert_on_circle(&small_sweep, point(0.0, 1.0), 1.0);
+        assert_on_circle(&small, point(1.0, 0.0), 1.0);
+        assert_on_circle(&large_sweep, point(1.0, 0.0), 1.0);
+        assert_on_circle(&large, point(0.0, 1.0), 1.0);
+
+        let farthest = |points: &[FloatPoint]| points.iter().map(|point| point.distance_to(FloatPoint { x: 0.5, y: 0.5 })).fold(0.0, f32::max);
+        assert!(farthest(&small_sweep) < 0.75 && farthest(&small) < 0.75);
+        assert!(farthest(&large_sweep) > 1.5 && farthest(&large) > 1.5);
+    }
+
+    #[test]
+    fn rotates_elliptical_arcs() {
+        // NOTE: Rotating by 90 degrees puts the long radius along the y axis, which exactly spans the two points.
+        let points = arc(point(0.0, 0.0), point(0.0, 4.0), point(2.0, 1.0), std::f32::consts::FRAC_PI_2, false, true);
+        for point in &points {
+            let distance = point.x * point.x + (point.y - 2.0) * (point.y - 2.0) / 4.0;
+            assert!((distance - 1.0).abs() < 0.01, "{point:?} is not on the ellipse");
+        }
+        let widest = points.iter().map(|point| point.x.abs()).fold(0.0, f32::max);
+        assert!((0.95..=1.01).contains(&widest), "{widest}");
+    }
+
+    #[test]
+    fn handles_degenerate_arc_radii() {
+        // NOTE: Radii too small to reach the end point are scaled up, negative ones are used as positive.
+        assert_on_circle(&arc(point(0.0, 0.0), point(2.0, 0.0), point(0.1, 0.1), 0.0, false, true), point(1.0, 0.0), 1.0);
+        assert_on_circle(&arc(point(0.0, 0.0), point(2.0, 0.0), point(-1.0, -1.0), 0.0, false, true), point(1.0, 0.0), 1.0);
+
+        let mut path = Path::new();
+        path.move_to(point(0.0, 0.0));
+        path.elliptical_arc_to(point(2.0, 0.0), point(0.0, 1.0), 0.0, false, true);
+        path.elliptical_arc_to(point(2.0, 0.0), point(1.0, 1.0), 0.0, false, true);
+        assert_eq!(path.segments(), [PathSegment::MoveTo(point(0.0, 0.0)), PathSegment::LineTo(point(2.0, 0.0))]);
+    }
+
+    #[test]
+    fn flattens_into_polylines() {
+        let mut path = Path::new();
+        path.line_to(point(1.0, 0.0));
+        path.move_to(point(2.0, 0.0));
+        path.line_to(point(3.0, 0.0));
+        path.line_to(point(3.0, 1.0));
+        path.close();
+        path.line_to(point(2.0, 1.0));
+        path.move_to(point(5.0, 5.0));
+
+        let polylines = path.flatten(&|point| FloatPoint { x: point.x * 2.0, y: point.y + 1.0 });
+        assert_eq!(polylines, [
+            (vec![point(0.0, 1.0), point(2.0, 1.0)], false),
+            (vec![point(4.0, 1.0), point(6.0, 1.0), point(6.0, 2.0)], true),
+            (vec![point(4.0, 1.0), point(4.0, 2.0)], false)
+        ]);
+    }
+
+    #[test]
+    fn flattens_curves() {
+        let mut path = Path::new();
+        path.move_to(point(0.0, 0.0));
+        path.quadratic_bezier_curve_to(point(2.0, 2.0), point(4.0, 0.0));
+        path.cubic_bezier_curve_to(point(4.0, -4.0), point(8.0, -4.0), point(8.0, 0.0));
+        let (points, is_closed) = &flatten(&path)[0];
+        assert!(!is_closed);
+        assert!(points.len() > 4);
+        assert!(points.contains(&point(4.0, 0.0)) && points.ends_with(&[point(8.0, 0.0)]));
+
+        // NOTE: The quadratic curve peaks at y = 1 and the cubic one at y = -3, both halfway through.
+        let (first_half, second_half): (Vec<FloatPoint>, Vec<FloatPoint>) = points.iter().partition(|point| point.x <= 4.0);
+        let highest = first_half.iter().map(|point| point.y).fold(f32::MIN, f32::max);
+        let lowest = second_half.iter().map(|point| point.y).fold(f32::MAX, f32::min);
+        assert!((0.9..=1.0).contains(&highest), "{highest}");
+        assert!((-3.0..=-2.9).contains(&lowest), "{lowest}");
+    }
+
+    #[test]
+    fn interpolates_gradients() {
+        let rgba = |color: Color| [color.red(), color.green(), color.blue(), color.alpha()];
+        assert_eq!(rgba(Style::Color(0x80102030).color_at(point(3.0, 4.0))), [0x10, 0x20, 0x30, 0x80]);
+
+        let linear = Style::LinearGradient { start: point(0.0, 0.0), end: point(10.0, 0.0), start_color: 0xFF000000, end_color: 0x00FFFFFF };
+        assert_eq!(rgba(linear.color_at(point(-5.0, 0.0))), [0, 0, 0, 255]);
+        assert_eq!(rgba(linear.color_at(point(5.0, 7.0))), [128, 128, 128, 128]);
+        assert_eq!(rgba(linear.color_at(point(2.5, -3.0))), [64, 64, 64, 191]);
+        assert_eq!(rgba(linear.color_at(point(15.0, 0.0))), [255, 255, 255, 0]);
+
+        let diagonal = Style::LinearGradient { start: point(0.0, 0.0), end: point(4.0, 4.0), start_color: 0xFF000000, end_color: 0xFFFFFFFF };
+        assert_eq!(rgba(diagonal.color_at(point(4.0, 0.0))), [128, 128, 128, 255]);
+
+        let degenerate = Style::LinearGradient { start: point(1.0, 1.0), end: point(1.0, 1.0), start_color: 0xFFFF0000, end_color: 0xFF0000FF };
+        assert_eq!(rgba(degenerate.color_at(point(5.0, 5.0))), [255, 0, 0, 255]);
+
+        let radial = Style::RadialGradient { center: point(5.0, 5.0), edge: point(5.0, 9.0), center_color: 0xFFFF0000, edge_color: 0xFF0000FF };
+        assert_eq!(rgba(radial.color_at(point(5.0, 5.0))), [255, 0, 0, 255]);
+        assert_eq!(rgba(radial.color_at(point(7.0, 5.0))), [128, 0, 128, 255]);
+        assert_eq!(rgba(radial.color_at(point(5.0, 2.0))), [64, 0, 191, 255]);
+        assert_eq!(rgba(radial.color_at(point(0.0, 0.0))), [0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn rasterizes_scaled_to_size() {
+        let mut path = Path::new();
+        path.move_to(point(0.0, 0.0));
+        path.line_to(point(1.0, 0.0));
+        path.line_to(point(1.0, 2.0));
+        path.line_to(point(0.0, 2.0));
+        path.close();
+        let graphic = VectorGraphic {
+            size: IntSize { width: 2, height: 2 },
+            commands: vec![DrawCommand { path, fill: Some(Style::Color(0xFFFF0000)), stroke: None, stroke_width: 0.0 }]
+        };
+
+        let bitmap = graphic.rasterize(IntSize { width: 4, height: 2 }).unwrap();
+        assert_eq!(bitmap.pixels(), [0xFFFF0000, 0xFFFF0000, 0, 0, 0xFFFF0000, 0xFFFF0000, 0, 0]);
+    }
+}
@@ -0,0 +1,941 @@
+use std::ffi::c_void;
+use crate::imagedecoderplugin::{decoder_into_opaque, ImageDecoderPlugin, ImageFrameDescriptor};
+use crate::{ARGB, Color, IntSize};
+use crate::bitmap::{Bitmap, BitmapFormat};
+use crate::error::{Error, ErrorCode, ffi_call, write_ffi_result};
+use crate::webploaderlossless::{decode_webp_chunk_vp8l, decode_webp_chunk_vp8l_contents, decode_webp_chunk_vp8l_header, VP8LHeader};
+use crate::webploaderlossy::{decode_webp_chunk_vp8, decode_webp_chunk_vp8_header};
+
+// NOTE: See https://developers.google.com/speed/webp/docs/riff_container for the container format.
+const RIFF_HEADER_SIZE: usize = 12;
+const CHUNK_HEADER_SIZE: usize = 8;
+const VP8X_CHUNK_SIZE: usize = 10;
+const ANIM_CHUNK_SIZE: usize = 6;
+const ANMF_HEADER_SIZE: usize = 16;
+
+#[derive (Debug, Copy, Clone)]
+struct WebPChunk<'a> {
+    chunk_type: [u8; 4],
+    data: &'a [u8]
+}
+
+#[derive (Debug, Copy, Clone)]
+struct VP8XHeader {
+    has_icc: bool,
+    has_alpha: bool,
+    has_exif: bool,
+    has_xmp: bool,
+    has_animation: bool,
+    width: u32,
+    height: u32
+}
+
+/// The chunks making up a still image. The alpha chunk only exists for lossy images in the extended format.
+#[derive (Debug, Copy, Clone)]
+struct WebPImageData<'a> {
+    alpha_chunk: Option<WebPChunk<'a>>,
+    image_data_chunk: WebPChunk<'a>
+}
+
+#[derive (Debug, Copy, Clone)]
+struct WebPAnimationFrame<'a> {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    duration: u32,
+    should_blend: bool,
+    dispose_to_background: bool,
+    image_data: WebPImageData<'a>
+}
+
+// NOTE: An ANMF frame is blended onto, and disposed from, whatever the previous frames left on the canvas, so the canvas is kept
+//       between calls and only rebuilt from the first frame when seeking backwards.
+struct WebPAnimationState {
+    canvas: Bitmap,
+    next_frame_index: usize
+}
+
+pub struct WebPImageDecoderPlugin<'a> {
+    context: WebPLoadingContext<'a>
+}
+
+struct WebPLoadingContext<'a> {
+    bytes: &'a [u8],
+    size: IntSize,
+    chunks: Vec<WebPChunk<'a>>,
+    vp8x_header: Option<VP8XHeader>,
+    image_data: Option<WebPImageData<'a>>,
+    bitmap: Option<Bitmap>,
+    loop_count: usize,
+    background_color: Option<ARGB>,
+    animation_frames: Vec<WebPAnimationFrame<'a>>,
+    animation_state: Option<WebPAnimationState>
+}
+
+fn read_chunks(mut data: &[u8]) -> Result<Vec<WebPChunk<'_>>, Error> {
+    let mut chunks = Vec::new();
+    while !data.is_empty() {
+        if data.len() < CHUNK_HEADER_SIZE {
+            return Err(Error::Truncated);
+        }
+        let chunk_type: [u8; 4] = data[0..4].try_into().unwrap();
+        let length = u32::from_le_bytes(data[4..8].try_into().unwrap()) as usize;
+        if data.len() - CHUNK_HEADER_SIZE < length {
+            return Err(Error::Truncated);
+        }
+        chunks.push(WebPChunk { chunk_type, data: &data[CHUNK_HEADER_SIZE..CHUNK_HEADER_SIZE + length] });
+
+        // NOTE: Odd sized chunks are followed by a padding byte, which may be missing at the end of the file.
+        let padded_length = (CHUNK_HEADER_SIZE + length + (length & 1)).min(data.len());
+        data = &data[padded_length..];
+    }
+    Ok(chunks)
+}
+
+fn read_u24(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], 0])
+}
+
+fn decode_webp_chunk_vp8x(chunk: &WebPChunk) -> Result<VP8XHeader, Error> {
+    if chunk.data.len() < VP8X_CHUNK_SIZE {
+        return Err(Error::InvalidHeader("VP8X chunk too small"));
+    }
+
+    let data = chunk.data;
+    let flags = data[0];
+    Ok(VP8XHeader {
+        has_icc: flags & 0x20 != 0,
+        has_alpha: flags & 0x10 != 0,
+        has_exif: flags & 0x08 != 0,
+        has_xmp: flags & 0x04 != 0,
+        has_animation: flags & 0x02 != 0,
+        width: read_u24(data, 4) + 1,
+        height: read_u24(data, 7) + 1
+    })
+}
+
+fn decode_webp_chunk_alph(chunk: &WebPChunk, bitmap: &mut Bitmap) -> Result<(), Error> {
+    let Some((&flags, data)) = chunk.data.split_first() else {
+        return Err(Error::Truncated);
+    };
+    // NOTE: The pre-processing bits only say whether the encoder reduced the number of alpha levels, decoding is the same.
+    let filtering_method = (flags >> 2) & 3;
+    let compression_method = flags & 3;
+
+    let (width, height) = (bitmap.size.width as usize, bitmap.size.height as usize);
+    let mut alpha: Vec<u8> = match compression_method {
+        0 => {
+            if data.len() < width * height {
+                return Err(Error::Truncated);
+            }
+            data[..width * height].to_vec()
+        }
+        1 => {
+            let header = VP8LHeader { width: width as u32, height: height as u32, is_alpha_used: false };
+            let alpha_bitmap = decode_webp_chunk_vp8l_contents(header, data)?;
+            (0..width * height).map(|index| (alpha_bitmap.get_pixel((index % width) as i32, (index / width) as i32) >> 8) as u8).collect()
+        }
+        _ => return Err(Error::InvalidData("Invalid ALPH compression method"))
+    };
+
+    // NOTE: Filtered values are differences to a prediction from the pixels to the left and above, which the first row and
+    //       column can only partly use. The top left pixel is predicted as 0 by all filters.
+    for y in 0..height {
+        for x in 0..width {
+            let index = y * width + x;
+            let prediction = match (filtering_method, x, y) {
+                (0, _, _) | (_, 0, 0) => 0,
+                (_, _, 0) => alpha[index - 1],
+                (_, 0, _) => alpha[index - width],
+                (1, _, _) => alpha[index - 1],
+                (2, _, _) => alpha[index - width],
+                _ => (alpha[index - 1] as i32 + alpha[index - width] as i32 - alpha[index - width - 1] as i32).clamp(0, 255) as u8
+            };
+            alpha[index] = alpha[index].wrapping_add(prediction);
+        }
+    }
+
+    bitmap.format = BitmapFormat::BGRA8888;
+    for (index, &value) in alpha.iter().enumerate() {
+        let (x, y) = ((index % width) as i32, (index / width) as i32);
+        bitmap.set_pixel(x, y, (bitmap.get_pixel(x, y) & 0x00FF_FFFF) | (value as u32) << 24);
+    }
+    Ok(())
+}
+
+impl<'a> WebPImageDecoderPlugin<'a> {
+    pub fn sniff(bytes: &[u8]) -> bool {
+        bytes.len() >= RIFF_HEADER_SIZE && bytes.starts_with(b"RIFF") && &bytes[8..12] == b"WEBP"
+    }
+
+    pub fn create(bytes: &'a [u8]) -> Result<Self, Error> {
+        if bytes.len() < RIFF_HEADER_SIZE {
+            return Err(Error::Truncated);
+        }
+        if !Self::sniff(bytes) {
+            return Err(Error::InvalidHeader("Invalid WebP RIFF header"));
+        }
+
+        let riff_size = u32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize;
+        if riff_size < 4 {
+            return Err(Error::InvalidHeader("Invalid WebP RIFF size"));
+        }
+        // NOTE: Anything after the RIFF chunk is ignored.
+        let end = (riff_size + 8).min(bytes.len());
+        let chunks = read_chunks(&bytes[RIFF_HEADER_SIZE..end])?;
+
+        let mut context = WebPLoadingContext {
+            bytes,
+            size: IntSize { width: 0, height: 0 },
+            chunks,
+            vp8x_header: None,
+            image_data: None,
+            bitmap: None,
+            loop_count: 0,
+            background_color: None,
+            animation_frames: Vec::new(),
+            animation_state: None
+        };
+        context.decode_header()?;
+
+        Ok(Self { context })
+    }
+
+    /// The background color of an animation. Like browsers, we clear the canvas to transparent instead of using it,
+    /// which the specification allows, but callers may want it to fill the area around the image.
+    pub fn background_color(&self) -> Option<ARGB> {
+        self.context.background_color
+    }
+}
+
+impl<'a> WebPLoadingContext<'a> {
+    fn decode_header(&mut self) -> Result<(), Error> {
+        let Some(first_chunk) = self.chunks.first().copied() else {
+            return Err(Error::InvalidHeader("WebP file has no chunks"));
+        };
+
+        match &first_chunk.chunk_type {
+            b"VP8L" => {
+                let header = decode_webp_chunk_vp8l_header(first_chunk.data)?;
+                self.size = IntSize { width: header.width as i32, height: header.height as i32 };
+                self.image_data = Some(WebPImageData { alpha_chunk: None, image_data_chunk: first_chunk });
+            }
+            b"VP8 " => {
+                let header = decode_webp_chunk_vp8_header(first_chunk.data)?;
+                self.size = IntSize { width: header.width as i32, height: header.height as i32 };
+                self.image_data = Some(WebPImageData { alpha_chunk: None, image_data_chunk: first_chunk });
+            }
+            b"VP8X" => {
+                let header = decode_webp_chunk_vp8x(&first_chunk)?;
+                if header.width as u64 * header.height as u64 > u32::MAX as u64 {
+                    return Err(Error::InvalidHeader("WebP canvas is too large"));
+                }
+                self.size = IntSize { width: header.width as i32, height: header.height as i32 };
+                self.vp8x_header = Some(header);
+                if header.has_animation {
+                    self.decode_animation()?;
+                } else {
+                    self.image_data = Some(self.find_image_data(&self.chunks[1..])?);
+                }
+            }
+            _ => return Err(Error::InvalidHeader("WebP does not start with a VP8, VP8L or VP8X chunk"))
+        }
+        Ok(())
+    }
+
+    fn find_image_data(&self, chunks: &[WebPChunk<'a>]) -> Result<WebPImageData<'a>, Error> {
+        let mut alpha_chunk = None;
+        for chunk in chunks {
+            match &chunk.chunk_type {
+                // NOTE: Only the first ALPH chunk counts, and it has to come before the image data.
+                b"ALPH" => { alpha_chunk.get_or_insert(*chunk); }
+                b"VP8 " | b"VP8L" => return Ok(WebPImageData { alpha_chunk, image_data_chunk: *chunk }),
+                _ => {}
+            }
+        }
+        Err(Error::InvalidData("WebP file has no image data"))
+    }
+
+    fn is_animated(&self) -> bool {
+        self.vp8x_header.is_some_and(|header| header.has_animation)
+    }
+
+    fn decode_animation(&mut self) -> Result<(), Error> {
+        let mut has_animation_header = false;
+        for chunk in &self.chunks[1..] {
+            match &chunk.chunk_type {
+                b"ANIM" => {
+                    if chunk.data.len() < ANIM_CHUNK_SIZE {
+                        return Err(Error::InvalidHeader("ANIM chunk too small"));
+                    }
+                    // NOTE: Stored as blue, green, red and alpha bytes, which is ARGB in little endian.
+                    self.background_color = Some(u32::from_le_bytes(chunk.data[0..4].try_into().unwrap()));
+                    self.loop_count = u16::from_le_bytes([chunk.data[4], chunk.data[5]]) as usize;
+                    has_animation_header = true;
+                }
+                b"ANMF" => {
+                    if !has_animation_header {
+                        return Err(Error::InvalidData("WebP animation frame before the ANIM chunk"));
+                    }
+                    let frame = self.decode_webp_chunk_anmf(chunk)?;
+                    self.animation_frames.push(frame);
+                }
+                _ => {}
+            }
+        }
+
+        if self.animation_frames.is_empty() {
+            return Err(Error::InvalidData("WebP animation without any frames"));
+        }
+        Ok(())
+    }
+
+    fn decode_webp_chunk_anmf(&self, chunk: &WebPChunk<'a>) -> Result<WebPAnimationFrame<'a>, Error> {
+        if chunk.data.len() < ANMF_HEADER_SIZE {
+            return Err(Error::InvalidHeader("ANMF chunk too small"));
+        }
+
+        let data = chunk.data;
+        let flags = data[15];
+        let frame = WebPAnimationFrame {
+            x: read_u24(data, 0) * 2,
+            y: read_u24(data, 3) * 2,
+            width: read_u24(data, 6) + 1,
+            height: read_u24(data, 9) + 1,
+            duration: read_u24(data, 12),
+            should_blend: flags & 0x02 == 0,
+            dispose_to_background: flags & 0x01 != 0,
+            image_data: self.find_image_data(&read_chunks(&data[ANMF_HEADER_SIZE..])?)?
+        };
+        if frame.x + frame.width > self.size.width as u32 || frame.y + frame.height > self.size.height as u32 {
+            return Err(Error::InvalidData("WebP animation frame does not fit on the canvas"));
+        }
+        Ok(frame)
+    }
+
+    fn decode_image_data(&self, image_data: &WebPImageData) -> Result<Bitmap, Error> {
+        if &image_data.image_data_chunk.chunk_type == b"VP8L" {
+            return decode_webp_chunk_vp8l(image_data.image_data_chunk.data);
+        }
+
+        let mut bitmap = decode_webp_chunk_vp8(image_data.image_data_chunk.data)?;
+        if let Some(alpha_chunk) = &image_data.alpha_chunk {
+            decode_webp_chunk_alph(alpha_chunk, &mut bitmap)?;
+        }
+        Ok(bitmap)
+    }
+
+    fn decode_still_image(&mut self) -> Result<Bitmap, Error> {
+        let Some(image_data) = self.image_data else {
+            return Err(Error::InvalidData("WebP file has no image data"));
+        };
+
+        let bitmap = self.decode_image_data(&image_data)?;
+        if bitmap.size != self.size {
+            return Err(Error::InvalidData("WebP image data size does not match the canvas size"));
+        }
+        Ok(bitmap)
+    }
+
+    fn render_animation_frame(&mut self, index: usize) -> Result<ImageFrameDescriptor, Error> {
+        let mut state = match self.animation_state.take() {
+            Some(state) if state.next_frame_index <= index => state,
+            _ => WebPAnimationState {
+                canvas: Bitmap::new(BitmapFormat::BGRA8888, self.size, 1)?,
+                next_frame_index: 0
+            }
+        };
+
+        while state.next_frame_index <= index {
+            if state.next_frame_index > 0 {
+                let previous_frame = &self.animation_frames[state.next_frame_index - 1];
+                if previous_frame.dispose_to_background {
+                    for y in previous_frame.y..previous_frame.y + previous_frame.height {
+                        for x in previous_frame.x..previous_frame.x + previous_frame.width {
+                            state.canvas.set_pixel(x as i32, y as i32, 0);
+                        }
+                    }
+                }
+            }
+
+            let frame = &self.animation_frames[state.next_frame_index];
+            let bitmap = self.decode_image_data(&frame.image_data)?;
+            if bitmap.size != (IntSize { width: frame.width as i32, height: frame.height as i32 }) {
+                return Err(Error::InvalidData("WebP animation frame size does not match its image data"));
+            }
+            for y in 0..frame.height as i32 {
+                for x in 0..frame.width as i32 {
+                    let (canvas_x, canvas_y) = (frame.x as i32 + x, frame.y as i32 + y);
+                    let mut color = Color::from(bitmap.get_pixel(x, y));
+                    if frame.should_blend {
+                        color = Color::from(state.canvas.get_pixel(canvas_x, canvas_y)).blend(color);
+                    }
+                    state.canvas.set_pixel(canvas_x, canvas_y, color.color);
+                }
+            }
+            state.next_frame_index += 1;
+        }
+
+        let image = state.canvas.clone();
+        self.animation_state = Some(state);
+        Ok(ImageFrameDescriptor {
+            image,
+            duration: self.animation_frames[index].duration as i32
+        })
+    }
+}
+
+impl<'a> ImageDecoderPlugin for WebPImageDecoderPlugin<'a> {
+    fn size(&self) -> IntSize {
+        self.context.size
+    }
+
+    fn is_animated(&self) -> bool {
+        self.context.is_animated()
+    }
+
+    fn loop_count(&self) -> usize {
+        self.context.loop_count
+    }
+
+    fn frame_count(&self) -> usize {
+        if self.context.is_animated() { self.context.animation_frames.len() } else { 1 }
+    }
+
+    fn frame_with_ideal_size(&mut self, index: usize, _: Option<IntSize>) -> Result<ImageFrameDescriptor, Error> {
+        if index >= self.frame_count() {
+            return Err(Error::FrameIndexOutOfRange);
+        }
+        if self.context.is_animated() {
+            return self.context.render_animation_frame(index);
+        }
+
+        if let Some(bitmap) = &self.context.bitmap {
+            return Ok(ImageFrameDescriptor {
+                image: bitmap.clone(),
+                duration: 0
+            });
+        }
+
+        let bitmap = self.context.decode_still_image()?;
+        self.context.bitmap = Some(bitmap.clone());
+
+        Ok(ImageFrameDescriptor {
+            image: bitmap,
+            duration: 0
+        })
+    }
+}
+
+/// # Safety
+///
+/// `bytes` must point to `size` readable bytes that outlive the returned decoder,
+/// and `out_decoder` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn webp_image_decoder_plugin_new(bytes: *const u8, size: usize, out_decoder: *mut *mut c_void) -> ErrorCode {
+    ffi_call(|| unsafe {
+        if bytes.is_null() {
+            return Err(Error::InvalidArgument("Bytes are null"));
+        }
+        if out_decoder.is_null() {
+            return Err(Error::InvalidArgument("Output pointer is null"));
+        }
+        let bytes = std::slice::from_raw_parts(bytes, size);
+        let decoder = WebPImageDecoderPlugin::create(bytes)?;
+        write_ffi_result(out_decoder, decoder_into_opaque(Box::new(decoder)))
+    })
+}
+
+/// # Safety
+///
+/// `opaque_decoder` must either be null or come from `webp_image_decoder_plugin_new` and not be freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn webp_image_decoder_plugin_free(opaque_decoder: *mut c_void) -> ErrorCode {
+    ffi_call(|| {
+        if !opaque_decoder.is_null() {
+            let decoder: Box<Box<dyn ImageDecoderPlugin>> = unsafe { Box::from_raw(opaque_decoder as *mut _) };
+            drop(decoder);
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testsupport::assert_pixels;
+    use crate::Color;
+
+    // NOTE: 16x16 lossless with (x * 16, y * 16, (x + y) * 8, 255 - x * 4) pixels, encoded by libwebp with the
+    //       predictor and cross color transforms.
+    const GRADIENT_IMAGE: [u8; 54] = [
+        0x52, 0x49, 0x46, 0x46, 0x2E, 0x00, 0x00, 0x00, 0x57, 0x45, 0x42, 0x50, 0x56, 0x50, 0x38, 0x4C, 0x21, 0x00, 0x00, 0x00,
+        0x2F, 0x0F, 0xC0, 0x03, 0x10, 0x99, 0x0A, 0x44, 0xF4, 0x3F, 0x36, 0x85, 0x28, 0x44, 0xC1, 0xFB, 0x1F, 0x10, 0x12, 0x10,
+        0x26, 0xF8, 0x7F, 0x58, 0x54, 0x07, 0x22, 0x86, 0x3F, 0x81, 0xCB, 0x76, 0x01, 0x00
+    ];
+
+    // NOTE: 13x7 lossless with four colors, including transparent black, encoded with the color indexing
+    //       transform so that four pixels are bundled into each coded pixel.
+    const PALETTED_IMAGE: [u8; 70] = [
+        0x52, 0x49, 0x46, 0x46, 0x3E, 0x00, 0x00, 0x00, 0x57, 0x45, 0x42, 0x50, 0x56, 0x50, 0x38, 0x4C, 0x31, 0x00, 0x00, 0x00,
+        0x2F, 0x0C, 0x80, 0x01, 0x10, 0x1F, 0x20, 0x10, 0x48, 0xDA, 0x1F, 0x7A, 0x8D, 0xF9, 0x17, 0x10, 0x14, 0xF9, 0x3F, 0xDA,
+        0xFC, 0x07, 0xF0, 0xA5, 0x40, 0x21, 0x1B, 0x49, 0xD0, 0xDE, 0x27, 0x39, 0x92, 0xAB, 0xB6, 0x14, 0xC7, 0x10, 0xD1, 0xFF,
+        0x22, 0xF8, 0x5C, 0x04, 0x9D, 0x6F, 0x42, 0xFA, 0x37, 0x00
+    ];
+
+    // NOTE: 32x32 lossless with rows repeating every five lines, encoded with the subtract green, predictor and
+    //       cross color transforms, a color cache and backward references.
+    const REPEATED_IMAGE: [u8; 116] = [
+        0x52, 0x49, 0x46, 0x46, 0x6C, 0x00, 0x00, 0x00, 0x57, 0x45, 0x42, 0x50, 0x56, 0x50, 0x38, 0x4C, 0x60, 0x00, 0x00, 0x00,
+        0x2F, 0x1F, 0xC0, 0x07, 0x00, 0x4D, 0x00, 0x44, 0xD2, 0xFE, 0xE0, 0x2B, 0x44, 0xF4, 0x3F, 0x75, 0x01, 0x10, 0x84, 0xFF,
+        0x71, 0x0B, 0x11, 0xFD, 0x4F, 0x35, 0xA8, 0x8D, 0x24, 0xB5, 0x29, 0xE9, 0x23, 0x52, 0x5A, 0xA2, 0x03, 0x9A, 0xF7, 0x8A,
+        0xBD, 0xD4, 0xB4, 0x6D, 0xC0, 0x10, 0xEA, 0x35, 0x51, 0xF7, 0x2E, 0x3E, 0x21, 0x41, 0xF2, 0xBF, 0x13, 0x1D, 0xE0, 0xCC,
+        0x50, 0x9B, 0xB6, 0x01, 0x43, 0xC6, 0x9E, 0x37, 0x9E, 0x9C, 0x83, 0xEE, 0x7E, 0x12, 0xCA, 0xED, 0x18, 0xA1, 0x50, 0xB6,
+        0x63, 0x88, 0x62, 0x6C, 0x73, 0x86, 0x28, 0x94, 0xFD, 0x19, 0xA2, 0x88, 0xCD, 0x2E, 0xC9, 0x07
+    ];
+
+    // NOTE: 32x32 lossless with different noise in the top and bottom halves, encoded with meta prefix codes.
+    const REGIONS_IMAGE: [u8; 892] = [
+        0x52, 0x49, 0x46, 0x46, 0x74, 0x03, 0x00, 0x00, 0x57, 0x45, 0x42, 0x50, 0x56, 0x50, 0x38, 0x4C, 0x68, 0x03, 0x00, 0x00,
+        0x2F, 0x1F, 0xC0, 0x07, 0x00, 0xFF, 0xA3, 0xA0, 0x6D, 0x23, 0xC7, 0xC7, 0x9F, 0xF3, 0xFD, 0x9E, 0x08, 0x8C, 0xB6, 0x6D,
+        0xE3, 0xFC, 0xFF, 0xEC, 0xE8, 0x50, 0x05, 0x04, 0x45, 0xFE, 0x8F, 0x36, 0xFF, 0xEB, 0x80, 0x64, 0x26, 0xD8, 0x4C, 0x7C,
+        0x7F, 0xB8, 0x19, 0x93, 0xC0, 0xC4, 0x08, 0x08, 0x51, 0x30, 0x11, 0x73, 0xE4, 0x6D, 0x91, 0xCF, 0x48, 0x14, 0x30, 0x04,
+        0x02, 0xC9, 0xFB, 0x73, 0xE7, 0x10, 0x11, 0x19, 0x03, 0x41, 0x00, 0xC0, 0x24, 0xB1, 0xB4, 0x03, 0x86, 0x47, 0x8F, 0xEE,
+        0xEE, 0x9E, 0x8C, 0x50, 0xC9, 0x88, 0xFE, 0x87, 0xC3, 0x00, 0x00, 0x93, 0x54, 0xDA, 0x6B, 0x48, 0x97, 0x52, 0x1B, 0x9D,
+        0xE2, 0xE8, 0x0E, 0x69, 0x88, 0xE8, 0x7F, 0x6C, 0x33, 0xEF, 0x07, 0xB6, 0x65, 0xA7, 0x9F, 0xF6, 0x97, 0xC5, 0xCC, 0x01,
+        0x77, 0x69, 0xC3, 0xDA, 0x73, 0x9D, 0xF3, 0x21, 0x1A, 0x20, 0x11, 0x2D, 0x1C, 0xF9, 0x66, 0xC7, 0xBD, 0x92, 0x74, 0xB8,
+        0xEF, 0xB5, 0x0F, 0x4C, 0x59, 0x00, 0xA5, 0x2D, 0xCF, 0xCB, 0xC5, 0xF2, 0xC7, 0x73, 0x4F, 0x9F, 0x80, 0x1A, 0x90, 0xB5,
+        0xAE, 0x66, 0xB5, 0x2C, 0xA5, 0xD4, 0xE3, 0x2F, 0x66, 0x28, 0xC2, 0x3E, 0x38, 0x12, 0x81, 0x06, 0x96, 0x41, 0x63, 0x84,
+        0x24, 0xAF, 0x3A, 0x6A, 0xF7, 0x3E, 0xAC, 0xD6, 0x7C, 0x47, 0x40, 0x34, 0xE0, 0xBB, 0x50, 0x80, 0x94, 0xAB, 0xB8, 0x13,
+        0xA6, 0xE3, 0xFA, 0x48, 0xE8, 0x6D, 0x81, 0xAC, 0x06, 0xD9, 0x93, 0x8A, 0x6E, 0x2F, 0x2C, 0x0F, 0x25, 0xFE, 0x60, 0x74,
+        0x87, 0x23, 0xFF, 0x42, 0x2D, 0x42, 0x6E, 0xD8, 0xCD, 0xF8, 0x98, 0x3E, 0x54, 0xB6, 0x2A, 0x9C, 0xE3, 0x41, 0x3A, 0xFB,
+        0x39, 0xE6, 0x3F, 0xC5, 0xD8, 0x3B, 0xFC, 0x3B, 0x0D, 0x7B, 0xA4, 0x25, 0xE9, 0x68, 0xB4, 0x86, 0xBE, 0xE6, 0x09, 0xFA,
+        0xF9, 0x41, 0xF7, 0xDC, 0xA2, 0x46, 0x98, 0x02, 0xEB, 0xEF, 0x8A, 0xD7, 0x56, 0x79, 0x15, 0x36, 0x6D, 0xEF, 0x42, 0xA2,
+        0x85, 0x3A, 0x09, 0x9A, 0x75, 0xEF, 0xF6, 0x5F, 0x0E, 0x2A, 0xD7, 0x4F, 0xD5, 0xD9, 0xCE, 0xC3, 0x91, 0xC1, 0x86, 0xE1,
+        0x53, 0x43, 0x20, 0xCC, 0x2B, 0x97, 0x5F, 0x50, 0x70, 0x4C, 0x5C, 0x2B, 0x61, 0x01, 0xC4, 0x99, 0xF3, 0xFB, 0x33, 0xD3,
+        0x85, 0xEF, 0x89, 0x55, 0x1A, 0x89, 0x8D, 0xFC, 0x75, 0xF5, 0x61, 0x3D, 0xD1, 0x4A, 0x94, 0x7F, 0xB5, 0x9B, 0x8A, 0xB1,
+        0xFF, 0xE4, 0xB7, 0xD7, 0xEB, 0x4E, 0xB9, 0xB5, 0x82, 0x08, 0x09, 0x62, 0xDF, 0x99, 0x18, 0x21, 0x0F, 0x3C, 0x5E, 0x53,
+        0x08, 0x20, 0x3B, 0x56, 0x68, 0x7A, 0xCF, 0xE0, 0xC9, 0x86, 0x1C, 0xA6, 0xE4, 0xC6, 0xE0, 0xA2, 0x55, 0xD5, 0xB4, 0x59,
+        0x04, 0x26, 0x92, 0x99, 0xC7, 0xB3, 0xA6, 0x44, 0x28, 0x35, 0xD2, 0xEF, 0xE3, 0xFF, 0xA8, 0x53, 0x35, 0x6C, 0x2E, 0xAF,
+        0x3E, 0x66, 0xD1, 0x54, 0xEB, 0x54, 0x70, 0x65, 0x11, 0x72, 0x6D, 0x97, 0x18, 0x87, 0x79, 0x26, 0xE1, 0xF4, 0x0A, 0x99,
+        0x87, 0x25, 0x26, 0x46, 0x82, 0x17, 0xE6, 0x58, 0xD1, 0x0B, 0x83, 0xFA, 0xB8, 0x3F, 0xE3, 0x38, 0xFA, 0x08, 0xEE, 0x29,
+        0xCC, 0x95, 0xD2, 0x3C, 0x5F, 0xEF, 0x8B, 0x59, 0xB7, 0x9E, 0x5D, 0xB5, 0x3D, 0xAA, 0x8E, 0x54, 0x61, 0xDD, 0x58, 0xE4,
+        0x2F, 0x52, 0x6D, 0xA9, 0xCC, 0x20, 0x61, 0x1D, 0x59, 0xBA, 0x08, 0x3E, 0x5B, 0xDC, 0xB9, 0x74, 0xB2, 0x8D, 0x1E, 0x1C,
+        0x61, 0xFC, 0x23, 0x02, 0xE2, 0x4B, 0xFF, 0xD5, 0x02, 0x4F, 0xB2, 0x8A, 0x3E, 0xE7, 0x28, 0xFC, 0xAC, 0x0A, 0xA9, 0x2B,
+        0x62, 0x3D, 0x62, 0x44, 0x0D, 0xC6, 0xF7, 0xF9, 0x5B, 0x01, 0xA0, 0x15, 0xF8, 0x80, 0x9E, 0xFD, 0x7D, 0x5A, 0x6E, 0xCC,
+        0xF5, 0x8B, 0xB7, 0xA5, 0x40, 0x15, 0xB4, 0x62, 0x16, 0x50, 0xE5, 0x99, 0xA1, 0xE0, 0x8D, 0x34, 0xBE, 0xA6, 0x8D, 0xC4,
+        0x95, 0xFD, 0x6B, 0x43, 0x6B, 0xC7, 0x27, 0x7E, 0x07, 0x83, 0x3C, 0xEE, 0x24, 0xFC, 0xBD, 0xF3, 0xFF, 0xF8, 0x24, 0x37,
+        0xA2, 0x2E, 0xA9, 0x88, 0x0E, 0xF1, 0xB8, 0xEE, 0x5F, 0x01, 0xA4, 0xD1, 0xD6, 0xCE, 0x18, 0x83, 0x56, 0x69, 0x9A, 0xF0,
+        0xD5, 0x00, 0xFE, 0x9C, 0x15, 0x92, 0x28, 0xE5, 0x1F, 0x0F, 0x44, 0x54, 0x0B, 0x09, 0x5C, 0xEB, 0x26, 0x4D, 0xF7, 0xC8,
+        0xB2, 0xF2, 0xDF, 0xFF, 0xA4, 0xD6, 0xED, 0x45, 0x28, 0x40, 0xB0, 0x44, 0xF5, 0xAA, 0x6E, 0xDC, 0x76, 0x4E, 0xBE, 0x81,
+        0xAC, 0xC1, 0x8D, 0x58, 0x6B, 0xCC, 0xBD, 0xEB, 0xC2, 0x31, 0xEC, 0x23, 0x64, 0x93, 0xB5, 0x57, 0x0C, 0x65, 0x3E, 0xC0,
+        0xAD, 0xBE, 0x3F, 0x68, 0x07, 0x46, 0xE2, 0xA0, 0x47, 0x75, 0xF9, 0x4D, 0xDD, 0xD9, 0x5B, 0x76, 0x0B, 0x31, 0x01, 0xE6,
+        0x10, 0x25, 0x67, 0xF5, 0x76, 0x34, 0xBC, 0x3C, 0xE8, 0xBD, 0xC5, 0xD8, 0xDF, 0xD1, 0xF0, 0xB9, 0x34, 0x9E, 0x13, 0x7E,
+        0x65, 0x7F, 0x72, 0xF1, 0xE6, 0x68, 0x1C, 0xFC, 0x99, 0xB9, 0x47, 0xC2, 0xD6, 0xB3, 0x95, 0x0D, 0x2F, 0xB1, 0x77, 0xB8,
+        0x8C, 0xCB, 0x83, 0xEB, 0xCA, 0x84, 0x34, 0xC1, 0xBE, 0x24, 0x49, 0xC5, 0xD3, 0xBC, 0x13, 0x52, 0xFB, 0x38, 0x8F, 0x5E,
+        0xB9, 0x01, 0xB1, 0x19, 0xD7, 0xDD, 0xF5, 0x0E, 0x59, 0xDC, 0x6E, 0x85, 0xC2, 0x1B, 0xF3, 0x27, 0xCA, 0x87, 0x34, 0xC0,
+        0x78, 0xE6, 0x38, 0x26, 0x04, 0x11, 0x1E, 0xEC, 0x3A, 0x8E, 0x0E, 0xB1, 0xF2, 0x83, 0x6B, 0x99, 0xF1, 0x34, 0x8B, 0x10,
+        0xF8, 0xEE, 0x9F, 0x7B, 0xDA, 0xA4, 0xE0, 0x7E, 0x9C, 0xA0, 0x8A, 0x7B, 0x49, 0x94, 0x73, 0x65, 0xF5, 0xE8, 0x5F, 0xD0,
+        0xEF, 0xB6, 0x1B, 0x5B, 0xCB, 0x79, 0x41, 0x8C, 0xAD, 0x9E, 0xD7, 0x2A, 0x54, 0x0F, 0xAB, 0x8A, 0xA6, 0xE1, 0x9B, 0xAF,
+        0xF3, 0x86, 0x54, 0xF0, 0xD6, 0xC1, 0xD1, 0xC1, 0x6F, 0xA6, 0x8A, 0xE5, 0x3C, 0xDC, 0x4D, 0xA3, 0xE1, 0xDC, 0x50, 0xA9,
+        0x14, 0xEB, 0x0A, 0x38, 0xBF, 0xD7, 0xF2, 0xDD, 0xAB, 0x96, 0xA3, 0x98, 0xD0, 0xAC, 0x52, 0x92, 0x6F, 0xB0, 0xDC, 0x87,
+        0x68, 0x4D, 0x68, 0xDE, 0x0F, 0x9F, 0xA3, 0xF2, 0x92, 0xF6, 0xDB, 0x4F, 0x6C, 0x58, 0x72, 0xC3, 0x28, 0x2B, 0xFD, 0x72,
+        0xF5, 0x1D, 0xCF, 0x40, 0x5E, 0xE8, 0x14, 0x1C, 0x64, 0xDE, 0xE5, 0x01
+    ];
+
+    // NOTE: PALETTED_IMAGE in the extended format, with a VP8X chunk that flags alpha and an EXIF chunk
+    //       with an odd size before the image data.
+    const EXTENDED_IMAGE: [u8; 100] = [
+        0x52, 0x49, 0x46, 0x46, 0x5C, 0x00, 0x00, 0x00, 0x57, 0x45, 0x42, 0x50, 0x56, 0x50, 0x38, 0x58, 0x0A, 0x00, 0x00, 0x00,
+        0x10, 0x00, 0x00, 0x00, 0x0C, 0x00, 0x00, 0x06, 0x00, 0x00, 0x45, 0x58, 0x49, 0x46, 0x03, 0x00, 0x00, 0x00, 0x61, 0x62,
+        0x63, 0x00, 0x56, 0x50, 0x38, 0x4C, 0x31, 0x00, 0x00, 0x00, 0x2F, 0x0C, 0x80, 0x01, 0x10, 0x1F, 0x20, 0x10, 0x48, 0xDA,
+        0x1F, 0x7A, 0x8D, 0xF9, 0x17, 0x10, 0x14, 0xF9, 0x3F, 0xDA, 0xFC, 0x07, 0xF0, 0xA5, 0x40, 0x21, 0x1B, 0x49, 0xD0, 0xDE,
+        0x27, 0x39, 0x92, 0xAB, 0xB6, 0x14, 0xC7, 0x10, 0xD1, 0xFF, 0x22, 0xF8, 0x5C, 0x04, 0x9D, 0x6F, 0x42, 0xFA, 0x37, 0x00
+    ];
+
+    // NOTE: 24x20 lossy with partial macroblocks, encoded by libwebp without loop filter.
+    const LOSSY_IMAGE: [u8; 454] = [
+        0x52, 0x49, 0x46, 0x46, 0xBE, 0x01, 0x00, 0x00, 0x57, 0x45, 0x42, 0x50, 0x56, 0x50, 0x38, 0x20, 0xB2, 0x01, 0x00, 0x00,
+        0xD4, 0x09, 0x00, 0x9D, 0x01, 0x2A, 0x18, 0x00, 0x14, 0x00, 0x00, 0x00, 0x26, 0x00, 0x09, 0xD3, 0x28, 0x47, 0xA7, 0xFA,
+        0xAF, 0x08, 0x66, 0x81, 0x36, 0x19, 0xD9, 0x9C, 0x21, 0xE2, 0xAF, 0x0C, 0x07, 0xEA, 0x6F, 0xEA, 0x07, 0xBC, 0x57, 0xF2,
+        0xBE, 0x97, 0x2F, 0x35, 0x5F, 0x60, 0x0F, 0x40, 0x0F, 0xD0, 0x0F, 0x49, 0x1F, 0xD9, 0x9F, 0x84, 0x8F, 0xD9, 0xBF, 0xD3,
+        0x9F, 0x6A, 0xE6, 0x62, 0x48, 0xEA, 0xCC, 0xBB, 0x93, 0x0E, 0x2A, 0x04, 0x5D, 0x57, 0xE2, 0x89, 0x7D, 0xFB, 0xFA, 0x3C,
+        0xD9, 0x83, 0xAB, 0xF3, 0xAD, 0xA5, 0x80, 0x00, 0xFE, 0xFE, 0x79, 0x0C, 0x08, 0xF6, 0x75, 0x00, 0xBA, 0x42, 0x9B, 0xA3,
+        0x17, 0x87, 0x2B, 0xEC, 0xF2, 0xCA, 0xF1, 0x67, 0x1D, 0x48, 0x13, 0xE5, 0xD8, 0xEE, 0x30, 0xFA, 0xFB, 0xA5, 0xC0, 0x25,
+        0x3F, 0x2A, 0x5D, 0xF1, 0x1C, 0xCB, 0x4D, 0x66, 0x5B, 0xC0, 0x66, 0x26, 0x6E, 0xB4, 0x46, 0x29, 0xEE, 0x7F, 0xAB, 0xFB,
+        0xE6, 0x9E, 0xBD, 0x67, 0x90, 0x6E, 0x8A, 0x03, 0xF1, 0x08, 0xE6, 0x54, 0x4A, 0xBB, 0xE8, 0xD3, 0xC0, 0x33, 0x2E, 0x16,
+        0x51, 0xDD, 0xA8, 0xEB, 0x2C, 0x49, 0xDC, 0xAD, 0x7F, 0xA5, 0x99, 0x6F, 0x51, 0xB1, 0xCE, 0xE0, 0x8E, 0x61, 0x68, 0x5C,
+        0xD5, 0x82, 0xFF, 0xE5, 0x60, 0x25, 0x82, 0xF5, 0x59, 0x37, 0x89, 0xDA, 0x7C, 0x6F, 0xFA, 0x98, 0xBB, 0xE9, 0x43, 0xC6,
+        0xBC, 0xAC, 0xC0, 0xA4, 0x13, 0x02, 0xB9, 0x90, 0xE9, 0xAD, 0xF7, 0x94, 0x8F, 0xEF, 0xC7, 0x3B, 0xF0, 0x3D, 0x90, 0x52,
+        0x5A, 0xCF, 0x9B, 0xE6, 0xEF, 0xFB, 0x05, 0xBB, 0x10, 0x12, 0xD4, 0x2B, 0xF8, 0xEB, 0xEB, 0xC9, 0xFC, 0xB6, 0x35, 0xE8,
+        0xC2, 0xE9, 0x14, 0x52, 0xB5, 0x8A, 0x7E, 0xC8, 0x29, 0xE8, 0xE8, 0xFE, 0x5F, 0x0A, 0x8B, 0xD3, 0xA5, 0x85, 0xB6, 0x8E,
+        0xEA, 0xE2, 0x02, 0xB0, 0x3F, 0xB2, 0xE7, 0xB4, 0x27, 0xF6, 0x40, 0xFE, 0xC7, 0x7E, 0x7F, 0x95, 0xE6, 0x84, 0x15, 0x2F,
+        0xF9, 0x30, 0xE2, 0x92, 0x40, 0xEF, 0xF9, 0x2A, 0x6F, 0x91, 0xCC, 0x3C, 0x70, 0xFF, 0xBF, 0x84, 0x3A, 0x93, 0xD3, 0x1F,
+        0x9F, 0xF3, 0x59, 0x43, 0x79, 0x1D, 0xC1, 0x48, 0xCC, 0x1F, 0x8A, 0xB4, 0xBD, 0xFF, 0xFD, 0x00, 0xC9, 0x73, 0x23, 0x71,
+        0x89, 0xF2, 0x49, 0xE6, 0xB6, 0xC5, 0x83, 0xB5, 0xF8, 0x77, 0x49, 0xEF, 0x2D, 0x79, 0x02, 0x41, 0xA2, 0xB7, 0xE2, 0x0B,
+        0x82, 0x33, 0xD2, 0x22, 0x53, 0xC4, 0xAE, 0x03, 0x07, 0xCB, 0x16, 0x33, 0xD6, 0xF5, 0x9D, 0xC0, 0x63, 0xF5, 0x30, 0x64,
+        0x39, 0x5A, 0xF5, 0x3C, 0x4D, 0xBB, 0xF0, 0x3C, 0x64, 0x32, 0x1F, 0xC9, 0x5E, 0x35, 0x3A, 0x66, 0x29, 0x03, 0x72, 0x89,
+        0x3D, 0x00, 0x0A, 0xE8, 0x9E, 0x83, 0x70, 0xA1, 0xE3, 0xE0, 0xC8, 0xC6, 0x2D, 0x7C, 0x09, 0xE4, 0xDD, 0x08, 0x7D, 0xB0,
+        0x95, 0x8A, 0x06, 0x35, 0x31, 0x0D, 0x7B, 0x69, 0xDF, 0xBC, 0x76, 0xBF, 0x39, 0xE8, 0xEE, 0xB7, 0x96, 0xA1, 0xC2, 0x81,
+        0x4E, 0x15, 0x6C, 0x78, 0x97, 0x4D, 0xD9, 0xB7, 0xF4, 0x29, 0x40, 0x00, 0x00, 0x00
+    ];
+
+    // NOTE: The same picture encoded with the normal loop filter and four segments.
+    const NORMAL_LOOP_FILTER_IMAGE: [u8; 440] = [
+        0x52, 0x49, 0x46, 0x46, 0xB0, 0x01, 0x00, 0x00, 0x57, 0x45, 0x42, 0x50, 0x56, 0x50, 0x38, 0x20, 0xA4, 0x01, 0x00, 0x00,
+        0x30, 0x0A, 0x00, 0x9D, 0x01, 0x2A, 0x18, 0x00, 0x14, 0x00, 0x3E, 0x81, 0x34, 0x94, 0x45, 0x27, 0xA4, 0x23, 0x21, 0x28,
+        0x0A, 0xA8, 0xF0, 0x10, 0x09, 0xEC, 0x00, 0x9D, 0x32, 0x84, 0x75, 0x77, 0x99, 0xF0, 0xE7, 0xE8, 0x17, 0x78, 0x36, 0x84,
+        0x4B, 0xA7, 0x51, 0x3F, 0x80, 0xC4, 0x01, 0xCF, 0x41, 0xD1, 0x55, 0xE6, 0xC1, 0xEC, 0x01, 0xE8, 0x01, 0xFA, 0x01, 0xE9,
+        0x81, 0xFA, 0xC1, 0xF0, 0xA3, 0xFB, 0x64, 0x46, 0xFE, 0x18, 0x13, 0xA5, 0xFA, 0x35, 0x6E, 0xF2, 0x4D, 0xA8, 0x8C, 0xDD,
+        0xBE, 0xE9, 0xFB, 0x80, 0xCF, 0x9B, 0x03, 0x42, 0x51, 0xA2, 0x00, 0xFE, 0xFB, 0x19, 0x07, 0x03, 0x0D, 0x01, 0x9F, 0x67,
+        0xA0, 0xB6, 0x92, 0x30, 0xFB, 0x2E, 0x3A, 0x65, 0x15, 0x63, 0xD8, 0xA5, 0x86, 0x56, 0xC6, 0xAB, 0x42, 0x2F, 0x99, 0xA7,
+        0x65, 0xDC, 0x9E, 0x09, 0xF3, 0x00, 0xF7, 0x67, 0x0A, 0xD1, 0x6E, 0x00, 0x0E, 0xD3, 0x76, 0x84, 0x38, 0x4E, 0x4D, 0x13,
+        0xC1, 0x0F, 0x39, 0x5A, 0x16, 0x80, 0x0F, 0xB8, 0xF7, 0xD7, 0xCE, 0x69, 0xD9, 0x40, 0xCE, 0xDA, 0x8E, 0x3B, 0x57, 0x3C,
+        0xEF, 0x1B, 0x04, 0xE8, 0xB6, 0xA7, 0x45, 0xDE, 0x21, 0x2B, 0x95, 0xBD, 0x10, 0xB2, 0x74, 0xDA, 0x77, 0xE6, 0xDF, 0x44,
+        0xB5, 0x6E, 0x1C, 0x05, 0xFC, 0x0B, 0x64, 0xA3, 0x40, 0xDA, 0xD6, 0xE3, 0x51, 0xEA, 0xCB, 0x26, 0x77, 0xB9, 0x70, 0x19,
+        0x50, 0x3A, 0x40, 0x0B, 0x48, 0x00, 0xE5, 0xB2, 0x10, 0x5D, 0x85, 0xD2, 0xD1, 0x5C, 0xD8, 0xCB, 0xC5, 0x82, 0xD6, 0x07,
+        0xFB, 0x32, 0x23, 0xFD, 0x0A, 0x98, 0x3E, 0xEB, 0xB7, 0x34, 0x93, 0x96, 0x96, 0x33, 0x51, 0xC9, 0xD3, 0xA1, 0x42, 0x03,
+        0xFF, 0xC1, 0xAE, 0xA9, 0xC4, 0xE2, 0xAF, 0xFB, 0xFF, 0x8F, 0xCB, 0xEE, 0x23, 0xE0, 0x6D, 0x41, 0xFE, 0xC4, 0xD0, 0x3F,
+        0x0B, 0xD1, 0xC6, 0xB6, 0x34, 0xB0, 0x5B, 0x79, 0x5F, 0x9B, 0x7F, 0x83, 0xDF, 0x93, 0xFD, 0x9B, 0xA3, 0xFA, 0xFF, 0x9D,
+        0x8A, 0xD5, 0x8F, 0x0C, 0xB5, 0x7F, 0xA8, 0x7F, 0xDA, 0xAA, 0x7D, 0xF1, 0xA3, 0x40, 0x1C, 0x4F, 0x44, 0xA0, 0x2D, 0x9E,
+        0xBB, 0xD2, 0xFF, 0x38, 0x4C, 0xF4, 0x23, 0x6C, 0x3C, 0x84, 0x6B, 0x2B, 0xCE, 0x43, 0x99, 0xBD, 0x65, 0x56, 0xCB, 0x37,
+        0xE1, 0xB3, 0xF2, 0x22, 0xFD, 0x4C, 0x1F, 0xD4, 0xF9, 0x7B, 0x81, 0xC3, 0xF1, 0x4A, 0x09, 0x58, 0xFF, 0xEC, 0xDC, 0x06,
+        0x0F, 0xF4, 0x4E, 0x63, 0x14, 0x71, 0x2E, 0x50, 0x17, 0x72, 0x45, 0x60, 0x26, 0x47, 0x96, 0xDE, 0xBF, 0x1E, 0x2C, 0x50,
+        0xA0, 0xF8, 0xDA, 0xFB, 0x5A, 0xE6, 0xF8, 0xFF, 0x30, 0x1D, 0xBF, 0x88, 0x7B, 0x7C, 0x3E, 0x7E, 0x54, 0x4C, 0x0D, 0x1F,
+        0xC4, 0x0B, 0xC4, 0x9C, 0x61, 0x21, 0xD3, 0x46, 0x13, 0x4F, 0xE1, 0x27, 0x3F, 0x47, 0x9B, 0xB9, 0xF3, 0x9F, 0x23, 0x68,
+        0x6B, 0xF8, 0xCB, 0x67, 0x9C, 0xE3, 0x0D, 0x26, 0x6F, 0xCC, 0xF7, 0x59, 0x41, 0x90, 0xA3, 0xA1, 0x12, 0x00, 0x00, 0x00
+    ];
+
+    // NOTE: The same picture encoded with the simple loop filter.
+    const SIMPLE_LOOP_FILTER_IMAGE: [u8; 454] = [
+        0x52, 0x49, 0x46, 0x46, 0xBE, 0x01, 0x00, 0x00, 0x57, 0x45, 0x42, 0x50, 0x56, 0x50, 0x38, 0x20, 0xB2, 0x01, 0x00, 0x00,
+        0xD2, 0x09, 0x00, 0x9D, 0x01, 0x2A, 0x18, 0x00, 0x14, 0x00, 0x12, 0x00, 0x26, 0x00, 0x09, 0xD3, 0x28, 0x47, 0xA7, 0xFA,
+        0xAF, 0x08, 0x66, 0x81, 0x36, 0x19, 0xD9, 0x9C, 0x21, 0xE2, 0xAF, 0x0C, 0x07, 0xEA, 0x6F, 0xEA, 0x07, 0xBC, 0x57, 0xF2,
+        0xBE, 0x97, 0x2F, 0x35, 0x5F, 0x60, 0x0F, 0x40, 0x0F, 0xD0, 0x0F, 0x49, 0x1F, 0xD9, 0x9F, 0x84, 0x8F, 0xD9, 0xBF, 0xD3,
+        0x9F, 0x6A, 0xE6, 0x62, 0x48, 0xEA, 0xCC, 0xBB, 0x93, 0x0E, 0x2A, 0x04, 0x5D, 0x57, 0xE2, 0x89, 0x7D, 0xFB, 0xFA, 0x3C,
+        0xD9, 0x83, 0xAB, 0xF3, 0xAD, 0xA5, 0x80, 0x00, 0xFE, 0xFE, 0x79, 0x0C, 0x08, 0xF6, 0x75, 0x00, 0xBA, 0x42, 0x9B, 0xA3,
+        0x17, 0x87, 0x2B, 0xEC, 0xF2, 0xCA, 0xF1, 0x67, 0x1D, 0x48, 0x13, 0xE5, 0xD8, 0xEE, 0x30, 0xFA, 0xFB, 0xA5, 0xC0, 0x25,
+        0x3F, 0x2A, 0x5D, 0xF1, 0x1C, 0xCB, 0x4D, 0x66, 0x5B, 0xC0, 0x66, 0x26, 0x6E, 0xB4, 0x46, 0x29, 0xEE, 0x7F, 0xAB, 0xFB,
+        0xE6, 0x9E, 0xBD, 0x67, 0x90, 0x6E, 0x8A, 0x03, 0xF1, 0x08, 0xE6, 0x54, 0x4A, 0xBB, 0xE8, 0xD3, 0xC0, 0x33, 0x2E, 0x16,
+        0x51, 0xDD, 0xA8, 0xEB, 0x2C, 0x49, 0xDC, 0xAD, 0x7F, 0xA5, 0x99, 0x6F, 0x51, 0xB1, 0xCE, 0xE0, 0x8E, 0x61, 0x68, 0x5C,
+        0xD5, 0x82, 0xFF, 0xE5, 0x60, 0x25, 0x82, 0xF5, 0x59, 0x37, 0x89, 0xDA, 0x7C, 0x6F, 0xFA, 0x98, 0xBB, 0xE9, 0x43, 0xC6,
+        0xBC, 0xAC, 0xC0, 0xA4, 0x13, 0x02, 0xB9, 0x90, 0xE9, 0xAD, 0xF7, 0x94, 0x8F, 0xEF, 0xC7, 0x3B, 0xF0, 0x3D, 0x90, 0x52,
+        0x5A, 0xCF, 0x9B, 0xE6, 0xEF, 0xFB, 0x05, 0xBB, 0x10, 0x12, 0xD4, 0x2B, 0xF8, 0xEB, 0xEB, 0xC9, 0xFC, 0xB6, 0x35, 0xE8,
+        0xC2, 0xE9, 0x14, 0x52, 0xB5, 0x8A, 0x7E, 0xC8, 0x29, 0xE8, 0xE8, 0xFE, 0x5F, 0x0A, 0x8B, 0xD3, 0xA5, 0x85, 0xB6, 0x8E,
+        0xEA, 0xE2, 0x02, 0xB0, 0x3F, 0xB2, 0xE7, 0xB4, 0x27, 0xF6, 0x40, 0xFE, 0xC7, 0x7E, 0x7F, 0x95, 0xE6, 0x84, 0x15, 0x2F,
+        0xF9, 0x30, 0xE2, 0x92, 0x40, 0xEF, 0xF9, 0x2A, 0x6F, 0x91, 0xCC, 0x3C, 0x70, 0xFF, 0xBF, 0x84, 0x3A, 0x93, 0xD3, 0x1F,
+        0x9F, 0xF3, 0x59, 0x43, 0x79, 0x1D, 0xC1, 0x48, 0xCC, 0x1F, 0x8A, 0xB4, 0xBD, 0xFF, 0xFD, 0x00, 0xC9, 0x73, 0x23, 0x71,
+        0x89, 0xF2, 0x49, 0xE6, 0xB6, 0xC5, 0x83, 0xB5, 0xF8, 0x77, 0x49, 0xEF, 0x2D, 0x79, 0x02, 0x41, 0xA2, 0xB7, 0xE2, 0x0B,
+        0x82, 0x33, 0xD2, 0x22, 0x53, 0xC4, 0xAE, 0x03, 0x07, 0xCB, 0x16, 0x33, 0xD6, 0xF5, 0x9D, 0xC0, 0x63, 0xF5, 0x30, 0x64,
+        0x39, 0x5A, 0xF5, 0x3C, 0x4D, 0xBB, 0xF0, 0x3C, 0x64, 0x32, 0x1F, 0xC9, 0x5E, 0x35, 0x3A, 0x66, 0x29, 0x03, 0x72, 0x89,
+        0x3D, 0x00, 0x0A, 0xE8, 0x9E, 0x83, 0x70, 0xA1, 0xE3, 0xE0, 0xC8, 0xC6, 0x2D, 0x7C, 0x09, 0xE4, 0xDD, 0x08, 0x7D, 0xB0,
+        0x95, 0x8A, 0x06, 0x35, 0x31, 0x0D, 0x7B, 0x69, 0xDF, 0xBC, 0x76, 0xBF, 0x39, 0xE8, 0xEE, 0xB7, 0x96, 0xA1, 0xC2, 0x81,
+        0x4E, 0x15, 0x6C, 0x78, 0x97, 0x4D, 0xD9, 0xB7, 0xF4, 0x29, 0x40, 0x00, 0x00, 0x00
+    ];
+
+    // NOTE: 6x5 lossy without alpha, the color for the ALPH chunks below.
+    const ALPHA_BASE_IMAGE: [u8; 72] = [
+        0x52, 0x49, 0x46, 0x46, 0x40, 0x00, 0x00, 0x00, 0x57, 0x45, 0x42, 0x50, 0x56, 0x50, 0x38, 0x20, 0x34, 0x00, 0x00, 0x00,
+        0xD4, 0x01, 0x00, 0x9D, 0x01, 0x2A, 0x06, 0x00, 0x05, 0x00, 0x00, 0x00, 0x26, 0x00, 0x09, 0xD2, 0xE8, 0x00, 0x0C, 0x24,
+        0xB2, 0x13, 0xE4, 0x00, 0xFE, 0xEE, 0xEE, 0x43, 0x13, 0x3C, 0xFE, 0xBB, 0x64, 0xD4, 0x6B, 0xBF, 0x29, 0x7F, 0xF2, 0x69,
+        0x9F, 0xB5, 0xFD, 0x0E, 0x7A, 0xED, 0xFF, 0x16, 0x1E, 0xB4, 0x00, 0x00
+    ];
+
+    // NOTE: ALPH payloads for `alpha_plane()` compressed as lossless green channel without the VP8L header, one for
+    //       each filtering method.
+    const ALPH_NONE: [u8; 38] = [
+        0x99, 0x32, 0x44, 0xF4, 0x3F, 0xA0, 0xA0, 0x6D, 0x1B, 0x69, 0xBB, 0x6F, 0x2F, 0x81, 0x32, 0x38, 0x0C, 0xC7, 0x1F, 0x51,
+        0x29, 0x1C, 0x89, 0x88, 0x48, 0xB9, 0x1F, 0x66, 0x34, 0x83, 0xD1, 0x6C, 0x80, 0x66, 0x34, 0x98, 0xB1, 0x1F
+    ];
+
+    const ALPH_HORIZONTAL: [u8; 38] = [
+        0x37, 0x40, 0x26, 0x6D, 0x1B, 0xEA, 0xAE, 0x68, 0x58, 0xC4, 0xFC, 0x47, 0x6F, 0x00, 0xA0, 0x18, 0x92, 0x24, 0x36, 0x48,
+        0x73, 0x04, 0xE9, 0x1D, 0x05, 0x22, 0xC0, 0x45, 0xF4, 0x3F, 0x34, 0xB3, 0xE0, 0x1E, 0xC5, 0x32, 0x0B, 0x0E
+    ];
+
+    const ALPH_VERTICAL: [u8; 41] = [
+        0x37, 0xA0, 0xA6, 0x6D, 0x03, 0x16, 0xA6, 0xD7, 0x8C, 0x98, 0xFF, 0xE0, 0x99, 0x99, 0x09, 0x14, 0x43, 0x92, 0xC4, 0x86,
+        0x21, 0x8E, 0x20, 0x11, 0x24, 0x82, 0x55, 0x58, 0x80, 0x7B, 0x6D, 0x44, 0xFF, 0xE3, 0x25, 0x1C, 0xD1, 0x65, 0xED, 0x38,
+        0x02
+    ];
+
+    const ALPH_GRADIENT: [u8; 45] = [
+        0x3F, 0xA0, 0xA8, 0x6D, 0x1B, 0xA8, 0xED, 0xB6, 0xE7, 0xCD, 0x1F, 0xC8, 0xA0, 0x46, 0xCC, 0x7F, 0xA0, 0x84, 0x90, 0xDA,
+        0x79, 0x50, 0x08, 0x49, 0x0E, 0x1B, 0x75, 0x08, 0x42, 0xD7, 0xE9, 0x04, 0x31, 0xC8, 0x24, 0xA2, 0xFF, 0xE1, 0x33, 0x7C,
+        0xC8, 0x06, 0x8F, 0xC6, 0xB7
+    ];
+
+    // NOTE: 4x4 lossless animation with background color 0x10203040 and loop count 3. Frames:
+    //       0: opaque red over the whole canvas, 100 ms, blend, keep
+    //       1: half transparent blue 2x2 at (2, 0), 50 ms, blend, dispose to background
+    //       2: half transparent green 2x2 at (0, 0), 250 ms, no blend, keep
+    //       3: opaque white 2x2 at (2, 2), no duration, blend, keep
+    const ANIMATED_IMAGE: [u8; 238] = [
+        0x52, 0x49, 0x46, 0x46, 0xE6, 0x00, 0x00, 0x00, 0x57, 0x45, 0x42, 0x50, 0x56, 0x50, 0x38, 0x58, 0x0A, 0x00, 0x00, 0x00,
+        0x12, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x03, 0x00, 0x00, 0x41, 0x4E, 0x49, 0x4D, 0x06, 0x00, 0x00, 0x00, 0x40, 0x30,
+        0x20, 0x10, 0x03, 0x00, 0x41, 0x4E, 0x4D, 0x46, 0x28, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03, 0x00,
+        0x00, 0x03, 0x00, 0x00, 0x64, 0x00, 0x00, 0x00, 0x56, 0x50, 0x38, 0x4C, 0x0F, 0x00, 0x00, 0x00, 0x2F, 0x03, 0xC0, 0x00,
+        0x00, 0x07, 0x10, 0xFD, 0x8F, 0xFE, 0x07, 0x22, 0xA2, 0xFF, 0x01, 0x00, 0x41, 0x4E, 0x4D, 0x46, 0x28, 0x00, 0x00, 0x00,
+        0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x32, 0x00, 0x00, 0x01, 0x56, 0x50, 0x38, 0x4C,
+        0x0F, 0x00, 0x00, 0x00, 0x2F, 0x01, 0x40, 0x00, 0x10, 0x07, 0x10, 0xD1, 0xFF, 0x02, 0x06, 0x22, 0xA2, 0xFF, 0x01, 0x00,
+        0x41, 0x4E, 0x4D, 0x46, 0x28, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00,
+        0xFA, 0x00, 0x00, 0x02, 0x56, 0x50, 0x38, 0x4C, 0x0F, 0x00, 0x00, 0x00, 0x2F, 0x01, 0x40, 0x00, 0x10, 0x07, 0xD0, 0xFF,
+        0x88, 0x02, 0x06, 0x22, 0xA2, 0xFF, 0x01, 0x00, 0x41, 0x4E, 0x4D, 0x46, 0x2A, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x01,
+        0x00, 0x00, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x56, 0x50, 0x38, 0x4C, 0x11, 0x00, 0x00, 0x00,
+        0x2F, 0x01, 0x40, 0x00, 0x00, 0x07, 0xD0, 0xFF, 0xFE, 0xF7, 0xBF, 0xFF, 0x81, 0x88, 0xE8, 0x7F, 0x00, 0x00
+    ];
+
+    const PALETTE: [[u8; 4]; 4] = [[255, 0, 0, 255], [0, 255, 0, 255], [0, 0, 255, 255], [0, 0, 0, 0]];
+
+    fn decode(bytes: &[u8]) -> Bitmap {
+        WebPImageDecoderPlugin::create(bytes).unwrap().frame(0).unwrap().image
+    }
+
+    #[test]
+    fn decodes_predictor_and_cross_color_transforms() {
+        assert_pixels(&decode(&GRADIENT_IMAGE), 16, 16, |x, y| [(x * 16) as u8, (y * 16) as u8, ((x + y) * 8) as u8, (255 - x * 4) as u8]);
+    }
+
+    #[test]
+    fn decodes_color_indexing_transform_with_bundled_pixels() {
+        assert_pixels(&decode(&PALETTED_IMAGE), 13, 7, |x, y| PALETTE[((x / 2 + y) % 4) as usize]);
+    }
+
+    #[test]
+    fn decodes_color_cache_and_backward_references() {
+        assert_pixels(&decode(&REPEATED_IMAGE), 32, 32, |x, y| {
+            let value = (x * 37 + (y % 5) * 101) % 251;
+            [value as u8, (value * 3) as u8, (value * 7) as u8, 255]
+        });
+    }
+
+    #[test]
+    fn decodes_meta_prefix_codes() {
+        let mut seed = 1u32;
+        let mut expected = Vec::new();
+        for y in 0..32 {
+            for _ in 0..32 {
+                seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                let noise = (seed >> 16) as u8;
+                expected.push(if y < 16 { [noise & 0x07, 0, noise >> 5, 255] } else { [0xF0 | (noise & 0x07), noise >> 5, 0xFF, 255] });
+            }
+        }
+        assert_pixels(&decode(&REGIONS_IMAGE), 32, 32, |x, y| expected[(y * 32 + x) as usize]);
+    }
+
+    #[test]
+    fn decodes_extended_format() {
+        let mut decoder = WebPImageDecoderPlugin::create(&EXTENDED_IMAGE).unwrap();
+        assert_eq!((decoder.size().width, decoder.size().height), (13, 7));
+        assert_pixels(&decoder.frame(0).unwrap().image, 13, 7, |x, y| PALETTE[((x / 2 + y) % 4) as usize]);
+        assert_eq!(decoder.frame(1).err(), Some(Error::FrameIndexOutOfRange));
+
+        // NOTE: Grow the canvas to 14x7, which no longer matches the image data.
+        let mut data = EXTENDED_IMAGE;
+        data[24] = 13;
+        let mut decoder = WebPImageDecoderPlugin::create(&data).unwrap();
+        assert_eq!(decoder.frame(0).err(), Some(Error::InvalidData("WebP image data size does not match the canvas size")));
+    }
+
+    #[test]
+    fn ignores_data_after_riff_chunk() {
+        let mut data = GRADIENT_IMAGE.to_vec();
+        data.extend_from_slice(b"trailing garbage");
+        assert_pixels(&decode(&data), 16, 16, |x, y| [(x * 16) as u8, (y * 16) as u8, ((x + y) * 8) as u8, (255 - x * 4) as u8]);
+    }
+
+    #[test]
+    fn rejects_malformed_files() {
+        assert_eq!(WebPImageDecoderPlugin::create(&GRADIENT_IMAGE[..8]).err(), Some(Error::Truncated));
+        assert_eq!(WebPImageDecoderPlugin::create(&GRADIENT_IMAGE[..24]).err(), Some(Error::Truncated));
+
+        let mut data = GRADIENT_IMAGE;
+        data[20] = 0x2E;
+        assert_eq!(WebPImageDecoderPlugin::create(&data).err(), Some(Error::InvalidHeader("Invalid VP8L signature")));
+
+        // NOTE: Cut the bit stream short, the header is still intact.
+        let mut data = GRADIENT_IMAGE[..40].to_vec();
+        data[4..8].copy_from_slice(&32u32.to_le_bytes());
+        data[16..20].copy_from_slice(&20u32.to_le_bytes());
+        let mut decoder = WebPImageDecoderPlugin::create(&data).unwrap();
+        assert_eq!(decoder.frame(0).err(), Some(Error::Truncated));
+
+        unsafe {
+            assert_eq!(webp_image_decoder_plugin_new(GRADIENT_IMAGE.as_ptr(), GRADIENT_IMAGE.len(), std::ptr::null_mut()), ErrorCode::InvalidArgument);
+        }
+    }
+
+    // NOTE: FNV-1a over the RGB bytes of the pixels, as decoded by libwebp.
+    fn checksum(image: &Bitmap) -> u32 {
+        let mut hash = 0x811C9DC5u32;
+        for y in 0..image.size.height {
+            for x in 0..image.size.width {
+                let color = Color::from(image.get_pixel(x, y));
+                for byte in [color.red(), color.green(), color.blue()] {
+                    hash = (hash ^ byte as u32).wrapping_mul(0x01000193);
+                }
+            }
+        }
+        hash
+    }
+
+    #[test]
+    fn decodes_lossy_images_like_libwebp() {
+        for (name, data, expected_checksum) in [
+            ("no loop filter", &LOSSY_IMAGE[..], 0x0FDAAAC8),
+            ("normal loop filter", &NORMAL_LOOP_FILTER_IMAGE[..], 0x0FCF4D5C),
+            ("simple loop filter", &SIMPLE_LOOP_FILTER_IMAGE[..], 0xB77E3477)
+        ] {
+            let image = decode(data);
+            assert_eq!((image.size.width, image.size.height), (24, 20), "{name}");
+            assert!(matches!(image.format, BitmapFormat::BGRx8888), "{name}");
+            assert_eq!(checksum(&image), expected_checksum, "{name}");
+        }
+    }
+
+    fn alpha_plane() -> Vec<u8> {
+        (0..5).flat_map(|y| (0..6).map(move |x| ((x * 40 + y * 25 + (x * y) % 3 * 7) % 256) as u8)).collect()
+    }
+
+    fn filter_alpha_plane(plane: &[u8], filtering_method: u8) -> Vec<u8> {
+        let width = 6;
+        (0..plane.len()).map(|index| {
+            let (x, y) = (index % width, index / width);
+            let prediction = match (filtering_method, x, y) {
+                (0, _, _) | (_, 0, 0) => 0,
+                (_, _, 0) => plane[index - 1],
+                (_, 0, _) => plane[index - width],
+                (1, _, _) => plane[index - 1],
+                (2, _, _) => plane[index - width],
+                _ => (plane[index - 1] as i32 + plane[index - width] as i32 - plane[index - width - 1] as i32).clamp(0, 255) as u8
+            };
+            plane[index].wrapping_sub(prediction)
+        }).collect()
+    }
+
+    fn with_alpha_chunk(flags: u8, payload: &[u8]) -> Vec<u8> {
+        let chunk = |chunk_type: &[u8], data: &[u8]| {
+            let mut chunk = [chunk_type, &(data.len() as u32).to_le_bytes(), data].concat();
+            if data.len() % 2 == 1 {
+                chunk.push(0);
+            }
+            chunk
+        };
+        let vp8x = chunk(b"VP8X", &[0x10, 0, 0, 0, 5, 0, 0, 4, 0, 0]);
+        let alph = chunk(b"ALPH", &[&[flags], payload].concat());
+        let body = [&b"WEBP"[..], &vp8x, &alph, &ALPHA_BASE_IMAGE[12..]].concat();
+        [&b"RIFF"[..], &(body.len() as u32).to_le_bytes(), &body].concat()
+    }
+
+    #[test]
+    fn decodes_alpha_with_each_filtering_and_compression_method() {
+        let color = decode(&ALPHA_BASE_IMAGE);
+        let plane = alpha_plane();
+        for filtering_method in 0..4u8 {
+            let compressed_payloads = [&ALPH_NONE[..], &ALPH_HORIZONTAL, &ALPH_VERTICAL, &ALPH_GRADIENT];
+            for (compression_method, payload) in [(0, filter_alpha_plane(&plane, filtering_method)), (1, compressed_payloads[filtering_method as usize].to_vec())] {
+                let image = decode(&with_alpha_chunk(filtering_method << 2 | compression_method, &payload));
+                assert!(matches!(image.format, BitmapFormat::BGRA8888));
+                for (index, &alpha) in plane.iter().enumerate() {
+                    let (x, y) = ((index % 6) as i32, (index / 6) as i32);
+                    assert_eq!(image.get_pixel(x, y), (color.get_pixel(x, y) & 0x00FF_FFFF) | (alpha as u32) << 24, "filtering {filtering_method}, compression {compression_method}, pixel ({x}, {y})");
+                }
+            }
+        }
+
+        // NOTE: The pre-processing bits do not change decoding.
+        let image = decode(&with_alpha_chunk(0x30, &plane));
+        assert_eq!(Color::from(image.get_pixel(5, 4)).alpha(), plane[29]);
+    }
+
+    #[test]
+    fn rejects_invalid_alpha_chunks() {
+        let plane = alpha_plane();
+        let data = with_alpha_chunk(2, &plane);
+        let mut decoder = WebPImageDecoderPlugin::create(&data).unwrap();
+        assert_eq!(decoder.frame(0).err(), Some(Error::InvalidData("Invalid ALPH compression method")));
+        let data = with_alpha_chunk(0, &plane[..29]);
+        let mut decoder = WebPImageDecoderPlugin::create(&data).unwrap();
+        assert_eq!(decoder.frame(0).err(), Some(Error::Truncated));
+    }
+
+    fn animation_frame(index: usize) -> [[ARGB; 4]; 4] {
+        const RED: ARGB = 0xFFFF0000;
+        const BLUE: ARGB = 0x800000FF;
+        const GREEN: ARGB = 0x8000FF00;
+        const WHITE: ARGB = 0xFFFFFFFF;
+        let mut canvas = [[RED; 4]; 4];
+        let mut fill = |x: usize, y: usize, color: ARGB| {
+            for row in &mut canvas[y..y + 2] {
+                row[x..x + 2].fill(color);
+            }
+        };
+        if index == 1 {
+            fill(2, 0, Color::from(RED).blend(Color::from(BLUE)).color);
+        }
+        if index >= 2 {
+            fill(2, 0, 0);
+            fill(0, 0, GREEN);
+        }
+        if index >= 3 {
+            fill(2, 2, WHITE);
+        }
+        canvas
+    }
+
+    fn assert_animation_frame(image: &Bitmap, index: usize) {
+        for (y, row) in animation_frame(index).iter().enumerate() {
+            for (x, &color) in row.iter().enumerate() {
+                assert_eq!(image.get_pixel(x as i32, y as i32), color, "frame {index}, pixel ({x}, {y})");
+            }
+        }
+    }
+
+    #[test]
+    fn decodes_animation_header() {
+        let decoder = WebPImageDecoderPlugin::create(&ANIMATED_IMAGE).unwrap();
+        assert!(decoder.is_animated());
+        assert_eq!((decoder.size().width, decoder.size().height), (4, 4));
+        assert_eq!(decoder.frame_count(), 4);
+        assert_eq!(decoder.loop_count(), 3);
+        assert_eq!(decoder.background_color(), Some(0x10203040));
+
+        let decoder = WebPImageDecoderPlugin::create(&GRADIENT_IMAGE).unwrap();
+        assert!(!decoder.is_animated());
+        assert_eq!((decoder.frame_count(), decoder.loop_count(), decoder.background_color()), (1, 0, None));
+    }
+
+    #[test]
+    fn composites_animation_frames() {
+        let mut decoder = WebPImageDecoderPlugin::create(&ANIMATED_IMAGE).unwrap();
+        for (index, duration) in [100, 50, 250, 0].into_iter().enumerate() {
+            let frame = decoder.frame(index).unwrap();
+            assert_eq!(frame.duration, duration);
+            assert_animation_frame(&frame.image, index);
+        }
+        assert_eq!(decoder.frame(4).err(), Some(Error::FrameIndexOutOfRange));
+    }
+
+    #[test]
+    fn renders_animation_frames_out_of_order() {
+        let mut decoder = WebPImageDecoderPlugin::create(&ANIMATED_IMAGE).unwrap();
+        for index in [3, 1, 2, 0, 3] {
+            assert_animation_frame(&decoder.frame(index).unwrap().image, index);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_animations() {
+        // NOTE: Move the last frame to (4, 2), which no longer fits on the canvas.
+        let mut data = ANIMATED_IMAGE;
+        let last_frame = data.windows(4).rposition(|chunk_type| chunk_type == b"ANMF").unwrap();
+        data[last_frame + 8] = 2;
+        assert_eq!(WebPImageDecoderPlugin::create(&data).err(), Some(Error::InvalidData("WebP animation frame does not fit on the canvas")));
+
+        // NOTE: Rename the ANIM chunk, so that the frames come without an animation header.
+        let mut data = ANIMATED_IMAGE;
+        data[30..34].copy_from_slice(b"ANIX");
+        assert_eq!(WebPImageDecoderPlugin::create(&data).err(), Some(Error::InvalidData("WebP animation frame before the ANIM chunk")));
+    }
+}
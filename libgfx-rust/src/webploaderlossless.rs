@@ -0,0 +1,804 @@
+use crate::{ARGB, IntSize};
+use crate::bitmap::{Bitmap, BitmapFormat};
+use crate::error::Error;
+use crate::inflate::BitReader;
+
+// NOTE: See https://www.rfc-editor.org/rfc/rfc9649 for the format.
+const VP8L_SIGNATURE: u8 = 0x2F;
+const VP8L_HEADER_SIZE: usize = 5;
+
+const NUM_LITERAL_CODES: usize = 256;
+const NUM_LENGTH_CODES: usize = 24;
+const NUM_DISTANCE_CODES: usize = 40;
+const MAX_COLOR_CACHE_BITS: u32 = 11;
+const MAX_CODE_LENGTH: usize = 15;
+const FAST_TABLE_BITS: u32 = 8;
+
+const CODE_LENGTH_CODE_ORDER: [usize; 19] = [17, 18, 0, 1, 2, 3, 4, 5, 16, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15];
+
+const DISTANCE_MAP: [(i8, i8); 120] = [
+    (0, 1), (1, 0), (1, 1), (-1, 1), (0, 2), (2, 0), (1, 2), (-1, 2),
+    (2, 1), (-2, 1), (2, 2), (-2, 2), (0, 3), (3, 0), (1, 3), (-1, 3),
+    (3, 1), (-3, 1), (2, 3), (-2, 3), (3, 2), (-3, 2), (0, 4), (4, 0),
+    (1, 4), (-1, 4), (4, 1), (-4, 1), (3, 3), (-3, 3), (2, 4), (-2, 4),
+    (4, 2), (-4, 2), (0, 5), (3, 4), (-3, 4), (4, 3), (-4, 3), (5, 0),
+    (1, 5), (-1, 5), (5, 1), (-5, 1), (2, 5), (-2, 5), (5, 2), (-5, 2),
+    (4, 4), (-4, 4), (3, 5), (-3, 5), (5, 3), (-5, 3), (0, 6), (6, 0),
+    (1, 6), (-1, 6), (6, 1), (-6, 1), (2, 6), (-2, 6), (6, 2), (-6, 2),
+    (4, 5), (-4, 5), (5, 4), (-5, 4), (3, 6), (-3, 6), (6, 3), (-6, 3),
+    (0, 7), (7, 0), (1, 7), (-1, 7), (5, 5), (-5, 5), (7, 1), (-7, 1),
+    (4, 6), (-4, 6), (6, 4), (-6, 4), (2, 7), (-2, 7), (7, 2), (-7, 2),
+    (3, 7), (-3, 7), (7, 3), (-7, 3), (5, 6), (-5, 6), (6, 5), (-6, 5),
+    (8, 0), (4, 7), (-4, 7), (7, 4), (-7, 4), (8, 1), (8, 2), (6, 6),
+    (-6, 6), (8, 3), (5, 7), (-5, 7), (7, 5), (-7, 5), (8, 4), (6, 7),
+    (-6, 7), (7, 6), (-7, 6), (8, 5), (7, 7), (-7, 7), (8, 6), (8, 7)
+];
+
+#[derive (Debug, Copy, Clone)]
+pub(crate) struct VP8LHeader {
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) is_alpha_used: bool
+}
+
+/// A canonical prefix code. Codes up to FAST_TABLE_BITS long are looked up directly, longer ones are decoded bit by bit.
+struct PrefixCode {
+    // NOTE: A code with a single symbol doesn't use any bits at all.
+    single_symbol: Option<u16>,
+    // NOTE: Each entry is (symbol << 4) | code length, or 0 if the code is longer than FAST_TABLE_BITS.
+    fast_table: Vec<u32>,
+    length_counts: [u16; MAX_CODE_LENGTH + 1],
+    sorted_symbols: Vec<u16>
+}
+
+impl PrefixCode {
+    fn new(code_lengths: &[u8]) -> Result<Self, Error> {
+        let mut length_counts = [0u16; MAX_CODE_LENGTH + 1];
+        for &length in code_lengths {
+            length_counts[length as usize] += 1;
+        }
+        length_counts[0] = 0;
+
+        let used_symbols: Vec<usize> = (0..code_lengths.len()).filter(|&symbol| code_lengths[symbol] != 0).collect();
+        match used_symbols.len() {
+            0 => return Err(Error::InvalidData("Empty WebP prefix code")),
+            1 => return Ok(Self { single_symbol: Some(used_symbols[0] as u16), fast_table: Vec::new(), length_counts, sorted_symbols: Vec::new() }),
+            _ => {}
+        }
+
+        let mut codes_left = 1i32;
+        for &count in &length_counts[1..] {
+            codes_left = (codes_left << 1) - count as i32;
+            if codes_left < 0 {
+                return Err(Error::InvalidData("Over-subscribed WebP prefix code"));
+            }
+        }
+        if codes_left != 0 {
+            return Err(Error::InvalidData("Incomplete WebP prefix code"));
+        }
+
+        let mut next_code = [0u32; MAX_CODE_LENGTH + 2];
+        for length in 1..=MAX_CODE_LENGTH {
+            next_code[length + 1] = (next_code[length] + length_counts[length] as u32) << 1;
+        }
+
+        let mut fast_table = vec![0u32; 1 << FAST_TABLE_BITS];
+        let mut sorted_symbols = Vec::with_capacity(used_symbols.len());
+        for length in 1..=MAX_CODE_LENGTH as u8 {
+            for &symbol in &used_symbols {
+                if code_lengths[symbol] != length {
+                    continue;
+                }
+                sorted_symbols.push(symbol as u16);
+
+                let code = next_code[length as usize];
+                next_code[length as usize] += 1;
+                if length as u32 > FAST_TABLE_BITS {
+                    continue;
+                }
+                let mut index = (code.reverse_bits() >> (32 - length as u32)) as usize;
+                while index < fast_table.len() {
+                    fast_table[index] = ((symbol as u32) << 4) | length as u32;
+                    index += 1 << length;
+                }
+            }
+        }
+
+        Ok(Self { single_symbol: None, fast_table, length_counts, sorted_symbols })
+    }
+
+    fn decode_symbol(&self, reader: &mut BitReader) -> Result<u16, Error> {
+        if let Some(symbol) = self.single_symbol {
+            return Ok(symbol);
+        }
+
+        let entry = self.fast_table[reader.peek_bits(FAST_TABLE_BITS) as usize];
+        if entry != 0 {
+            reader.consume_bits(entry & 0xF)?;
+            return Ok((entry >> 4) as u16);
+        }
+
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for &count in &self.length_counts[1..] {
+            code |= reader.read_bits(1)? as i32;
+            if code - (count as i32) < first {
+                return Ok(self.sorted_symbols[(index + code - first) as usize]);
+            }
+            index += count as i32;
+            first = (first + count as i32) << 1;
+            code <<= 1;
+        }
+        Err(Error::InvalidData("Invalid WebP prefix code"))
+    }
+}
+
+/// The five prefix codes used for one region of the image.
+struct PrefixCodeGroup {
+    green: PrefixCode,
+    red: PrefixCode,
+    blue: PrefixCode,
+    alpha: PrefixCode,
+    distance: PrefixCode
+}
+
+#[derive (Debug, Clone)]
+enum VP8LTransform {
+    Predictor { width: u32, size_bits: u32, modes: Vec<ARGB> },
+    Color { width: u32, size_bits: u32, elements: Vec<ARGB> },
+    SubtractGreen,
+    ColorIndexing { width: u32, width_bits: u32, palette: Vec<ARGB> }
+}
+
+fn div_round_up(value: u32, bits: u32) -> u32 {
+    (value + (1 << bits) - 1) >> bits
+}
+
+fn allocate_pixels(width: u32, height: u32) -> Result<Vec<ARGB>, Error> {
+    let count = width as usize * height as usize;
+    let mut pixels = Vec::new();
+    if pixels.try_reserve_exact(count).is_err() {
+        return Err(Error::OutOfMemory);
+    }
+    pixels.resize(count, 0);
+    Ok(pixels)
+}
+
+fn read_prefix_code(reader: &mut BitReader, alphabet_size: usize) -> Result<PrefixCode, Error> {
+    let mut code_lengths = vec![0u8; alphabet_size];
+
+    let is_simple_code = reader.read_bits(1)? == 1;
+    if is_simple_code {
+        let symbol_count = reader.read_bits(1)? + 1;
+        let first_symbol_bits = if reader.read_bits(1)? == 1 { 8 } else { 1 };
+        let mut symbols = vec![reader.read_bits(first_symbol_bits)? as usize];
+        if symbol_count == 2 {
+            symbols.push(reader.read_bits(8)? as usize);
+        }
+        for symbol in symbols {
+            if symbol >= alphabet_size {
+                return Err(Error::InvalidData("WebP prefix code symbol out of range"));
+            }
+            code_lengths[symbol] = 1;
+        }
+        return PrefixCode::new(&code_lengths);
+    }
+
+    let mut code_length_code_lengths = [0u8; 19];
+    let code_length_code_count = reader.read_bits(4)? as usize + 4;
+    for &symbol in &CODE_LENGTH_CODE_ORDER[..code_length_code_count] {
+        code_length_code_lengths[symbol] = reader.read_bits(3)? as u8;
+    }
+    let code_length_code = PrefixCode::new(&code_length_code_lengths)?;
+
+    let mut max_symbol = if reader.read_bits(1)? == 1 {
+        let length_bits = 2 + 2 * reader.read_bits(3)?;
+        let max_symbol = 2 + reader.read_bits(length_bits)? as usize;
+        if max_symbol > alphabet_size {
+            return Err(Error::InvalidData("WebP prefix code has too many code lengths"));
+        }
+        max_symbol
+    } else {
+        alphabet_size
+    };
+
+    let mut symbol = 0;
+    let mut previous_code_length = 8u8;
+    while symbol < alphabet_size && max_symbol > 0 {
+        max_symbol -= 1;
+        let code_length = code_length_code.decode_symbol(reader)?;
+        if code_length < 16 {
+            code_lengths[symbol] = code_length as u8;
+            symbol += 1;
+            if code_length != 0 {
+                previous_code_length = code_length as u8;
+            }
+            continue;
+        }
+
+        let (repeat_count, repeated_length) = match code_length {
+            16 => (3 + reader.read_bits(2)? as usize, previous_code_length),
+            17 => (3 + reader.read_bits(3)? as usize, 0),
+            _ => (11 + reader.read_bits(7)? as usize, 0)
+        };
+        if symbol + repeat_count > alphabet_size {
+            return Err(Error::InvalidData("WebP prefix code lengths overflow the alphabet"));
+        }
+        code_lengths[symbol..symbol + repeat_count].fill(repeated_length);
+        symbol += repeat_count;
+    }
+
+    PrefixCode::new(&code_lengths)
+}
+
+fn read_prefix_code_group(reader: &mut BitReader, color_cache_size: usize) -> Result<PrefixCodeGroup, Error> {
+    Ok(PrefixCodeGroup {
+        green: read_prefix_code(reader, NUM_LITERAL_CODES + NUM_LENGTH_CODES + color_cache_size)?,
+        red: read_prefix_code(reader, NUM_LITERAL_CODES)?,
+        blue: read_prefix_code(reader, NUM_LITERAL_CODES)?,
+        alpha: read_prefix_code(reader, NUM_LITERAL_CODES)?,
+        distance: read_prefix_code(reader, NUM_DISTANCE_CODES)?
+    })
+}
+
+/// Turns a length or distance prefix symbol into its value, reading the extra bits.
+fn read_prefix_coded_value(reader: &mut BitReader, prefix: u16) -> Result<usize, Error> {
+    if prefix < 4 {
+        return Ok(prefix as usize + 1);
+    }
+    let extra_bits = (prefix as u32 - 2) >> 1;
+    let offset = (2 + (prefix as usize & 1)) << extra_bits;
+    Ok(offset + reader.read_bits(extra_bits)? as usize + 1)
+}
+
+fn distance_for_code(code: usize, width: u32) -> usize {
+    if code > DISTANCE_MAP.len() {
+        return code - DISTANCE_MAP.len();
+    }
+    let (x_offset, y_offset) = DISTANCE_MAP[code - 1];
+    (x_offset as i64 + y_offset as i64 * width as i64).max(1) as usize
+}
+
+/// Decodes an entropy-coded image. Only the main ARGB image may use different prefix codes for different regions.
+fn decode_entropy_coded_image(reader: &mut BitReader, width: u32, height: u32, is_main_image: bool) -> Result<Vec<ARGB>, Error> {
+    let color_cache_bits = if reader.read_bits(1)? == 1 {
+        let bits = reader.read_bits(4)?;
+        if bits == 0 || bits > MAX_COLOR_CACHE_BITS {
+            return Err(Error::InvalidData("Invalid WebP color cache size"));
+        }
+        bits
+    } else {
+        0
+    };
+    let color_cache_size = if color_cache_bits > 0 { 1 << color_cache_bits } else { 0 };
+
+    let mut prefix_bits = 0;
+    let mut entropy_image = Vec::new();
+    let mut group_count = 1;
+    if is_main_image && reader.read_bits(1)? == 1 {
+        prefix_bits = reader.read_bits(3)? + 2;
+        entropy_image = decode_entropy_coded_image(reader, div_round_up(width, prefix_bits), div_round_up(height, prefix_bits), false)?;
+        for meta_prefix_code in &mut entropy_image {
+            *meta_prefix_code = (*meta_prefix_code >> 8) & 0xFFFF;
+            group_count = group_count.max(*meta_prefix_code as usize + 1);
+        }
+    }
+
+    let mut groups = Vec::new();
+    for _ in 0..group_count {
+        groups.push(read_prefix_code_group(reader, color_cache_size)?);
+    }
+
+    let mut pixels = allocate_pixels(width, height)?;
+    let mut color_cache = vec![0 as ARGB; color_cache_size];
+    let mut cached_pixel_count = 0;
+    let mut position = 0;
+    let entropy_image_width = div_round_up(width, prefix_bits) as usize;
+    while position < pixels.len() {
+        let group = if entropy_image.is_empty() {
+            &groups[0]
+        } else {
+            let (x, y) = (position % width as usize, position / width as usize);
+            &groups[entropy_image[(y >> prefix_bits) * entropy_image_width + (x >> prefix_bits)] as usize]
+        };
+
+        let symbol = group.green.decode_symbol(reader)? as usize;
+        if symbol < NUM_LITERAL_CODES {
+            let red = group.red.decode_symbol(reader)? as u32;
+            let blue = group.blue.decode_symbol(reader)? as u32;
+            let alpha = group.alpha.decode_symbol(reader)? as u32;
+            pixels[position] = (alpha << 24) | (red << 16) | ((symbol as u32) << 8) | blue;
+            position += 1;
+        } else if symbol < NUM_LITERAL_CODES + NUM_LENGTH_CODES {
+            let length = read_prefix_coded_value(reader, (symbol - NUM_LITERAL_CODES) as u16)?;
+            let distance_symbol = group.distance.decode_symbol(reader)?;
+            let distance = distance_for_code(read_prefix_coded_value(reader, distance_symbol)?, width);
+            if distance > position || length > pixels.len() - position {
+                return Err(Error::InvalidData("WebP backward reference out of range"));
+            }
+            for index in position..position + length {
+                pixels[index] = pixels[index - distance];
+            }
+            position += length;
+        } else {
+            let index = symbol - NUM_LITERAL_CODES - NUM_LENGTH_CODES;
+            pixels[position] = color_cache[index];
+            position += 1;
+        }
+
+        if color_cache_size > 0 {
+            for &pixel in &pixels[cached_pixel_count..position] {
+                color_cache[(0x1E35A7BDu32.wrapping_mul(pixel) >> (32 - color_cache_bits)) as usize] = pixel;
+            }
+            cached_pixel_count = position;
+        }
+    }
+
+    Ok(pixels)
+}
+
+fn add_pixels(a: ARGB, b: ARGB) -> ARGB {
+    (((a & 0xFF00FF00).wrapping_add(b & 0xFF00FF00)) & 0xFF00FF00) | (((a & 0x00FF00FF).wrapping_add(b & 0x00FF00FF)) & 0x00FF00FF)
+}
+
+fn average2(a: ARGB, b: ARGB) -> ARGB {
+    (((a ^ b) & 0xFEFEFEFE) >> 1) + (a & b)
+}
+
+fn map_channels(a: ARGB, b: ARGB, c: ARGB, function: impl Fn(i32, i32, i32) -> i32) -> ARGB {
+    let (a, b, c) = (a.to_be_bytes(), b.to_be_bytes(), c.to_be_bytes());
+    u32::from_be_bytes(std::array::from_fn(|channel| function(a[channel] as i32, b[channel] as i32, c[channel] as i32).clamp(0, 255) as u8))
+}
+
+fn select(left: ARGB, top: ARGB, top_left: ARGB) -> ARGB {
+    let (left_bytes, top_bytes, top_left_bytes) = (left.to_be_bytes(), top.to_be_bytes(), top_left.to_be_bytes());
+    let mut distance_to_left = 0;
+    let mut distance_to_top = 0;
+    for channel in 0..4 {
+        let estimate = left_bytes[channel] as i32 + top_bytes[channel] as i32 - top_left_bytes[channel] as i32;
+        distance_to_left += (estimate - left_bytes[channel] as i32).abs();
+        distance_to_top += (estimate - top_bytes[channel] as i32).abs();
+    }
+    if distance_to_left < distance_to_top { left } else { top }
+}
+
+fn predict(mode: u32, left: ARGB, top: ARGB, top_left: ARGB, top_right: ARGB) -> ARGB {
+    match mode {
+        1 => left,
+        2 => top,
+        3 => top_right,
+        4 => top_left,
+        5 => average2(average2(left, top_right), top),
+        6 => average2(left, top_left),
+        7 => average2(left, top),
+        8 => average2(top_left, top),
+        9 => average2(top, top_right),
+        10 => average2(average2(left, top_left), average2(top, top_right)),
+        11 => select(left, top, top_left),
+        12 => map_channels(left, top, top_left, |a, b, c| a + b - c),
+        13 => map_channels(average2(left, top), top_left, 0, |a, b, _| a + (a - b) / 2),
+        // NOTE: Modes 14 and 15 aren't valid, libwebp treats them like mode 0.
+        _ => 0xFF000000
+    }
+}
+
+fn color_transform_delta(transform: u8, color: u8) -> i32 {
+    (transform as i8 as i32 * color as i8 as i32) >> 5
+}
+
+impl VP8LTransform {
+    fn apply_inverse(&self, pixels: Vec<ARGB>, height: u32) -> Result<Vec<ARGB>, Error> {
+        match self {
+            VP8LTransform::Predictor { width, size_bits, modes } => {
+                let mut pixels = pixels;
+                let width = *width as usize;
+                let blocks_per_row = div_round_up(width as u32, *size_bits) as usize;
+                for y in 0..height as usize {
+                    for x in 0..width {
+                        let position = y * width + x;
+                        let prediction = if y == 0 {
+                            if x == 0 { 0xFF000000 } else { pixels[position - 1] }
+                        } else if x == 0 {
+                            pixels[position - width]
+                        } else {
+                            let mode = (modes[(y >> size_bits) * blocks_per_row + (x >> size_bits)] >> 8) & 0xF;
+                            // NOTE: The top right pixel of the last column is the first pixel of the current row.
+                            predict(mode, pixels[position - 1], pixels[position - width], pixels[position - width - 1], pixels[position - width + 1])
+                        };
+                        pixels[position] = add_pixels(pixels[position], prediction);
+                    }
+                }
+                Ok(pixels)
+            }
+            VP8LTransform::Color { width, size_bits, elements } => {
+                let mut pixels = pixels;
+                let width = *width as usize;
+                let blocks_per_row = div_round_up(width as u32, *size_bits) as usize;
+                for (position, pixel) in pixels.iter_mut().enumerate() {
+                    let (x, y) = (position % width, position / width);
+                    let [_, red_to_blue, green_to_blue, green_to_red] = elements[(y >> size_bits) * blocks_per_row + (x >> size_bits)].to_be_bytes();
+                    let [alpha, red, green, blue] = pixel.to_be_bytes();
+                    let red = (red as i32 + color_transform_delta(green_to_red, green)) as u8;
+                    let blue = (blue as i32 + color_transform_delta(green_to_blue, green) + color_transform_delta(red_to_blue, red)) as u8;
+                    *pixel = u32::from_be_bytes([alpha, red, green, blue]);
+                }
+                Ok(pixels)
+            }
+            VP8LTransform::SubtractGreen => {
+                let mut pixels = pixels;
+                for pixel in &mut pixels {
+                    let green = (*pixel >> 8) & 0xFF;
+                    *pixel = add_pixels(*pixel, (green << 16) | green);
+                }
+                Ok(pixels)
+            }
+            VP8LTransform::ColorIndexing { width, width_bits, palette } => {
+                let packed_width = div_round_up(*width, *width_bits) as usize;
+                let bits_per_index = 8 >> width_bits;
+                let index_mask = (1u32 << bits_per_index) - 1;
+                let mut unpacked = allocate_pixels(*width, height)?;
+                for (position, pixel) in unpacked.iter_mut().enumerate() {
+                    let (x, y) = (position % *width as usize, position / *width as usize);
+                    let packed = (pixels[y * packed_width + (x >> width_bits)] >> 8) & 0xFF;
+                    let index = (packed >> (bits_per_index * (x as u32 & ((1 << width_bits) - 1)))) & index_mask;
+                    // NOTE: Indices past the end of the palette are transparent black.
+                    *pixel = palette.get(index as usize).copied().unwrap_or(0);
+                }
+                Ok(unpacked)
+            }
+        }
+    }
+}
+
+/// Decodes transforms and the main image that follows them, which is the VP8L bitstream after its header.
+fn decode_image_stream(reader: &mut BitReader, width: u32, height: u32) -> Result<Vec<ARGB>, Error> {
+    let mut transforms = Vec::new();
+    let mut seen_transform_types = 0u32;
+    let mut coded_width = width;
+    while reader.read_bits(1)? == 1 {
+        let transform_type = reader.read_bits(2)?;
+        if seen_transform_types & (1 << transform_type) != 0 {
+            return Err(Error::InvalidData("WebP transform used more than once"));
+        }
+        seen_transform_types |= 1 << transform_type;
+
+        let transform = match transform_type {
+            0 | 1 => {
+                let size_bits = reader.read_bits(3)? + 2;
+                let data = decode_entropy_coded_image(reader, div_round_up(coded_width, size_bits), div_round_up(height, size_bits), false)?;
+                if transform_type == 0 {
+                    VP8LTransform::Predictor { width: coded_width, size_bits, modes: data }
+                } else {
+                    VP8LTransform::Color { width: coded_width, size_bits, elements: data }
+                }
+            }
+            2 => VP8LTransform::SubtractGreen,
+            _ => {
+                let palette_size = reader.read_bits(8)? + 1;
+                let mut palette = decode_entropy_coded_image(reader, palette_size, 1, false)?;
+                for index in 1..palette.len() {
+                    palette[index] = add_pixels(palette[index], palette[index - 1]);
+                }
+                let width_bits = match palette_size {
+                    0..=2 => 3,
+                    3..=4 => 2,
+                    5..=16 => 1,
+                    _ => 0
+                };
+                let transform = VP8LTransform::ColorIndexing { width: coded_width, width_bits, palette };
+                coded_width = div_round_up(coded_width, width_bits);
+                transform
+            }
+        };
+        transforms.push(transform);
+    }
+
+    let mut pixels = decode_entropy_coded_image(reader, coded_width, height, true)?;
+    for transform in transforms.iter().rev() {
+        pixels = transform.apply_inverse(pixels, height)?;
+    }
+    Ok(pixels)
+}
+
+pub(crate) fn decode_webp_chunk_vp8l_header(data: &[u8]) -> Result<VP8LHeader, Error> {
+    if data.len() < VP8L_HEADER_SIZE {
+        return Err(Error::Truncated);
+    }
+    if data[0] != VP8L_SIGNATURE {
+        return Err(Error::InvalidHeader("Invalid VP8L signature"));
+    }
+
+    let bits = u32::from_le_bytes([data[1], data[2], data[3], data[4]]);
+    if bits >> 29 != 0 {
+        return Err(Error::Unsupported("VP8L version"));
+    }
+    Ok(VP8LHeader {
+        width: (bits & 0x3FFF) + 1,
+        height: ((bits >> 14) & 0x3FFF) + 1,
+        is_alpha_used: (bits >> 28) & 1 == 1
+    })
+}
+
+pub(crate) fn decode_webp_chunk_vp8l(data: &[u8]) -> Result<Bitmap, Error> {
+    let header = decode_webp_chunk_vp8l_header(data)?;
+    decode_webp_chunk_vp8l_contents(header, &data[VP8L_HEADER_SIZE..])
+}
+
+/// Decodes a VP8L image stream without its header. ALPH chunks store their alpha channel like this, in the green channel.
+pub(crate) fn decode_webp_chunk_vp8l_contents(header: VP8LHeader, data: &[u8]) -> Result<Bitmap, Error> {
+    let size = IntSize { width: header.width as i32, height: header.height as i32 };
+    let mut bitmap = Bitmap::new(BitmapFormat::BGRA8888, size, 1)?;
+
+    let mut reader = BitReader::new(data);
+    let pixels = decode_image_stream(&mut reader, header.width, header.height)?;
+    for (position, pixel) in pixels.into_iter().enumerate() {
+        bitmap.set_pixel((position % header.width as usize) as i32, (position / header.width as usize) as i32, pixel);
+    }
+    Ok(bitmap)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Packs bits least significant bit first, the way `BitReader` reads them.
+    #[derive(Default)]
+    struct BitWriter {
+        bytes: Vec<u8>,
+        bit_count: usize
+    }
+
+    impl BitWriter {
+        fn write_bits(&mut self, value: u32, count: u32) {
+            for bit in 0..count {
+                if self.bit_count.is_multiple_of(8) {
+                    self.bytes.push(0);
+                }
+                self.bytes[self.bit_count / 8] |= (((value >> bit) & 1) as u8) << (self.bit_count % 8);
+                self.bit_count += 1;
+            }
+        }
+
+        // NOTE: Prefix codes are stored starting with their most significant bit.
+        fn write_code(&mut self, code: u32, length: u32) {
+            for bit in (0..length).rev() {
+                self.write_bits(code >> bit, 1);
+            }
+        }
+    }
+
+    fn write_simple_code(writer: &mut BitWriter, symbols: &[u32]) {
+        writer.write_bits(1, 1);
+        writer.write_bits(symbols.len() as u32 - 1, 1);
+        writer.write_bits(1, 1);
+        for &symbol in symbols {
+            writer.write_bits(symbol, 8);
+        }
+    }
+
+    // NOTE: Gives the code lengths 0 to 15 a 4-bit code each, so every code length is stored as itself.
+    fn write_normal_code(writer: &mut BitWriter, code_lengths: &[u8]) {
+        writer.write_bits(0, 1);
+        writer.write_bits(15, 4);
+        for symbol in CODE_LENGTH_CODE_ORDER {
+            writer.write_bits(if symbol < 16 { 4 } else { 0 }, 3);
+        }
+        writer.write_bits(0, 1);
+        for &length in code_lengths {
+            writer.write_code(length as u32, 4);
+        }
+    }
+
+    fn decode_symbols(code: &PrefixCode, reader: &mut BitReader, count: usize) -> Vec<u16> {
+        (0..count).map(|_| code.decode_symbol(reader).unwrap()).collect()
+    }
+
+    #[test]
+    fn decodes_canonical_prefix_codes() {
+        // NOTE: Codes are assigned by length, then by symbol: 1 is 0, 0 is 10, 2 is 110 and 3 is 111.
+        let code = PrefixCode::new(&[2, 1, 3, 3]).unwrap();
+        let mut writer = BitWriter::default();
+        for (bits, length) in [(0b110, 3), (0b0, 1), (0b111, 3), (0b10, 2)] {
+            writer.write_code(bits, length);
+        }
+        assert_eq!(decode_symbols(&code, &mut BitReader::new(&writer.bytes), 4), [2, 1, 3, 0]);
+    }
+
+    #[test]
+    fn decodes_codes_longer_than_the_fast_table() {
+        // NOTE: Symbol n uses n + 1 bits, except for the last two, which both use 11 bits.
+        let mut code_lengths: Vec<u8> = (1..=10).collect();
+        code_lengths.extend([11, 11]);
+        let code = PrefixCode::new(&code_lengths).unwrap();
+        let mut writer = BitWriter::default();
+        for (bits, length) in [(0b1111111110, 10), (0b11111111110, 11), (0b11111111111, 11), (0b11111110, 8), (0b0, 1)] {
+            writer.write_code(bits, length);
+        }
+        assert_eq!(decode_symbols(&code, &mut BitReader::new(&writer.bytes), 5), [9, 10, 11, 7, 0]);
+    }
+
+    #[test]
+    fn uses_no_bits_for_single_symbol_codes() {
+        let code = PrefixCode::new(&[0, 0, 5, 0]).unwrap();
+        assert_eq!(decode_symbols(&code, &mut BitReader::new(&[]), 2), [2, 2]);
+    }
+
+    #[test]
+    fn rejects_invalid_code_lengths() {
+        assert_eq!(PrefixCode::new(&[0, 0]).err(), Some(Error::InvalidData("Empty WebP prefix code")));
+        assert_eq!(PrefixCode::new(&[1, 1, 1]).err(), Some(Error::InvalidData("Over-subscribed WebP prefix code")));
+        assert_eq!(PrefixCode::new(&[1, 2]).err(), Some(Error::InvalidData("Incomplete WebP prefix code")));
+    }
+
+    #[test]
+    fn reads_simple_prefix_codes() {
+        let mut writer = BitWriter::default();
+        // NOTE: A single symbol stored in one bit.
+        writer.write_bits(0b1001, 4);
+        // NOTE: Two symbols get one bit each, ordered by symbol.
+        write_simple_code(&mut writer, &[200, 7]);
+        writer.write_code(0b1, 1);
+        writer.write_code(0b0, 1);
+        write_simple_code(&mut writer, &[50]);
+
+        let mut reader = BitReader::new(&writer.bytes);
+        let code = read_prefix_code(&mut reader, NUM_LITERAL_CODES).unwrap();
+        assert_eq!(decode_symbols(&code, &mut reader, 1), [1]);
+        let code = read_prefix_code(&mut reader, NUM_LITERAL_CODES).unwrap();
+        assert_eq!(decode_symbols(&code, &mut reader, 2), [200, 7]);
+        assert_eq!(read_prefix_code(&mut reader, NUM_DISTANCE_CODES).err(), Some(Error::InvalidData("WebP prefix code symbol out of range")));
+    }
+
+    #[test]
+    fn reads_normal_prefix_codes_with_repeated_lengths() {
+        // NOTE: The code length code gives 2, 16, 17 and 18 two bits each.
+        let mut writer = BitWriter::default();
+        writer.write_bits(0, 1);
+        writer.write_bits(5, 4);
+        for length in [2, 2, 0, 0, 2, 0, 0, 0, 2] {
+            writer.write_bits(length, 3);
+        }
+        writer.write_bits(0, 1);
+        // NOTE: A length of 2 repeated 3 more times, then 3 and 23 zeros.
+        writer.write_code(0b00, 2);
+        writer.write_code(0b01, 2);
+        writer.write_bits(0, 2);
+        writer.write_code(0b10, 2);
+        writer.write_bits(0, 3);
+        writer.write_code(0b11, 2);
+        writer.write_bits(12, 7);
+        writer.write_code(0b11, 2);
+        writer.write_code(0b00, 2);
+
+        let mut reader = BitReader::new(&writer.bytes);
+        let code = read_prefix_code(&mut reader, 30).unwrap();
+        assert_eq!(decode_symbols(&code, &mut reader, 2), [3, 0]);
+        assert_eq!(read_prefix_code(&mut BitReader::new(&writer.bytes), 20).err(), Some(Error::InvalidData("WebP prefix code lengths overflow the alphabet")));
+    }
+
+    #[test]
+    fn reads_length_and_distance_values() {
+        let mut writer = BitWriter::default();
+        writer.write_bits(1, 1);
+        writer.write_bits(1, 1);
+        writer.write_bits(0b1011, 4);
+
+        let mut reader = BitReader::new(&writer.bytes);
+        assert_eq!(read_prefix_coded_value(&mut reader, 0), Ok(1));
+        assert_eq!(read_prefix_coded_value(&mut reader, 3), Ok(4));
+        assert_eq!(read_prefix_coded_value(&mut reader, 4), Ok(6));
+        assert_eq!(read_prefix_coded_value(&mut reader, 5), Ok(8));
+        assert_eq!(read_prefix_coded_value(&mut reader, 10), Ok(44));
+    }
+
+    #[test]
+    fn maps_distance_codes_to_pixel_offsets() {
+        // NOTE: The first 120 codes are nearby (x, y) offsets, the rest are plain distances.
+        assert_eq!(distance_for_code(1, 10), 10);
+        assert_eq!(distance_for_code(2, 10), 1);
+        assert_eq!(distance_for_code(4, 10), 9);
+        assert_eq!(distance_for_code(120, 10), 78);
+        assert_eq!(distance_for_code(10, 1), 1);
+        assert_eq!(distance_for_code(121, 10), 1);
+        assert_eq!(distance_for_code(200, 10), 80);
+    }
+
+    // NOTE: Green 0x20, a length of 2 and the first two color cache entries get two bits each, blue 0x01 and 0x03 one bit each.
+    fn write_prefix_codes_with_color_cache(writer: &mut BitWriter) {
+        writer.write_bits(1, 1);
+        writer.write_bits(2, 4);
+        let mut green_code_lengths = [0u8; NUM_LITERAL_CODES + NUM_LENGTH_CODES + 4];
+        for symbol in [0x20, 257, 280, 281] {
+            green_code_lengths[symbol] = 2;
+        }
+        write_normal_code(writer, &green_code_lengths);
+        write_simple_code(writer, &[0x40]);
+        write_simple_code(writer, &[0x01, 0x03]);
+        write_simple_code(writer, &[0xFF]);
+        // NOTE: Distance symbol 1 is distance code 2, the pixel to the left.
+        write_simple_code(writer, &[1]);
+    }
+
+    #[test]
+    fn decodes_backward_references_and_color_cache_hits() {
+        const FIRST: ARGB = 0xFF402001;
+        const SECOND: ARGB = 0xFF402003;
+
+        let mut writer = BitWriter::default();
+        write_prefix_codes_with_color_cache(&mut writer);
+        writer.write_code(0b00, 2);
+        writer.write_code(0b0, 1);
+        writer.write_code(0b00, 2);
+        writer.write_code(0b1, 1);
+        writer.write_code(0b01, 2);
+        // NOTE: FIRST hashes to color cache entry 0 and SECOND to entry 1.
+        writer.write_code(0b10, 2);
+        writer.write_code(0b11, 2);
+        let pixels = decode_entropy_coded_image(&mut BitReader::new(&writer.bytes), 6, 1, false).unwrap();
+        assert_eq!(pixels, [FIRST, SECOND, SECOND, SECOND, FIRST, SECOND]);
+
+        let mut writer = BitWriter::default();
+        write_prefix_codes_with_color_cache(&mut writer);
+        writer.write_code(0b01, 2);
+        let result = decode_entropy_coded_image(&mut BitReader::new(&writer.bytes), 6, 1, false);
+        assert_eq!(result.err(), Some(Error::InvalidData("WebP backward reference out of range")));
+    }
+
+    #[test]
+    fn predicts_with_every_mode() {
+        let (left, top, top_left, top_right) = (0x40608090, 0x20406080, 0x10203040, 0x80A0C0E0);
+        let expected = [
+            0xFF000000, left, top, top_right, top_left, 0x4060809C, 0x28405868, 0x30507088,
+            0x18304860, 0x507090B0, 0x3C58748C, left, 0x5080B0D0, 0x406890AC, 0xFF000000, 0xFF000000
+        ];
+        for (mode, expected) in expected.into_iter().enumerate() {
+            assert_eq!(predict(mode as u32, left, top, top_left, top_right), expected, "mode {mode}");
+        }
+        assert_eq!(select(top, left, top_left), left);
+        assert_eq!(predict(12, 0xFFFFFFFF, 0xFFFFFFFF, 0, 0), 0xFFFFFFFF);
+        assert_eq!(predict(12, 0, 0, 0xFFFFFFFF, 0), 0);
+    }
+
+    #[test]
+    fn inverts_predictor_transform() {
+        // NOTE: The first row predicts from the left and the first column from the top, whatever the mode.
+        let transform = |mode: u32| VP8LTransform::Predictor { width: 3, size_bits: 2, modes: vec![mode << 8] };
+        let residuals = vec![0x01010101; 6];
+        assert_eq!(transform(4).apply_inverse(residuals.clone(), 2), Ok(vec![0x00010101, 0x01020202, 0x02030303, 0x01020202, 0x01020202, 0x02030303]));
+        // NOTE: The top right pixel of the last column wraps around to the start of the current row.
+        assert_eq!(transform(3).apply_inverse(residuals, 2), Ok(vec![0x00010101, 0x01020202, 0x02030303, 0x01020202, 0x03040404, 0x02030303]));
+    }
+
+    #[test]
+    fn inverts_color_transform() {
+        // NOTE: The first 4x4 block adds 2 * green to red, subtracts green from blue and adds red / 2 to blue.
+        let transform = VP8LTransform::Color { width: 8, size_bits: 2, elements: vec![0x0010E020, 0] };
+        let pixels = vec![0xFF104080, 0x8010C000, 0, 0, 0xFF104080, 0, 0, 0];
+        assert_eq!(transform.apply_inverse(pixels, 1), Ok(vec![0xFF504068, 0x80D0C028, 0, 0, 0xFF104080, 0, 0, 0]));
+    }
+
+    #[test]
+    fn inverts_subtract_green_transform() {
+        assert_eq!(VP8LTransform::SubtractGreen.apply_inverse(vec![0xFF10F020, 0x80FF0000], 1), Ok(vec![0xFF00F010, 0x80FF0000]));
+    }
+
+    #[test]
+    fn inverts_color_indexing_transform() {
+        let palette: Vec<ARGB> = (0..17).map(|index| 0xFF000000 | (index * 0x10101)).collect();
+        let packed = |bytes: &[u32]| bytes.iter().map(|byte| 0xFF000000 | (byte << 8)).collect::<Vec<ARGB>>();
+        let colors = |indices: &[u32]| indices.iter().map(|&index| palette.get(index as usize).copied().unwrap_or(0)).collect::<Vec<ARGB>>();
+
+        // NOTE: With two palette entries, each byte packs eight indices, starting at the least significant bit.
+        let transform = VP8LTransform::ColorIndexing { width: 10, width_bits: 3, palette: palette[..2].to_vec() };
+        assert_eq!(
+            transform.apply_inverse(packed(&[0xB2, 0x01, 0x0F, 0x02]), 2),
+            Ok(colors(&[0, 1, 0, 0, 1, 1, 0, 1, 1, 0, 1, 1, 1, 1, 0, 0, 0, 0, 0, 1]))
+        );
+
+        // NOTE: Index 3 is past the end of a three-entry palette.
+        let transform = VP8LTransform::ColorIndexing { width: 5, width_bits: 2, palette: palette[..3].to_vec() };
+        assert_eq!(transform.apply_inverse(packed(&[0xC9, 0x02]), 1), Ok(vec![palette[1], palette[2], palette[0], 0, palette[2]]));
+
+        let transform = VP8LTransform::ColorIndexing { width: 2, width_bits: 0, palette: palette.clone() };
+        assert_eq!(transform.apply_inverse(packed(&[16, 200]), 1), Ok(vec![palette[16], 0]));
+    }
+}
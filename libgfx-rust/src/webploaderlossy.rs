@@ -0,0 +1,1742 @@
+use crate::{Color, IntSize};
+use crate::bitmap::{Bitmap, BitmapFormat};
+use crate::error::Error;
+
+// NOTE: See https://www.rfc-editor.org/rfc/rfc6386 for the format. Only keyframes can appear in WebP files.
+//       Where the specification leaves room, this follows libwebp, so that the output matches it exactly.
+const VP8_FRAME_TAG_SIZE: usize = 3;
+const VP8_KEYFRAME_HEADER_SIZE: usize = 10;
+const VP8_START_CODE: [u8; 3] = [0x9D, 0x01, 0x2A];
+
+const NUM_SEGMENTS: usize = 4;
+const NUM_COEFFICIENT_TYPES: usize = 4;
+const NUM_COEFFICIENT_BANDS: usize = 8;
+const NUM_COEFFICIENT_CONTEXTS: usize = 3;
+const NUM_COEFFICIENT_PROBABILITIES: usize = 11;
+
+type CoefficientProbabilities = [[[[u8; NUM_COEFFICIENT_PROBABILITIES]; NUM_COEFFICIENT_CONTEXTS]; NUM_COEFFICIENT_BANDS]; NUM_COEFFICIENT_TYPES];
+
+const ZIGZAG: [usize; 16] = [0, 1, 4, 8, 5, 2, 3, 6, 9, 12, 13, 10, 7, 11, 14, 15];
+const COEFFICIENT_BANDS: [usize; 16] = [0, 1, 2, 3, 6, 4, 5, 6, 6, 6, 6, 6, 6, 6, 6, 7];
+
+const DCT_CATEGORY_3_PROBABILITIES: [u8; 3] = [173, 148, 140];
+const DCT_CATEGORY_4_PROBABILITIES: [u8; 4] = [176, 155, 140, 135];
+const DCT_CATEGORY_5_PROBABILITIES: [u8; 5] = [180, 157, 141, 134, 130];
+const DCT_CATEGORY_6_PROBABILITIES: [u8; 11] = [254, 254, 243, 230, 196, 177, 153, 140, 133, 130, 129];
+
+// NOTE: Trees are stored the way the specification does: positive entries index further into the tree, the others are negated leaves.
+const SUBBLOCK_MODE_TREE: [i8; 18] = [0, 2, -1, 4, -2, 6, 8, 12, -3, 10, -5, -6, -4, 14, -7, 16, -8, -9];
+const SUBBLOCK_MODES: [PredictionMode; 10] = [
+    PredictionMode::DirectCurrent,
+    PredictionMode::TrueMotion,
+    PredictionMode::Vertical,
+    PredictionMode::Horizontal,
+    PredictionMode::LeftDown,
+    PredictionMode::RightDown,
+    PredictionMode::VerticalRight,
+    PredictionMode::VerticalLeft,
+    PredictionMode::HorizontalDown,
+    PredictionMode::HorizontalUp
+];
+
+const SUBBLOCK_MODE_PROBABILITIES: [[[u8; 9]; 10]; 10] = [
+    [
+        [231, 120, 48, 89, 115, 113, 120, 152, 112],
+        [152, 179, 64, 126, 170, 118, 46, 70, 95],
+        [175, 69, 143, 80, 85, 82, 72, 155, 103],
+        [56, 58, 10, 171, 218, 189, 17, 13, 152],
+        [144, 71, 10, 38, 171, 213, 144, 34, 26],
+        [114, 26, 17, 163, 44, 195, 21, 10, 173],
+        [121, 24, 80, 195, 26, 62, 44, 64, 85],
+        [170, 46, 55, 19, 136, 160, 33, 206, 71],
+        [63, 20, 8, 114, 114, 208, 12, 9, 226],
+        [81, 40, 11, 96, 182, 84, 29, 16, 36]
+    ],
+    [
+        [134, 183, 89, 137, 98, 101, 106, 165, 148],
+        [72, 187, 100, 130, 157, 111, 32, 75, 80],
+        [66, 102, 167, 99, 74, 62, 40, 234, 128],
+        [41, 53, 9, 178, 241, 141, 26, 8, 107],
+        [104, 79, 12, 27, 217, 255, 87, 17, 7],
+        [74, 43, 26, 146, 73, 166, 49, 23, 157],
+        [65, 38, 105, 160, 51, 52, 31, 115, 128],
+        [87, 68, 71, 44, 114, 51, 15, 186, 23],
+        [47, 41, 14, 110, 182, 183, 21, 17, 194],
+        [66, 45, 25, 102, 197, 189, 23, 18, 22]
+    ],
+    [
+        [88, 88, 147, 150, 42, 46, 45, 196, 205],
+        [43, 97, 183, 117, 85, 38, 35, 179, 61],
+        [39, 53, 200, 87, 26, 21, 43, 232, 171],
+        [56, 34, 51, 104, 114, 102, 29, 93, 77],
+        [107, 54, 32, 26, 51, 1, 81, 43, 31],
+        [39, 28, 85, 171, 58, 165, 90, 98, 64],
+        [34, 22, 116, 206, 23, 34, 43, 166, 73],
+        [68, 25, 106, 22, 64, 171, 36, 225, 114],
+        [34, 19, 21, 102, 132, 188, 16, 76, 124],
+        [62, 18, 78, 95, 85, 57, 50, 48, 51]
+    ],
+    [
+        [193, 101, 35, 159, 215, 111, 89, 46, 111],
+        [60, 148, 31, 172, 219, 228, 21, 18, 111],
+        [112, 113, 77, 85, 179, 255, 38, 120, 114],
+        [40, 42, 1, 196, 245, 209, 10, 25, 109],
+        [100, 80, 8, 43, 154, 1, 51, 26, 71],
+        [88, 43, 29, 140, 166, 213, 37, 43, 154],
+        [61, 63, 30, 155, 67, 45, 68, 1, 209],
+        [142, 78, 78, 16, 255, 128, 34, 197, 171],
+        [41, 40, 5, 102, 211, 183, 4, 1, 221],
+        [51, 50, 17, 168, 209, 192, 23, 25, 82]
+    ],
+    [
+        [125, 98, 42, 88, 104, 85, 117, 175, 82],
+        [95, 84, 53, 89, 128, 100, 113, 101, 45],
+        [75, 79, 123, 47, 51, 128, 81, 171, 1],
+        [57, 17, 5, 71, 102, 57, 53, 41, 49],
+        [115, 21, 2, 10, 102, 255, 166, 23, 6],
+        [38, 33, 13, 121, 57, 73, 26, 1, 85],
+        [41, 10, 67, 138, 77, 110, 90, 47, 114],
+        [101, 29, 16, 10, 85, 128, 101, 196, 26],
+        [57, 18, 10, 102, 102, 213, 34, 20, 43],
+        [117, 20, 15, 36, 163, 128, 68, 1, 26]
+    ],
+    [
+        [138, 31, 36, 171, 27, 166, 38, 44, 229],
+        [67, 87, 58, 169, 82, 115, 26, 59, 179],
+        [63, 59, 90, 180, 59, 166, 93, 73, 154],
+        [40, 40, 21, 116, 143, 209, 34, 39, 175],
+        [57, 46, 22, 24, 128, 1, 54, 17, 37],
+        [47, 15, 16, 183, 34, 223, 49, 45, 183],
+        [46, 17, 33, 183, 6, 98, 15, 32, 183],
+        [65, 32, 73, 115, 28, 128, 23, 128, 205],
+        [40, 3, 9, 115, 51, 192, 18, 6, 223],
+        [87, 37, 9, 115, 59, 77, 64, 21, 47]
+    ],
+    [
+        [104, 55, 44, 218, 9, 54, 53, 130, 226],
+        [64, 90, 70, 205, 40, 41, 23, 26, 57],
+        [54, 57, 112, 184, 5, 41, 38, 166, 213],
+        [30, 34, 26, 133, 152, 116, 10, 32, 134],
+        [75, 32, 12, 51, 192, 255, 160, 43, 51],
+        [39, 19, 53, 221, 26, 114, 32, 73, 255],
+        [31, 9, 65, 234, 2, 15, 1, 118, 73],
+        [88, 31, 35, 67, 102, 85, 55, 186, 85],
+        [56, 21, 23, 111, 59, 205, 45, 37, 192],
+        [55, 38, 70, 124, 73, 102, 1, 34, 98]
+    ],
+    [
+        [102, 61, 71, 37, 34, 53, 31, 243, 192],
+        [69, 60, 71, 38, 73, 119, 28, 222, 37],
+        [68, 45, 128, 34, 1, 47, 11, 245, 171],
+        [62, 17, 19, 70, 146, 85, 55, 62, 70],
+        [75, 15, 9, 9, 64, 255, 184, 119, 16],
+        [37, 43, 37, 154, 100, 163, 85, 160, 1],
+        [63, 9, 92, 136, 28, 64, 32, 201, 85],
+        [86, 6, 28, 5, 64, 255, 25, 248, 1],
+        [56, 8, 17, 132, 137, 255, 55, 116, 128],
+        [58, 15, 20, 82, 135, 57, 26, 121, 40]
+    ],
+    [
+        [164, 50, 31, 137, 154, 133, 25, 35, 218],
+        [51, 103, 44, 131, 131, 123, 31, 6, 158],
+        [86, 40, 64, 135, 148, 224, 45, 183, 128],
+        [22, 26, 17, 131, 240, 154, 14, 1, 209],
+        [83, 12, 13, 54, 192, 255, 68, 47, 28],
+        [45, 16, 21, 91, 64, 222, 7, 1, 197],
+        [56, 21, 39, 155, 60, 138, 23, 102, 213],
+        [85, 26, 85, 85, 128, 128, 32, 146, 171],
+        [18, 11, 7, 63, 144, 171, 4, 4, 246],
+        [35, 27, 10, 146, 174, 171, 12, 26, 128]
+    ],
+    [
+        [190, 80, 35, 99, 180, 80, 126, 54, 45],
+        [85, 126, 47, 87, 176, 51, 41, 20, 32],
+        [101, 75, 128, 139, 118, 146, 116, 128, 85],
+        [56, 41, 15, 176, 236, 85, 37, 9, 62],
+        [146, 36, 19, 30, 171, 255, 97, 27, 20],
+        [71, 30, 17, 119, 118, 255, 17, 18, 138],
+        [101, 38, 60, 138, 55, 70, 43, 26, 142],
+        [138, 45, 61, 62, 219, 1, 81, 188, 64],
+        [32, 41, 20, 117, 151, 142, 20, 21, 163],
+        [112, 19, 12, 61, 195, 128, 48, 4, 24]
+    ]
+];
+
+const COEFFICIENT_UPDATE_PROBABILITIES: CoefficientProbabilities = [
+    [
+        [
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]
+        ],
+        [
+            [176, 246, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [223, 241, 252, 255, 255, 255, 255, 255, 255, 255, 255],
+            [249, 253, 253, 255, 255, 255, 255, 255, 255, 255, 255]
+        ],
+        [
+            [255, 244, 252, 255, 255, 255, 255, 255, 255, 255, 255],
+            [234, 254, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+            [253, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]
+        ],
+        [
+            [255, 246, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+            [239, 253, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+            [254, 255, 254, 255, 255, 255, 255, 255, 255, 255, 255]
+        ],
+        [
+            [255, 248, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+            [251, 255, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]
+        ],
+        [
+            [255, 253, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+            [251, 254, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+            [254, 255, 254, 255, 255, 255, 255, 255, 255, 255, 255]
+        ],
+        [
+            [255, 254, 253, 255, 254, 255, 255, 255, 255, 255, 255],
+            [250, 255, 254, 255, 254, 255, 255, 255, 255, 255, 255],
+            [254, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]
+        ],
+        [
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]
+        ]
+    ],
+    [
+        [
+            [217, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [225, 252, 241, 253, 255, 255, 254, 255, 255, 255, 255],
+            [234, 250, 241, 250, 253, 255, 253, 254, 255, 255, 255]
+        ],
+        [
+            [255, 254, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [223, 254, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+            [238, 253, 254, 254, 255, 255, 255, 255, 255, 255, 255]
+        ],
+        [
+            [255, 248, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+            [249, 254, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]
+        ],
+        [
+            [255, 253, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [247, 254, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]
+        ],
+        [
+            [255, 253, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+            [252, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]
+        ],
+        [
+            [255, 254, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+            [253, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]
+        ],
+        [
+            [255, 254, 253, 255, 255, 255, 255, 255, 255, 255, 255],
+            [250, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [254, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]
+        ],
+        [
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]
+        ]
+    ],
+    [
+        [
+            [186, 251, 250, 255, 255, 255, 255, 255, 255, 255, 255],
+            [234, 251, 244, 254, 255, 255, 255, 255, 255, 255, 255],
+            [251, 251, 243, 253, 254, 255, 254, 255, 255, 255, 255]
+        ],
+        [
+            [255, 253, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+            [236, 253, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+            [251, 253, 253, 254, 254, 255, 255, 255, 255, 255, 255]
+        ],
+        [
+            [255, 254, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+            [254, 254, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]
+        ],
+        [
+            [255, 254, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [254, 254, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [254, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]
+        ],
+        [
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [254, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]
+        ],
+        [
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]
+        ],
+        [
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]
+        ],
+        [
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]
+        ]
+    ],
+    [
+        [
+            [248, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [250, 254, 252, 254, 255, 255, 255, 255, 255, 255, 255],
+            [248, 254, 249, 253, 255, 255, 255, 255, 255, 255, 255]
+        ],
+        [
+            [255, 253, 253, 255, 255, 255, 255, 255, 255, 255, 255],
+            [246, 253, 253, 255, 255, 255, 255, 255, 255, 255, 255],
+            [252, 254, 251, 254, 254, 255, 255, 255, 255, 255, 255]
+        ],
+        [
+            [255, 254, 252, 255, 255, 255, 255, 255, 255, 255, 255],
+            [248, 254, 253, 255, 255, 255, 255, 255, 255, 255, 255],
+            [253, 255, 254, 254, 255, 255, 255, 255, 255, 255, 255]
+        ],
+        [
+            [255, 251, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+            [245, 251, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+            [253, 253, 254, 255, 255, 255, 255, 255, 255, 255, 255]
+        ],
+        [
+            [255, 251, 253, 255, 255, 255, 255, 255, 255, 255, 255],
+            [252, 253, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 254, 255, 255, 255, 255, 255, 255, 255, 255, 255]
+        ],
+        [
+            [255, 252, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [249, 255, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 254, 255, 255, 255, 255, 255, 255, 255, 255]
+        ],
+        [
+            [255, 255, 253, 255, 255, 255, 255, 255, 255, 255, 255],
+            [250, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]
+        ],
+        [
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [254, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255]
+        ]
+    ]
+];
+
+const DEFAULT_COEFFICIENT_PROBABILITIES: CoefficientProbabilities = [
+    [
+        [
+            [128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128],
+            [128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128],
+            [128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128]
+        ],
+        [
+            [253, 136, 254, 255, 228, 219, 128, 128, 128, 128, 128],
+            [189, 129, 242, 255, 227, 213, 255, 219, 128, 128, 128],
+            [106, 126, 227, 252, 214, 209, 255, 255, 128, 128, 128]
+        ],
+        [
+            [1, 98, 248, 255, 236, 226, 255, 255, 128, 128, 128],
+            [181, 133, 238, 254, 221, 234, 255, 154, 128, 128, 128],
+            [78, 134, 202, 247, 198, 180, 255, 219, 128, 128, 128]
+        ],
+        [
+            [1, 185, 249, 255, 243, 255, 128, 128, 128, 128, 128],
+            [184, 150, 247, 255, 236, 224, 128, 128, 128, 128, 128],
+            [77, 110, 216, 255, 236, 230, 128, 128, 128, 128, 128]
+        ],
+        [
+            [1, 101, 251, 255, 241, 255, 128, 128, 128, 128, 128],
+            [170, 139, 241, 252, 236, 209, 255, 255, 128, 128, 128],
+            [37, 116, 196, 243, 228, 255, 255, 255, 128, 128, 128]
+        ],
+        [
+            [1, 204, 254, 255, 245, 255, 128, 128, 128, 128, 128],
+            [207, 160, 250, 255, 238, 128, 128, 128, 128, 128, 128],
+            [102, 103, 231, 255, 211, 171, 128, 128, 128, 128, 128]
+        ],
+        [
+            [1, 152, 252, 255, 240, 255, 128, 128, 128, 128, 128],
+            [177, 135, 243, 255, 234, 225, 128, 128, 128, 128, 128],
+            [80, 129, 211, 255, 194, 224, 128, 128, 128, 128, 128]
+        ],
+        [
+            [1, 1, 255, 128, 128, 128, 128, 128, 128, 128, 128],
+            [246, 1, 255, 128, 128, 128, 128, 128, 128, 128, 128],
+            [255, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128]
+        ]
+    ],
+    [
+        [
+            [198, 35, 237, 223, 193, 187, 162, 160, 145, 155, 62],
+            [131, 45, 198, 221, 172, 176, 220, 157, 252, 221, 1],
+            [68, 47, 146, 208, 149, 167, 221, 162, 255, 223, 128]
+        ],
+        [
+            [1, 149, 241, 255, 221, 224, 255, 255, 128, 128, 128],
+            [184, 141, 234, 253, 222, 220, 255, 199, 128, 128, 128],
+            [81, 99, 181, 242, 176, 190, 249, 202, 255, 255, 128]
+        ],
+        [
+            [1, 129, 232, 253, 214, 197, 242, 196, 255, 255, 128],
+            [99, 121, 210, 250, 201, 198, 255, 202, 128, 128, 128],
+            [23, 91, 163, 242, 170, 187, 247, 210, 255, 255, 128]
+        ],
+        [
+            [1, 200, 246, 255, 234, 255, 128, 128, 128, 128, 128],
+            [109, 178, 241, 255, 231, 245, 255, 255, 128, 128, 128],
+            [44, 130, 201, 253, 205, 192, 255, 255, 128, 128, 128]
+        ],
+        [
+            [1, 132, 239, 251, 219, 209, 255, 165, 128, 128, 128],
+            [94, 136, 225, 251, 218, 190, 255, 255, 128, 128, 128],
+            [22, 100, 174, 245, 186, 161, 255, 199, 128, 128, 128]
+        ],
+        [
+            [1, 182, 249, 255, 232, 235, 128, 128, 128, 128, 128],
+            [124, 143, 241, 255, 227, 234, 128, 128, 128, 128, 128],
+            [35, 77, 181, 251, 193, 211, 255, 205, 128, 128, 128]
+        ],
+        [
+            [1, 157, 247, 255, 236, 231, 255, 255, 128, 128, 128],
+            [121, 141, 235, 255, 225, 227, 255, 255, 128, 128, 128],
+            [45, 99, 188, 251, 195, 217, 255, 224, 128, 128, 128]
+        ],
+        [
+            [1, 1, 251, 255, 213, 255, 128, 128, 128, 128, 128],
+            [203, 1, 248, 255, 255, 128, 128, 128, 128, 128, 128],
+            [137, 1, 177, 255, 224, 255, 128, 128, 128, 128, 128]
+        ]
+    ],
+    [
+        [
+            [253, 9, 248, 251, 207, 208, 255, 192, 128, 128, 128],
+            [175, 13, 224, 243, 193, 185, 249, 198, 255, 255, 128],
+            [73, 17, 171, 221, 161, 179, 236, 167, 255, 234, 128]
+        ],
+        [
+            [1, 95, 247, 253, 212, 183, 255, 255, 128, 128, 128],
+            [239, 90, 244, 250, 211, 209, 255, 255, 128, 128, 128],
+            [155, 77, 195, 248, 188, 195, 255, 255, 128, 128, 128]
+        ],
+        [
+            [1, 24, 239, 251, 218, 219, 255, 205, 128, 128, 128],
+            [201, 51, 219, 255, 196, 186, 128, 128, 128, 128, 128],
+            [69, 46, 190, 239, 201, 218, 255, 228, 128, 128, 128]
+        ],
+        [
+            [1, 191, 251, 255, 255, 128, 128, 128, 128, 128, 128],
+            [223, 165, 249, 255, 213, 255, 128, 128, 128, 128, 128],
+            [141, 124, 248, 255, 255, 128, 128, 128, 128, 128, 128]
+        ],
+        [
+            [1, 16, 248, 255, 255, 128, 128, 128, 128, 128, 128],
+            [190, 36, 230, 255, 236, 255, 128, 128, 128, 128, 128],
+            [149, 1, 255, 128, 128, 128, 128, 128, 128, 128, 128]
+        ],
+        [
+            [1, 226, 255, 128, 128, 128, 128, 128, 128, 128, 128],
+            [247, 192, 255, 128, 128, 128, 128, 128, 128, 128, 128],
+            [240, 128, 255, 128, 128, 128, 128, 128, 128, 128, 128]
+        ],
+        [
+            [1, 134, 252, 255, 255, 128, 128, 128, 128, 128, 128],
+            [213, 62, 250, 255, 255, 128, 128, 128, 128, 128, 128],
+            [55, 93, 255, 128, 128, 128, 128, 128, 128, 128, 128]
+        ],
+        [
+            [128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128],
+            [128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128],
+            [128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128]
+        ]
+    ],
+    [
+        [
+            [202, 24, 213, 235, 186, 191, 220, 160, 240, 175, 255],
+            [126, 38, 182, 232, 169, 184, 228, 174, 255, 187, 128],
+            [61, 46, 138, 219, 151, 178, 240, 170, 255, 216, 128]
+        ],
+        [
+            [1, 112, 230, 250, 199, 191, 247, 159, 255, 255, 128],
+            [166, 109, 228, 252, 211, 215, 255, 174, 128, 128, 128],
+            [39, 77, 162, 232, 172, 180, 245, 178, 255, 255, 128]
+        ],
+        [
+            [1, 52, 220, 246, 198, 199, 249, 220, 255, 255, 128],
+            [124, 74, 191, 243, 183, 193, 250, 221, 255, 255, 128],
+            [24, 71, 130, 219, 154, 170, 243, 182, 255, 255, 128]
+        ],
+        [
+            [1, 182, 225, 249, 219, 240, 255, 224, 128, 128, 128],
+            [149, 150, 226, 252, 216, 205, 255, 171, 128, 128, 128],
+            [28, 108, 170, 242, 183, 194, 254, 223, 255, 255, 128]
+        ],
+        [
+            [1, 81, 230, 252, 204, 203, 255, 192, 128, 128, 128],
+            [123, 102, 209, 247, 188, 196, 255, 233, 128, 128, 128],
+            [20, 95, 153, 243, 164, 173, 255, 203, 128, 128, 128]
+        ],
+        [
+            [1, 222, 248, 255, 216, 213, 128, 128, 128, 128, 128],
+            [168, 175, 246, 252, 235, 205, 255, 255, 128, 128, 128],
+            [47, 116, 215, 255, 211, 212, 255, 255, 128, 128, 128]
+        ],
+        [
+            [1, 121, 236, 253, 212, 214, 255, 255, 128, 128, 128],
+            [141, 84, 213, 252, 201, 202, 255, 219, 128, 128, 128],
+            [42, 80, 160, 240, 162, 185, 255, 205, 128, 128, 128]
+        ],
+        [
+            [1, 1, 255, 128, 128, 128, 128, 128, 128, 128, 128],
+            [244, 1, 255, 128, 128, 128, 128, 128, 128, 128, 128],
+            [238, 1, 255, 128, 128, 128, 128, 128, 128, 128, 128]
+        ]
+    ]
+];
+
+const DC_QUANTIZER: [i32; 128] = [
+    4, 5, 6, 7, 8, 9, 10, 10, 11, 12, 13, 14, 15, 16, 17, 17,
+    18, 19, 20, 20, 21, 21, 22, 22, 23, 23, 24, 25, 25, 26, 27, 28,
+    29, 30, 31, 32, 33, 34, 35, 36, 37, 37, 38, 39, 40, 41, 42, 43,
+    44, 45, 46, 46, 47, 48, 49, 50, 51, 52, 53, 54, 55, 56, 57, 58,
+    59, 60, 61, 62, 63, 64, 65, 66, 67, 68, 69, 70, 71, 72, 73, 74,
+    75, 76, 76, 77, 78, 79, 80, 81, 82, 83, 84, 85, 86, 87, 88, 89,
+    91, 93, 95, 96, 98, 100, 101, 102, 104, 106, 108, 110, 112, 114, 116, 118,
+    122, 124, 126, 128, 130, 132, 134, 136, 138, 140, 143, 145, 148, 151, 154, 157
+];
+
+const AC_QUANTIZER: [i32; 128] = [
+    4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19,
+    20, 21, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31, 32, 33, 34, 35,
+    36, 37, 38, 39, 40, 41, 42, 43, 44, 45, 46, 47, 48, 49, 50, 51,
+    52, 53, 54, 55, 56, 57, 58, 60, 62, 64, 66, 68, 70, 72, 74, 76,
+    78, 80, 82, 84, 86, 88, 90, 92, 94, 96, 98, 100, 102, 104, 106, 108,
+    110, 112, 114, 116, 119, 122, 125, 128, 131, 134, 137, 140, 143, 146, 149, 152,
+    155, 158, 161, 164, 167, 170, 173, 177, 181, 185, 189, 193, 197, 201, 205, 209,
+    213, 217, 221, 225, 229, 234, 239, 245, 249, 254, 259, 264, 269, 274, 279, 284
+];
+
+/// The prediction modes, in the order of the subblock mode probability table. Whole macroblocks only use the first four.
+#[derive (Debug, Copy, Clone, PartialEq)]
+enum PredictionMode {
+    DirectCurrent,
+    TrueMotion,
+    Vertical,
+    Horizontal,
+    LeftDown,
+    RightDown,
+    VerticalRight,
+    VerticalLeft,
+    HorizontalDown,
+    HorizontalUp
+}
+
+#[derive (Debug, Copy, Clone)]
+enum LumaPrediction {
+    Macroblock(PredictionMode),
+    Subblocks([PredictionMode; 16])
+}
+
+#[derive (Debug, Copy, Clone)]
+pub(crate) struct VP8Header {
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    first_partition_size: usize
+}
+
+#[derive (Debug, Copy, Clone, Default)]
+struct Segmentation {
+    update_map: bool,
+    is_enabled: bool,
+    is_absolute: bool,
+    quantizer_levels: [i32; NUM_SEGMENTS],
+    filter_levels: [i32; NUM_SEGMENTS],
+    tree_probabilities: [u8; 3]
+}
+
+#[derive (Debug, Copy, Clone, Default)]
+struct LoopFilterHeader {
+    is_simple: bool,
+    level: i32,
+    sharpness: i32,
+    use_deltas: bool,
+    reference_frame_deltas: [i32; 4],
+    mode_deltas: [i32; 4]
+}
+
+/// The dequantization factors for the DC and AC coefficients of each block type.
+#[derive (Debug, Copy, Clone, Default)]
+struct Dequantizer {
+    y: [i32; 2],
+    y2: [i32; 2],
+    uv: [i32; 2]
+}
+
+#[derive (Debug, Copy, Clone, Default)]
+struct FilterParameters {
+    limit: i32,
+    interior_limit: i32,
+    hev_threshold: i32,
+    filter_inner_edges: bool
+}
+
+/// Whether the neighboring blocks had non-zero coefficients, which selects the probabilities for the next ones.
+#[derive (Debug, Copy, Clone, Default)]
+struct NonzeroContext {
+    y: [bool; 4],
+    u: [bool; 2],
+    v: [bool; 2],
+    y2: bool
+}
+
+struct Plane {
+    data: Vec<u8>,
+    stride: usize
+}
+
+/// The pixels a block is predicted from.
+struct PredictionEdges {
+    above: [u8; 20],
+    left: [u8; 16],
+    top_left: u8
+}
+
+struct BooleanDecoder<'a> {
+    data: &'a [u8],
+    position: usize,
+    value: u32,
+    range: u32,
+    bit_count: u32
+}
+
+struct VP8Decoder<'a> {
+    header: VP8Header,
+    macroblock_width: usize,
+    macroblock_height: usize,
+    first_partition: BooleanDecoder<'a>,
+    partitions: Vec<BooleanDecoder<'a>>,
+    segmentation: Segmentation,
+    loop_filter: LoopFilterHeader,
+    dequantizers: [Dequantizer; NUM_SEGMENTS],
+    coefficient_probabilities: Box<CoefficientProbabilities>,
+    skip_probability: Option<u8>,
+    y_plane: Plane,
+    u_plane: Plane,
+    v_plane: Plane,
+    filter_parameters: Vec<FilterParameters>
+}
+
+impl<'a> BooleanDecoder<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        let mut decoder = Self { data, position: 0, value: 0, range: 255, bit_count: 0 };
+        decoder.value = (decoder.next_byte() << 8) | decoder.next_byte();
+        decoder
+    }
+
+    // NOTE: Reading past the end behaves as if the data was padded with zeros, like libwebp does.
+    fn next_byte(&mut self) -> u32 {
+        let byte = self.data.get(self.position).copied().unwrap_or(0);
+        self.position += 1;
+        byte as u32
+    }
+
+    fn read_bool(&mut self, probability: u8) -> bool {
+        let split = 1 + (((self.range - 1) * probability as u32) >> 8);
+        let big_split = split << 8;
+        let bit = if self.value >= big_split {
+            self.range -= split;
+            self.value -= big_split;
+            true
+        } else {
+            self.range = split;
+            false
+        };
+
+        while self.range < 128 {
+            self.value <<= 1;
+            self.range <<= 1;
+            self.bit_count += 1;
+            if self.bit_count == 8 {
+                self.bit_count = 0;
+                self.value |= self.next_byte();
+            }
+        }
+        bit
+    }
+
+    fn read_flag(&mut self) -> bool {
+        self.read_bool(128)
+    }
+
+    fn read_literal(&mut self, bits: u32) -> i32 {
+        (0..bits).fold(0, |value, _| (value << 1) | self.read_flag() as i32)
+    }
+
+    fn read_signed_literal(&mut self, bits: u32) -> i32 {
+        let value = self.read_literal(bits);
+        if self.read_flag() { -value } else { value }
+    }
+
+    fn read_optional_signed_literal(&mut self, bits: u32) -> i32 {
+        if self.read_flag() { self.read_signed_literal(bits) } else { 0 }
+    }
+
+    fn read_tree(&mut self, tree: &[i8], probabilities: &[u8]) -> usize {
+        let mut index = 0;
+        loop {
+            let next = tree[index + self.read_bool(probabilities[index >> 1]) as usize];
+            if next <= 0 {
+                return -next as usize;
+            }
+            index = next as usize;
+        }
+    }
+}
+
+impl Plane {
+    fn new(width: usize, height: usize) -> Result<Self, Error> {
+        let mut data = Vec::new();
+        if data.try_reserve_exact(width * height).is_err() {
+            return Err(Error::OutOfMemory);
+        }
+        data.resize(width * height, 0);
+        Ok(Self { data, stride: width })
+    }
+}
+
+impl PredictionEdges {
+    // NOTE: Rows above the frame read as 127 and columns left of it as 129. The corner in between counts as a row.
+    fn read(plane: &Plane, x: usize, y: usize, size: usize) -> Self {
+        let mut edges = Self { above: [127; 20], left: [129; 16], top_left: 127 };
+        if y > 0 {
+            let row = &plane.data[(y - 1) * plane.stride..y * plane.stride];
+            let available = (row.len() - x).min(edges.above.len());
+            edges.above[..available].copy_from_slice(&row[x..x + available]);
+            edges.top_left = if x > 0 { row[x - 1] } else { 129 };
+        }
+        if x > 0 {
+            for (index, left) in edges.left[..size].iter_mut().enumerate() {
+                *left = plane.data[(y + index) * plane.stride + x - 1];
+            }
+        }
+        edges
+    }
+}
+
+fn clamp_to_u8(value: i32) -> u8 {
+    value.clamp(0, 255) as u8
+}
+
+fn average2(a: u8, b: u8) -> u8 {
+    ((a as u32 + b as u32 + 1) >> 1) as u8
+}
+
+fn average3(a: u8, b: u8, c: u8) -> u8 {
+    ((a as u32 + 2 * b as u32 + c as u32 + 2) >> 2) as u8
+}
+
+fn predict_macroblock(plane: &mut Plane, x: usize, y: usize, size: usize, mode: PredictionMode) {
+    let edges = PredictionEdges::read(plane, x, y, size);
+    let (above, left) = (&edges.above[..size], &edges.left[..size]);
+    let sum = |values: &[u8]| values.iter().map(|&value| value as u32).sum::<u32>();
+    let shift = size.trailing_zeros();
+
+    // NOTE: Unlike the other modes, DC prediction only averages the edges that are inside the frame.
+    let direct_current = match (y > 0, x > 0) {
+        (true, true) => (sum(above) + sum(left) + size as u32) >> (shift + 1),
+        (true, false) => (sum(above) + (size as u32 >> 1)) >> shift,
+        (false, true) => (sum(left) + (size as u32 >> 1)) >> shift,
+        (false, false) => 128
+    } as u8;
+
+    for (row, &left) in left.iter().enumerate() {
+        for (column, &above) in above.iter().enumerate() {
+            plane.data[(y + row) * plane.stride + x + column] = match mode {
+                PredictionMode::DirectCurrent => direct_current,
+                PredictionMode::TrueMotion => clamp_to_u8(left as i32 + above as i32 - edges.top_left as i32),
+                PredictionMode::Vertical => above,
+                PredictionMode::Horizontal => left,
+                _ => unreachable!("Subblock prediction mode used for a macroblock")
+            };
+        }
+    }
+}
+
+fn predict_subblock(plane: &mut Plane, x: usize, y: usize, mode: PredictionMode, edges: &PredictionEdges) {
+    let [a, b, c, d, e, f, g, h] = [0, 1, 2, 3, 4, 5, 6, 7].map(|index| edges.above[index]);
+    let [i, j, k, l] = [0, 1, 2, 3].map(|index| edges.left[index]);
+    let top_left = edges.top_left;
+
+    let mut block = [[0u8; 4]; 4];
+    match mode {
+        PredictionMode::DirectCurrent => {
+            let sum = edges.above[..4].iter().chain(&edges.left[..4]).map(|&value| value as u32).sum::<u32>();
+            block = [[((sum + 4) >> 3) as u8; 4]; 4];
+        }
+        PredictionMode::TrueMotion => {
+            for (row, left) in block.iter_mut().zip([i, j, k, l]) {
+                for (pixel, above) in row.iter_mut().zip([a, b, c, d]) {
+                    *pixel = clamp_to_u8(left as i32 + above as i32 - top_left as i32);
+                }
+            }
+        }
+        PredictionMode::Vertical => {
+            block = [[average3(top_left, a, b), average3(a, b, c), average3(b, c, d), average3(c, d, e)]; 4];
+        }
+        PredictionMode::Horizontal => {
+            block = [[average3(top_left, i, j); 4], [average3(i, j, k); 4], [average3(j, k, l); 4], [average3(k, l, l); 4]];
+        }
+        PredictionMode::LeftDown => {
+            for (row, values) in block.iter_mut().enumerate() {
+                for (column, pixel) in values.iter_mut().enumerate() {
+                    let index = row + column;
+                    *pixel = if index == 6 { average3(g, h, h) } else { average3(edges.above[index], edges.above[index + 1], edges.above[index + 2]) };
+                }
+            }
+        }
+        PredictionMode::RightDown => {
+            let edge = [l, k, j, i, top_left, a, b, c, d];
+            for (row, values) in block.iter_mut().enumerate() {
+                for (column, pixel) in values.iter_mut().enumerate() {
+                    let index = 3 + column - row;
+                    *pixel = average3(edge[index], edge[index + 1], edge[index + 2]);
+                }
+            }
+        }
+        PredictionMode::VerticalRight => {
+            (block[0][0], block[2][1]) = (average2(top_left, a), average2(top_left, a));
+            (block[0][1], block[2][2]) = (average2(a, b), average2(a, b));
+            (block[0][2], block[2][3]) = (average2(b, c), average2(b, c));
+            block[0][3] = average2(c, d);
+            block[3][0] = average3(k, j, i);
+            block[2][0] = average3(j, i, top_left);
+            (block[1][0], block[3][1]) = (average3(i, top_left, a), average3(i, top_left, a));
+            (block[1][1], block[3][2]) = (average3(top_left, a, b), average3(top_left, a, b));
+            (block[1][2], block[3][3]) = (average3(a, b, c), average3(a, b, c));
+            block[1][3] = average3(b, c, d);
+        }
+        PredictionMode::VerticalLeft => {
+            block[0][0] = average2(a, b);
+            (block[0][1], block[2][0]) = (average2(b, c), average2(b, c));
+            (block[0][2], block[2][1]) = (average2(c, d), average2(c, d));
+            (block[0][3], block[2][2]) = (average2(d, e), average2(d, e));
+            block[1][0] = average3(a, b, c);
+            (block[1][1], block[3][0]) = (average3(b, c, d), average3(b, c, d));
+            (block[1][2], block[3][1]) = (average3(c, d, e), average3(c, d, e));
+            (block[1][3], block[3][2]) = (average3(d, e, f), average3(d, e, f));
+            block[2][3] = average3(e, f, g);
+            block[3][3] = average3(f, g, h);
+        }
+        PredictionMode::HorizontalDown => {
+            (block[0][0], block[1][2]) = (average2(i, top_left), average2(i, top_left));
+            (block[1][0], block[2][2]) = (average2(j, i), average2(j, i));
+            (block[2][0], block[3][2]) = (average2(k, j), average2(k, j));
+            block[3][0] = average2(l, k);
+            block[0][3] = average3(a, b, c);
+            block[0][2] = average3(top_left, a, b);
+            (block[0][1], block[1][3]) = (average3(i, top_left, a), average3(i, top_left, a));
+            (block[1][1], block[2][3]) = (average3(j, i, top_left), average3(j, i, top_left));
+            (block[2][1], block[3][3]) = (average3(k, j, i), average3(k, j, i));
+            block[3][1] = average3(l, k, j);
+        }
+        PredictionMode::HorizontalUp => {
+            block[0][0] = average2(i, j);
+            (block[0][2], block[1][0]) = (average2(j, k), average2(j, k));
+            (block[1][2], block[2][0]) = (average2(k, l), average2(k, l));
+            block[0][1] = average3(i, j, k);
+            (block[0][3], block[1][1]) = (average3(j, k, l), average3(j, k, l));
+            (block[1][3], block[2][1]) = (average3(k, l, l), average3(k, l, l));
+            (block[2][2], block[2][3], block[3]) = (l, l, [l; 4]);
+        }
+    }
+
+    for (row, values) in block.iter().enumerate() {
+        let offset = (y + row) * plane.stride + x;
+        plane.data[offset..offset + 4].copy_from_slice(values);
+    }
+}
+
+fn inverse_dct_add(coefficients: &[i16; 16], plane: &mut Plane, x: usize, y: usize) {
+    // NOTE: These are fixed point versions of sqrt(2) * cos(pi / 8) - 1 and sqrt(2) * sin(pi / 8).
+    let multiply1 = |value: i32| ((value as i64 * 20091) >> 16) as i32 + value;
+    let multiply2 = |value: i32| ((value as i64 * 35468) >> 16) as i32;
+
+    let input = coefficients.map(i32::from);
+    let mut temporary = [0i32; 16];
+    for column in 0..4 {
+        let a = input[column] + input[8 + column];
+        let b = input[column] - input[8 + column];
+        let c = multiply2(input[4 + column]) - multiply1(input[12 + column]);
+        let d = multiply1(input[4 + column]) + multiply2(input[12 + column]);
+        temporary[column * 4..column * 4 + 4].copy_from_slice(&[a + d, b + c, b - c, a - d]);
+    }
+    for row in 0..4 {
+        let direct_current = temporary[row] + 4;
+        let a = direct_current + temporary[8 + row];
+        let b = direct_current - temporary[8 + row];
+        let c = multiply2(temporary[4 + row]) - multiply1(temporary[12 + row]);
+        let d = multiply1(temporary[4 + row]) + multiply2(temporary[12 + row]);
+        let offset = (y + row) * plane.stride + x;
+        for (pixel, value) in plane.data[offset..offset + 4].iter_mut().zip([a + d, b + c, b - c, a - d]) {
+            *pixel = clamp_to_u8(*pixel as i32 + (value >> 3));
+        }
+    }
+}
+
+/// Turns the Y2 block into the DC coefficients of the 16 luma blocks.
+fn inverse_walsh_hadamard_transform(input: &[i16; 16], coefficients: &mut [[i16; 16]; 25]) {
+    let input = input.map(i32::from);
+    let mut temporary = [0i32; 16];
+    for column in 0..4 {
+        let a0 = input[column] + input[12 + column];
+        let a1 = input[4 + column] + input[8 + column];
+        let a2 = input[4 + column] - input[8 + column];
+        let a3 = input[column] - input[12 + column];
+        temporary[column] = a0 + a1;
+        temporary[4 + column] = a3 + a2;
+        temporary[8 + column] = a0 - a1;
+        temporary[12 + column] = a3 - a2;
+    }
+    for row in 0..4 {
+        let direct_current = temporary[row * 4] + 3;
+        let a0 = direct_current + temporary[row * 4 + 3];
+        let a1 = temporary[row * 4 + 1] + temporary[row * 4 + 2];
+        let a2 = temporary[row * 4 + 1] - temporary[row * 4 + 2];
+        let a3 = direct_current - temporary[row * 4 + 3];
+        coefficients[row * 4][0] = ((a0 + a1) >> 3) as i16;
+        coefficients[row * 4 + 1][0] = ((a3 + a2) >> 3) as i16;
+        coefficients[row * 4 + 2][0] = ((a0 - a1) >> 3) as i16;
+        coefficients[row * 4 + 3][0] = ((a3 - a2) >> 3) as i16;
+    }
+}
+
+fn read_large_coefficient(reader: &mut BooleanDecoder, probabilities: &[u8; NUM_COEFFICIENT_PROBABILITIES]) -> i32 {
+    if !reader.read_bool(probabilities[3]) {
+        if !reader.read_bool(probabilities[4]) {
+            return 2;
+        }
+        return 3 + reader.read_bool(probabilities[5]) as i32;
+    }
+    if !reader.read_bool(probabilities[6]) {
+        if !reader.read_bool(probabilities[7]) {
+            return 5 + reader.read_bool(159) as i32;
+        }
+        return 7 + 2 * reader.read_bool(165) as i32 + reader.read_bool(145) as i32;
+    }
+
+    let high_bit = reader.read_bool(probabilities[8]) as usize;
+    let low_bit = reader.read_bool(probabilities[9 + high_bit]) as usize;
+    let category = 2 * high_bit + low_bit;
+    let extra_bit_probabilities: &[u8] = match category {
+        0 => &DCT_CATEGORY_3_PROBABILITIES,
+        1 => &DCT_CATEGORY_4_PROBABILITIES,
+        2 => &DCT_CATEGORY_5_PROBABILITIES,
+        _ => &DCT_CATEGORY_6_PROBABILITIES
+    };
+    let extra = extra_bit_probabilities.iter().fold(0, |value, &probability| 2 * value + reader.read_bool(probability) as i32);
+    extra + 3 + (8 << category)
+}
+
+/// Reads the tokens of one block starting at `first_coefficient`, and returns the index after the last one read.
+fn read_coefficients(reader: &mut BooleanDecoder, probabilities: &[[[u8; NUM_COEFFICIENT_PROBABILITIES]; NUM_COEFFICIENT_CONTEXTS]; NUM_COEFFICIENT_BANDS], context: usize, dequantizer: [i32; 2], first_coefficient: usize, block: &mut [i16; 16]) -> usize {
+    let mut index = first_coefficient;
+    let mut band_probabilities = &probabilities[COEFFICIENT_BANDS[index]][context];
+    while index < 16 {
+        if !reader.read_bool(band_probabilities[0]) {
+            return index;
+        }
+        while !reader.read_bool(band_probabilities[1]) {
+            index += 1;
+            if index == 16 {
+                return 16;
+            }
+            band_probabilities = &probabilities[COEFFICIENT_BANDS[index]][0];
+        }
+
+        let (value, next_context) = if !reader.read_bool(band_probabilities[2]) {
+            (1, 1)
+        } else {
+            (read_large_coefficient(reader, band_probabilities), 2)
+        };
+        let value = if reader.read_flag() { -value } else { value };
+        // NOTE: Out of range values wrap around, the same as they do in libwebp.
+        block[ZIGZAG[index]] = (value * dequantizer[(index > 0) as usize]) as i16;
+
+        index += 1;
+        if index < 16 {
+            band_probabilities = &probabilities[COEFFICIENT_BANDS[index]][next_context];
+        }
+    }
+    16
+}
+
+/// Reads all coefficients of a macroblock, and returns whether any of them are non-zero.
+fn read_residuals(reader: &mut BooleanDecoder, probabilities: &CoefficientProbabilities, dequantizer: &Dequantizer, has_y2: bool, above: &mut NonzeroContext, left: &mut NonzeroContext, coefficients: &mut [[i16; 16]; 25]) -> bool {
+    let (first_coefficient, y_probabilities) = if has_y2 {
+        let mut y2 = [0i16; 16];
+        let context = above.y2 as usize + left.y2 as usize;
+        let count = read_coefficients(reader, &probabilities[1], context, dequantizer.y2, 0, &mut y2);
+        above.y2 = count > 0;
+        left.y2 = count > 0;
+        inverse_walsh_hadamard_transform(&y2, coefficients);
+        (1, &probabilities[0])
+    } else {
+        (0, &probabilities[3])
+    };
+
+    let mut has_nonzero_coefficients = false;
+    for y in 0..4 {
+        let mut is_left_nonzero = left.y[y];
+        for x in 0..4 {
+            let block = &mut coefficients[y * 4 + x];
+            let context = is_left_nonzero as usize + above.y[x] as usize;
+            let count = read_coefficients(reader, y_probabilities, context, dequantizer.y, first_coefficient, block);
+            is_left_nonzero = count > first_coefficient;
+            above.y[x] = is_left_nonzero;
+            has_nonzero_coefficients |= count > 1 || block[0] != 0;
+        }
+        left.y[y] = is_left_nonzero;
+    }
+
+    for (index, (above, left)) in [(&mut above.u, &mut left.u), (&mut above.v, &mut left.v)].into_iter().enumerate() {
+        for y in 0..2 {
+            let mut is_left_nonzero = left[y];
+            for x in 0..2 {
+                let block = &mut coefficients[16 + index * 4 + y * 2 + x];
+                let context = is_left_nonzero as usize + above[x] as usize;
+                let count = read_coefficients(reader, &probabilities[2], context, dequantizer.uv, 0, block);
+                is_left_nonzero = count > 0;
+                above[x] = is_left_nonzero;
+                has_nonzero_coefficients |= count > 1 || block[0] != 0;
+            }
+            left[y] = is_left_nonzero;
+        }
+    }
+    has_nonzero_coefficients
+}
+
+fn needs_filter(data: &[u8], position: usize, step: usize, threshold: i32) -> bool {
+    let [p1, p0, q0, q1] = [position - 2 * step, position - step, position, position + step].map(|index| data[index] as i32);
+    4 * (p0 - q0).abs() + (p1 - q1).abs() <= threshold
+}
+
+fn needs_normal_filter(data: &[u8], position: usize, step: usize, threshold: i32, interior_limit: i32) -> bool {
+    let [p3, p2, p1, p0, q0, q1, q2, q3] = [4, 3, 2, 1, 0, -1, -2, -3].map(|offset: isize| data[position.wrapping_add_signed(-offset * step as isize)] as i32);
+    4 * (p0 - q0).abs() + (p1 - q1).abs() <= threshold
+        && [p3 - p2, p2 - p1, p1 - p0, q3 - q2, q2 - q1, q1 - q0].iter().all(|difference| difference.abs() <= interior_limit)
+}
+
+fn has_high_edge_variance(data: &[u8], position: usize, step: usize, threshold: i32) -> bool {
+    let [p1, p0, q0, q1] = [position - 2 * step, position - step, position, position + step].map(|index| data[index] as i32);
+    (p1 - p0).abs() > threshold || (q1 - q0).abs() > threshold
+}
+
+/// Adjusts the two pixels next to the edge.
+fn filter_common(data: &mut [u8], position: usize, step: usize, use_outer_taps: bool) {
+    let [p1, p0, q0, q1] = [position - 2 * step, position - step, position, position + step].map(|index| data[index] as i32);
+    let outer_taps = if use_outer_taps { (p1 - q1).clamp(-128, 127) } else { 0 };
+    let a = 3 * (q0 - p0) + outer_taps;
+    let a1 = ((a + 4) >> 3).clamp(-16, 15);
+    let a2 = ((a + 3) >> 3).clamp(-16, 15);
+    data[position - step] = clamp_to_u8(p0 + a2);
+    data[position] = clamp_to_u8(q0 - a1);
+    if !use_outer_taps {
+        let a3 = (a1 + 1) >> 1;
+        data[position - 2 * step] = clamp_to_u8(p1 + a3);
+        data[position + step] = clamp_to_u8(q1 - a3);
+    }
+}
+
+/// Adjusts the three pixels on either side of a macroblock edge.
+fn filter_macroblock_edge(data: &mut [u8], position: usize, step: usize) {
+    let [p2, p1, p0, q0, q1, q2] = [position - 3 * step, position - 2 * step, position - step, position, position + step, position + 2 * step].map(|index| data[index] as i32);
+    let a = (3 * (q0 - p0) + (p1 - q1).clamp(-128, 127)).clamp(-128, 127);
+    let a1 = (27 * a + 63) >> 7;
+    let a2 = (18 * a + 63) >> 7;
+    let a3 = (9 * a + 63) >> 7;
+    data[position - 3 * step] = clamp_to_u8(p2 + a3);
+    data[position - 2 * step] = clamp_to_u8(p1 + a2);
+    data[position - step] = clamp_to_u8(p0 + a1);
+    data[position] = clamp_to_u8(q0 - a1);
+    data[position + step] = clamp_to_u8(q1 - a2);
+    data[position + 2 * step] = clamp_to_u8(q2 - a3);
+}
+
+/// Filters the 16 pixels along an edge of a luma macroblock with the simple filter.
+fn simple_filter_edge(plane: &mut Plane, mut position: usize, step: usize, advance: usize, limit: i32) {
+    for _ in 0..16 {
+        if needs_filter(&plane.data, position, step, 2 * limit + 1) {
+            filter_common(&mut plane.data, position, step, true);
+        }
+        position += advance;
+    }
+}
+
+/// Filters `count` pixels along an edge with the normal filter. `step` crosses the edge and `advance` follows it.
+fn normal_filter_edge(plane: &mut Plane, mut position: usize, step: usize, advance: usize, count: usize, parameters: &FilterParameters, is_macroblock_edge: bool) {
+    let limit = if is_macroblock_edge { parameters.limit + 4 } else { parameters.limit };
+    for _ in 0..count {
+        if needs_normal_filter(&plane.data, position, step, 2 * limit + 1, parameters.interior_limit) {
+            if has_high_edge_variance(&plane.data, position, step, parameters.hev_threshold) {
+                filter_common(&mut plane.data, position, step, true);
+            } else if is_macroblock_edge {
+                filter_macroblock_edge(&mut plane.data, position, step);
+            } else {
+                filter_common(&mut plane.data, position, step, false);
+            }
+        }
+        position += advance;
+    }
+}
+
+fn read_segmentation(reader: &mut BooleanDecoder) -> Segmentation {
+    let mut segmentation = Segmentation { tree_probabilities: [255; 3], ..Default::default() };
+    segmentation.is_enabled = reader.read_flag();
+    if !segmentation.is_enabled {
+        return segmentation;
+    }
+
+    segmentation.update_map = reader.read_flag();
+    let update_data = reader.read_flag();
+    if update_data {
+        segmentation.is_absolute = reader.read_flag();
+        for level in &mut segmentation.quantizer_levels {
+            *level = reader.read_optional_signed_literal(7);
+        }
+        for level in &mut segmentation.filter_levels {
+            *level = reader.read_optional_signed_literal(6);
+        }
+    }
+    if segmentation.update_map {
+        for probability in &mut segmentation.tree_probabilities {
+            *probability = if reader.read_flag() { reader.read_literal(8) as u8 } else { 255 };
+        }
+    }
+    segmentation
+}
+
+fn read_loop_filter_header(reader: &mut BooleanDecoder) -> LoopFilterHeader {
+    let mut header = LoopFilterHeader {
+        is_simple: reader.read_flag(),
+        level: reader.read_literal(6),
+        sharpness: reader.read_literal(3),
+        use_deltas: reader.read_flag(),
+        ..Default::default()
+    };
+    if header.use_deltas && reader.read_flag() {
+        for delta in header.reference_frame_deltas.iter_mut().chain(&mut header.mode_deltas) {
+            if reader.read_flag() {
+                *delta = reader.read_signed_literal(6);
+            }
+        }
+    }
+    header
+}
+
+fn read_partitions<'a>(reader: &mut BooleanDecoder, data: &'a [u8]) -> Result<Vec<BooleanDecoder<'a>>, Error> {
+    let count = 1 << reader.read_literal(2);
+    let sizes_length = 3 * (count - 1);
+    if data.len() < sizes_length {
+        return Err(Error::Truncated);
+    }
+
+    let (sizes, mut data) = data.split_at(sizes_length);
+    let mut partitions = Vec::with_capacity(count);
+    for size in sizes.chunks_exact(3) {
+        let size = u32::from_le_bytes([size[0], size[1], size[2], 0]) as usize;
+        if size > data.len() {
+            return Err(Error::Truncated);
+        }
+        let (partition, rest) = data.split_at(size);
+        partitions.push(BooleanDecoder::new(partition));
+        data = rest;
+    }
+    // NOTE: The last partition takes up the rest of the data.
+    partitions.push(BooleanDecoder::new(data));
+    Ok(partitions)
+}
+
+fn read_dequantizers(reader: &mut BooleanDecoder, segmentation: &Segmentation) -> [Dequantizer; NUM_SEGMENTS] {
+    let base_index = reader.read_literal(7);
+    let y_dc_delta = reader.read_optional_signed_literal(4);
+    let y2_dc_delta = reader.read_optional_signed_literal(4);
+    let y2_ac_delta = reader.read_optional_signed_literal(4);
+    let uv_dc_delta = reader.read_optional_signed_literal(4);
+    let uv_ac_delta = reader.read_optional_signed_literal(4);
+
+    [0, 1, 2, 3].map(|segment| {
+        let index = match segmentation {
+            Segmentation { is_enabled: false, .. } => base_index,
+            Segmentation { is_absolute: true, .. } => segmentation.quantizer_levels[segment],
+            _ => base_index + segmentation.quantizer_levels[segment]
+        };
+        let lookup = |table: &[i32; 128], delta: i32, maximum: i32| table[(index + delta).clamp(0, maximum) as usize];
+        Dequantizer {
+            y: [lookup(&DC_QUANTIZER, y_dc_delta, 127), lookup(&AC_QUANTIZER, 0, 127)],
+            y2: [lookup(&DC_QUANTIZER, y2_dc_delta, 127) * 2, (lookup(&AC_QUANTIZER, y2_ac_delta, 127) * 155 / 100).max(8)],
+            // NOTE: libwebp caps the chroma DC factor at 132, which is the value at index 117.
+            uv: [lookup(&DC_QUANTIZER, uv_dc_delta, 117), lookup(&AC_QUANTIZER, uv_ac_delta, 127)]
+        }
+    })
+}
+
+fn read_coefficient_probabilities(reader: &mut BooleanDecoder) -> Box<CoefficientProbabilities> {
+    let mut probabilities = Box::new(DEFAULT_COEFFICIENT_PROBABILITIES);
+    for (coefficient_type, bands) in probabilities.iter_mut().enumerate() {
+        for (band, contexts) in bands.iter_mut().enumerate() {
+            for (context, values) in contexts.iter_mut().enumerate() {
+                for (index, probability) in values.iter_mut().enumerate() {
+                    if reader.read_bool(COEFFICIENT_UPDATE_PROBABILITIES[coefficient_type][band][context][index]) {
+                        *probability = reader.read_literal(8) as u8;
+                    }
+                }
+            }
+        }
+    }
+    probabilities
+}
+
+/// Interpolates one row of chroma samples to full resolution, weighing the nearest chroma row three times as much as the other one.
+fn upsample_chroma_row(near: &[u8], far: &[u8], output: &mut [u8]) {
+    let near: Vec<u32> = near.iter().map(|&value| value as u32).collect();
+    let far: Vec<u32> = far.iter().map(|&value| value as u32).collect();
+    let edge = |index: usize| ((3 * near[index] + far[index] + 2) >> 2) as u8;
+
+    output[0] = edge(0);
+    for x in 1..near.len() {
+        let sum = near[x - 1] + near[x] + far[x - 1] + far[x] + 8;
+        output[2 * x - 1] = ((((sum + 2 * (near[x] + far[x - 1])) >> 3) + near[x - 1]) >> 1) as u8;
+        output[2 * x] = ((((sum + 2 * (near[x - 1] + far[x])) >> 3) + near[x]) >> 1) as u8;
+    }
+    if output.len().is_multiple_of(2) {
+        output[output.len() - 1] = edge(near.len() - 1);
+    }
+}
+
+fn yuv_to_rgb(y: u8, u: u8, v: u8) -> (u8, u8, u8) {
+    let multiply_high = |value: u8, coefficient: i32| (value as i32 * coefficient) >> 8;
+    let clip = |value: i32| (value >> 6).clamp(0, 255) as u8;
+    (
+        clip(multiply_high(y, 19077) + multiply_high(v, 26149) - 14234),
+        clip(multiply_high(y, 19077) - multiply_high(u, 6419) - multiply_high(v, 13320) + 8708),
+        clip(multiply_high(y, 19077) + multiply_high(u, 33050) - 17685)
+    )
+}
+
+impl<'a> VP8Decoder<'a> {
+    fn new(data: &'a [u8]) -> Result<Self, Error> {
+        let header = decode_webp_chunk_vp8_header(data)?;
+        let (first_partition_data, partitions_data) = data[VP8_KEYFRAME_HEADER_SIZE..].split_at(header.first_partition_size);
+        let mut reader = BooleanDecoder::new(first_partition_data);
+
+        // NOTE: Neither the color space nor the clamping type change how the image is decoded.
+        reader.read_flag();
+        reader.read_flag();
+        let segmentation = read_segmentation(&mut reader);
+        let loop_filter = read_loop_filter_header(&mut reader);
+        let partitions = read_partitions(&mut reader, partitions_data)?;
+        let dequantizers = read_dequantizers(&mut reader, &segmentation);
+        // NOTE: This says whether the probabilities persist into the next frame, which doesn't exist.
+        reader.read_flag();
+        let coefficient_probabilities = read_coefficient_probabilities(&mut reader);
+        let skip_probability = if reader.read_flag() { Some(reader.read_literal(8) as u8) } else { None };
+
+        let macroblock_width = header.width.div_ceil(16) as usize;
+        let macroblock_height = header.height.div_ceil(16) as usize;
+        Ok(Self {
+            header,
+            macroblock_width,
+            macroblock_height,
+            first_partition: reader,
+            partitions,
+            segmentation,
+            loop_filter,
+            dequantizers,
+            coefficient_probabilities,
+            skip_probability,
+            y_plane: Plane::new(macroblock_width * 16, macroblock_height * 16)?,
+            u_plane: Plane::new(macroblock_width * 8, macroblock_height * 8)?,
+            v_plane: Plane::new(macroblock_width * 8, macroblock_height * 8)?,
+            filter_parameters: vec![FilterParameters::default(); macroblock_width * macroblock_height]
+        })
+    }
+
+    /// Returns the filter parameters for each segment, without and with subblock prediction.
+    fn segment_filter_parameters(&self) -> [[FilterParameters; 2]; NUM_SEGMENTS] {
+        let header = &self.loop_filter;
+        [0, 1, 2, 3].map(|segment| {
+            let base_level = match self.segmentation {
+                Segmentation { is_enabled: false, .. } => header.level,
+                Segmentation { is_absolute: true, .. } => self.segmentation.filter_levels[segment],
+                _ => header.level + self.segmentation.filter_levels[segment]
+            };
+            [false, true].map(|has_subblocks| {
+                let mut level = base_level;
+                if header.use_deltas {
+                    // NOTE: Keyframes only use the deltas for the intra frame and for subblock prediction.
+                    level += header.reference_frame_deltas[0];
+                    if has_subblocks {
+                        level += header.mode_deltas[0];
+                    }
+                }
+                let level = level.clamp(0, 63);
+                if level == 0 {
+                    return FilterParameters { filter_inner_edges: has_subblocks, ..Default::default() };
+                }
+
+                let mut interior_limit = level;
+                if header.sharpness > 0 {
+                    interior_limit >>= if header.sharpness > 4 { 2 } else { 1 };
+                    interior_limit = interior_limit.min(9 - header.sharpness);
+                }
+                let interior_limit = interior_limit.max(1);
+                FilterParameters {
+                    limit: 2 * level + interior_limit,
+                    interior_limit,
+                    hev_threshold: if level >= 40 { 2 } else if level >= 15 { 1 } else { 0 },
+                    filter_inner_edges: has_subblocks
+                }
+            })
+        })
+    }
+
+    fn read_luma_prediction(&mut self, above_modes: &mut [PredictionMode; 4], left_modes: &mut [PredictionMode; 4]) -> LumaPrediction {
+        let reader = &mut self.first_partition;
+        if reader.read_bool(145) {
+            let mode = if reader.read_bool(156) {
+                if reader.read_bool(128) { PredictionMode::TrueMotion } else { PredictionMode::Horizontal }
+            } else if reader.read_bool(163) {
+                PredictionMode::Vertical
+            } else {
+                PredictionMode::DirectCurrent
+            };
+            // NOTE: The subblock modes of the next macroblocks are predicted from the equivalent subblock mode.
+            *above_modes = [mode; 4];
+            *left_modes = [mode; 4];
+            return LumaPrediction::Macroblock(mode);
+        }
+
+        let mut modes = [PredictionMode::DirectCurrent; 16];
+        for y in 0..4 {
+            for x in 0..4 {
+                let probabilities = &SUBBLOCK_MODE_PROBABILITIES[above_modes[x] as usize][left_modes[y] as usize];
+                let mode = SUBBLOCK_MODES[reader.read_tree(&SUBBLOCK_MODE_TREE, probabilities)];
+                modes[y * 4 + x] = mode;
+                above_modes[x] = mode;
+                left_modes[y] = mode;
+            }
+        }
+        LumaPrediction::Subblocks(modes)
+    }
+
+    fn read_chroma_mode(&mut self) -> PredictionMode {
+        let reader = &mut self.first_partition;
+        if !reader.read_bool(142) {
+            PredictionMode::DirectCurrent
+        } else if !reader.read_bool(114) {
+            PredictionMode::Vertical
+        } else if reader.read_bool(183) {
+            PredictionMode::TrueMotion
+        } else {
+            PredictionMode::Horizontal
+        }
+    }
+
+    fn decode_macroblocks(&mut self) {
+        let segment_filter_parameters = self.segment_filter_parameters();
+        let mut above_modes = vec![[PredictionMode::DirectCurrent; 4]; self.macroblock_width];
+        let mut above_nonzero = vec![NonzeroContext::default(); self.macroblock_width];
+
+        for macroblock_y in 0..self.macroblock_height {
+            let mut left_modes = [PredictionMode::DirectCurrent; 4];
+            let mut left_nonzero = NonzeroContext::default();
+            let partition_index = macroblock_y & (self.partitions.len() - 1);
+
+            for macroblock_x in 0..self.macroblock_width {
+                let segment = if self.segmentation.update_map {
+                    let reader = &mut self.first_partition;
+                    let probabilities = self.segmentation.tree_probabilities;
+                    if !reader.read_bool(probabilities[0]) {
+                        reader.read_bool(probabilities[1]) as usize
+                    } else {
+                        2 + reader.read_bool(probabilities[2]) as usize
+                    }
+                } else {
+                    0
+                };
+                let is_skipped = match self.skip_probability {
+                    Some(probability) => self.first_partition.read_bool(probability),
+                    None => false
+                };
+                let luma_prediction = self.read_luma_prediction(&mut above_modes[macroblock_x], &mut left_modes);
+                let chroma_mode = self.read_chroma_mode();
+                let has_subblocks = matches!(luma_prediction, LumaPrediction::Subblocks(_));
+
+                let mut coefficients = [[0i16; 16]; 25];
+                let above = &mut above_nonzero[macroblock_x];
+                let has_nonzero_coefficients = if is_skipped {
+                    // NOTE: Macroblocks with subblock prediction have no Y2 block, so they leave its context alone.
+                    let (above_y2, left_y2) = (above.y2, left_nonzero.y2);
+                    *above = NonzeroContext { y2: has_subblocks && above_y2, ..Default::default() };
+                    left_nonzero = NonzeroContext { y2: has_subblocks && left_y2, ..Default::default() };
+                    false
+                } else {
+                    let reader = &mut self.partitions[partition_index];
+                    read_residuals(reader, &self.coefficient_probabilities, &self.dequantizers[segment], !has_subblocks, above, &mut left_nonzero, &mut coefficients)
+                };
+
+                let mut filter_parameters = segment_filter_parameters[segment][has_subblocks as usize];
+                filter_parameters.filter_inner_edges |= has_nonzero_coefficients;
+                self.filter_parameters[macroblock_y * self.macroblock_width + macroblock_x] = filter_parameters;
+
+                self.reconstruct_macroblock(macroblock_x, macroblock_y, &luma_prediction, chroma_mode, &coefficients);
+            }
+        }
+    }
+
+    fn reconstruct_macroblock(&mut self, macroblock_x: usize, macroblock_y: usize, luma_prediction: &LumaPrediction, chroma_mode: PredictionMode, coefficients: &[[i16; 16]; 25]) {
+        let (x, y) = (macroblock_x * 16, macroblock_y * 16);
+        match luma_prediction {
+            LumaPrediction::Macroblock(mode) => {
+                predict_macroblock(&mut self.y_plane, x, y, 16, *mode);
+                for (index, block) in coefficients[..16].iter().enumerate() {
+                    inverse_dct_add(block, &mut self.y_plane, x + index % 4 * 4, y + index / 4 * 4);
+                }
+            }
+            LumaPrediction::Subblocks(modes) => {
+                // NOTE: The rightmost subblocks all use the pixels above and to the right of the macroblock, as the ones
+                //       to their right aren't decoded yet. The last macroblock in a row repeats the pixel above its corner.
+                let above_right = if y == 0 {
+                    [127; 4]
+                } else if macroblock_x + 1 < self.macroblock_width {
+                    let offset = (y - 1) * self.y_plane.stride + x + 16;
+                    self.y_plane.data[offset..offset + 4].try_into().unwrap()
+                } else {
+                    [self.y_plane.data[(y - 1) * self.y_plane.stride + x + 15]; 4]
+                };
+
+                for (index, block) in coefficients[..16].iter().enumerate() {
+                    let (subblock_x, subblock_y) = (x + index % 4 * 4, y + index / 4 * 4);
+                    let mut edges = PredictionEdges::read(&self.y_plane, subblock_x, subblock_y, 4);
+                    if index % 4 == 3 {
+                        edges.above[4..8].copy_from_slice(&above_right);
+                    }
+                    predict_subblock(&mut self.y_plane, subblock_x, subblock_y, modes[index], &edges);
+                    inverse_dct_add(block, &mut self.y_plane, subblock_x, subblock_y);
+                }
+            }
+        }
+
+        let (x, y) = (macroblock_x * 8, macroblock_y * 8);
+        for (plane, blocks) in [(&mut self.u_plane, &coefficients[16..20]), (&mut self.v_plane, &coefficients[20..24])] {
+            predict_macroblock(plane, x, y, 8, chroma_mode);
+            for (index, block) in blocks.iter().enumerate() {
+                inverse_dct_add(block, plane, x + index % 2 * 4, y + index / 2 * 4);
+            }
+        }
+    }
+
+    // NOTE: Prediction uses the unfiltered pixels, so the whole frame is filtered after it has been reconstructed.
+    fn apply_loop_filter(&mut self) {
+        if self.loop_filter.level == 0 {
+            return;
+        }
+
+        for macroblock_y in 0..self.macroblock_height {
+            for macroblock_x in 0..self.macroblock_width {
+                let parameters = self.filter_parameters[macroblock_y * self.macroblock_width + macroblock_x];
+                if parameters.limit == 0 {
+                    continue;
+                }
+
+                let stride = self.y_plane.stride;
+                let position = macroblock_y * 16 * stride + macroblock_x * 16;
+                if self.loop_filter.is_simple {
+                    if macroblock_x > 0 {
+                        simple_filter_edge(&mut self.y_plane, position, 1, stride, parameters.limit + 4);
+                    }
+                    if parameters.filter_inner_edges {
+                        for offset in [4, 8, 12] {
+                            simple_filter_edge(&mut self.y_plane, position + offset, 1, stride, parameters.limit);
+                        }
+                    }
+                    if macroblock_y > 0 {
+                        simple_filter_edge(&mut self.y_plane, position, stride, 1, parameters.limit + 4);
+                    }
+                    if parameters.filter_inner_edges {
+                        for offset in [4, 8, 12] {
+                            simple_filter_edge(&mut self.y_plane, position + offset * stride, stride, 1, parameters.limit);
+                        }
+                    }
+                    continue;
+                }
+
+                let chroma_stride = self.u_plane.stride;
+                let chroma_position = macroblock_y * 8 * chroma_stride + macroblock_x * 8;
+                if macroblock_x > 0 {
+                    normal_filter_edge(&mut self.y_plane, position, 1, stride, 16, &parameters, true);
+                    for plane in [&mut self.u_plane, &mut self.v_plane] {
+                        normal_filter_edge(plane, chroma_position, 1, chroma_stride, 8, &parameters, true);
+                    }
+                }
+                if parameters.filter_inner_edges {
+                    for offset in [4, 8, 12] {
+                        normal_filter_edge(&mut self.y_plane, position + offset, 1, stride, 16, &parameters, false);
+                    }
+                    for plane in [&mut self.u_plane, &mut self.v_plane] {
+                        normal_filter_edge(plane, chroma_position + 4, 1, chroma_stride, 8, &parameters, false);
+                    }
+                }
+                if macroblock_y > 0 {
+                    normal_filter_edge(&mut self.y_plane, position, stride, 1, 16, &parameters, true);
+                    for plane in [&mut self.u_plane, &mut self.v_plane] {
+                        normal_filter_edge(plane, chroma_position, chroma_stride, 1, 8, &parameters, true);
+                    }
+                }
+                if parameters.filter_inner_edges {
+                    for offset in [4, 8, 12] {
+                        normal_filter_edge(&mut self.y_plane, position + offset * stride, stride, 1, 16, &parameters, false);
+                    }
+                    for plane in [&mut self.u_plane, &mut self.v_plane] {
+                        normal_filter_edge(plane, chroma_position + 4 * chroma_stride, chroma_stride, 1, 8, &parameters, false);
+                    }
+                }
+            }
+        }
+    }
+
+    fn convert_to_bitmap(&self) -> Result<Bitmap, Error> {
+        let (width, height) = (self.header.width as usize, self.header.height as usize);
+        let mut bitmap = Bitmap::new(BitmapFormat::BGRx8888, IntSize { width: width as i32, height: height as i32 }, 1)?;
+
+        let (chroma_width, chroma_height) = (width.div_ceil(2), height.div_ceil(2));
+        let chroma_row = |plane: &Plane, row: usize| plane.data[row * plane.stride..row * plane.stride + chroma_width].to_vec();
+        let mut u_row = vec![0u8; width];
+        let mut v_row = vec![0u8; width];
+        for y in 0..height {
+            // NOTE: Odd rows are closer to the chroma row below them, even rows to the one above them.
+            let near = y / 2;
+            let far = if y % 2 == 1 { (near + 1).min(chroma_height - 1) } else { near.saturating_sub(1) };
+            upsample_chroma_row(&chroma_row(&self.u_plane, near), &chroma_row(&self.u_plane, far), &mut u_row);
+            upsample_chroma_row(&chroma_row(&self.v_plane, near), &chroma_row(&self.v_plane, far), &mut v_row);
+
+            let luma_row = &self.y_plane.data[y * self.y_plane.stride..];
+            for x in 0..width {
+                let (r, g, b) = yuv_to_rgb(luma_row[x], u_row[x], v_row[x]);
+                bitmap.set_pixel(x as i32, y as i32, Color::from_rgb(r, g, b).color);
+            }
+        }
+        Ok(bitmap)
+    }
+}
+
+pub(crate) fn decode_webp_chunk_vp8_header(data: &[u8]) -> Result<VP8Header, Error> {
+    if data.len() < VP8_KEYFRAME_HEADER_SIZE {
+        return Err(Error::Truncated);
+    }
+
+    let frame_tag = u32::from_le_bytes([data[0], data[1], data[2], 0]);
+    if frame_tag & 1 != 0 {
+        return Err(Error::InvalidData("VP8 frame is not a keyframe"));
+    }
+    if (frame_tag >> 1) & 7 > 3 {
+        return Err(Error::Unsupported("VP8 version"));
+    }
+    if (frame_tag >> 4) & 1 == 0 {
+        return Err(Error::InvalidData("VP8 frame is not meant to be shown"));
+    }
+    if data[VP8_FRAME_TAG_SIZE..VP8_FRAME_TAG_SIZE + 3] != VP8_START_CODE {
+        return Err(Error::InvalidHeader("Invalid VP8 start code"));
+    }
+
+    // NOTE: The top two bits of each dimension are an upscaling hint, which is meant for the application and is ignored.
+    let width = u16::from_le_bytes([data[6], data[7]]) as u32 & 0x3FFF;
+    let height = u16::from_le_bytes([data[8], data[9]]) as u32 & 0x3FFF;
+    if width == 0 || height == 0 {
+        return Err(Error::InvalidHeader("VP8 frame has no pixels"));
+    }
+
+    let first_partition_size = (frame_tag >> 5) as usize;
+    if first_partition_size > data.len() - VP8_KEYFRAME_HEADER_SIZE {
+        return Err(Error::Truncated);
+    }
+    Ok(VP8Header { width, height, first_partition_size })
+}
+
+/// Decodes a VP8 keyframe to an opaque bitmap.
+pub(crate) fn decode_webp_chunk_vp8(data: &[u8]) -> Result<Bitmap, Error> {
+    let mut decoder = VP8Decoder::new(data)?;
+    decoder.decode_macroblocks();
+    decoder.apply_loop_filter();
+    decoder.convert_to_bitmap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // NOTE: Written with the boolean encoder from RFC 6386, section 7.3.
+    const BOOLEAN_DATA: [u8; 5] = [0xA5, 0xAE, 0xBF, 0x53, 0x80];
+
+    const STEP_EDGE: [u8; 8] = [100, 100, 100, 100, 120, 120, 120, 120];
+    const TEXTURED_EDGE: [u8; 8] = [100, 100, 100, 96, 120, 120, 120, 120];
+
+    fn filter_parameters(limit: i32, interior_limit: i32, hev_threshold: i32) -> FilterParameters {
+        FilterParameters { limit, interior_limit, hev_threshold, filter_inner_edges: true }
+    }
+
+    /// Runs the filter across a vertical edge in the middle of 16 copies of the row, and returns the filtered row.
+    fn filter_across_edge(row: [u8; 8], filter: impl FnOnce(&mut Plane)) -> [u8; 8] {
+        let mut plane = Plane { data: row.repeat(16), stride: 8 };
+        filter(&mut plane);
+        assert!(plane.data.chunks(8).all(|filtered| filtered == &plane.data[..8]));
+        plane.data[..8].try_into().unwrap()
+    }
+
+    /// Predicts the macroblock at (size, size), with the given pixels above and left of it.
+    fn predict_macroblock_from(size: usize, mode: PredictionMode, above: &[u8], left: &[u8], top_left: u8) -> Vec<Vec<u8>> {
+        let mut plane = Plane::new(2 * size, 2 * size).unwrap();
+        let stride = plane.stride;
+        plane.data[(size - 1) * stride + size - 1] = top_left;
+        plane.data[(size - 1) * stride + size..size * stride].copy_from_slice(above);
+        for (row, &value) in left.iter().enumerate() {
+            plane.data[(size + row) * stride + size - 1] = value;
+        }
+        predict_macroblock(&mut plane, size, size, size, mode);
+        plane.data[size * stride..].chunks(stride).map(|row| row[size..].to_vec()).collect()
+    }
+
+    #[test]
+    fn reads_booleans_literals_and_trees() {
+        let mut reader = BooleanDecoder::new(&BOOLEAN_DATA);
+        assert_eq!(reader.read_literal(8), 0xA5);
+        assert_eq!([1, 255, 10, 250, 200].map(|probability| reader.read_bool(probability)), [true, false, true, true, false]);
+        assert_eq!(reader.read_signed_literal(4), -5);
+        assert_eq!(reader.read_tree(&SUBBLOCK_MODE_TREE, &[120, 90, 200, 30, 150, 60, 70, 80, 90]), 5);
+
+        // NOTE: Missing data reads as zeros.
+        assert_eq!(BooleanDecoder::new(&[]).read_literal(16), 0);
+    }
+
+    #[test]
+    fn inverse_transforms_coefficients() {
+        let transform = |coefficients: [i16; 16]| {
+            let mut plane = Plane { data: vec![128; 16], stride: 4 };
+            inverse_dct_add(&coefficients, &mut plane, 0, 0);
+            plane.data
+        };
+        assert_eq!(transform([80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]), [138; 16]);
+        assert_eq!(transform([0, 100, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]), [144, 135, 121, 112].repeat(4));
+        assert_eq!(transform([0, 0, 0, 0, 100, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]), [[144; 4], [135; 4], [121; 4], [112; 4]].concat());
+        assert_eq!(
+            transform([200, -60, 0, 25, 40, 0, -30, 0, 0, 15, 0, 0, -10, 0, 0, 5]),
+            [148, 156, 171, 159, 144, 151, 168, 166, 141, 136, 157, 161, 146, 135, 149, 158]
+        );
+    }
+
+    #[test]
+    fn inverse_walsh_hadamard_transforms_into_direct_current_coefficients() {
+        let transform = |input: [i16; 16]| {
+            let mut coefficients = [[0i16; 16]; 25];
+            inverse_walsh_hadamard_transform(&input, &mut coefficients);
+            assert!(coefficients.iter().all(|block| block[1..].iter().all(|&coefficient| coefficient == 0)));
+            coefficients[..16].iter().map(|block| block[0]).collect::<Vec<i16>>()
+        };
+        assert_eq!(transform([80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]), [10; 16]);
+        assert_eq!(transform([100, -40, 0, 20, 16, 0, 0, 0, 0, 0, -8, 0, 24, 0, 0, 0]), [14, 11, 26, 19, 10, 3, 18, 15, 12, 5, 20, 17, 4, 1, 16, 9]);
+    }
+
+    #[test]
+    fn predicts_subblocks_with_every_mode() {
+        let mut edges = PredictionEdges { above: [0; 20], left: [0; 16], top_left: 60 };
+        edges.above[..8].copy_from_slice(&[10, 40, 90, 160, 200, 230, 250, 255]);
+        edges.left[..4].copy_from_slice(&[30, 70, 120, 180]);
+
+        let expected = [
+            (PredictionMode::DirectCurrent, [88; 16]),
+            (PredictionMode::TrueMotion, [0, 10, 60, 130, 20, 50, 100, 170, 70, 100, 150, 220, 130, 160, 210, 255]),
+            (PredictionMode::Vertical, [30, 45, 95, 153, 30, 45, 95, 153, 30, 45, 95, 153, 30, 45, 95, 153]),
+            (PredictionMode::Horizontal, [48, 48, 48, 48, 73, 73, 73, 73, 123, 123, 123, 123, 165, 165, 165, 165]),
+            (PredictionMode::LeftDown, [45, 95, 153, 198, 95, 153, 198, 228, 153, 198, 228, 246, 198, 228, 246, 254]),
+            (PredictionMode::RightDown, [40, 30, 45, 95, 48, 40, 30, 45, 73, 48, 40, 30, 123, 73, 48, 40]),
+            (PredictionMode::VerticalRight, [35, 25, 65, 125, 40, 30, 45, 95, 48, 35, 25, 65, 73, 40, 30, 45]),
+            (PredictionMode::VerticalLeft, [25, 65, 125, 180, 45, 95, 153, 198, 65, 125, 180, 228, 95, 153, 198, 246]),
+            (PredictionMode::HorizontalDown, [45, 40, 30, 45, 50, 48, 45, 40, 95, 73, 50, 48, 150, 123, 95, 73]),
+            (PredictionMode::HorizontalUp, [50, 73, 95, 123, 95, 123, 150, 165, 150, 165, 180, 180, 180, 180, 180, 180])
+        ];
+        for (mode, expected) in expected {
+            let mut plane = Plane::new(4, 4).unwrap();
+            predict_subblock(&mut plane, 0, 0, mode, &edges);
+            assert_eq!(plane.data, expected, "{mode:?}");
+        }
+    }
+
+    #[test]
+    fn predicts_luma_and_chroma_macroblocks() {
+        for (size, direct_current) in [(16, 110), (8, 106)] {
+            let above: Vec<u8> = (0..size).map(|index| (10 * index + 5) as u8).collect();
+            let left: Vec<u8> = (0..size).map(|index| (200 - 8 * index) as u8).collect();
+            let predict = |mode| predict_macroblock_from(size, mode, &above, &left, 50);
+
+            assert_eq!(predict(PredictionMode::DirectCurrent), vec![vec![direct_current; size]; size]);
+            assert_eq!(predict(PredictionMode::Vertical), vec![above.clone(); size]);
+            assert_eq!(predict(PredictionMode::Horizontal), left.iter().map(|&left| vec![left; size]).collect::<Vec<_>>());
+            let true_motion: Vec<Vec<u8>> = left.iter().map(|&left| above.iter().map(|&above| clamp_to_u8(left as i32 + above as i32 - 50)).collect()).collect();
+            assert_eq!(predict(PredictionMode::TrueMotion), true_motion);
+        }
+    }
+
+    #[test]
+    fn predicts_macroblocks_at_frame_edges() {
+        // NOTE: DC prediction only averages the edges inside the frame, the other modes use 127 above it and 129 left of it.
+        let predict = |x: usize, y: usize, mode| {
+            let mut plane = Plane { data: vec![40; 256], stride: 16 };
+            predict_macroblock(&mut plane, x, y, 8, mode);
+            let block: Vec<u8> = plane.data.chunks(16).skip(y).take(8).flat_map(|row| row[x..x + 8].to_vec()).collect();
+            assert!(block.iter().all(|&pixel| pixel == block[0]));
+            block[0]
+        };
+        assert_eq!(predict(0, 0, PredictionMode::DirectCurrent), 128);
+        assert_eq!(predict(8, 0, PredictionMode::DirectCurrent), 40);
+        assert_eq!(predict(0, 8, PredictionMode::DirectCurrent), 40);
+        assert_eq!(predict(8, 0, PredictionMode::Vertical), 127);
+        assert_eq!(predict(0, 8, PredictionMode::Horizontal), 129);
+        assert_eq!(predict(0, 0, PredictionMode::TrueMotion), 129);
+        assert_eq!(predict(8, 0, PredictionMode::TrueMotion), 40);
+        assert_eq!(predict(0, 8, PredictionMode::TrueMotion), 40);
+    }
+
+    #[test]
+    fn filters_edges_with_the_simple_filter() {
+        // NOTE: Only the two pixels next to the edge change, and only while the step is below the limit.
+        assert_eq!(filter_across_edge(STEP_EDGE, |plane| simple_filter_edge(plane, 4, 1, 8, 50)), [100, 100, 100, 105, 115, 120, 120, 120]);
+        assert_eq!(filter_across_edge(STEP_EDGE, |plane| simple_filter_edge(plane, 4, 1, 8, 49)), STEP_EDGE);
+        assert_eq!(filter_across_edge(TEXTURED_EDGE, |plane| simple_filter_edge(plane, 4, 1, 8, 60)), [100, 100, 100, 102, 113, 120, 120, 120]);
+    }
+
+    #[test]
+    fn filters_edges_with_the_normal_filter() {
+        let filter = |row, parameters: FilterParameters, is_macroblock_edge| {
+            filter_across_edge(row, |plane| normal_filter_edge(plane, 4, 1, 8, 16, &parameters, is_macroblock_edge))
+        };
+
+        // NOTE: Macroblock edges get a higher limit and change three pixels on either side, inner edges only two.
+        assert_eq!(filter(STEP_EDGE, filter_parameters(46, 10, 5), true), [100, 103, 106, 108, 112, 114, 117, 120]);
+        assert_eq!(filter(STEP_EDGE, filter_parameters(46, 10, 5), false), STEP_EDGE);
+        assert_eq!(filter(STEP_EDGE, filter_parameters(50, 10, 5), false), [100, 100, 104, 107, 112, 116, 120, 120]);
+
+        // NOTE: Unlike the simple filter, it leaves textured edges alone, and only changes p0 and q0 where the edge variance is high.
+        assert_eq!(filter(TEXTURED_EDGE, filter_parameters(56, 3, 2), true), TEXTURED_EDGE);
+        assert_eq!(filter(TEXTURED_EDGE, filter_parameters(56, 10, 2), true), [100, 100, 100, 102, 113, 120, 120, 120]);
+    }
+}